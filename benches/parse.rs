@@ -0,0 +1,98 @@
+/**
+ * 文件名: "benches/parse.rs" json解析器性能测试代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::time::{Duration, Instant};
+
+use zjy_json::zjy::json::Json;
+
+/**
+ * 生成性能测试用的语料: 长字符串,中文,深层嵌套和大量数字
+ * 使用固定的伪随机数,保证每次生成的内容相同
+ */
+fn corpus() -> Vec<(&'static str, String)> {
+    let mut seed: u64 = 114514;
+    let mut next = move || {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        return seed >> 33;
+    };
+    let mut records = String::from("[");
+    for i in 0..2000 {
+        if i > 0 {
+            records.push(',');
+        }
+        records.push_str(&std::format!(
+            "\n  {{\"id\":{},\"name\":\"用户{}\",\"score\":{}.{},\"active\":{},\"bio\":\"{}\",\"tags\":[\"a\",\"b\\n\",\"\\u4f60\\u597d\"],\"parent\":null}}",
+            next(), next() % 1000, next() % 100, next() % 1000, next() % 2 == 0,
+            "The quick brown fox jumps over the lazy dog. 敏捷的棕色狐狸跳过了懒狗。".repeat((next() % 8 + 1) as usize)
+        ));
+    }
+    records.push_str("\n]");
+
+    let mut numbers = String::from("[");
+    for i in 0..50000 {
+        if i > 0 {
+            numbers.push(',');
+        }
+        numbers.push_str(&std::format!("{}.{}e-{}", next() % 100000, next() % 1000, next() % 10));
+    }
+    numbers.push(']');
+
+    let mut nested = String::new();
+    for _ in 0..60 {
+        nested.push_str("{\"k\":[");
+    }
+    nested.push_str("\"leaf\"");
+    for _ in 0..60 {
+        nested.push_str("]}");
+    }
+
+    let mut result = vec![("records", records), ("numbers", numbers), ("nested", nested)];
+    for file in ["testarray", "testobject", "teststring"] {
+        let path = std::format!("tests/{}.json", file);
+        if let Ok(s) = std::fs::read_to_string(&path) {
+            result.push((file, s));
+        }
+    }
+    return result;
+}
+
+/**
+ * 反复执行f至少一段时间,返回吞吐量(MB/s)
+ */
+fn throughput<F: FnMut()>(len: usize, mut f: F) -> f64 {
+    let budget = Duration::from_millis(300);
+    let start = Instant::now();
+    let mut rounds: u64 = 0;
+    while start.elapsed() < budget {
+        f();
+        rounds += 1;
+    }
+    let secs = start.elapsed().as_secs_f64();
+    return (len as f64) * (rounds as f64) / secs / 1024.0 / 1024.0;
+}
+
+fn main() {
+    println!("{:<12}{:>14}{:>14}{:>14}", "语料", "str_to_json", "bytes_to_json", "serde_json");
+    for (name, text) in corpus() {
+        let slow = throughput(text.len(), || { Json::str_to_json(&text).ok(); });
+        let fast = throughput(text.len(), || { Json::bytes_to_json(text.as_bytes()).unwrap(); });
+        let serde = throughput(text.len(), || { serde_json::from_str::<serde_json::Value>(&text).unwrap(); });
+        println!("{:<12}{:>10.1}MB/s{:>10.1}MB/s{:>10.1}MB/s", name, slow, fast, serde);
+    }
+}
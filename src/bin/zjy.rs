@@ -0,0 +1,458 @@
+/**
+ * 文件名: "src/bin/zjy.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::io::{IsTerminal, Read, Write};
+use std::process::ExitCode;
+
+use zjy_json::zjy::json::{generate_rust, CodegenOptions, CodegenStyle, DiagnosticRenderer, Json, JsonStreamReader, NumberMode, Parser, Query, SerializeOptions};
+
+const USAGE: &str = "用法: zjy <命令> [选项] [文件...]
+      zjy query [选项] <查询> [文件...]
+      zjy codegen [选项] [文件...]
+
+命令:
+  validate    检查输入是否是合法的json,不合法时输出带行号和列号的诊断信息
+  fmt         带缩进格式化输出
+  minify      去掉所有空白输出
+  canon       按照RFC 8785(JCS)输出规范化的json
+  query       对输入中的每个json值执行jq风格的查询,输出所有结果
+  codegen     根据输入的json样本推断schema,生成对应的Rust类型定义
+
+选项:
+  --indent <N>    fmt每一层缩进的空格数,默认2
+  -w, --write     把结果写回文件,而不是输出到标准输出
+  --check         只检查文件是否已经是输出的格式,不是时返回1
+  -q, --quiet     validate不输出合法的文件
+  -c, --compact   query紧凑输出每个结果
+  -r, --raw       query的结果是string时直接输出内容
+  -n, --null-input          query不读取输入,对null执行一次
+  -s, --slurp               query把所有输入放进一个array,执行一次
+  --arg <名字> <值>         query中定义string类型的变量$名字
+  --argjson <名字> <json>   query中定义json类型的变量$名字
+  --schema        codegen的输入是JSON Schema,而不是json样本
+  --serde         codegen生成serde的derive,而不是ToJson/FromJson的实现
+  --name <名字>   codegen最外层类型的名字,默认Root
+  --ascii         codegen把key中的非ascii字符转换成u+十六进制码点
+  -h, --help      输出帮助信息
+
+没有文件或者文件是\"-\"时读取标准输入
+fmt,minify和canon遇到不能被f64精确表示的整数时报错,不会改变它的值
+退出码: 0代表成功,1代表json不合法,--check没有通过,查询出错或者schema不合法,2代表参数,查询语法或io错误";
+
+/**
+ * 子命令
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Command {
+    Validate,
+    Fmt,
+    Minify,
+    Canon,
+    Query,
+    Codegen,
+}
+
+/**
+ * 解析之后的命令行参数
+ */
+struct Args {
+    command: Command,
+    indent: usize,
+    write: bool,
+    check: bool,
+    quiet: bool,
+    /// query的查询和选项
+    filter: Option<String>,
+    compact: bool,
+    raw: bool,
+    null_input: bool,
+    slurp: bool,
+    vars: Vec<(String, Json)>,
+    /// codegen的选项
+    codegen: CodegenOptions,
+    schema: bool,
+    files: Vec<String>,
+}
+
+/**
+ * 解析命令行参数(不包括程序名),需要输出帮助时返回Ok(None)
+ */
+fn parse_args(args: &[String]) -> Result<Option<Args>, String> {
+    let mut iter = args.iter();
+    let command = match iter.next().map(|s| s.as_str()) {
+        None | Some("-h") | Some("--help") | Some("help") => { return Ok(None); },
+        Some("validate") => Command::Validate,
+        Some("fmt") => Command::Fmt,
+        Some("minify") => Command::Minify,
+        Some("canon") => Command::Canon,
+        Some("query") => Command::Query,
+        Some("codegen") => Command::Codegen,
+        Some(other) => { return Err(std::format!("未知的命令:{}", other)); },
+    };
+    let mut parsed = Args {
+        command, indent: 2, write: false, check: false, quiet: false,
+        filter: None, compact: false, raw: false, null_input: false, slurp: false, vars: Vec::new(),
+        codegen: CodegenOptions::default(), schema: false, files: Vec::new(),
+    };
+    let mut query_only = None;
+    let mut codegen_only = None;
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => { return Ok(None); },
+            "--indent" => {
+                let value = iter.next().ok_or("--indent后面需要空格数")?;
+                parsed.indent = value.parse().map_err(|_| std::format!("--indent的值不是合法的数字:{}", value))?;
+            },
+            "-w" | "--write" => { parsed.write = true; },
+            "--check" => { parsed.check = true; },
+            "-q" | "--quiet" => { parsed.quiet = true; },
+            "-c" | "--compact" => { parsed.compact = true; query_only = Some(arg); },
+            "-r" | "--raw" => { parsed.raw = true; query_only = Some(arg); },
+            "-n" | "--null-input" => { parsed.null_input = true; query_only = Some(arg); },
+            "-s" | "--slurp" => { parsed.slurp = true; query_only = Some(arg); },
+            "--arg" | "--argjson" => {
+                let (name, value) = match (iter.next(), iter.next()) {
+                    (Some(name), Some(value)) => (name, value),
+                    _ => { return Err(std::format!("{}后面需要变量名和值", arg)); },
+                };
+                let value = if arg == "--arg" {
+                    Json::String(value.clone())
+                } else {
+                    Json::str_to_json(value).map_err(|err| std::format!("--argjson {}的值不是合法的json:{}", name, err))?
+                };
+                parsed.vars.push((name.clone(), value));
+                query_only = Some(arg);
+            },
+            "--schema" => { parsed.schema = true; codegen_only = Some(arg); },
+            "--serde" => { parsed.codegen.style = CodegenStyle::Serde; codegen_only = Some(arg); },
+            "--ascii" => { parsed.codegen.ascii_identifiers = true; codegen_only = Some(arg); },
+            "--name" => {
+                parsed.codegen.root_name = iter.next().ok_or("--name后面需要类型名")?.clone();
+                codegen_only = Some(arg);
+            },
+            "-" => { parsed.files.push(arg.clone()); },
+            _ if arg.starts_with('-') => { return Err(std::format!("未知的选项:{}", arg)); },
+            _ if command == Command::Query && parsed.filter.is_none() => { parsed.filter = Some(arg.clone()); },
+            _ => { parsed.files.push(arg.clone()); },
+        }
+    }
+    if command == Command::Query {
+        if parsed.filter.is_none() {
+            return Err("query需要查询".to_string());
+        }
+        if parsed.write || parsed.check {
+            return Err("query不支持--write和--check".to_string());
+        }
+    } else if let Some(arg) = query_only {
+        return Err(std::format!("只有query支持{}", arg));
+    }
+    if command != Command::Codegen {
+        if let Some(arg) = codegen_only {
+            return Err(std::format!("只有codegen支持{}", arg));
+        }
+    }
+    if matches!(command, Command::Validate | Command::Codegen) && (parsed.write || parsed.check) {
+        return Err(std::format!("{}不支持--write和--check", if command == Command::Validate { "validate" } else { "codegen" }));
+    }
+    if parsed.write && parsed.check {
+        return Err("--write和--check不能同时使用".to_string());
+    }
+    if parsed.write && (parsed.files.is_empty() || parsed.files.iter().any(|file| file == "-")) {
+        return Err("--write需要指定文件".to_string());
+    }
+    return Ok(Some(parsed));
+}
+
+/**
+ * 按照子命令输出json,结尾带有换行
+ */
+fn render(json: &Json, args: &Args) -> Result<String, String> {
+    let options = match args.command {
+        Command::Fmt => SerializeOptions::pretty(args.indent),
+        Command::Canon => SerializeOptions::canonical(),
+        _ => SerializeOptions::compact(),
+    };
+    let mut out = json.to_string_with_options(&options).map_err(|err| err.to_string())?;
+    out.push('\n');
+    return Ok(out);
+}
+
+/**
+ * 执行命令,返回退出码;color代表诊断信息是否使用ANSI颜色
+ */
+fn run(args: &[String], stdin: &mut dyn Read, out: &mut dyn Write, err: &mut dyn Write, color: bool) -> u8 {
+    let args = match parse_args(args) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            let _ = writeln!(out, "{}", USAGE);
+            return 0;
+        },
+        Err(msg) => {
+            let _ = writeln!(err, "zjy: {}\n\n{}", msg, USAGE);
+            return 2;
+        },
+    };
+    if args.command == Command::Query {
+        return run_query(&args, stdin, out, err);
+    }
+    if args.command == Command::Codegen {
+        return run_codegen(&args, stdin, out, err);
+    }
+    let files = if args.files.is_empty() { vec!["-".to_string()] } else { args.files.clone() };
+    let mut code = 0;
+    for file in &files {
+        let name = display_name(file);
+        let source = match read_input(file, stdin) {
+            Ok(source) => source,
+            Err(e) => {
+                let _ = writeln!(err, "zjy: {}: {}", name, e);
+                code = 2;
+                continue;
+            },
+        };
+        // 输出时number会经过f64,不能精确表示的整数(比如很长的id)报错而不是悄悄改变它的值
+        let parser = if args.command == Command::Validate { Parser::new() } else { Parser::new().number_mode(NumberMode::Lossless) };
+        let json = match parser.parse_diagnostic(&source) {
+            Ok(json) => json,
+            Err(diagnostic) => {
+                let _ = writeln!(err, "{}:{}:{}:", name, diagnostic.position.line, diagnostic.position.column);
+                let _ = write!(err, "{}", DiagnosticRenderer::new().color(color).render(&source, &diagnostic));
+                code = code.max(1);
+                continue;
+            },
+        };
+        if args.command == Command::Validate {
+            if !args.quiet {
+                let _ = writeln!(out, "{}: 合法", name);
+            }
+            continue;
+        }
+        let output = match render(&json, &args) {
+            Ok(output) => output,
+            Err(msg) => {
+                let _ = writeln!(err, "zjy: {}: {}", name, msg);
+                code = code.max(1);
+                continue;
+            },
+        };
+        if args.check {
+            if output != source {
+                let _ = writeln!(err, "{}: 格式不符合要求", name);
+                code = code.max(1);
+            }
+        } else if args.write {
+            if output != source {
+                if let Err(e) = std::fs::write(file, &output) {
+                    let _ = writeln!(err, "zjy: {}: {}", name, e);
+                    code = 2;
+                }
+            }
+        } else if let Err(e) = out.write_all(output.as_bytes()) {
+            let _ = writeln!(err, "zjy: {}", e);
+            return 2;
+        }
+    }
+    return code;
+}
+
+fn display_name(file: &str) -> &str {
+    return if file == "-" { "<stdin>" } else { file };
+}
+
+/**
+ * 读取文件,"-"代表标准输入
+ */
+fn read_input(file: &str, stdin: &mut dyn Read) -> std::io::Result<String> {
+    if file == "-" {
+        let mut source = String::new();
+        stdin.read_to_string(&mut source)?;
+        return Ok(source);
+    }
+    return std::fs::read_to_string(file);
+}
+
+/**
+ * 读取所有输入中用空白分隔的json值,返回读到的值和退出码
+ */
+fn read_values(args: &Args, stdin: &mut dyn Read, err: &mut dyn Write) -> (Vec<Json>, u8) {
+    let mut code = 0;
+    let mut values = Vec::new();
+    let files = if args.files.is_empty() { vec!["-".to_string()] } else { args.files.clone() };
+    for file in &files {
+        let source = match read_input(file, stdin) {
+            Ok(source) => source,
+            Err(e) => {
+                let _ = writeln!(err, "zjy: {}: {}", display_name(file), e);
+                code = 2;
+                continue;
+            },
+        };
+        for value in JsonStreamReader::new(source.as_bytes()) {
+            match value {
+                Ok(value) => { values.push(value); },
+                Err(e) => {
+                    let _ = writeln!(err, "zjy: {}: {}", display_name(file), e);
+                    code = code.max(1);
+                    break;
+                },
+            }
+        }
+    }
+    return (values, code);
+}
+
+/**
+ * 执行query命令: 每个输入可以包含多个用空白分隔的json值,每个值执行一次查询
+ */
+fn run_query(args: &Args, stdin: &mut dyn Read, out: &mut dyn Write, err: &mut dyn Write) -> u8 {
+    let query = match Query::compile(args.filter.as_deref().unwrap_or(".")) {
+        Ok(query) => query,
+        Err(e) => {
+            let _ = writeln!(err, "zjy: {}", e);
+            return 2;
+        },
+    };
+    let (mut inputs, mut code) = if args.null_input { (Vec::new(), 0) } else { read_values(args, stdin, err) };
+    if args.null_input {
+        inputs.push(Json::Null);
+    } else if args.slurp {
+        inputs = vec![Json::Array(inputs)];
+    }
+    let options = if args.compact { SerializeOptions::compact() } else { SerializeOptions::pretty(args.indent) };
+    for input in &inputs {
+        let results = match query.run_with_vars(input, &args.vars) {
+            Ok(results) => results,
+            Err(e) => {
+                let _ = writeln!(err, "zjy: {}", e);
+                code = code.max(1);
+                continue;
+            },
+        };
+        for result in results {
+            let text = match &result {
+                Json::String(s) if args.raw => Ok(s.clone()),
+                _ => result.to_string_with_options(&options),
+            };
+            match text {
+                Ok(text) => {
+                    if let Err(e) = writeln!(out, "{}", text) {
+                        let _ = writeln!(err, "zjy: {}", e);
+                        return 2;
+                    }
+                },
+                Err(e) => {
+                    let _ = writeln!(err, "zjy: {}", e);
+                    code = code.max(1);
+                },
+            }
+        }
+    }
+    return code;
+}
+
+/**
+ * 执行codegen命令: 默认把所有输入中的json值作为样本推断schema,--schema时输入是一个JSON Schema
+ */
+fn run_codegen(args: &Args, stdin: &mut dyn Read, out: &mut dyn Write, err: &mut dyn Write) -> u8 {
+    let (values, code) = read_values(args, stdin, err);
+    if code != 0 {
+        return code;
+    }
+    let schema = if args.schema {
+        match values.as_slice() {
+            [schema] => schema.clone(),
+            _ => {
+                let _ = writeln!(err, "zjy: --schema需要一个schema,读到了{}个json值", values.len());
+                return 1;
+            },
+        }
+    } else {
+        Json::infer_schema(&values)
+    };
+    match generate_rust(&schema, &args.codegen) {
+        Ok(code) => {
+            if let Err(e) = write!(out, "{}", code) {
+                let _ = writeln!(err, "zjy: {}", e);
+                return 2;
+            }
+            return 0;
+        },
+        Err(e) => {
+            let _ = writeln!(err, "zjy: {}", e);
+            return 1;
+        },
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let color = std::io::stderr().is_terminal();
+    let code = run(&args, &mut std::io::stdin().lock(), &mut std::io::stdout().lock(), &mut std::io::stderr().lock(), color);
+    return ExitCode::from(code);
+}
+
+#[cfg(test)]
+#[test]
+fn test_cli() {
+    let call = |args: &[&str], stdin: &str| -> (u8, String, String) {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = run(&args, &mut stdin.as_bytes(), &mut out, &mut err, false);
+        return (code, String::from_utf8(out).unwrap(), String::from_utf8(err).unwrap());
+    };
+    assert_eq!(call(&["validate"], "{\"a\":[1,2]}"), (0, "<stdin>: 合法\n".to_string(), String::new()));
+    let (code, _, err) = call(&["validate", "-q"], "{\"a\":[1,\n2,]}");
+    assert_eq!(code, 1);
+    assert!(err.starts_with("<stdin>:2:3:\n"));
+    assert_eq!(call(&["minify"], " { \"b\" : 1 , \"a\" : [ true ] } "), (0, "{\"a\":[true],\"b\":1}\n".to_string(), String::new()));
+    assert_eq!(call(&["fmt", "--indent", "4"], "{\"a\":[1]}").1, "{\n    \"a\": [\n        1\n    ]\n}\n");
+    assert_eq!(call(&["canon"], "{\"b\":1E30,\"a\":\"\\u00e9\"}").1, "{\"a\":\"é\",\"b\":1e+30}\n");
+    assert_eq!(call(&["minify", "--check"], "[1,2]\n").0, 0);
+    // 不能被f64精确表示的整数不会被改写
+    let (code, out, err) = call(&["minify"], "{\"id\":12345678901234567891}");
+    assert_eq!((code, out.as_str()), (1, ""));
+    assert!(err.contains("不能被f64精确表示"));
+    assert_eq!(call(&["validate", "-q"], "{\"id\":12345678901234567891}").0, 0);
+    assert_eq!(call(&["minify", "--check"], "[1, 2]\n").0, 1);
+    assert_eq!(call(&["fmt", "--write"], "[]").0, 2);
+    assert_eq!(call(&["lint"], "").0, 2);
+    assert_eq!(call(&["validate", "tests/testobject.json", "tests/no-such-file.json"], "").0, 2);
+    assert!(call(&["--help"], "").1.starts_with("用法"));
+    // query
+    let input = "{\"a\":[1,2]} {\"a\":[3]}";
+    assert_eq!(call(&["query", "-c", ".a | add"], input), (0, "3\n3\n".to_string(), String::new()));
+    assert_eq!(call(&["query", ".a"], "{\"a\":[1]}").1, "[\n  1\n]\n");
+    assert_eq!(call(&["query", "-s", "-c", "map(.a[])"], input).1, "[1,2,3]\n");
+    assert_eq!(call(&["query", "-n", "-r", "--arg", "x", "hi", "--argjson", "y", "{\"z\":1}", "\"\\($x) \\($y.z)\""], "").1, "hi 1\n");
+    assert_eq!(call(&["query", ".a +"], "1").0, 2);
+    let (code, out, err) = call(&["query", ".a"], "1 {\"a\":2}");
+    assert_eq!((code, out.as_str()), (1, "2\n"));
+    assert!(err.contains("不能用string索引number"));
+    assert_eq!(call(&["minify", "-r"], "1").0, 2);
+    // codegen
+    let (code, out, _) = call(&["codegen", "--name", "user"], "{\"名字\":\"a\",\"age\":1} {\"名字\":\"b\"}");
+    assert_eq!(code, 0);
+    assert!(out.contains("pub struct User {\n    pub age: Option<i64>,\n    pub 名字: String,\n}"));
+    let (code, out, _) = call(&["codegen", "--schema", "--serde"], "{\"type\":\"object\",\"properties\":{\"a-b\":{\"type\":\"boolean\"}},\"required\":[\"a-b\"]}");
+    assert_eq!(code, 0);
+    assert!(out.contains("    #[serde(rename = \"a-b\")]\n    pub a_b: bool,\n"));
+    assert_eq!(call(&["codegen", "--schema"], "{} {}").0, 1);
+    assert_eq!(call(&["codegen", "--schema"], "{\"type\":1}").0, 1);
+    assert_eq!(call(&["fmt", "--serde"], "1").0, 2);
+}
@@ -17,8 +17,7 @@
  * You should have received a copy of the GNU Affero General Public License
  * along with this program.  If not, see <https://www.gnu.org/licenses/>. 
  */
-
-use crate::zjy::json::{Json, judgment_json_type, ast::string::StringMode};
+use crate::zjy::json::{Json, judgment_json_type, ast::string::StringMode, error::JsonError, options::ParseOptions, span::{Position, Span, SpanChildren, SpanTree}, diagnostic::Expected};
 
 use super::{null::NullMode, r#true::TrueMode, r#false::FalseMode, number::NumberContext, string::StringContext, object::{ObjectContext, ObjectMode}};
 /**
@@ -28,7 +27,13 @@ pub struct ArrayContext{
     /// 存储解析出来的json对象的数组
     arr:Vec<Json>,
     /// 存储解析对象的行为枚举
-    mode:ArrayMode
+    mode:ArrayMode,
+    /// 这个数组的嵌套深度,最外层为1
+    depth:usize,
+    /// 开启范围记录时保存每个元素的范围树
+    spans:Option<Vec<SpanTree>>,
+    /// 当前元素开始的位置
+    value_start:Position
 }
 /**
  * 存储解析对象的行为枚举
@@ -64,7 +69,7 @@ impl ArrayContext {
     /**
      * 获取当前模式的借用,一般用于判断array的解析是否完成
      */
-    pub fn get_mode_ptr<'a>(&'a self) ->&'a ArrayMode{
+    pub fn get_mode_ptr(&self) ->&ArrayMode{
         return &self.mode;
     }
     /**
@@ -91,7 +96,62 @@ impl ArrayContext {
     /**
      * 初始化一个array上下文句柄,并将ArrayMode字段指定为判断类型模式
      */
-    pub fn new_judgment_type() -> ArrayContext{ ArrayContext { arr: Vec::new(), mode: ArrayMode::JudgmentType }}
+    pub fn new_judgment_type(depth:usize, spanned:bool) -> ArrayContext{
+        ArrayContext { arr: Vec::new(), mode: ArrayMode::JudgmentType, depth, spans: if spanned { Some(Vec::new()) } else { None }, value_start: Position::start() }
+    }
+    /**
+     * 取出元素的范围树,没有开启范围记录时返回SpanChildren::Scalar
+     */
+    pub fn take_spans(&mut self) -> SpanChildren{
+        match self.spans.take() {
+            Some(spans) => {return SpanChildren::Array(spans);},
+            None => {return SpanChildren::Scalar;},
+        }
+    }
+    /**
+     * 把解析出的元素存入数组,记录它的范围(到end为止),并切换到逗号模式
+     */
+    fn push_value(&mut self, value:Json, end:Position, children:SpanChildren, options:&ParseOptions) ->Result<(),JsonError>{
+        self.arr.push(value);
+        if let Some(spans)=&mut self.spans{
+            spans.push(SpanTree { span: Span { start: self.value_start, end }, key: None, children });
+        }
+        options.check_array_length(self.arr.len())?;
+        self.mode=ArrayMode::Douhao;
+        return Ok(());
+    }
+    /**
+     * 当前位置需要的符号,用于错误提示;正在解析number或string时返回None
+     */
+    pub fn expected(&self, options:&ParseOptions) ->Option<Vec<Expected>>{
+        match &self.mode {
+            ArrayMode::JudgmentType => {
+                if self.arr.is_empty() || options.extensions.trailing_commas{
+                    return Some(vec![Expected::Value, Expected::Char(']')]);
+                }
+                return Some(vec![Expected::Value]);
+            },
+            ArrayMode::Null(_) => {return Some(vec![Expected::Literal("null")]);},
+            ArrayMode::JsonTrue(_) => {return Some(vec![Expected::Literal("true")]);},
+            ArrayMode::JsonFalse(_) => {return Some(vec![Expected::Literal("false")]);},
+            ArrayMode::NumberContext(_)|ArrayMode::String(_) => {return None;},
+            ArrayMode::Array(context) => {return context.expected(options);},
+            ArrayMode::Object(context) => {return context.expected(options);},
+            ArrayMode::Douhao => {return Some(vec![Expected::Char(','), Expected::Char(']')]);},
+            ArrayMode::End => {return Some(Vec::new());},
+        }
+    }
+    /**
+     * 判断当前是否正在解析number
+     */
+    pub fn in_number(&self) ->bool{
+        match &self.mode {
+            ArrayMode::NumberContext(_) => {return true;},
+            ArrayMode::Array(context) => {return context.in_number();},
+            ArrayMode::Object(context) => {return context.in_number();},
+            _ => {return false;},
+        }
+    }
     /**
      * 通过循环调用该函数的方式解析json数组,将上下文保存在句柄中
      * 如果数组不合法,返回Err
@@ -99,17 +159,25 @@ impl ArrayContext {
      * 可以通过get_mode_ptr()函数获取,
      * 并通过这个字段判断是否解析完成
      * 如果解析完成,再次调用check_array()函数会返回Err
+     * 如果超出options中的资源限制,返回JsonError::LimitExceeded
+     * pos是字符c在输入中的位置,用于记录元素的范围
      */
-    pub fn check_array(&mut self, c:char) ->Result<(),String>{
+    pub fn check_array(&mut self, c:char, pos:Position, options:&ParseOptions) ->Result<(),JsonError>{
         match self.mode {
             ArrayMode::JudgmentType => {
                 match c {
-                    '\r'|'\n'|'\x20'=>{
+                    _ if options.whitespace.contains(c)=>{
                         return Ok(())
                     }
+                    // 空数组,或者允许末尾逗号时逗号后面直接结束
+                    ']' if self.arr.is_empty() || options.extensions.trailing_commas=>{
+                        self.mode=ArrayMode::End;
+                        return Ok(());
+                    }
                     _=>{ 
+                        self.value_start=pos;
                         // 根据不同的类型,走不同的分支,将上下文存入
-                        match judgment_json_type(c)? {
+                        match judgment_json_type(c, options)? {
                             Json::Null => {
                                 self.mode=ArrayMode::Null(NullMode::u);
                                 return Ok(());
@@ -123,20 +191,23 @@ impl ArrayContext {
                                 return Ok(());
                             },
                             Json::Number(_) => {
+                                options.check_number_length(1)?;
                                 self.mode=ArrayMode::NumberContext(NumberContext::new_from_str(&c.to_string()));
                                 return Ok(());
                             },
                             Json::String(_) => {
-                                self.mode=ArrayMode::String(StringContext{ str: String::new(), mode: StringMode::str });
+                                self.mode=ArrayMode::String(StringContext::new_str(c));
                                 return Ok(());
 
                             },
                             Json::Object(_) => {
-                                self.mode=ArrayMode::Object(Box::new(ObjectContext::new_key()));
+                                options.check_depth(self.depth+1)?;
+                                self.mode=ArrayMode::Object(Box::new(ObjectContext::new_key(self.depth+1, self.spans.is_some())));
                                 return Ok(());
                             },
                             Json::Array(_) => {
-                                self.mode=ArrayMode::Array(Box::new(ArrayContext::new_judgment_type()));
+                                options.check_depth(self.depth+1)?;
+                                self.mode=ArrayMode::Array(Box::new(ArrayContext::new_judgment_type(self.depth+1, self.spans.is_some())));
                                 return Ok(());
                             },
                         };
@@ -147,15 +218,13 @@ impl ArrayContext {
                 match context.check_null(c) {
                     Ok(ok) => {
                         if ok{
-                            // true代表null识别完毕
-                            self.mode=ArrayMode::Douhao;
-                            // 存入数组
-                            self.arr.push(Json::Null);
+                            // true代表null识别完毕,存入数组
+                            self.push_value(Json::Null, pos.advance(c), SpanChildren::Scalar, options)?;
                         }
                         return Ok(());
                     },
                     Err(_err) => {
-                        return Err(std::format!("null识别错误:非法字符:{}",c));
+                        return Err(std::format!("null识别错误:非法字符:{}",c).into());
                     },
                 };
             },
@@ -164,14 +233,13 @@ impl ArrayContext {
                     Ok(ok) => {
                         // true代表true识别完毕
                         if ok{
-                            self.mode=ArrayMode::Douhao;
                             // 存入数组
-                            self.arr.push(Json::Boolean(true));
+                            self.push_value(Json::Boolean(true), pos.advance(c), SpanChildren::Scalar, options)?;
                         }
                         return Ok(());
                     },
                     Err(_err) => {
-                        return Err(std::format!("true识别错误:非法字符:{}",c));
+                        return Err(std::format!("true识别错误:非法字符:{}",c).into());
                     },
                 }
             },
@@ -179,57 +247,56 @@ impl ArrayContext {
                 match context.check_false(c) {
                     Ok(ok) => {
                         if ok{
-                            // 解析完毕
-                            self.mode=ArrayMode::Douhao;
-                            // 存入数组
-                            self.arr.push(Json::Boolean(false));
+                            // 解析完毕,存入数组
+                            self.push_value(Json::Boolean(false), pos.advance(c), SpanChildren::Scalar, options)?;
                         }
                         return Ok(());
                     },
                     Err(_) => {
-                        return Err(std::format!("false识别错误:非法字符:{}",c));
+                        return Err(std::format!("false识别错误:非法字符:{}",c).into());
                     },
                 }
             },
             ArrayMode::NumberContext(ref mut context) => {
                 match c {
                     // 关于浮点数的字符全存进去
-                    '0'|'1'|'2'|'3'|'4'|'5'|'6'|'7'|'8'|'9'|'-'|'E'|'e'|'.'=>{
+                    _ if NumberContext::is_number_char(c, options)=>{
                         context.get_number_str_mut().push(c);
+                        options.check_number_length(context.get_number_str_mut().len())?;
                         return Ok(());
                     }
                     // 如果出现其他字符,说明浮点数已经到头了
                     _=>{
-                        match context.check_number("") {
+                        match context.check_number("", options) {
                             Ok(_) => {
                                 // 如果浮点数合法
-                                // 存入浮点数
-                                self.arr.push(Json::Number(context.get_number()));
+                                // 存入浮点数,number在这个字符之前结束
+                                let value=Json::Number(context.get_number());
+                                self.push_value(value, pos, SpanChildren::Scalar, options)?;
                                 // 立即执行一次逗号检测,并且返回
-                                self.mode=ArrayMode::Douhao;
-                                return self.check_array(c);
+                                return self.check_array(c, pos, options);
                             },
                             Err(err) => {
-                                return Err(std::format!("{}",err));
+                                return Err(err.to_string().into());
                             },
                         };
                     }
                 }
             },
             ArrayMode::String(ref mut context) =>{
-                match context.check_string(c) {
+                match context.check_string(c, options) {
                     Ok(_) => {
+                        options.check_string_length(context.get_str().len())?;
                         // 判断是否解析string完成
                         if (*context.get_mode())==StringMode::end{
-                            // 存入数组
-                            self.arr.push(Json::String(context.get_str().to_string()));
-                            // 解析完成
-                            self.mode=ArrayMode::Douhao;
+                            // 解析完成,存入数组
+                            let value=Json::String(context.get_str().to_string());
+                            self.push_value(value, pos.advance(c), SpanChildren::Scalar, options)?;
                         }
                         return Ok(());
                     },
                     Err(_) => {
-                        return Err(std::format!("string识别错误:非法字符:{}",c));
+                        return Err(std::format!("string识别错误:非法字符:{}",c).into());
                     },
                 }
             },
@@ -240,8 +307,8 @@ impl ArrayContext {
                         self.mode=ArrayMode::JudgmentType;
                         return Ok(());
                     }
-                    '\n'|'\r'|'\x20' =>{
-                        // 允许空格和换行
+                    _ if options.whitespace.contains(c) =>{
+                        // 允许空白
                         return Ok(());
                     }
                     ']'=>{
@@ -251,13 +318,13 @@ impl ArrayContext {
                     }
                     _=>{
                         // 异常
-                        return Err(std::format!("此处需要',' 但实际为{}",c));
+                        return Err(std::format!("此处需要',' 但实际为{}",c).into());
                     }
                     
                 }
             },
             ArrayMode::End => {
-                return Err(std::format!("array已经解析完毕,不应该再次调用该函数"));
+                return Err("array已经解析完毕,不应该再次调用该函数".to_string().into());
             },
             /*
             逐层传递字符串,递归调用,我觉得没必要优化成循环,
@@ -267,24 +334,26 @@ impl ArrayContext {
              */
             ArrayMode::Array(ref mut context) => {
                 // 解析1+n级数组
-                context.check_array(c)?;
+                context.check_array(c, pos, options)?;
                 // 判断这个1+n级数组解析完成了吗
                 if let ArrayMode::End=context.get_mode_ptr(){
                     // 子数组解析完成,把这个数组里面的东西拿出来
-                    self.arr.push(Json::Array(context.get_arr()));
-                    self.mode=ArrayMode::Douhao;
+                    let value=Json::Array(context.get_arr());
+                    let children=context.take_spans();
+                    self.push_value(value, pos.advance(c), children, options)?;
                 }
                 return Ok(());
                 // 否则继续解析子数组
             },
             ArrayMode::Object(ref mut context) => {
                 // 解析obj
-                match context.check_object(c) {
+                match context.check_object(c, pos, options) {
                     Ok(_) => {
                         if let ObjectMode::End=context.get_mode_ptr(){
                             // 解析完成
-                            self.arr.push(context.get_json());
-                            self.mode=ArrayMode::Douhao;
+                            let value=context.get_json();
+                            let children=context.take_spans();
+                            self.push_value(value, pos.advance(c), children, options)?;
                         }
                         return Ok(());
                     },
@@ -0,0 +1,119 @@
+/**
+ * 文件名: "src/zjy/json/ast/comment.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::zjy::json::options::ParseOptions;
+
+/**
+ * 过滤注释的上下文,放在其他上下文前面,注释整体被替换成一个空格
+ * 需要知道当前是否在string中,string中的 "//" 不是注释
+ */
+pub struct CommentContext{
+    mode:CommentMode
+}
+/**
+ * 过滤注释的模式
+ * Code代表不在string和注释中
+ * Str和StrZhuanyi代表在string中,保存了开始string的引号
+ * Slash代表读到了一个 '/' ,需要判断是单行注释还是多行注释
+ * LineComment代表单行注释,直到换行结束
+ * BlockComment和BlockStar代表多行注释,BlockStar代表刚刚读到了 '*'
+ */
+pub enum CommentMode {
+    Code,Str(char),StrZhuanyi(char),Slash,LineComment,BlockComment,BlockStar
+}
+
+impl CommentContext {
+    /**
+     * 初始化一个注释上下文句柄
+     */
+    pub fn new() -> CommentContext{ CommentContext { mode: CommentMode::Code }}
+    /**
+     * 通过循环调用该函数的方式过滤注释
+     * # 返回值: Some(c)代表需要交给其他上下文解析的字符,None代表这个字符属于注释
+     * # Err: 单独出现的 '/'
+     */
+    pub fn check_comment(&mut self, c:char, options:&ParseOptions) ->Result<Option<char>,String>{
+        match self.mode {
+            CommentMode::Code => {
+                match c {
+                    '/'=>{
+                        self.mode=CommentMode::Slash;
+                        return Ok(None);
+                    }
+                    '"'=>{self.mode=CommentMode::Str(c);}
+                    '\''  if options.extensions.single_quoted_strings=>{self.mode=CommentMode::Str(c);}
+                    _=>{}
+                }
+                return Ok(Some(c));
+            },
+            CommentMode::Str(quote) => {
+                if c=='\\'{
+                    self.mode=CommentMode::StrZhuanyi(quote);
+                } else if c==quote{
+                    self.mode=CommentMode::Code;
+                }
+                return Ok(Some(c));
+            },
+            CommentMode::StrZhuanyi(quote) => {
+                self.mode=CommentMode::Str(quote);
+                return Ok(Some(c));
+            },
+            CommentMode::Slash => {
+                match c {
+                    '/'=>{self.mode=CommentMode::LineComment;}
+                    '*'=>{self.mode=CommentMode::BlockComment;}
+                    _=>{return Err("此处不应是:'/'".to_string());}
+                }
+                // 注释被当作一个空格
+                return Ok(Some(' '));
+            },
+            CommentMode::LineComment => {
+                if c=='\n' || c=='\r' || c=='\u{2028}' || c=='\u{2029}'{
+                    self.mode=CommentMode::Code;
+                    return Ok(Some(c));
+                }
+                return Ok(None);
+            },
+            CommentMode::BlockComment => {
+                if c=='*'{
+                    self.mode=CommentMode::BlockStar;
+                }
+                return Ok(None);
+            },
+            CommentMode::BlockStar => {
+                match c {
+                    '/'=>{self.mode=CommentMode::Code;}
+                    '*'=>{}
+                    _=>{self.mode=CommentMode::BlockComment;}
+                }
+                return Ok(None);
+            },
+        }
+    }
+    /**
+     * 输入已经结束,检查注释是否完整
+     */
+    pub fn finish(&self) ->Result<(),String>{
+        match self.mode {
+            CommentMode::Slash => {return Err("此处不应是:'/'".to_string());},
+            CommentMode::BlockComment|CommentMode::BlockStar => {return Err("多行注释没有结束,缺少'*/'".to_string());},
+            _ => {return Ok(());},
+        }
+    }
+}
@@ -17,7 +17,6 @@
  * You should have received a copy of the GNU Affero General Public License
  * along with this program.  If not, see <https://www.gnu.org/licenses/>. 
  */
-
 #[allow(non_camel_case_types)]
 #[allow(dead_code)]
 /**
@@ -28,3 +28,5 @@ pub mod array;
 
 pub mod object;
 
+pub mod root;
+pub mod comment;
@@ -17,7 +17,7 @@
  * You should have received a copy of the GNU Affero General Public License
  * along with this program.  If not, see <https://www.gnu.org/licenses/>. 
  */
-use std::num::ParseFloatError;
+use crate::zjy::json::options::{NumberMode, ParseOptions, Strictness};
 /**
  * 保存解析number时所需要的上下文的结构体
  */
@@ -30,7 +30,7 @@ impl NumberContext {
     /**
      * 获取NumberContext中number_str字段的可变借用
      */
-    pub fn get_number_str_mut<'a>(&'a mut self) ->&'a mut String{ return &mut self.number_str; }
+    pub fn get_number_str_mut(&mut self) ->&mut String{ return &mut self.number_str; }
     /**
      * 获取NumberContext中number字段的值,这是已经解析的浮点数的值
      */
@@ -41,20 +41,155 @@ impl NumberContext {
      * 指定number_str字段初始化NumberContext
      */
     pub fn new_from_str (str:&str) ->NumberContext{NumberContext { number_str: String::from(str), number: 0.0 }}
+    #[allow(dead_code)]
     /**
      * 初始化NumberContext
      */
     pub fn new() -> NumberContext{NumberContext { number_str: String::new(), number: 0.0 }}
+    /**
+     * 判断c是否可能是number的一部分,array和object用它判断number是否已经结束
+     */
+    pub fn is_number_char(c:char, options:&ParseOptions) ->bool{
+        if options.extensions.json5_numbers{
+            // 16进制数字,Infinity和NaN
+            return c.is_ascii_alphanumeric() || matches!(c, '-'|'+'|'.');
+        }
+        return matches!(c, '0'..='9'|'-'|'+'|'E'|'e'|'.');
+    }
     /**
      * 将str参数加到number_str的后面,然后尝试将number_str转化成浮点数,成功会把转化
      * 之后的得到的浮点数保存在number字段中,如果转化失败,返回Err
+     * 严格模式下number_str必须符合json的number语法
      */
-    pub fn check_number(&mut self, str:&str) ->Result<(),ParseFloatError>{
+    pub fn check_number(&mut self, str:&str, options:&ParseOptions) ->Result<(),String>{
         self.number_str.push_str(str);
-        self.number =  self.number_str.parse::<f64>()?;
+        if options.extensions.json5_numbers{
+            return self.check_json5_number();
+        }
+        if options.strictness==Strictness::Strict && !is_json_number(&self.number_str){
+            return Err(std::format!("'{}'不符合json的number语法",self.number_str));
+        }
+        self.number = match self.number_str.parse::<f64>() {
+            Ok(n) => n,
+            Err(err) => {return Err(std::format!("{}",err));},
+        };
+        if !self.number.is_finite(){
+            return Err(std::format!("'{}'超出了f64的范围",self.number_str));
+        }
+        if options.number_mode==NumberMode::Lossless && !is_exact_integer(&self.number_str,self.number){
+            return Err(std::format!("'{}'不能被f64精确表示",self.number_str));
+        }
         return Ok(());
     }
 }
+impl NumberContext {
+    /**
+     * 按照json5的语法解析number_str: 可以有+号,16进制,小数点前后可以没有数字,以及Infinity和NaN
+     */
+    fn check_json5_number(&mut self) ->Result<(),String>{
+        let (negative,body)=match self.number_str.strip_prefix('-') {
+            Some(body) => (true,body),
+            None => (false,self.number_str.strip_prefix('+').unwrap_or(&self.number_str)),
+        };
+        let value=if body=="Infinity"{
+            f64::INFINITY
+        } else if body=="NaN"{
+            f64::NAN
+        } else if let Some(hex)=body.strip_prefix("0x").or_else(|| body.strip_prefix("0X")){
+            if hex.is_empty() || !hex.chars().all(|c| c.is_ascii_hexdigit()){
+                return Err(std::format!("'{}'不是合法的16进制数字",self.number_str));
+            }
+            let mut n:f64=0.0;
+            for c in hex.chars(){
+                n=n*16.0+c.to_digit(16).unwrap_or(0) as f64;
+            }
+            n
+        } else {
+            if !is_json5_decimal(body){
+                return Err(std::format!("'{}'不符合json5的number语法",self.number_str));
+            }
+            let n=match body.parse::<f64>() {
+                Ok(n) => n,
+                Err(err) => {return Err(std::format!("{}",err));},
+            };
+            if !n.is_finite(){
+                return Err(std::format!("'{}'超出了f64的范围",self.number_str));
+            }
+            n
+        };
+        self.number= if negative { -value } else { value };
+        return Ok(());
+    }
+}
+/**
+ * 判断字符串是否符合json5中不带符号的10进制number语法: (0|[1-9][0-9]*)?(\.[0-9]*)?([eE][+-]?[0-9]+)?
+ * 小数点前后至少要有一个数字
+ */
+fn is_json5_decimal(s:&str) ->bool{
+    let bytes=s.as_bytes();
+    let mut i=0;
+    let mut digits=0;
+    match bytes.get(i) {
+        Some(b'0') => {i+=1;digits+=1;},
+        Some(b'1'..=b'9') => {
+            while matches!(bytes.get(i),Some(b'0'..=b'9')) {i+=1;digits+=1;}
+        },
+        _ => {},
+    }
+    if bytes.get(i)==Some(&b'.'){
+        i+=1;
+        while matches!(bytes.get(i),Some(b'0'..=b'9')) {i+=1;digits+=1;}
+    }
+    if digits==0{
+        return false;
+    }
+    if matches!(bytes.get(i),Some(b'e'|b'E')){
+        i+=1;
+        if matches!(bytes.get(i),Some(b'+'|b'-')) {i+=1;}
+        if !matches!(bytes.get(i),Some(b'0'..=b'9')) {return false;}
+        while matches!(bytes.get(i),Some(b'0'..=b'9')) {i+=1;}
+    }
+    return i==bytes.len();
+}
+/**
+ * 如果number_str是整数,判断number是否恰好等于它,小数和指数形式总是返回true
+ */
+fn is_exact_integer(number_str:&str, number:f64) ->bool{
+    if number_str.contains(['.','e','E']) || number.abs() < 9007199254740992.0{
+        return true;
+    }
+    let digits=number_str.trim_start_matches(['-','+']).trim_start_matches('0');
+    return digits==std::format!("{:.0}",number.abs());
+}
+/**
+ * 判断字符串是否符合json的number语法: -?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?
+ */
+pub(crate) fn is_json_number(s:&str) ->bool{
+    let bytes=s.as_bytes();
+    let mut i=0;
+    if bytes.get(i)==Some(&b'-'){
+        i+=1;
+    }
+    match bytes.get(i) {
+        Some(b'0') => {i+=1;},
+        Some(b'1'..=b'9') => {
+            while matches!(bytes.get(i),Some(b'0'..=b'9')) {i+=1;}
+        },
+        _ => {return false;},
+    }
+    if bytes.get(i)==Some(&b'.'){
+        i+=1;
+        if !matches!(bytes.get(i),Some(b'0'..=b'9')) {return false;}
+        while matches!(bytes.get(i),Some(b'0'..=b'9')) {i+=1;}
+    }
+    if matches!(bytes.get(i),Some(b'e'|b'E')){
+        i+=1;
+        if matches!(bytes.get(i),Some(b'+'|b'-')) {i+=1;}
+        if !matches!(bytes.get(i),Some(b'0'..=b'9')) {return false;}
+        while matches!(bytes.get(i),Some(b'0'..=b'9')) {i+=1;}
+    }
+    return i==bytes.len();
+}
 #[cfg(test)]
 #[test]
 fn test_f64tostr(){
@@ -64,3 +199,19 @@ fn test_f64tostr(){
     println!("str={},f={}",str,f);
     
 }
+#[cfg(test)]
+#[test]
+fn test_check_number_strict(){
+    let options=ParseOptions::default();
+    for ok in ["0","-0","1.5","-1E+10","314.15926e-2"]{
+        assert!(NumberContext::new().check_number(ok,&options).is_ok(),"{}",ok);
+    }
+    for err in ["01","1.","-",".5","1e","1e400","--1"]{
+        assert!(NumberContext::new().check_number(err,&options).is_err(),"{}",err);
+    }
+    let legacy=ParseOptions::legacy();
+    assert!(NumberContext::new().check_number("01",&legacy).is_ok());
+    let lossless=ParseOptions{number_mode:NumberMode::Lossless,..ParseOptions::default()};
+    assert!(NumberContext::new().check_number("9007199254740993",&lossless).is_err());
+    assert!(NumberContext::new().check_number("9007199254740992",&lossless).is_ok());
+}
@@ -19,18 +19,30 @@
  */
 use std::collections::HashMap;
 
-use crate::zjy::json::{ast::string::StringMode, Json, judgment_json_type};
+use crate::zjy::json::{ast::string::StringMode, Json, judgment_json_type, error::JsonError, options::{DuplicateKeys, ParseOptions}, span::{Position, Span, SpanChildren, SpanTree}, diagnostic::Expected};
+
+use crate::zjy::json::json5::{is_identifier_part, is_identifier_start};
 
 use super::{string::StringContext, null::NullMode, r#true::TrueMode, r#false::FalseMode, array::{ArrayContext, ArrayMode}, number::NumberContext};
 
 pub struct ObjectContext{
     obj:HashMap<String,Json>,
     mode:ObjectMode,
-    key_tmp:String
-
+    key_tmp:String,
+    /// 这个object的嵌套深度,最外层为1
+    depth:usize,
+    /// 开启范围记录时保存每个成员的范围树
+    spans:Option<HashMap<String,SpanTree>>,
+    /// 当前key开始的位置
+    key_start:Position,
+    /// 当前key的范围
+    key_span:Span,
+    /// 当前value开始的位置
+    value_start:Position
 }
 pub enum ObjectMode {
     Key(StringContext),
+    KeyIdentifier(String),
     Maohao,
     Value,
     ValueNull(NullMode),ValueTrue(TrueMode),ValueFalse(FalseMode),ValueString(StringContext),
@@ -49,26 +61,128 @@ impl ObjectContext {
     pub fn get_json(&self) -> Json{
         return Json::Object(self.obj.clone());
     }
-    pub fn get_mode_ptr<'a>(&'a self) ->&'a ObjectMode{ return &self.mode; }
+    pub fn get_mode_ptr(&self) ->&ObjectMode{ return &self.mode; }
     pub fn into_json(self) ->Json {return Json::Object(self.obj);}
-    pub fn new_key() ->ObjectContext{ ObjectContext { obj:HashMap::new(), mode: ObjectMode::Key(StringContext::new()), key_tmp:String::new() }}
-    pub fn check_object(&mut self, c:char) ->Result<(),String>{
+    pub fn new_key(depth:usize, spanned:bool) ->ObjectContext{
+        ObjectContext { obj:HashMap::new(), mode: ObjectMode::Key(StringContext::new()), key_tmp:String::new(), depth,
+            spans: if spanned { Some(HashMap::new()) } else { None }, key_start: Position::start(), key_span: Span::default(), value_start: Position::start() }
+    }
+    /**
+     * 取出成员的范围树,没有开启范围记录时返回SpanChildren::Scalar
+     */
+    pub fn take_spans(&mut self) -> SpanChildren{
+        match self.spans.take() {
+            Some(spans) => {return SpanChildren::Object(spans);},
+            None => {return SpanChildren::Scalar;},
+        }
+    }
+    /**
+     * 把解析出的value和key_tmp中的key存入hashmap,并清空key_tmp
+     * 重复的key按照options.duplicate_keys处理,开启范围记录时同时记录key和value(到end为止)的范围
+     */
+    fn insert_value(&mut self, value:Json, end:Position, children:SpanChildren, options:&ParseOptions) ->Result<(),JsonError>{
+        let key=std::mem::take(&mut self.key_tmp);
+        if self.obj.contains_key(&key){
+            match options.duplicate_keys {
+                DuplicateKeys::Last => {},
+                DuplicateKeys::First => {return Ok(());},
+                DuplicateKeys::Error => {return Err(std::format!("object中的key重复:\"{}\"",key).into());},
+            }
+        }
+        if let Some(spans)=&mut self.spans{
+            spans.insert(key.clone(), SpanTree { span: Span { start: self.value_start, end }, key: Some(self.key_span), children });
+        }
+        self.obj.insert(key, value);
+        options.check_object_members(self.obj.len())?;
+        return Ok(());
+    }
+    /**
+     * 当前位置需要的符号,用于错误提示;正在解析number或string时返回None
+     */
+    pub fn expected(&self, options:&ParseOptions) ->Option<Vec<Expected>>{
+        match &self.mode {
+            ObjectMode::Key(context) => {
+                if *context.get_mode()!=StringMode::start{
+                    return None;
+                }
+                if self.obj.is_empty() || options.extensions.trailing_commas{
+                    return Some(vec![Expected::Key, Expected::Char('}')]);
+                }
+                return Some(vec![Expected::Key]);
+            },
+            ObjectMode::KeyIdentifier(_) => {return None;},
+            ObjectMode::Maohao => {return Some(vec![Expected::Char(':')]);},
+            ObjectMode::Value => {return Some(vec![Expected::Value]);},
+            ObjectMode::ValueNull(_) => {return Some(vec![Expected::Literal("null")]);},
+            ObjectMode::ValueTrue(_) => {return Some(vec![Expected::Literal("true")]);},
+            ObjectMode::ValueFalse(_) => {return Some(vec![Expected::Literal("false")]);},
+            ObjectMode::ValueString(_)|ObjectMode::ValueNumber(_) => {return None;},
+            ObjectMode::ValueArray(context) => {return context.expected(options);},
+            ObjectMode::ValueObject(context) => {return context.expected(options);},
+            ObjectMode::Douhao => {return Some(vec![Expected::Char(','), Expected::Char('}')]);},
+            ObjectMode::End => {return Some(Vec::new());},
+        }
+    }
+    /**
+     * 判断当前是否正在解析number
+     */
+    pub fn in_number(&self) ->bool{
+        match &self.mode {
+            ObjectMode::ValueNumber(_) => {return true;},
+            ObjectMode::ValueArray(context) => {return context.in_number();},
+            ObjectMode::ValueObject(context) => {return context.in_number();},
+            _ => {return false;},
+        }
+    }
+    /**
+     * 通过循环调用该函数的方式解析json对象,如果mode字段是End,代表已经解析完毕
+     * 如果超出options中的资源限制,返回JsonError::LimitExceeded
+     * pos是字符c在输入中的位置,用于记录key和value的范围
+     */
+    pub fn check_object(&mut self, c:char, pos:Position, options:&ParseOptions) ->Result<(),JsonError>{
         match &mut self.mode {
             // object中的key,必须为string
             ObjectMode::Key(stringcontext) => {
-                if let Err(_)=stringcontext.check_string(c){
-                    return Err(std::format!("这个object中的name不合法"));
+                // 空object,或者允许末尾逗号时逗号后面直接结束
+                if c=='}' && *stringcontext.get_mode()==StringMode::start && (self.obj.is_empty() || options.extensions.trailing_commas){
+                    self.mode=ObjectMode::End;
+                    return Ok(());
                 }
+                if *stringcontext.get_mode()==StringMode::start && !options.whitespace.contains(c){
+                    self.key_start=pos;
+                }
+                // json5中不带引号的key
+                if options.extensions.unquoted_keys && *stringcontext.get_mode()==StringMode::start && is_identifier_start(c){
+                    self.mode=ObjectMode::KeyIdentifier(c.to_string());
+                    return Ok(());
+                }
+                if stringcontext.check_string(c, options).is_err(){
+                    return Err("这个object中的name不合法".to_string().into());
+                }
+                options.check_string_length(stringcontext.get_str().len())?;
                 // 如果解析完成
                 if let StringMode::end= stringcontext.get_mode(){
                     // 把name存进临时变量
                     self.key_tmp.push_str(stringcontext.get_str());
+                    self.key_span=Span { start: self.key_start, end: pos.advance(c) };
                     // 切换模式
                     self.mode=ObjectMode::Maohao;
                 }
                 // 返回
                 return Ok(());
             },
+            ObjectMode::KeyIdentifier(ident) => {
+                if is_identifier_part(c){
+                    ident.push(c);
+                    options.check_string_length(ident.len())?;
+                    return Ok(());
+                }
+                // 标识符已经结束,立即检查一次这个字符
+                self.key_tmp=std::mem::take(ident);
+                self.key_span=Span { start: self.key_start, end: pos };
+                self.mode=ObjectMode::Maohao;
+                return self.check_object(c, pos, options);
+            },
             ObjectMode::Maohao => {
                 // key和value的分隔符
                 match c {
@@ -77,25 +191,26 @@ impl ObjectContext {
                         self.mode=ObjectMode::Value;
                         return Ok(());
                     }
-                    '\r'|'\n'|'\x20'=>{
-                        // 允许空格回车
+                    _ if options.whitespace.contains(c)=>{
+                        // 允许空白
                         return Ok(());
                     }
                     _=>{
                         // 不允许出现其他字符
-                        return Err(std::format!("此处需要 ':' ,但实际为: '{}' ",c));
+                        return Err(std::format!("此处需要 ':' ,但实际为: '{}' ",c).into());
                     }
                 }
             },
             // 需要判断json的类型
             ObjectMode::Value => {
                 match c {
-                    '\r'|'\n'|'\x20'=>{
-                        // 允许空格回车
+                    _ if options.whitespace.contains(c)=>{
+                        // 允许空白
                         return Ok(());
                     }
                     _=>{
-                        match judgment_json_type(c)? {
+                        self.value_start=pos;
+                        match judgment_json_type(c, options)? {
                             Json::Null => {
                                 self.mode=ObjectMode::ValueNull(NullMode::u);
                                 return Ok(());
@@ -109,23 +224,26 @@ impl ObjectContext {
                                 return Ok(());
                             },
                             Json::Number(_) => {
+                                options.check_number_length(1)?;
                                 // 切换模式
                                 self.mode=ObjectMode::ValueNumber(NumberContext::new_from_str(&c.to_string()));
                                 return Ok(());
                             },
                             Json::String(_) => {
                                 // 切换模式
-                                self.mode=ObjectMode::ValueString(StringContext { str: String::new(), mode: StringMode::str });
+                                self.mode=ObjectMode::ValueString(StringContext::new_str(c));
                                 return Ok(());
                             },
                             Json::Object(_) => {
                                 // 切换模式
-                                self.mode=ObjectMode::ValueObject(Box::new(ObjectContext::new_key()));
+                                options.check_depth(self.depth+1)?;
+                                self.mode=ObjectMode::ValueObject(Box::new(ObjectContext::new_key(self.depth+1, self.spans.is_some())));
                                 return Ok(());
                             },
                             Json::Array(_) => {
                                 // 切换模式
-                                self.mode=ObjectMode::ValueArray(ArrayContext::new_judgment_type());
+                                options.check_depth(self.depth+1)?;
+                                self.mode=ObjectMode::ValueArray(ArrayContext::new_judgment_type(self.depth+1, self.spans.is_some()));
                                 return Ok(());
                             },
                         }
@@ -139,9 +257,7 @@ impl ObjectContext {
                         if isfinish{
                             // 如果已经识别完
                             // 存入hashmap
-                            self.obj.insert(self.key_tmp.clone(), Json::Null);
-                            // 清空tmp
-                            self.key_tmp=String::new();
+                            self.insert_value(Json::Null, pos.advance(c), SpanChildren::Scalar, options)?;
                             // 切换模式
                             self.mode=ObjectMode::Douhao;
                         }
@@ -149,7 +265,7 @@ impl ObjectContext {
                         return Ok(());
                     },
                     Err(_) => {
-                        return Err(std::format!("null解析失败,实际上的字符:{}",c));
+                        return Err(std::format!("null解析失败,实际上的字符:{}",c).into());
                     },
                 }
             },
@@ -159,16 +275,14 @@ impl ObjectContext {
                         if isfinish{
                             // 如果已经识别完
                             // 存入hashmap
-                            self.obj.insert(self.key_tmp.clone(), Json::Boolean(true));
-                            // 清空tmp
-                            self.key_tmp=String::new();
+                            self.insert_value(Json::Boolean(true), pos.advance(c), SpanChildren::Scalar, options)?;
                             // 切换模式
                             self.mode=ObjectMode::Douhao;
                         }
                         // 返回
                         return Ok(());
                     },
-                    Err(_) => {return Err(std::format!("true解析失败,实际上的字符:{}",c));},
+                    Err(_) => {return Err(std::format!("true解析失败,实际上的字符:{}",c).into());},
                 }
             },
             ObjectMode::ValueFalse(context) => {
@@ -177,22 +291,20 @@ impl ObjectContext {
                         if isfinish{
                             // 如果已经识别完
                             // 存入hashmap
-                            self.obj.insert(self.key_tmp.clone(), Json::Boolean(false));
-                            // 清空tmp
-                            self.key_tmp=String::new();
+                            self.insert_value(Json::Boolean(false), pos.advance(c), SpanChildren::Scalar, options)?;
                             // 切换模式
                             self.mode=ObjectMode::Douhao;
                         }
                         // 返回
                         return Ok(());
                     },
-                    Err(_) => {return Err(std::format!("false解析失败,实际上的字符:{}",c));},
+                    Err(_) => {return Err(std::format!("false解析失败,实际上的字符:{}",c).into());},
                 }
 
             },
             ObjectMode::End => {
                 // 不应该调用
-                return Err(std::format!("这个Object已经解析完毕,不应该再次调用check_object函数"));
+                return Err("这个Object已经解析完毕,不应该再次调用check_object函数".to_string().into());
             },
             ObjectMode::Douhao => {
                 match c {
@@ -201,8 +313,8 @@ impl ObjectContext {
                         self.mode=ObjectMode::Key(StringContext::new());
                         return Ok(());
                     }
-                    '\r'|'\n'|'\x20'=>{
-                        // 允许空格回车
+                    _ if options.whitespace.contains(c)=>{
+                        // 允许空白
                         return Ok(());
                     }
                     '}'=>{
@@ -212,80 +324,83 @@ impl ObjectContext {
                     }
                     _=>{
                         // 其他字符
-                        return Err(std::format!("这里需要 ',' 但实际为{}",c));
+                        return Err(std::format!("这里需要 ',' 但实际为{}",c).into());
                     }
                     
                 }
             },
             ObjectMode::ValueString(context) => {
-                match context.check_string(c) {
+                match context.check_string(c, options) {
                     Ok(_) => {
+                        options.check_string_length(context.get_str().len())?;
                         // 如果解析完成
                         if let StringMode::end=context.get_mode(){
                             // 存入hashmap
-                            self.obj.insert(self.key_tmp.clone(), Json::String(context.get_str().to_string()));
-                            // 清空tmp
-                            self.key_tmp=String::new();
+                            let value=Json::String(context.get_str().to_string());
+                            self.insert_value(value, pos.advance(c), SpanChildren::Scalar, options)?;
                             // 切换模式
                             self.mode=ObjectMode::Douhao;
                         }
                         // 返回
                         return Ok(());
                     },
-                    Err(_) => {return Err(std::format!("string解析失败,实际上的字符:{}",c));},
+                    Err(_) => {return Err(std::format!("string解析失败,实际上的字符:{}",c).into());},
                 }
             },
             ObjectMode::ValueArray(context) => {
-                match context.check_array(c) {
+                match context.check_array(c, pos, options) {
                     Ok(_) => {
                         // 如果解析完成
                         if let ArrayMode::End =context.get_mode_ptr(){
                             // 存入hashmap
-                            self.obj.insert(self.key_tmp.clone(), Json::Array(context.get_arr()));
-                            // 清空tmp
-                            self.key_tmp=String::new();
+                            let value=Json::Array(context.get_arr());
+                            let children=context.take_spans();
+                            self.insert_value(value, pos.advance(c), children, options)?;
                             // 切换模式
                             self.mode=ObjectMode::Douhao;
                         }
                         // 返回
                         return Ok(());
                     },
-                    Err(err) => {return Err(std::format!("array解析失败,错误原因{}",err));},
+                    Err(err) => {return Err(err.prefix("array解析失败,错误原因"));},
                 }
             },
             ObjectMode::ValueNumber(context) => {
                 match c {
                     // 关于浮点数的字符全存进去
-                    '0'|'1'|'2'|'3'|'4'|'5'|'6'|'7'|'8'|'9'|'-'|'E'|'e'|'.'=>{
+                    _ if NumberContext::is_number_char(c, options)=>{
                         context.get_number_str_mut().push(c);
+                        options.check_number_length(context.get_number_str_mut().len())?;
                         return Ok(());
                     }
                     _=>{
                         // 如果出现其他字符,说明浮点数已经到头了
-                        match context.check_number("") {
+                        match context.check_number("", options) {
                             Ok(_) => {
                                 // 浮点数合法
                                 // 存入hashmap
-                                self.obj.insert(self.key_tmp.clone(), Json::Number(context.get_number()));
-                                // 清空tmp
-                                self.key_tmp=String::new();
+                                let value=Json::Number(context.get_number());
+                                // number在这个字符之前结束
+                                self.insert_value(value, pos, SpanChildren::Scalar, options)?;
                                 // 切换模式
                                 self.mode=ObjectMode::Douhao;
                                 // 立即执行一次检测
-                                return self.check_object(c);
+                                return self.check_object(c, pos, options);
                             },
-                            Err(err) => {Err(std::format!("浮点数解析失败,实际的字符为:{},异常对象:{}",c,err))},
+                            Err(err) => {Err(std::format!("浮点数解析失败,实际的字符为:{},异常对象:{}",c,err).into())},
                         }
                     }
                 }
             },
             ObjectMode::ValueObject(context) => {
                 // 解析n+1obj
-                context.check_object(c)?;
+                context.check_object(c, pos, options)?;
                 // 判断解析是否完成
                 if let ObjectMode::End=context.mode{
                     // 子obj解析完成
-                    self.obj.insert(self.key_tmp.clone(), Json::Object(context.obj.clone()));
+                    let value=Json::Object(context.obj.clone());
+                    let children=context.take_spans();
+                    self.insert_value(value, pos.advance(c), children, options)?;
                     // 切换模式
                     self.mode=ObjectMode::Douhao;
                 }
@@ -0,0 +1,270 @@
+/**
+ * 文件名: "src/zjy/json/ast/root.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::zjy::json::{Json, judgment_json_type, ast::string::StringMode, error::JsonError, options::ParseOptions, span::{Position, Span, SpanChildren, SpanTree}, diagnostic::Expected};
+
+use super::{null::NullMode, r#true::TrueMode, r#false::FalseMode, number::NumberContext, string::StringContext, array::{ArrayContext, ArrayMode}, object::{ObjectContext, ObjectMode}};
+
+/**
+ * 解析最外层json值需要保存的上下文
+ * 和array中的元素一样逐个字符解析,区别是最外层的值后面只允许出现空白,
+ * 并且number可以在输入结束时结束
+ */
+pub struct RootContext{
+    /// 存储解析对象的行为枚举
+    mode:RootMode,
+    /// 解析完成的json值
+    value:Option<Json>,
+    /// 是否记录每个值的范围
+    spanned:bool,
+    /// 最外层值开始的位置
+    start:Position,
+    /// 解析完成的范围树
+    spans:Option<SpanTree>
+}
+/**
+ * 存储解析对象的行为枚举
+ * JudgmentType代表需要判断最外层的json值是什么类型
+ * End代表json值已经解析完毕,只允许出现空白
+ */
+pub enum RootMode {
+    JudgmentType,Null(NullMode),JsonTrue(TrueMode),
+        JsonFalse(FalseMode),NumberContext(NumberContext),String(StringContext),Array(Box<ArrayContext>),Object(Box<ObjectContext>),
+    End
+}
+
+impl RootContext {
+    /**
+     * 初始化一个root上下文句柄
+     */
+    pub fn new() -> RootContext{ RootContext { mode: RootMode::JudgmentType, value: None, spanned: false, start: Position::start(), spans: None }}
+    /**
+     * 初始化一个记录每个值范围的root上下文句柄
+     */
+    pub fn new_spanned() -> RootContext{ RootContext { spanned: true, ..RootContext::new() }}
+    /**
+     * 取出范围树,需要在finish()成功之后调用
+     */
+    pub fn take_spans(&mut self) ->Option<SpanTree>{ return self.spans.take(); }
+    /**
+     * 判断最外层的json值是否已经解析完毕
+     */
+    pub fn is_end(&self) ->bool{ return matches!(self.mode, RootMode::End); }
+    /**
+     * 判断最外层的json值是否还没有开始
+     */
+    pub fn is_start(&self) ->bool{ return matches!(self.mode, RootMode::JudgmentType); }
+    /**
+     * 前缀解析时判断字符c是否已经在最外层的json值后面:
+     * 值已经解析完毕,或者最外层的number遇到了不属于number的字符
+     */
+    pub fn ends_before(&self, c:char, options:&ParseOptions) ->bool{
+        match &self.mode {
+            RootMode::End => {return true;},
+            RootMode::NumberContext(_) => {return !NumberContext::is_number_char(c, options);},
+            _ => {return false;},
+        }
+    }
+    /**
+     * 当前位置需要的符号,用于错误提示;正在解析number或string时返回None
+     */
+    pub fn expected(&self, options:&ParseOptions) ->Option<Vec<Expected>>{
+        match &self.mode {
+            RootMode::JudgmentType => {return Some(vec![Expected::Value]);},
+            RootMode::Null(_) => {return Some(vec![Expected::Literal("null")]);},
+            RootMode::JsonTrue(_) => {return Some(vec![Expected::Literal("true")]);},
+            RootMode::JsonFalse(_) => {return Some(vec![Expected::Literal("false")]);},
+            RootMode::NumberContext(_)|RootMode::String(_) => {return None;},
+            RootMode::Array(context) => {return context.expected(options);},
+            RootMode::Object(context) => {return context.expected(options);},
+            RootMode::End => {return Some(vec![Expected::EndOfInput]);},
+        }
+    }
+    /**
+     * 判断当前是否正在解析number
+     */
+    pub fn in_number(&self) ->bool{
+        match &self.mode {
+            RootMode::NumberContext(_) => {return true;},
+            RootMode::Array(context) => {return context.in_number();},
+            RootMode::Object(context) => {return context.in_number();},
+            _ => {return false;},
+        }
+    }
+    /**
+     * 通过循环调用该函数的方式解析最外层的json值
+     * 如果json不合法,返回Err,错误信息不包含位置
+     * pos是字符c在输入中的位置,用于记录值的范围
+     */
+    pub fn check_root(&mut self, c:char, pos:Position, options:&ParseOptions) ->Result<(),JsonError>{
+        match self.mode {
+            RootMode::JudgmentType => {
+                if options.whitespace.contains(c){
+                    return Ok(());
+                }
+                self.start=pos;
+                match judgment_json_type(c, options)? {
+                    Json::Null => {self.mode=RootMode::Null(NullMode::u);},
+                    Json::Boolean(b) => {
+                        self.mode= if b { RootMode::JsonTrue(TrueMode::r) } else { RootMode::JsonFalse(FalseMode::a) };
+                    },
+                    Json::Number(_) => {
+                        options.check_number_length(1)?;
+                        self.mode=RootMode::NumberContext(NumberContext::new_from_str(&c.to_string()));
+                    },
+                    Json::String(_) => {
+                        self.mode=RootMode::String(StringContext::new_str(c));
+                    },
+                    Json::Object(_) => {
+                        options.check_depth(1)?;
+                        self.mode=RootMode::Object(Box::new(ObjectContext::new_key(1, self.spanned)));
+                    },
+                    Json::Array(_) => {
+                        options.check_depth(1)?;
+                        self.mode=RootMode::Array(Box::new(ArrayContext::new_judgment_type(1, self.spanned)));
+                    },
+                }
+                return Ok(());
+            },
+            RootMode::Null(ref mut context) => {
+                match context.check_null(c) {
+                    Ok(isfinish) => {
+                        if isfinish{
+                            self.finish_value(Json::Null, pos.advance(c), SpanChildren::Scalar);
+                        }
+                        return Ok(());
+                    },
+                    Err(_) => {return Err(std::format!("此处不应是:'{}'",c).into());},
+                }
+            },
+            RootMode::JsonTrue(ref mut context) => {
+                match context.check_true(c) {
+                    Ok(isfinish) => {
+                        if isfinish{
+                            self.finish_value(Json::Boolean(true), pos.advance(c), SpanChildren::Scalar);
+                        }
+                        return Ok(());
+                    },
+                    Err(_) => {return Err(std::format!("此处不应是:'{}'",c).into());},
+                }
+            },
+            RootMode::JsonFalse(ref mut context) => {
+                match context.check_false(c) {
+                    Ok(isfinish) => {
+                        if isfinish{
+                            self.finish_value(Json::Boolean(false), pos.advance(c), SpanChildren::Scalar);
+                        }
+                        return Ok(());
+                    },
+                    Err(_) => {return Err(std::format!("此处不应是:'{}'",c).into());},
+                }
+            },
+            RootMode::NumberContext(ref mut context) => {
+                if NumberContext::is_number_char(c, options){
+                    context.get_number_str_mut().push(c);
+                    options.check_number_length(context.get_number_str_mut().len())?;
+                    return Ok(());
+                }
+                // 出现其他字符,说明number已经到头了
+                if let Err(err)=context.check_number("", options){
+                    return Err(std::format!("不是一个合法的数字,错误对象:{}",err).into());
+                }
+                let number=context.get_number();
+                // number在这个字符之前结束
+                self.finish_value(Json::Number(number), pos, SpanChildren::Scalar);
+                // 立即检查一次这个字符
+                return self.check_root(c, pos, options);
+            },
+            RootMode::String(ref mut context) => {
+                match context.check_string(c, options) {
+                    Ok(_) => {
+                        options.check_string_length(context.get_str().len())?;
+                        if *context.get_mode()==StringMode::end{
+                            let str=std::mem::take(&mut context.str);
+                            self.finish_value(Json::String(str), pos.advance(c), SpanChildren::Scalar);
+                        }
+                        return Ok(());
+                    },
+                    Err(_) => {return Err(std::format!("此处不应是:'{}'",c).into());},
+                }
+            },
+            RootMode::Array(ref mut context) => {
+                context.check_array(c, pos, options)?;
+                if let ArrayMode::End=context.get_mode_ptr(){
+                    if let RootMode::Array(mut context)=std::mem::replace(&mut self.mode, RootMode::End){
+                        let children=context.take_spans();
+                        self.finish_value(context.into_json(), pos.advance(c), children);
+                    }
+                }
+                return Ok(());
+            },
+            RootMode::Object(ref mut context) => {
+                context.check_object(c, pos, options)?;
+                if let ObjectMode::End=context.get_mode_ptr(){
+                    if let RootMode::Object(mut context)=std::mem::replace(&mut self.mode, RootMode::End){
+                        let children=context.take_spans();
+                        self.finish_value(context.into_json(), pos.advance(c), children);
+                    }
+                }
+                return Ok(());
+            },
+            RootMode::End => {
+                if options.whitespace.contains(c){
+                    return Ok(());
+                }
+                return Err(std::format!("json已经结束,此处不应是:'{}'",c).into());
+            },
+        }
+    }
+    /**
+     * 输入已经结束,取出解析完成的json值
+     * 如果json值还没有结束(number除外),返回Err
+     * end是输入结束的位置
+     */
+    pub fn finish(&mut self, end:Position, options:&ParseOptions) ->Result<Json,JsonError>{
+        match self.mode {
+            RootMode::JudgmentType => {return Err("没能解析出任何json对象".to_string().into());},
+            RootMode::Null(_) => {return Err("null字符没有结束".to_string().into());},
+            RootMode::JsonTrue(_) => {return Err("true字符串没有结束".to_string().into());},
+            RootMode::JsonFalse(_) => {return Err("false字符串没有结束".to_string().into());},
+            RootMode::NumberContext(ref mut context) => {
+                if let Err(err)=context.check_number("", options){
+                    return Err(std::format!("不是一个合法的数字,错误对象:{}",err).into());
+                }
+                let number=context.get_number();
+                self.finish_value(Json::Number(number), end, SpanChildren::Scalar);
+            },
+            RootMode::String(_) => {return Err(std::format!("这个Sting类型缺少 '{}' 结束",'"').into());},
+            RootMode::Array(_) => {return Err("此处需要']'结束数组".to_string().into());},
+            RootMode::Object(_) => {return Err("这个Object并没有结束".to_string().into());},
+            RootMode::End => {},
+        }
+        match self.value.take() {
+            Some(json) => {return Ok(json);},
+            None => {return Err("json值已经被取出".to_string().into());},
+        }
+    }
+    fn finish_value(&mut self, json:Json, end:Position, children:SpanChildren){
+        self.value=Some(json);
+        if self.spanned{
+            self.spans=Some(SpanTree { span: Span { start: self.start, end }, key: None, children });
+        }
+        self.mode=RootMode::End;
+    }
+}
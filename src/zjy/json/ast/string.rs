@@ -17,7 +17,7 @@
  * You should have received a copy of the GNU Affero General Public License
  * along with this program.  If not, see <https://www.gnu.org/licenses/>. 
  */
-
+use crate::zjy::json::options::{ParseOptions, Strictness};
 
 #[allow(dead_code)]
 /**
@@ -27,7 +27,9 @@ pub struct StringContext{
     /// 已经识别到的json中的sting
     pub(crate) str:String,
     /// 识别string的模式
-    pub(crate) mode:StringMode
+    pub(crate) mode:StringMode,
+    /// 开始和结束string的引号,json中只能是 '"' ,json5中也可以是 '\''
+    pub(crate) quote:char
 }
 #[allow(dead_code)]
 #[allow(non_camel_case_types)]
@@ -37,10 +39,13 @@ pub struct StringContext{
  * str是表示string的正文
  * zhuanyi表示当前在转义模式
  * zhuanyi_utf表示当前在unicode转义模式,(u8,[u16;2])元组用于存储识别unicode所需的上下文
+ * zhuanyi_hex表示json5的\xHH转义模式,(u8,u8)元组是已经识别的16进制字符个数和值
+ * line_continuation表示json5中反斜杠后面跟着\r,如果下一个字符是\n需要一起跳过
+ * zhuanyi_null表示json5中刚刚识别了\0,后面不能紧跟数字
  * end表示字符串判断已经完成
  */
 pub enum StringMode {
-    start,str,zhuanyi,zhuanyi_utf(u8,[u16;2]),end
+    start,str,zhuanyi,zhuanyi_utf(u8,[u16;2]),zhuanyi_hex(u8,u8),line_continuation,zhuanyi_null,end
     
 }
 #[cfg(test)]
@@ -48,8 +53,9 @@ pub enum StringMode {
 fn test_check_string(){
     let str=std::fs::read_to_string("tests/teststring.json").unwrap();
     let mut context=StringContext::new();
+    let options=ParseOptions::default();
     for c in str.chars(){
-        context.check_string(c).unwrap();
+        context.check_string(c,&options).unwrap();
         if context.mode==StringMode::end{
             // 结束
             println!("json:{}",context.str);
@@ -61,21 +67,26 @@ impl StringContext {
     /**
      * 获取str字段的借用
      */
-    pub fn get_str<'a>(&'a self) -> &'a str { return &self.str; }
+    pub fn get_str(&self) -> &str { return &self.str; }
     /**
      * 获取mode字段的借用
      */
-    pub fn get_mode<'a>(&'a self) -> &'a StringMode { return &self.mode; }
+    pub fn get_mode(&self) -> &StringMode { return &self.mode; }
     /**
      * 初始化一个StringContext
      */
-    pub fn new() -> StringContext{ StringContext { str: String::new(), mode: StringMode::start }}
+    pub fn new() -> StringContext{ StringContext { str: String::new(), mode: StringMode::start, quote: '"' }}
+    /**
+     * 初始化一个已经读到开头引号的StringContext,quote是开头的引号
+     */
+    pub fn new_str(quote:char) -> StringContext{ StringContext { str: String::new(), mode: StringMode::str, quote }}
     #[allow(dead_code)]
     /**
      * 通过循环调用的方式解析string,如果句柄中的mode字段是end值,代表已经解析完毕
      * 如果string格式错误,返回Err
+     * 开头的空白和需要转义的控制字符由options决定
      */
-    pub fn check_string(&mut self, c:char) ->Result<(),()>{
+    pub fn check_string(&mut self, c:char, options:&ParseOptions) ->Result<(),()>{
         match self.mode {
             StringMode::start => {
                 match c {
@@ -83,8 +94,13 @@ impl StringContext {
                         self.mode=StringMode::str;
                         return Ok(());
                     }
-                    // 允许空格回车
-                    '\r'|'\n'|'\x20'=>{
+                    '\'' if options.extensions.single_quoted_strings=>{
+                        self.quote=c;
+                        self.mode=StringMode::str;
+                        return Ok(());
+                    }
+                    // 允许空白
+                    _ if options.whitespace.contains(c)=>{
                         return Ok(());
                     }
                     _=>{
@@ -94,7 +110,7 @@ impl StringContext {
             },
             StringMode::str => {
                 match c {
-                    '"'=>{
+                    _ if c==self.quote=>{
                         // 引号代表结束
                         self.mode=StringMode::end;
                         return Ok(());
@@ -105,12 +121,22 @@ impl StringContext {
 
                     }
                     
+                    // json5中除了换行都可以直接出现
+                    '\x00'..='\x1f' if options.extensions.json5_escapes && c!='\n' && c!='\r'=>{
+                        self.str.push(c);
+                        return Ok(());
+                    }
                     // \x08=\b  \x0c=\f  
                     '\x08'|'\x0c'|'\n'|'\r'|'\t'=>{
                         //必须通过转义的方式表示这些字符
                         self.mode=StringMode::end;
                         return Err(());
                     }
+                    // 严格模式下所有的控制字符都必须转义
+                    '\x00'..='\x1f' if options.strictness==Strictness::Strict=>{
+                        self.mode=StringMode::end;
+                        return Err(());
+                    }
                     _=>{
                         // 正常存入
                         self.str.push(c);
@@ -169,6 +195,9 @@ impl StringContext {
                         return Ok(());
 
                     }
+                    _ if options.extensions.json5_escapes=>{
+                        return self.check_json5_escape(c);
+                    }
                     _=>{
                         // 切回去
                         self.mode=StringMode::end;
@@ -187,33 +216,35 @@ impl StringContext {
                     };
                     // 循环乘,一次比一次少
                     for _x in 0..(3-(*ptr)){
-                        tmp=tmp*16;
+                        tmp *= 16;
                     }
                     //加进来
-                    point[0] = point[0] + tmp;
+                    point[0] += tmp;
                     // 如果已经是第4个,需要判断有没有下一个低4位
                     // 如果码点不在U+D800到U+DBFF
                     if (3==*ptr) && (!((0xD800 <= point[0]) && (point[0] <= 0xDBFF))){
-                        // rust char类型其实就是unicode的裸码点
-                        self.str.push(unsafe { char::from_u32_unchecked(point[0].into()) });
+                        // 单独出现的低代理项不是合法的码点
+                        match char::from_u32(point[0].into()) {
+                            Some(ch) => {self.str.push(ch);},
+                            None => {
+                                self.mode=StringMode::end;
+                                return Err(());
+                            },
+                        }
                         // 切换回去
                         self.mode=StringMode::str;
                         return Ok(());
                     }
                 } 
                 // ptr=4
-                if *ptr==4{
-                    if c!='\\'{
-                        self.mode=StringMode::end;
-                        return Err(());
-                    }
+                if *ptr==4 && c!='\\'{
+                    self.mode=StringMode::end;
+                    return Err(());
                 }
                 // ptr==5
-                if *ptr==5{
-                    if c!='u'{
-                        self.mode=StringMode::end;
-                        return Err(());
-                    }
+                if *ptr==5 && c!='u'{
+                    self.mode=StringMode::end;
+                    return Err(());
                 }
                 // ptr==6,这里是第二个转义序列
                 if (6==*ptr)||(7==*ptr)||(8==*ptr)||(9==*ptr){
@@ -227,10 +258,10 @@ impl StringContext {
                     };
                     // 循环乘16
                     for _x in 0..9-*ptr{
-                        tmp=tmp*16;
+                        tmp *= 16;
                     }
                     // 加进去
-                    point[1] = point[1] + tmp;
+                    point[1] += tmp;
                     // 判断点
                     if 9==*ptr{
                         // 低代理项必须在0xDC00到0xDFFF之间
@@ -259,9 +290,41 @@ impl StringContext {
                 }
 
                 // 循环计数器加一
-                *ptr=*ptr+1;
+                *ptr += 1;
                 return Ok(());
             }
+            StringMode::zhuanyi_hex(ref mut count,ref mut value) => {
+                match char_to_u8_16hx(c) {
+                    Ok(i) => {*value=*value*16+i;},
+                    Err(_) => {
+                        self.mode=StringMode::end;
+                        return Err(());
+                    },
+                }
+                *count += 1;
+                if *count==2{
+                    self.str.push(char::from(*value));
+                    self.mode=StringMode::str;
+                }
+                return Ok(());
+            },
+            StringMode::line_continuation => {
+                // \r\n一起作为续行
+                self.mode=StringMode::str;
+                if c=='\n'{
+                    return Ok(());
+                }
+                return self.check_string(c, options);
+            },
+            StringMode::zhuanyi_null => {
+                // \0后面紧跟数字时不是合法的转义(json5规范)
+                self.mode=StringMode::str;
+                if c.is_ascii_digit(){
+                    self.mode=StringMode::end;
+                    return Err(());
+                }
+                return self.check_string(c, options);
+            },
             StringMode::end => {
                 // 已经判断完毕,不应该再次调用
                 return Err(());
@@ -273,6 +336,31 @@ impl StringContext {
     }
     
 }
+impl StringContext {
+    /**
+     * json5额外的转义: \' \v \0 \xHH,反斜杠加换行表示续行,其他非数字字符转义为自身
+     */
+    fn check_json5_escape(&mut self, c:char) ->Result<(),()>{
+        self.mode=StringMode::str;
+        match c {
+            'v'=>{self.str.push('\x0b');},
+            '0'=>{
+                self.str.push('\0');
+                self.mode=StringMode::zhuanyi_null;
+            },
+            'x'=>{self.mode=StringMode::zhuanyi_hex(0,0);},
+            // 续行,不存入任何字符
+            '\n'|'\u{2028}'|'\u{2029}'=>{},
+            '\r'=>{self.mode=StringMode::line_continuation;},
+            '1'..='9'=>{
+                self.mode=StringMode::end;
+                return Err(());
+            },
+            _=>{self.str.push(c);},
+        }
+        return Ok(());
+    }
+}
 /**
  * 将16进制字符串转换为整形数字
  */
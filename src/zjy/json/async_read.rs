@@ -0,0 +1,199 @@
+/**
+ * 文件名: "src/zjy/json/async_read.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+
+use crate::zjy::json::{Json, JsonError, Parser, PushParser};
+
+/// 每次读取的字节数
+const READ_SIZE: usize = 8 * 1024;
+
+impl Json {
+    /**
+     * 从异步reader中读取并按照RFC 8259严格解析一个json文档
+     * 读到的每一块都立即推送给增量解析器,不会阻塞线程,也不需要缓存整个输入
+     */
+    pub async fn from_async_reader<R: AsyncRead + Unpin>(reader: R) -> Result<Json, JsonError> {
+        return Json::from_async_reader_with(reader, &Parser::new()).await;
+    }
+    /**
+     * 使用parser的选项从异步reader中读取并解析一个json文档
+     */
+    pub async fn from_async_reader_with<R: AsyncRead + Unpin>(mut reader: R, parser: &Parser) -> Result<Json, JsonError> {
+        let mut push = parser.push_parser();
+        let mut buf = vec![0u8; READ_SIZE];
+        loop {
+            let n = reader.read(&mut buf).await?;
+            if n == 0 {
+                return push.finish();
+            }
+            push.feed(&buf[..n])?;
+        }
+    }
+}
+
+/**
+ * 异步读取NDJSON / JSON Lines的Stream,每个非空行是一个json值
+ * 和JsonLinesReader一样,错误信息带有行号,格式错误的行不影响后面的行,io错误之后结束
+ */
+pub struct AsyncJsonLines<R: AsyncRead + Unpin> {
+    reader: R,
+    parser: PushParser,
+    buf: Box<[u8]>,
+    /// buf中已经推送的部分和读取到的部分的结尾
+    pos: usize,
+    filled: usize,
+    /// 当前行的行号,从1开始
+    line: usize,
+    /// 当前行已经出现的错误,读到行尾时返回
+    error: Option<JsonError>,
+    done: bool,
+}
+
+impl<R: AsyncRead + Unpin> AsyncJsonLines<R> {
+    /**
+     * 使用严格的默认选项解析每一行
+     */
+    pub fn new(reader: R) -> AsyncJsonLines<R> {
+        return AsyncJsonLines::with_parser(reader, &Parser::new());
+    }
+    /**
+     * 使用指定解析器的选项解析每一行
+     */
+    pub fn with_parser(reader: R, parser: &Parser) -> AsyncJsonLines<R> {
+        return AsyncJsonLines { reader, parser: parser.push_parser(), buf: vec![0u8; READ_SIZE].into_boxed_slice(), pos: 0, filled: 0, line: 1, error: None, done: false };
+    }
+    /**
+     * 取出内部的reader
+     */
+    pub fn into_inner(self) -> R { return self.reader; }
+
+    /**
+     * 把一段行内容推送给解析器,当前行出错之后忽略剩下的内容
+     */
+    fn feed(&mut self, start: usize, end: usize) {
+        if self.error.is_none() {
+            if let Err(err) = self.parser.feed(&self.buf[start..end]) {
+                self.error = Some(err);
+            }
+        }
+    }
+
+    /**
+     * 一行结束,返回这一行的结果,空白行返回None
+     */
+    fn end_line(&mut self) -> Option<Result<Json, JsonError>> {
+        let line = self.line;
+        self.line += 1;
+        let result = match self.error.take() {
+            Some(err) => Some(Err(err)),
+            None if self.parser.is_empty() => None,
+            None => Some(self.parser.finish()),
+        };
+        self.parser.reset();
+        return result.map(|result| result.map_err(|err| err.line(line)));
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for AsyncJsonLines<R> {
+    type Item = Result<Json, JsonError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        while !this.done {
+            if this.pos < this.filled {
+                let start = this.pos;
+                match this.buf[start..this.filled].iter().position(|b| *b == b'\n') {
+                    Some(i) => {
+                        this.feed(start, start + i);
+                        this.pos = start + i + 1;
+                        if let Some(result) = this.end_line() {
+                            return Poll::Ready(Some(result));
+                        }
+                    },
+                    None => {
+                        this.feed(start, this.filled);
+                        this.pos = this.filled;
+                    },
+                }
+                continue;
+            }
+            let mut read_buf = ReadBuf::new(&mut this.buf);
+            match Pin::new(&mut this.reader).poll_read(cx, &mut read_buf) {
+                Poll::Pending => { return Poll::Pending; },
+                Poll::Ready(Err(err)) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(JsonError::from(err).line(this.line))));
+                },
+                Poll::Ready(Ok(())) => {
+                    let n = read_buf.filled().len();
+                    this.pos = 0;
+                    this.filled = n;
+                    if n == 0 {
+                        // 输入结束,最后一行可以没有换行
+                        this.done = true;
+                        return Poll::Ready(this.end_line());
+                    }
+                },
+            }
+        }
+        return Poll::Ready(None);
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_async_read() {
+    use std::future::Future;
+
+    /// 测试用的执行器,&[u8]读取时总是立即就绪
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let mut cx = Context::from_waker(std::task::Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+    let str = std::fs::read_to_string("tests/testobject.json").unwrap();
+    let json = block_on(Json::from_async_reader(str.as_bytes())).unwrap();
+    assert_eq!(json.to_json5_string(), Json::str_to_json(&str).unwrap().to_json5_string());
+    assert!(block_on(Json::from_async_reader("[1,".as_bytes())).is_err());
+
+    let input = "{\"a\":1}\n\n  [1,2]\r\n{\"a\":\n\"中文\"";
+    let mut lines = AsyncJsonLines::new(input.as_bytes());
+    let mut next = || block_on(std::future::poll_fn(|cx| Pin::new(&mut lines).poll_next(cx)));
+    assert_eq!(next().unwrap().unwrap().to_json5_string(), "{a:1}");
+    assert_eq!(next().unwrap().unwrap().to_json5_string(), "[1,2]");
+    assert!(next().unwrap().err().unwrap().to_string().starts_with("第4行:"));
+    assert_eq!(next().unwrap().unwrap().to_json5_string(), "\"中文\"");
+    assert!(next().is_none());
+    // 资源限制错误也带有行号
+    let mut lines = AsyncJsonLines::with_parser("[1]\n[[2]]".as_bytes(), &Parser::new().max_depth(1));
+    let mut next = || block_on(std::future::poll_fn(|cx| Pin::new(&mut lines).poll_next(cx)));
+    assert!(next().unwrap().is_ok());
+    let err = next().unwrap().err().unwrap();
+    assert!(err.is_limit_exceeded());
+    assert_eq!(err.to_string(), "第2行:超出资源限制:嵌套深度超过1");
+}
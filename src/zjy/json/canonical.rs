@@ -0,0 +1,94 @@
+/**
+ * 文件名: "src/zjy/json/canonical.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::cmp::Ordering;
+use std::io::Write;
+
+use crate::zjy::json::{Json, JsonError, SerializeOptions};
+
+impl Json {
+    /**
+     * 按照RFC 8785(JSON Canonicalization Scheme)输出规范化的json,
+     * 相同的值总是得到相同的字节,适合签名和比较;number是NaN或Infinity时返回错误
+     */
+    pub fn to_canonical_string(&self) -> Result<String, JsonError> {
+        return self.to_string_with_options(&SerializeOptions::canonical());
+    }
+    /**
+     * 把规范化的json写入out
+     */
+    pub fn write_canonical<W: Write>(&self, out: W) -> Result<(), JsonError> {
+        return self.write_to(out, &SerializeOptions::canonical());
+    }
+}
+
+/**
+ * 按照UTF-16编码单元比较两个string,RFC 8785规定的key的顺序
+ */
+pub(crate) fn utf16_cmp(a: &str, b: &str) -> Ordering {
+    return a.encode_utf16().cmp(b.encode_utf16());
+}
+
+#[cfg(test)]
+#[test]
+fn test_canonical() {
+    // RFC 8785 3.2.2中的例子
+    let json = Json::str_to_json(r#"{
+  "numbers": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+  "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+  "literals": [null, true, false]
+}"#).unwrap();
+    assert_eq!(json.to_canonical_string().unwrap(), r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#);
+    // RFC 8785 3.2.3中key的顺序
+    let json = Json::str_to_json(r#"{
+  "\u20ac": "Euro Sign",
+  "\r": "Carriage Return",
+  "\ufb33": "Hebrew Letter Dalet With Dagesh",
+  "1": "One",
+  "\ud83d\ude00": "Emoji: Grinning Face",
+  "\u0080": "Control",
+  "\u00f6": "Latin Small Letter O With Diaeresis"
+}"#).unwrap();
+    let expect = "{\"\\r\":\"Carriage Return\",\"1\":\"One\",\"\u{80}\":\"Control\",\"\u{f6}\":\"Latin Small Letter O With Diaeresis\",\
+        \"\u{20ac}\":\"Euro Sign\",\"\u{1f600}\":\"Emoji: Grinning Face\",\"\u{fb33}\":\"Hebrew Letter Dalet With Dagesh\"}";
+    assert_eq!(json.to_canonical_string().unwrap(), expect);
+    // RFC 8785附录B中number的例子
+    let cases: [(u64, &str); 15] = [
+        (0x0000000000000000, "0"),
+        (0x8000000000000000, "0"),
+        (0x0000000000000001, "5e-324"),
+        (0x8000000000000001, "-5e-324"),
+        (0x7fefffffffffffff, "1.7976931348623157e+308"),
+        (0xffefffffffffffff, "-1.7976931348623157e+308"),
+        (0x4340000000000000, "9007199254740992"),
+        (0xc340000000000000, "-9007199254740992"),
+        (0x4430000000000000, "295147905179352830000"),
+        (0x44b52d02c7e14af5, "9.999999999999997e+22"),
+        (0x44b52d02c7e14af6, "1e+23"),
+        (0x444b1ae4d6e2ef50, "1e+21"),
+        (0x444b1ae4d6e2ef4f, "999999999999999900000"),
+        (0x3eb0c6f7a0b5ed8d, "0.000001"),
+        (0x3eb0c6f7a0b5ed8c, "9.999999999999997e-7"),
+    ];
+    for (bits, expect) in cases {
+        assert_eq!(Json::Number(f64::from_bits(bits)).to_canonical_string().unwrap(), expect);
+    }
+    assert_eq!(Json::Number(0.0000001).to_canonical_string().unwrap(), "1e-7");
+    assert!(Json::Number(f64::INFINITY).to_canonical_string().is_err());
+}
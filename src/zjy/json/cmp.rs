@@ -0,0 +1,210 @@
+/**
+ * 文件名: "src/zjy/json/cmp.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use crate::zjy::json::Json;
+
+/**
+ * 比较number的方式
+ * Canonical代表规范化之后比较: 0和-0相等,所有的NaN相等,其他按照f64的值比较,
+ * 这也是Json的PartialEq,Ord和Hash使用的方式;
+ * Ieee代表按照IEEE 754比较: NaN和任何值都不相等;
+ * Tolerance(e)代表差的绝对值不超过e就相等
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NumberEquality {
+    Canonical,
+    Ieee,
+    Tolerance(f64),
+}
+
+impl Json {
+    /**
+     * 按照指定的number比较方式判断两个json值是否相等,object比较时不考虑key的顺序
+     */
+    pub fn eq_with(&self, other: &Json, numbers: NumberEquality) -> bool {
+        match (self, other) {
+            (Json::Null, Json::Null) => { return true; },
+            (Json::Boolean(a), Json::Boolean(b)) => { return a == b; },
+            (Json::Number(a), Json::Number(b)) => {
+                match numbers {
+                    NumberEquality::Canonical => { return canonical_bits(*a) == canonical_bits(*b); },
+                    NumberEquality::Ieee => { return a == b; },
+                    NumberEquality::Tolerance(e) => { return a == b || (a - b).abs() <= e; },
+                }
+            },
+            (Json::String(a), Json::String(b)) => { return a == b; },
+            (Json::Array(a), Json::Array(b)) => {
+                return a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.eq_with(b, numbers));
+            },
+            (Json::Object(a), Json::Object(b)) => {
+                return a.len() == b.len() && a.iter().all(|(key, a)| b.get(key).is_some_and(|b| a.eq_with(b, numbers)));
+            },
+            _ => { return false; },
+        }
+    }
+
+    /**
+     * 类型的顺序: null < boolean < number < string < array < object
+     */
+    fn type_rank(&self) -> u8 {
+        match self {
+            Json::Null => { return 0; },
+            Json::Boolean(_) => { return 1; },
+            Json::Number(_) => { return 2; },
+            Json::String(_) => { return 3; },
+            Json::Array(_) => { return 4; },
+            Json::Object(_) => { return 5; },
+        }
+    }
+}
+
+/**
+ * 规范化的number: -0变成0,所有的NaN变成同一个NaN
+ */
+fn canonical(n: f64) -> f64 {
+    if n == 0.0 {
+        return 0.0;
+    }
+    if n.is_nan() {
+        return f64::NAN;
+    }
+    return n;
+}
+
+fn canonical_bits(n: f64) -> u64 {
+    return canonical(n).to_bits();
+}
+
+/**
+ * 按照key排序的object成员
+ */
+fn sorted_entries(obj: &HashMap<String, Json>) -> Vec<(&String, &Json)> {
+    let mut entries: Vec<(&String, &Json)> = obj.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    return entries;
+}
+
+impl PartialEq for Json {
+    fn eq(&self, other: &Json) -> bool {
+        return self.eq_with(other, NumberEquality::Canonical);
+    }
+}
+
+/// 规范化比较时NaN等于自身,满足Eq的要求
+impl Eq for Json {}
+
+impl PartialOrd for Json {
+    fn partial_cmp(&self, other: &Json) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+/**
+ * 全序,可以用于排序和BTreeMap的key,和PartialEq一致
+ * 不同类型按照type_rank比较;number按照规范化之后的f64::total_cmp比较(NaN最大);
+ * string按照unicode码点比较;array按照元素的字典序比较;object按照key排序之后的(key,值)字典序比较
+ */
+impl Ord for Json {
+    fn cmp(&self, other: &Json) -> Ordering {
+        match (self, other) {
+            (Json::Boolean(a), Json::Boolean(b)) => { return a.cmp(b); },
+            (Json::Number(a), Json::Number(b)) => { return canonical(*a).total_cmp(&canonical(*b)); },
+            (Json::String(a), Json::String(b)) => { return a.cmp(b); },
+            (Json::Array(a), Json::Array(b)) => { return a.cmp(b); },
+            (Json::Object(a), Json::Object(b)) => { return sorted_entries(a).cmp(&sorted_entries(b)); },
+            _ => { return self.type_rank().cmp(&other.type_rank()); },
+        }
+    }
+}
+
+/**
+ * 和PartialEq一致的hash: 相等的值(包括0和-0,key顺序不同的object)hash相同
+ */
+impl Hash for Json {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u8(self.type_rank());
+        match self {
+            Json::Null => {},
+            Json::Boolean(b) => { b.hash(state); },
+            Json::Number(n) => { canonical_bits(*n).hash(state); },
+            Json::String(s) => { s.hash(state); },
+            Json::Array(arr) => { arr.hash(state); },
+            Json::Object(obj) => { sorted_entries(obj).hash(state); },
+        }
+    }
+}
+
+/**
+ * 调试输出,object的key按照字典序输出,结果是确定的
+ */
+impl fmt::Debug for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => { return f.write_str("Null"); },
+            Json::Boolean(b) => { return f.debug_tuple("Boolean").field(b).finish(); },
+            Json::Number(n) => { return f.debug_tuple("Number").field(n).finish(); },
+            Json::String(s) => { return f.debug_tuple("String").field(s).finish(); },
+            Json::Array(arr) => { return f.debug_tuple("Array").field(arr).finish(); },
+            Json::Object(obj) => {
+                let mut map = f.debug_map();
+                for (key, value) in sorted_entries(obj) {
+                    map.entry(key, value);
+                }
+                return map.finish();
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_json_cmp() {
+    use std::collections::{BTreeMap, HashSet};
+
+    let a = Json::str_to_json("{\"x\":[1,2.5,{\"k\":null}],\"y\":\"s\"}").unwrap();
+    let b = Json::str_to_json("{ \"y\" : \"s\", \"x\" : [1.0, 25e-1, {\"k\":null}] }").unwrap();
+    assert_eq!(a, b);
+    assert_ne!(a, Json::str_to_json("{\"x\":[1,2.5,{\"k\":false}],\"y\":\"s\"}").unwrap());
+    // number的比较方式
+    assert_eq!(Json::Number(0.0), Json::Number(-0.0));
+    assert_eq!(Json::Number(f64::NAN), Json::Number(f64::NAN));
+    assert!(!Json::Number(f64::NAN).eq_with(&Json::Number(f64::NAN), NumberEquality::Ieee));
+    assert!(Json::Number(0.1 + 0.2).eq_with(&Json::Number(0.3), NumberEquality::Tolerance(1e-9)));
+    assert_ne!(Json::Number(0.1 + 0.2), Json::Number(0.3));
+    // 全序
+    let mut values = vec![
+        Json::Object(HashMap::new()), Json::String("b".to_string()), Json::Number(f64::NAN), Json::Array(vec![Json::Null]),
+        Json::Number(-1.0), Json::Boolean(true), Json::String("a".to_string()), Json::Null, Json::Boolean(false), Json::Array(Vec::new()),
+    ];
+    values.sort();
+    assert_eq!(std::format!("{:?}", values), "[Null, Boolean(false), Boolean(true), Number(-1.0), Number(NaN), String(\"a\"), String(\"b\"), Array([]), Array([Null]), {}]");
+    assert!(Json::Number(0.0).cmp(&Json::Number(-0.0)) == Ordering::Equal);
+    let mut map = BTreeMap::new();
+    map.insert(a.clone(), 1);
+    assert_eq!(map.get(&b), Some(&1));
+    // hash和相等一致
+    let set: HashSet<Json> = [a, b, Json::Number(0.0), Json::Number(-0.0)].into_iter().collect();
+    assert_eq!(set.len(), 2);
+    assert_eq!(std::format!("{:?}", Json::str_to_json("{\"b\":1,\"a\":[\"x\"]}").unwrap()), "{\"a\": Array([String(\"x\")]), \"b\": Number(1.0)}");
+}
@@ -0,0 +1,680 @@
+/**
+ * 文件名: "src/zjy/json/codegen.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use crate::zjy::json::{Json, JsonError};
+
+/**
+ * 生成的代码使用的转换方式
+ * Zjy代表实现本库的ToJson/FromJson;Serde代表derive serde的Serialize/Deserialize,没有固定类型的值使用serde_json::Value
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CodegenStyle {
+    #[default]
+    Zjy,
+    Serde,
+}
+
+/**
+ * 生成Rust代码的选项
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodegenOptions {
+    /// 最外层类型的名字
+    pub root_name: String,
+    pub style: CodegenStyle,
+    /// 把key中的非ascii字符转换成u+十六进制码点,比如"名字"转换成u540d_u5b57;默认保留原来的字符(Rust支持非ascii的标识符)
+    pub ascii_identifiers: bool,
+}
+
+impl Default for CodegenOptions {
+    fn default() -> CodegenOptions {
+        return CodegenOptions { root_name: "Root".to_string(), style: CodegenStyle::default(), ascii_identifiers: false };
+    }
+}
+
+/**
+ * 字段和类型引用的Rust类型
+ */
+#[derive(Clone, Debug, PartialEq)]
+enum RustType {
+    Bool,
+    I64,
+    F64,
+    String,
+    /// 没有固定类型的值
+    Any,
+    Option(Box<RustType>),
+    Vec(Box<RustType>),
+    Map(Box<RustType>),
+    /// 递归引用正在生成的类型
+    Boxed(Box<RustType>),
+    Named(String),
+}
+
+/**
+ * Vec和HashMap的元素已经在堆上,不需要再Box
+ */
+fn unboxed(ty: RustType) -> RustType {
+    match ty {
+        RustType::Boxed(inner) => { return *inner; },
+        other => { return other; },
+    }
+}
+
+struct Field {
+    key: String,
+    ident: String,
+    ty: RustType,
+    /// 不是required,值是None时不输出这个key
+    optional: bool,
+    description: Option<String>,
+}
+
+enum Definition {
+    Struct { name: String, fields: Vec<Field>, description: Option<String> },
+    /// 变量名和对应的string
+    Enum { name: String, variants: Vec<(String, String)>, description: Option<String> },
+}
+
+/**
+ * 把JSON Schema转换成Rust的类型定义: object(properties)生成struct,不是required的成员是Option,
+ * type包含null时是Option,string的enum生成enum,array生成Vec,只有additionalProperties的object生成HashMap,
+ * 支持"#/$defs/..."和"#/definitions/..."的$ref;key按照Rust的命名规范转换成标识符,和key不同时保留原来的key
+ */
+pub fn generate_rust(schema: &Json, options: &CodegenOptions) -> Result<String, JsonError> {
+    // 生成的代码用到的类型名不能再用作生成的类型名
+    let mut names: HashSet<String> = RESERVED_NAMES.iter().map(|name| name.to_string()).collect();
+    let style_names = if options.style == CodegenStyle::Serde { &SERDE_NAMES[..] } else { &ZJY_NAMES[..] };
+    names.extend(style_names.iter().map(|name| name.to_string()));
+    let mut generator = Generator {
+        root: schema, options, definitions: Vec::new(), names, refs: HashMap::new(),
+        in_progress: HashSet::new(), recursive: HashSet::new(), reserved: None,
+    };
+    let root_name = type_ident(&options.root_name, options.ascii_identifiers);
+    // 最外层的schema也可以被"$ref": "#"引用
+    let (root, name) = generator.define("#", schema, &root_name)?;
+    if !matches!(root, RustType::Named(_)) {
+        // 最外层不是struct或enum时生成类型别名
+        let alias = generator.render_type(&root);
+        let mut out = generator.render();
+        let _ = writeln!(out, "{}pub type {} = {};", if out.is_empty() { "" } else { "\n" }, name, alias);
+        return Ok(out);
+    }
+    return Ok(generator.render());
+}
+
+/// 两种风格生成的代码都会用到的类型名
+const RESERVED_NAMES: [&str; 7] = ["HashMap", "String", "Vec", "Option", "Box", "Result", "Self"];
+/// CodegenStyle::Zjy生成的代码用到的类型名
+const ZJY_NAMES: [&str; 4] = ["Json", "JsonError", "ToJson", "FromJson"];
+/// CodegenStyle::Serde生成的代码用到的类型名
+const SERDE_NAMES: [&str; 2] = ["Serialize", "Deserialize"];
+
+struct Generator<'a> {
+    root: &'a Json,
+    options: &'a CodegenOptions,
+    /// 按照发现的顺序排列,生成struct的子类型之前先占位,保证外层的类型在前面
+    definitions: Vec<Option<Definition>>,
+    names: HashSet<String>,
+    /// 已经解析(或者正在解析)的$ref
+    refs: HashMap<String, RustType>,
+    /// 正在生成的$ref,在生成期间引用它们是递归引用
+    in_progress: HashSet<String>,
+    /// 被递归引用过的$ref
+    recursive: HashSet<String>,
+    /// define预先确定的类型名,只给下一次type_of生成的struct或enum使用
+    reserved: Option<String>,
+}
+
+fn invalid(msg: String) -> JsonError {
+    return JsonError::from(msg).prefix("schema不合法:");
+}
+
+impl<'a> Generator<'a> {
+    /**
+     * 取一个没有用过的类型名
+     */
+    fn unique_name(&mut self, name: &str) -> String {
+        let mut candidate = name.to_string();
+        let mut n = 2;
+        while self.names.contains(&candidate) {
+            candidate = std::format!("{}{}", name, n);
+            n += 1;
+        }
+        self.names.insert(candidate.clone());
+        return candidate;
+    }
+
+    /**
+     * 求出schema对应的Rust类型,hint是需要生成新类型时使用的名字
+     */
+    fn type_of(&mut self, schema: &'a Json, hint: &str) -> Result<RustType, JsonError> {
+        let reserved = self.reserved.take();
+        let obj = match schema {
+            Json::Object(obj) => obj,
+            Json::Boolean(true) => { return Ok(RustType::Any); },
+            _ => { return Err(invalid("schema需要是object".to_string())); },
+        };
+        if let Some(reference) = obj.get("$ref") {
+            return self.resolve_ref(reference);
+        }
+        let hint = match (&reserved, obj.get("title")) {
+            (Some(name), _) => name.clone(),
+            (None, Some(Json::String(title))) => type_ident(title, self.options.ascii_identifiers),
+            _ => hint.to_string(),
+        };
+        let description = match obj.get("description") {
+            Some(Json::String(description)) => Some(description.clone()),
+            _ => None,
+        };
+        let mut types: Vec<&str> = match obj.get("type") {
+            Some(Json::String(t)) => vec![t.as_str()],
+            Some(Json::Array(arr)) => arr.iter().filter_map(|t| if let Json::String(t) = t { Some(t.as_str()) } else { None }).collect(),
+            Some(_) => { return Err(invalid("type需要是string或array".to_string())); },
+            None if obj.contains_key("properties") => vec!["object"],
+            None if obj.contains_key("items") => vec!["array"],
+            None => Vec::new(),
+        };
+        if let Some(Json::Array(values)) = obj.get("enum") {
+            let nullable = values.contains(&Json::Null);
+            let strings: Vec<&String> = values.iter().filter_map(|value| if let Json::String(s) = value { Some(s) } else { None }).collect();
+            if !strings.is_empty() && strings.len() + nullable as usize == values.len() {
+                let name = match reserved {
+                    Some(name) => name,
+                    None => self.unique_name(&hint),
+                };
+                let mut used = HashSet::new();
+                let mut variants = Vec::new();
+                for value in strings {
+                    let mut ident = type_ident(value, self.options.ascii_identifiers);
+                    if ident == "Type" && value.is_empty() {
+                        ident = "Empty".to_string();
+                    }
+                    let base = ident.clone();
+                    let mut n = 2;
+                    while !used.insert(ident.clone()) {
+                        ident = std::format!("{}{}", base, n);
+                        n += 1;
+                    }
+                    variants.push((ident, value.clone()));
+                }
+                self.definitions.push(Some(Definition::Enum { name: name.clone(), variants, description }));
+                let ty = RustType::Named(name);
+                return Ok(if nullable { RustType::Option(Box::new(ty)) } else { ty });
+            }
+            return Ok(RustType::Any);
+        }
+        let nullable = types.contains(&"null");
+        types.retain(|t| *t != "null");
+        if types.len() == 2 && types.contains(&"integer") && types.contains(&"number") {
+            types = vec!["number"];
+        }
+        let ty = match types.as_slice() {
+            ["boolean"] => RustType::Bool,
+            ["integer"] => RustType::I64,
+            ["number"] => RustType::F64,
+            ["string"] => RustType::String,
+            ["array"] => {
+                match obj.get("items") {
+                    Some(items) => RustType::Vec(Box::new(unboxed(self.type_of(items, &std::format!("{}Item", hint))?))),
+                    None => RustType::Vec(Box::new(RustType::Any)),
+                }
+            },
+            ["object"] => {
+                match (obj.get("properties"), obj.get("additionalProperties")) {
+                    (Some(Json::Object(properties)), _) => self.generate_struct(obj, properties, reserved, &hint, description)?,
+                    (Some(_), _) => { return Err(invalid("properties需要是object".to_string())); },
+                    (None, Some(value @ Json::Object(_))) => RustType::Map(Box::new(unboxed(self.type_of(value, &std::format!("{}Value", hint))?))),
+                    (None, _) => RustType::Map(Box::new(RustType::Any)),
+                }
+            },
+            _ => RustType::Any,
+        };
+        if nullable && ty != RustType::Any {
+            return Ok(RustType::Option(Box::new(ty)));
+        }
+        return Ok(ty);
+    }
+
+    fn generate_struct(&mut self, obj: &'a HashMap<String, Json>, properties: &'a HashMap<String, Json>, reserved: Option<String>, hint: &str, description: Option<String>) -> Result<RustType, JsonError> {
+        let name = match reserved {
+            Some(name) => name,
+            None => self.unique_name(hint),
+        };
+        let slot = self.definitions.len();
+        self.definitions.push(None);
+        let required: HashSet<&str> = match obj.get("required") {
+            Some(Json::Array(arr)) => arr.iter().filter_map(|key| if let Json::String(key) = key { Some(key.as_str()) } else { None }).collect(),
+            _ => HashSet::new(),
+        };
+        let mut keys: Vec<&String> = properties.keys().collect();
+        keys.sort();
+        let mut fields = Vec::new();
+        let mut idents = HashSet::new();
+        for key in keys {
+            let property = &properties[key];
+            let mut ty = self.type_of(property, &type_ident(key, self.options.ascii_identifiers))?;
+            let optional = !required.contains(key.as_str());
+            if optional && !matches!(ty, RustType::Option(_) | RustType::Any) {
+                ty = RustType::Option(Box::new(ty));
+            }
+            let base = field_ident(key, self.options.ascii_identifiers);
+            let mut ident = base.clone();
+            let mut n = 2;
+            while !idents.insert(ident.clone()) {
+                ident = std::format!("{}_{}", base.trim_start_matches("r#"), n);
+                n += 1;
+            }
+            let description = match property {
+                Json::Object(property) => match property.get("description") {
+                    Some(Json::String(description)) => Some(description.clone()),
+                    _ => None,
+                },
+                _ => None,
+            };
+            fields.push(Field { key: key.clone(), ident, ty, optional, description });
+        }
+        self.definitions[slot] = Some(Definition::Struct { name: name.clone(), fields, description });
+        return Ok(RustType::Named(name));
+    }
+
+    /**
+     * 解析本文档中的$ref,比如"#/$defs/User"
+     */
+    fn resolve_ref(&mut self, reference: &Json) -> Result<RustType, JsonError> {
+        let reference = match reference {
+            Json::String(reference) => reference,
+            _ => { return Err(invalid("$ref需要是string".to_string())); },
+        };
+        if let Some(ty) = self.refs.get(reference) {
+            if self.in_progress.contains(reference) {
+                self.recursive.insert(reference.clone());
+            }
+            return Ok(ty.clone());
+        }
+        let target = match reference.strip_prefix('#').and_then(|pointer| self.root.pointer(pointer)) {
+            Some(target) => target,
+            None => { return Err(invalid(std::format!("找不到$ref:{}", reference))); },
+        };
+        let last = reference.rsplit('/').next().unwrap_or_default().replace("~1", "/").replace("~0", "~");
+        let hint = type_ident(&last, self.options.ascii_identifiers);
+        return Ok(self.define(reference, target, &hint)?.0);
+    }
+
+    /**
+     * 生成reference(最外层是"#")对应的类型,名字优先使用title;
+     * 生成期间对它的引用(递归引用)使用Box,避免类型的大小无限;返回类型和使用的类型名
+     */
+    fn define(&mut self, reference: &str, target: &'a Json, hint: &str) -> Result<(RustType, String), JsonError> {
+        let hint = match target {
+            Json::Object(obj) => match obj.get("title") {
+                Some(Json::String(title)) => type_ident(title, self.options.ascii_identifiers),
+                _ => hint.to_string(),
+            },
+            _ => hint.to_string(),
+        };
+        let name = self.unique_name(&hint);
+        self.refs.insert(reference.to_string(), RustType::Boxed(Box::new(RustType::Named(name.clone()))));
+        self.in_progress.insert(reference.to_string());
+        self.reserved = Some(name.clone());
+        let ty = self.type_of(target, &name)?;
+        self.in_progress.remove(reference);
+        if self.recursive.contains(reference) && ty != RustType::Named(name.clone()) {
+            return Err(invalid(std::format!("{}递归引用了自身,但是不是object或enum", reference)));
+        }
+        self.refs.insert(reference.to_string(), ty.clone());
+        return Ok((ty, name));
+    }
+
+    fn render_type(&self, ty: &RustType) -> String {
+        match ty {
+            RustType::Bool => { return "bool".to_string(); },
+            RustType::I64 => { return "i64".to_string(); },
+            RustType::F64 => { return "f64".to_string(); },
+            RustType::String => { return "String".to_string(); },
+            RustType::Any => { return if self.options.style == CodegenStyle::Serde { "serde_json::Value" } else { "Json" }.to_string(); },
+            RustType::Option(inner) => { return std::format!("Option<{}>", self.render_type(inner)); },
+            RustType::Vec(inner) => { return std::format!("Vec<{}>", self.render_type(inner)); },
+            RustType::Map(inner) => { return std::format!("HashMap<String, {}>", self.render_type(inner)); },
+            RustType::Boxed(inner) => { return std::format!("Box<{}>", self.render_type(inner)); },
+            RustType::Named(name) => { return name.clone(); },
+        }
+    }
+
+    fn uses_map(&self) -> bool {
+        fn has_map(ty: &RustType) -> bool {
+            match ty {
+                RustType::Map(_) => { return true; },
+                RustType::Option(inner) | RustType::Vec(inner) | RustType::Boxed(inner) => { return has_map(inner); },
+                _ => { return false; },
+            }
+        }
+        return self.definitions.iter().flatten().any(|definition| match definition {
+            Definition::Struct { fields, .. } => fields.iter().any(|field| has_map(&field.ty)),
+            Definition::Enum { .. } => false,
+        });
+    }
+
+    /**
+     * 输出所有的类型定义
+     */
+    fn render(&self) -> String {
+        let serde = self.options.style == CodegenStyle::Serde;
+        let mut out = String::new();
+        if self.definitions.is_empty() {
+            return out;
+        }
+        if serde {
+            if self.uses_map() {
+                out.push_str("use std::collections::HashMap;\n\n");
+            }
+            out.push_str("use serde::{Deserialize, Serialize};\n");
+        } else {
+            out.push_str("use std::collections::HashMap;\n\n");
+            out.push_str("use zjy_json::zjy::json::convert::{expect_object, field, FromJson, ToJson};\n");
+            out.push_str("use zjy_json::zjy::json::{Json, JsonError};\n");
+        }
+        for definition in self.definitions.iter().flatten() {
+            out.push('\n');
+            match definition {
+                Definition::Struct { name, fields, description } => { self.render_struct(&mut out, name, fields, description.as_deref()); },
+                Definition::Enum { name, variants, description } => { self.render_enum(&mut out, name, variants, description.as_deref()); },
+            }
+        }
+        return out;
+    }
+
+    fn render_struct(&self, out: &mut String, name: &str, fields: &[Field], description: Option<&str>) {
+        let serde = self.options.style == CodegenStyle::Serde;
+        render_doc(out, "", description);
+        let _ = writeln!(out, "#[derive(Clone, Debug, PartialEq{})]", if serde { ", Serialize, Deserialize" } else { "" });
+        let _ = writeln!(out, "pub struct {} {{", name);
+        for field in fields {
+            render_doc(out, "    ", field.description.as_deref());
+            if serde {
+                if field.ident.trim_start_matches("r#") != field.key {
+                    let _ = writeln!(out, "    #[serde(rename = {:?})]", field.key);
+                }
+                if field.optional && matches!(field.ty, RustType::Option(_)) {
+                    out.push_str("    #[serde(default, skip_serializing_if = \"Option::is_none\")]\n");
+                } else if field.optional {
+                    // 没有固定类型的值不包在Option中,缺少时是null
+                    out.push_str("    #[serde(default)]\n");
+                }
+            }
+            let _ = writeln!(out, "    pub {}: {},", field.ident, self.render_type(&field.ty));
+        }
+        out.push_str("}\n");
+        if serde {
+            return;
+        }
+
+        let _ = writeln!(out, "\nimpl ToJson for {} {{", name);
+        out.push_str("    fn to_json(&self) -> Json {\n");
+        if fields.is_empty() {
+            out.push_str("        return Json::Object(HashMap::new());\n");
+        } else {
+            out.push_str("        let mut obj = HashMap::new();\n");
+            for field in fields {
+                if field.optional && matches!(field.ty, RustType::Option(_)) {
+                    let _ = writeln!(out, "        if let Some(value) = &self.{} {{", field.ident);
+                    let _ = writeln!(out, "            obj.insert({:?}.to_string(), value.to_json());", field.key);
+                    out.push_str("        }\n");
+                } else {
+                    let _ = writeln!(out, "        obj.insert({:?}.to_string(), self.{}.to_json());", field.key, field.ident);
+                }
+            }
+            out.push_str("        return Json::Object(obj);\n");
+        }
+        out.push_str("    }\n}\n");
+
+        let _ = writeln!(out, "\nimpl FromJson for {} {{", name);
+        out.push_str("    fn from_json(json: &Json) -> Result<Self, JsonError> {\n");
+        if fields.is_empty() {
+            let _ = writeln!(out, "        expect_object(json, {:?})?;", name);
+            let _ = writeln!(out, "        return Ok({} {{}});", name);
+        } else {
+            let _ = writeln!(out, "        let obj = expect_object(json, {:?})?;", name);
+            let _ = writeln!(out, "        return Ok({} {{", name);
+            for field in fields {
+                let _ = writeln!(out, "            {}: field(obj, {:?})?,", field.ident, field.key);
+            }
+            out.push_str("        });\n");
+        }
+        out.push_str("    }\n}\n");
+    }
+
+    fn render_enum(&self, out: &mut String, name: &str, variants: &[(String, String)], description: Option<&str>) {
+        let serde = self.options.style == CodegenStyle::Serde;
+        render_doc(out, "", description);
+        let _ = writeln!(out, "#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash{})]", if serde { ", Serialize, Deserialize" } else { "" });
+        let _ = writeln!(out, "pub enum {} {{", name);
+        for (ident, value) in variants {
+            if serde && ident != value {
+                let _ = writeln!(out, "    #[serde(rename = {:?})]", value);
+            }
+            let _ = writeln!(out, "    {},", ident);
+        }
+        out.push_str("}\n");
+        if serde {
+            return;
+        }
+
+        let _ = writeln!(out, "\nimpl ToJson for {} {{", name);
+        out.push_str("    fn to_json(&self) -> Json {\n");
+        out.push_str("        let value = match self {\n");
+        for (ident, value) in variants {
+            let _ = writeln!(out, "            {}::{} => {:?},", name, ident, value);
+        }
+        out.push_str("        };\n");
+        out.push_str("        return Json::String(value.to_string());\n");
+        out.push_str("    }\n}\n");
+
+        let _ = writeln!(out, "\nimpl FromJson for {} {{", name);
+        out.push_str("    fn from_json(json: &Json) -> Result<Self, JsonError> {\n");
+        out.push_str("        match String::from_json(json)?.as_str() {\n");
+        for (ident, value) in variants {
+            let _ = writeln!(out, "            {:?} => {{ return Ok({}::{}); }},", value, name, ident);
+        }
+        let _ = writeln!(out, "            other => {{ return Err(std::format!(\"{}不能是{{:?}}\", other).into()); }},", name);
+        out.push_str("        }\n    }\n}\n");
+    }
+}
+
+fn render_doc(out: &mut String, indent: &str, description: Option<&str>) {
+    if let Some(description) = description {
+        for line in description.lines() {
+            let _ = writeln!(out, "{}/// {}", indent, line);
+        }
+    }
+}
+
+/// Rust的关键字,作为字段名时使用r#,self等不能使用r#的关键字在后面加 '_'
+const KEYWORDS: [&str; 51] = [
+    "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn", "for", "if", "impl", "in", "let",
+    "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait",
+    "true", "type", "unsafe", "use", "where", "while", "async", "await", "dyn", "abstract", "become", "box", "do", "final",
+    "macro", "override", "priv", "typeof", "unsized", "virtual", "yield", "try",
+];
+
+/**
+ * 把key拆分成单词: 字母和数字以外的字符是分隔符,小写字母或数字后面的大写字母开始新的单词;
+ * ascii为true时每个非ascii字符转换成一个"u+十六进制码点"的单词
+ */
+fn words(key: &str, ascii: bool) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut prev: Option<char> = None;
+    for c in key.chars() {
+        if !c.is_alphabetic() && !c.is_ascii_digit() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            prev = None;
+            continue;
+        }
+        if !c.is_ascii() && ascii {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            words.push(std::format!("u{:x}", c as u32));
+            prev = None;
+            continue;
+        }
+        if c.is_ascii_uppercase() && prev.is_some_and(|p| p.is_ascii_lowercase() || p.is_ascii_digit()) && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        current.push(c);
+        prev = Some(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    return words;
+}
+
+/**
+ * 字段名: snake_case
+ */
+fn field_ident(key: &str, ascii: bool) -> String {
+    let ident = words(key, ascii).iter().map(|word| word.to_lowercase()).collect::<Vec<String>>().join("_");
+    if ident.is_empty() {
+        return "field".to_string();
+    }
+    if ident.starts_with(|c: char| c.is_ascii_digit()) {
+        return std::format!("field_{}", ident);
+    }
+    if KEYWORDS.contains(&ident.as_str()) {
+        if matches!(ident.as_str(), "self" | "crate" | "super") {
+            return std::format!("{}_", ident);
+        }
+        return std::format!("r#{}", ident);
+    }
+    return ident;
+}
+
+/**
+ * 类型名和enum的变量名: PascalCase
+ */
+fn type_ident(key: &str, ascii: bool) -> String {
+    let mut ident = String::new();
+    for word in words(key, ascii) {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            ident.extend(first.to_uppercase());
+            ident.push_str(chars.as_str());
+        }
+    }
+    if ident.is_empty() {
+        return "Type".to_string();
+    }
+    if ident.starts_with(|c: char| c.is_ascii_digit()) {
+        return std::format!("T{}", ident);
+    }
+    if ident == "Self" {
+        return "Self_".to_string();
+    }
+    return ident;
+}
+
+#[cfg(test)]
+#[test]
+fn test_codegen() {
+    assert_eq!(field_ident("userName", false), "user_name");
+    assert_eq!(field_ident("Content-Type", false), "content_type");
+    assert_eq!(field_ident("type", false), "r#type");
+    assert_eq!(field_ident("self", false), "self_");
+    assert_eq!(field_ident("1st", false), "field_1st");
+    assert_eq!(field_ident("名字", false), "名字");
+    assert_eq!(field_ident("用户 名字", true), "u7528_u6237_u540d_u5b57");
+    assert_eq!(type_ident("order_item", false), "OrderItem");
+    assert_eq!(type_ident("2fa", false), "T2fa");
+    assert_eq!(type_ident("", false), "Type");
+
+    let schema = Json::str_to_json(r##"{
+        "title": "user",
+        "type": "object",
+        "properties": {
+            "名字": {"type": "string", "description": "用户名"},
+            "age": {"type": ["integer", "null"]},
+            "type": {"enum": ["admin", "normal-user"]},
+            "address": {"type": "object", "properties": {"city": {"type": "string"}}, "required": ["city"]},
+            "tags": {"type": "array", "items": {"type": "string"}},
+            "extra": {"type": "object", "additionalProperties": {"type": "number"}},
+            "friends": {"type": "array", "items": {"$ref": "#/$defs/friend"}},
+            "raw": {}
+        },
+        "required": ["名字", "age", "type", "friends"],
+        "$defs": {"friend": {"type": "object", "properties": {"id": {"type": "integer"}}, "required": ["id"]}}
+    }"##).unwrap();
+    let code = generate_rust(&schema, &CodegenOptions::default()).unwrap();
+    assert!(code.contains("pub struct User {\n    pub address: Option<Address>,\n    pub age: Option<i64>,\n    pub extra: Option<HashMap<String, f64>>,\n    pub friends: Vec<Friend>,\n    pub raw: Json,\n    pub tags: Option<Vec<String>>,\n    pub r#type: Type,\n    /// 用户名\n    pub 名字: String,\n}"));
+    assert!(code.contains("pub enum Type {\n    Admin,\n    NormalUser,\n}"));
+    assert!(code.contains("        if let Some(value) = &self.address {\n            obj.insert(\"address\".to_string(), value.to_json());\n        }\n"));
+    assert!(code.contains("        obj.insert(\"age\".to_string(), self.age.to_json());\n"));
+    assert!(code.contains("            名字: field(obj, \"名字\")?,\n"));
+    assert!(code.contains("            \"normal-user\" => { return Ok(Type::NormalUser); },\n"));
+    assert!(code.contains("pub struct Friend {\n    pub id: i64,\n}"));
+    // 外层的类型在前面
+    assert!(code.find("pub struct User").unwrap() < code.find("pub struct Address").unwrap());
+
+    let options = CodegenOptions { root_name: "Config".to_string(), style: CodegenStyle::Serde, ascii_identifiers: true };
+    let code = generate_rust(&schema, &options).unwrap();
+    assert!(code.starts_with("use std::collections::HashMap;\n\nuse serde::{Deserialize, Serialize};\n"));
+    assert!(code.contains("    #[serde(rename = \"名字\")]\n    pub u540d_u5b57: String,\n"));
+    assert!(code.contains("    #[serde(default, skip_serializing_if = \"Option::is_none\")]\n    pub tags: Option<Vec<String>>,\n"));
+    assert!(code.contains("    #[serde(default)]\n    pub raw: serde_json::Value,\n"));
+    assert!(code.contains("    #[serde(rename = \"normal-user\")]\n    NormalUser,\n"));
+
+    // 生成的类型不能和生成的代码用到的类型重名
+    let schema = Json::str_to_json(r#"{"type": "object", "properties": {
+        "json": {"type": "object", "properties": {"a": {"type": "integer"}}},
+        "option": {"type": "object", "properties": {"b": {"type": "integer"}}},
+        "string": {"type": "object", "properties": {"c": {"type": "string"}}},
+        "serialize": {"enum": ["x"]}
+    }}"#).unwrap();
+    let code = generate_rust(&schema, &CodegenOptions::default()).unwrap();
+    assert!(code.contains("pub struct Json2 {") && code.contains("pub struct Option2 {") && code.contains("pub struct String2 {\n    pub c: Option<String>,\n}"));
+    assert!(code.contains("    pub json: Option<Json2>,\n") && code.contains("pub enum Serialize {"));
+    assert!(!code.contains("pub struct Json {") && !code.contains("pub struct Option {") && !code.contains("pub struct String {"));
+    let code = generate_rust(&schema, &CodegenOptions { style: CodegenStyle::Serde, ..CodegenOptions::default() }).unwrap();
+    assert!(code.contains("pub struct Json {") && code.contains("pub enum Serialize2 {"));
+    let options = CodegenOptions { root_name: "json".to_string(), ..CodegenOptions::default() };
+    assert_eq!(generate_rust(&Json::str_to_json(r#"{"type":"array","items":{"type":"number"}}"#).unwrap(), &options).unwrap(), "pub type Json2 = Vec<f64>;\n");
+
+    // 递归引用使用Box,Vec中的元素不需要Box
+    let schema = Json::str_to_json(r##"{
+        "title": "tree",
+        "type": "object",
+        "properties": {"head": {"$ref": "#/$defs/node"}, "children": {"type": "array", "items": {"$ref": "#"}}},
+        "required": ["children"],
+        "$defs": {"node": {"type": "object", "properties": {"value": {"type": "integer"}, "next": {"$ref": "#/$defs/node"}}, "required": ["value"]}}
+    }"##).unwrap();
+    let code = generate_rust(&schema, &CodegenOptions::default()).unwrap();
+    assert!(code.contains("pub struct Tree {\n    pub children: Vec<Tree>,\n    pub head: Option<Node>,\n}"));
+    assert!(code.contains("pub struct Node {\n    pub next: Option<Box<Node>>,\n    pub value: i64,\n}"));
+    assert!(!code.contains("Tree2") && !code.contains("Node2"));
+    let schema = Json::str_to_json(r##"{"type": "array", "items": {"$ref": "#"}}"##).unwrap();
+    assert!(generate_rust(&schema, &CodegenOptions::default()).is_err());
+    assert_eq!(generate_rust(&Json::str_to_json(r#"{"type":"array","items":{"type":"number"}}"#).unwrap(), &CodegenOptions::default()).unwrap(), "pub type Root = Vec<f64>;\n");
+    assert_eq!(generate_rust(&Json::str_to_json(r##"{"$ref":"#/$defs/x"}"##).unwrap(), &CodegenOptions::default()).err().unwrap().to_string(), "schema不合法:找不到$ref:#/$defs/x");
+}
@@ -0,0 +1,208 @@
+/**
+ * 文件名: "src/zjy/json/convert.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::collections::HashMap;
+
+use crate::zjy::json::{Json, JsonError};
+
+/**
+ * 把Rust的值转换成json,codegen生成的类型实现了这个trait
+ */
+pub trait ToJson {
+    fn to_json(&self) -> Json;
+}
+
+/**
+ * 从json转换成Rust的值,类型不符合时返回错误,codegen生成的类型实现了这个trait
+ */
+pub trait FromJson: Sized {
+    fn from_json(json: &Json) -> Result<Self, JsonError>;
+}
+
+/**
+ * 类型不符合时的错误
+ */
+fn mismatch(expected: &str, json: &Json) -> JsonError {
+    let found = match json {
+        Json::Null => "null",
+        Json::Boolean(_) => "boolean",
+        Json::Number(_) => "number",
+        Json::String(_) => "string",
+        Json::Array(_) => "array",
+        Json::Object(_) => "object",
+    };
+    return std::format!("需要{},不能是{}", expected, found).into();
+}
+
+/**
+ * 取出object,name是错误信息中的类型名
+ */
+pub fn expect_object<'a>(json: &'a Json, name: &str) -> Result<&'a HashMap<String, Json>, JsonError> {
+    match json {
+        Json::Object(obj) => { return Ok(obj); },
+        other => { return Err(mismatch(&std::format!("object({})", name), other)); },
+    }
+}
+
+/**
+ * 转换object的一个成员,不存在的成员当作null,错误信息带有key
+ */
+pub fn field<T: FromJson>(obj: &HashMap<String, Json>, key: &str) -> Result<T, JsonError> {
+    return T::from_json(obj.get(key).unwrap_or(&Json::Null)).map_err(|err| err.prefix(&std::format!("{}:", key)));
+}
+
+impl ToJson for Json {
+    fn to_json(&self) -> Json { return self.clone(); }
+}
+
+impl FromJson for Json {
+    fn from_json(json: &Json) -> Result<Self, JsonError> { return Ok(json.clone()); }
+}
+
+impl ToJson for bool {
+    fn to_json(&self) -> Json { return Json::Boolean(*self); }
+}
+
+impl FromJson for bool {
+    fn from_json(json: &Json) -> Result<Self, JsonError> {
+        match json {
+            Json::Boolean(b) => { return Ok(*b); },
+            other => { return Err(mismatch("boolean", other)); },
+        }
+    }
+}
+
+impl ToJson for f64 {
+    fn to_json(&self) -> Json { return Json::Number(*self); }
+}
+
+impl FromJson for f64 {
+    fn from_json(json: &Json) -> Result<Self, JsonError> {
+        match json {
+            Json::Number(n) => { return Ok(*n); },
+            other => { return Err(mismatch("number", other)); },
+        }
+    }
+}
+
+impl ToJson for i64 {
+    fn to_json(&self) -> Json { return Json::Number(*self as f64); }
+}
+
+impl FromJson for i64 {
+    fn from_json(json: &Json) -> Result<Self, JsonError> {
+        match json {
+            Json::Number(n) if n.fract() == 0.0 && *n >= i64::MIN as f64 && *n < i64::MAX as f64 => { return Ok(*n as i64); },
+            Json::Number(n) => { return Err(std::format!("需要整数,不能是{}", n).into()); },
+            other => { return Err(mismatch("integer", other)); },
+        }
+    }
+}
+
+impl ToJson for String {
+    fn to_json(&self) -> Json { return Json::String(self.clone()); }
+}
+
+impl FromJson for String {
+    fn from_json(json: &Json) -> Result<Self, JsonError> {
+        match json {
+            Json::String(s) => { return Ok(s.clone()); },
+            other => { return Err(mismatch("string", other)); },
+        }
+    }
+}
+
+/// None转换成null,null转换成None
+impl<T: ToJson> ToJson for Option<T> {
+    fn to_json(&self) -> Json {
+        match self {
+            Some(value) => { return value.to_json(); },
+            None => { return Json::Null; },
+        }
+    }
+}
+
+impl<T: FromJson> FromJson for Option<T> {
+    fn from_json(json: &Json) -> Result<Self, JsonError> {
+        match json {
+            Json::Null => { return Ok(None); },
+            other => { return T::from_json(other).map(Some); },
+        }
+    }
+}
+
+/// 递归的类型(比如链表的节点)需要Box
+impl<T: ToJson> ToJson for Box<T> {
+    fn to_json(&self) -> Json { return self.as_ref().to_json(); }
+}
+
+impl<T: FromJson> FromJson for Box<T> {
+    fn from_json(json: &Json) -> Result<Self, JsonError> { return T::from_json(json).map(Box::new); }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn to_json(&self) -> Json { return Json::Array(self.iter().map(ToJson::to_json).collect()); }
+}
+
+impl<T: FromJson> FromJson for Vec<T> {
+    fn from_json(json: &Json) -> Result<Self, JsonError> {
+        match json {
+            Json::Array(arr) => {
+                let mut items = Vec::with_capacity(arr.len());
+                for (i, item) in arr.iter().enumerate() {
+                    items.push(T::from_json(item).map_err(|err| err.prefix(&std::format!("[{}]:", i)))?);
+                }
+                return Ok(items);
+            },
+            other => { return Err(mismatch("array", other)); },
+        }
+    }
+}
+
+impl<T: ToJson> ToJson for HashMap<String, T> {
+    fn to_json(&self) -> Json { return Json::Object(self.iter().map(|(key, value)| (key.clone(), value.to_json())).collect()); }
+}
+
+impl<T: FromJson> FromJson for HashMap<String, T> {
+    fn from_json(json: &Json) -> Result<Self, JsonError> {
+        let mut map = HashMap::new();
+        for (key, value) in expect_object(json, "map")? {
+            map.insert(key.clone(), T::from_json(value).map_err(|err| err.prefix(&std::format!("{}:", key)))?);
+        }
+        return Ok(map);
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_convert() {
+    let json = Json::str_to_json(r#"{"a":[1,2],"b":null,"c":"x"}"#).unwrap();
+    let obj = expect_object(&json, "Test").unwrap();
+    assert_eq!(field::<Vec<i64>>(obj, "a").unwrap(), vec![1, 2]);
+    assert_eq!(field::<Option<String>>(obj, "b").unwrap(), None);
+    assert_eq!(field::<Option<f64>>(obj, "missing").unwrap(), None);
+    assert_eq!(field::<String>(obj, "c").unwrap(), "x");
+    assert_eq!(field::<String>(obj, "a").err().unwrap().to_string(), "a:需要string,不能是array");
+    assert_eq!(field::<Vec<bool>>(obj, "a").err().unwrap().to_string(), "a:[0]:需要boolean,不能是number");
+    assert_eq!(i64::from_json(&Json::Number(1.5)).err().unwrap().to_string(), "需要整数,不能是1.5");
+    let map = HashMap::<String, Json>::from_json(&json).unwrap();
+    assert_eq!(map.to_json(), json);
+    assert_eq!(Some(3i64).to_json(), Json::Number(3.0));
+    assert!(expect_object(&Json::Null, "Test").err().unwrap().to_string() == "需要object(Test),不能是null");
+}
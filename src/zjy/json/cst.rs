@@ -0,0 +1,653 @@
+/**
+ * 文件名: "src/zjy/json/cst.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::fmt;
+
+use crate::zjy::json::{Json, JsonError, ParseOptions};
+use crate::zjy::json::ast::string::{StringContext, StringMode};
+use crate::zjy::json::json5::{is_identifier, is_identifier_part};
+use crate::zjy::json::jsonc::write_json_string;
+use crate::zjy::json::parser::parse_str;
+use crate::zjy::json::format::es_number;
+use crate::zjy::json::pointer;
+
+/**
+ * 保留格式的具体语法树(CST)
+ * 记录了空白,注释,key的顺序以及number和string的原始写法,
+ * 没有修改过的文档通过to_string()可以逐字节还原输入,
+ * 修改(set,insert,remove,rename_key)只会重写被修改的部分
+ */
+#[derive(Clone)]
+pub struct CstDocument {
+    options: ParseOptions,
+    /// 最外层值前面的空白和注释
+    leading: String,
+    root: CstNode,
+    /// 最外层值后面的内容
+    trailing: String,
+}
+
+/**
+ * CST的节点,Scalar保存null,true,false,number,string的原始文本
+ */
+#[derive(Clone)]
+enum CstNode {
+    Scalar(String),
+    Container(CstContainer),
+}
+
+/**
+ * array或者object
+ * tail是最后一个逗号(或者开始括号)和结束括号之间的空白和注释
+ */
+#[derive(Clone)]
+struct CstContainer {
+    is_object: bool,
+    items: Vec<CstItem>,
+    tail: String,
+}
+
+/**
+ * array的元素或者object的成员
+ * 输出顺序: before key value after ','
+ */
+#[derive(Clone)]
+struct CstItem {
+    before: String,
+    key: Option<CstKey>,
+    value: CstNode,
+    after: String,
+    comma: bool,
+}
+
+/**
+ * object成员的key,输出顺序: raw after_key ':' before_value
+ * name是转义之后的key
+ */
+#[derive(Clone)]
+struct CstKey {
+    raw: String,
+    name: String,
+    after_key: String,
+    before_value: String,
+}
+
+impl CstDocument {
+    /**
+     * 按照RFC 8259严格解析,生成保留格式的语法树
+     */
+    pub fn parse(str: &str) -> Result<CstDocument, JsonError> {
+        return CstDocument::parse_with_options(str, &ParseOptions::default());
+    }
+    /**
+     * 按照options中的选项解析,生成保留格式的语法树,比如ParseOptions::jsonc()可以保留注释
+     */
+    pub fn parse_with_options(str: &str, options: &ParseOptions) -> Result<CstDocument, JsonError> {
+        // 先检查输入是否合法,之后构建语法树时不需要再处理错误
+        parse_str(str, options)?;
+        let mut parser = CstParser { str, pos: 0, options };
+        let leading = parser.trivia();
+        let root = parser.value();
+        let trailing = str[parser.pos..].to_string();
+        return Ok(CstDocument { options: options.clone(), leading, root, trailing });
+    }
+    /**
+     * 转换成json实例对象
+     */
+    pub fn to_json(&self) -> Result<Json, JsonError> {
+        return parse_str(&self.to_string(), &self.options);
+    }
+    /**
+     * 获取pointer对应的值,找不到时返回None
+     */
+    pub fn get(&self, pointer: &str) -> Option<Json> {
+        let node = self.find(pointer)?;
+        let mut text = String::new();
+        node.write(&mut text);
+        return parse_str(&text, &self.options).ok();
+    }
+    /**
+     * 把pointer对应的值替换成value,pointer指向object中不存在的key时添加这个成员
+     */
+    pub fn set(&mut self, pointer: &str, value: &Json) -> Result<(), JsonError> {
+        if let Some(node) = self.find_mut(pointer) {
+            *node = CstNode::from_json(value);
+            return Ok(());
+        }
+        return self.insert(pointer, value);
+    }
+    /**
+     * 插入一个新值
+     * pointer的父节点是object时添加成员,key已经存在时返回Err;
+     * 父节点是array时在下标处插入,下标可以是array的长度或者 "-" ,代表添加到最后
+     */
+    pub fn insert(&mut self, pointer: &str, value: &Json) -> Result<(), JsonError> {
+        let (parent, token) = split_last(pointer)?;
+        let container = self.find_container_mut(&parent, pointer)?;
+        let index = if container.is_object {
+            if container.position(&token).is_some() {
+                return Err(std::format!("key已经存在:{}", pointer).into());
+            }
+            container.items.len()
+        } else if token == "-" {
+            container.items.len()
+        } else {
+            match pointer::parse_index(&token) {
+                Some(index) if index <= container.items.len() => index,
+                _ => { return Err(std::format!("array下标不合法:{}", pointer).into()); },
+            }
+        };
+        let key = if container.is_object {
+            Some(CstKey::new(&token, container.key_style()))
+        } else {
+            None
+        };
+        container.insert(index, key, CstNode::from_json(value));
+        return Ok(());
+    }
+    /**
+     * 删除pointer对应的值,并且修正前后的逗号
+     */
+    pub fn remove(&mut self, pointer: &str) -> Result<(), JsonError> {
+        let (parent, token) = split_last(pointer)?;
+        let container = self.find_container_mut(&parent, pointer)?;
+        match container.position(&token) {
+            Some(index) => {
+                container.remove(index);
+                return Ok(());
+            },
+            None => { return Err(std::format!("找不到pointer对应的值:{}", pointer).into()); },
+        }
+    }
+    /**
+     * 修改object成员的key,值和格式不变,新的key已经存在时返回Err
+     */
+    pub fn rename_key(&mut self, pointer: &str, new_key: &str) -> Result<(), JsonError> {
+        let (parent, token) = split_last(pointer)?;
+        let container = self.find_container_mut(&parent, pointer)?;
+        if !container.is_object {
+            return Err(std::format!("pointer对应的值不是object的成员:{}", pointer).into());
+        }
+        if token != new_key && container.position(new_key).is_some() {
+            return Err(std::format!("key已经存在:{}", new_key).into());
+        }
+        let index = match container.position(&token) {
+            Some(index) => index,
+            None => { return Err(std::format!("找不到pointer对应的值:{}", pointer).into()); },
+        };
+        if let Some(key) = &mut container.items[index].key {
+            // 原来不带引号的key在可能的情况下继续不带引号
+            let unquoted = !key.raw.starts_with('"') && !key.raw.starts_with('\'');
+            let renamed = CstKey::new(new_key, unquoted);
+            key.raw = renamed.raw;
+            key.name = renamed.name;
+        }
+        return Ok(());
+    }
+
+    fn find(&self, pointer: &str) -> Option<&CstNode> {
+        let mut node = &self.root;
+        for token in pointer::split_pointer(pointer)? {
+            match node {
+                CstNode::Container(container) => {
+                    node = &container.items[container.position(&token)?].value;
+                },
+                CstNode::Scalar(_) => { return None; },
+            }
+        }
+        return Some(node);
+    }
+
+    fn find_mut(&mut self, pointer: &str) -> Option<&mut CstNode> {
+        let mut node = &mut self.root;
+        for token in pointer::split_pointer(pointer)? {
+            match node {
+                CstNode::Container(container) => {
+                    let index = container.position(&token)?;
+                    node = &mut container.items[index].value;
+                },
+                CstNode::Scalar(_) => { return None; },
+            }
+        }
+        return Some(node);
+    }
+
+    fn find_container_mut(&mut self, parent: &str, pointer: &str) -> Result<&mut CstContainer, JsonError> {
+        match self.find_mut(parent) {
+            Some(CstNode::Container(container)) => { return Ok(container); },
+            _ => { return Err(std::format!("找不到pointer对应的array或object:{}", pointer).into()); },
+        }
+    }
+}
+
+impl fmt::Display for CstDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::new();
+        out.push_str(&self.leading);
+        self.root.write(&mut out);
+        out.push_str(&self.trailing);
+        return f.write_str(&out);
+    }
+}
+
+/**
+ * 把pointer拆分成父节点的pointer和最后一级key
+ */
+fn split_last(pointer: &str) -> Result<(String, String), JsonError> {
+    let tokens = match pointer::split_pointer(pointer) {
+        Some(tokens) if !tokens.is_empty() => tokens,
+        _ => { return Err(std::format!("pointer不合法:'{}'", pointer).into()); },
+    };
+    let index = pointer.rfind('/').unwrap_or(0);
+    return Ok((pointer[..index].to_string(), tokens[tokens.len() - 1].clone()));
+}
+
+impl CstNode {
+    /**
+     * 把json值转换成紧凑格式的节点,object的key按照字典序输出
+     */
+    fn from_json(json: &Json) -> CstNode {
+        let mut raw = String::new();
+        match json {
+            Json::Null => { raw.push_str("null"); },
+            Json::Boolean(b) => { raw.push_str(if *b { "true" } else { "false" }); },
+            Json::Number(n) => { raw.push_str(&es_number(*n).unwrap_or_else(|| "null".to_string())); },
+            Json::String(s) => { write_json_string(s, &mut raw); },
+            Json::Array(arr) => {
+                let mut container = CstContainer { is_object: false, items: Vec::new(), tail: String::new() };
+                for value in arr {
+                    let index = container.items.len();
+                    container.insert(index, None, CstNode::from_json(value));
+                }
+                return CstNode::Container(container);
+            },
+            Json::Object(obj) => {
+                let mut keys: Vec<&String> = obj.keys().collect();
+                keys.sort();
+                let mut container = CstContainer { is_object: true, items: Vec::new(), tail: String::new() };
+                for key in keys {
+                    let index = container.items.len();
+                    container.insert(index, Some(CstKey::new(key, false)), CstNode::from_json(&obj[key]));
+                }
+                return CstNode::Container(container);
+            },
+        }
+        return CstNode::Scalar(raw);
+    }
+
+    fn write(&self, out: &mut String) {
+        match self {
+            CstNode::Scalar(raw) => { out.push_str(raw); },
+            CstNode::Container(container) => {
+                out.push(if container.is_object { '{' } else { '[' });
+                for item in &container.items {
+                    out.push_str(&item.before);
+                    if let Some(key) = &item.key {
+                        out.push_str(&key.raw);
+                        out.push_str(&key.after_key);
+                        out.push(':');
+                        out.push_str(&key.before_value);
+                    }
+                    item.value.write(out);
+                    out.push_str(&item.after);
+                    if item.comma {
+                        out.push(',');
+                    }
+                }
+                out.push_str(&container.tail);
+                out.push(if container.is_object { '}' } else { ']' });
+            },
+        }
+    }
+}
+
+impl CstKey {
+    /**
+     * 新建一个key,unquoted为true并且key符合标识符语法时不加引号,冒号前后没有空白
+     */
+    fn new(name: &str, unquoted: bool) -> CstKey {
+        let mut raw = String::new();
+        if unquoted && is_identifier(name) {
+            raw.push_str(name);
+        } else {
+            write_json_string(name, &mut raw);
+        }
+        return CstKey { raw, name: name.to_string(), after_key: String::new(), before_value: String::new() };
+    }
+}
+
+impl CstContainer {
+    /**
+     * 查找pointer中的一级对应的元素下标,object中有重复的key时使用最后一个
+     */
+    fn position(&self, token: &str) -> Option<usize> {
+        if self.is_object {
+            return self.items.iter().rposition(|item| matches!(&item.key, Some(key) if key.name == token));
+        }
+        return pointer::parse_index(token).filter(|index| *index < self.items.len());
+    }
+    /**
+     * 新成员的key是否不加引号,和已有的成员保持一致
+     */
+    fn key_style(&self) -> bool {
+        return self.items.iter().any(|item| matches!(&item.key, Some(key) if !key.raw.starts_with('"') && !key.raw.starts_with('\'')));
+    }
+    /**
+     * 在index处插入新元素,空白和相邻的元素保持一致
+     */
+    fn insert(&mut self, index: usize, mut key: Option<CstKey>, value: CstNode) {
+        if self.items.is_empty() {
+            self.items.push(CstItem { before: String::new(), key, value, after: String::new(), comma: false });
+            return;
+        }
+        // 复制相邻元素的缩进和key周围的空白
+        let neighbor = &self.items[if index < self.items.len() { index } else { index - 1 }];
+        let before = neighbor.before.clone();
+        if let (Some(key), Some(neighbor_key)) = (&mut key, &neighbor.key) {
+            key.after_key = neighbor_key.after_key.clone();
+            key.before_value = neighbor_key.before_value.clone();
+        }
+        if index == self.items.len() {
+            // 添加到最后: 原来最后一个元素后面(最后一个换行开始)的空白移到新元素后面,末尾逗号的写法不变
+            let last = self.items.last_mut().unwrap();
+            let comma = last.comma;
+            let split = last.after.rfind('\n').unwrap_or(0);
+            let after = last.after.split_off(split);
+            last.comma = true;
+            self.items.push(CstItem { before, key, value, after, comma });
+        } else {
+            self.items.insert(index, CstItem { before, key, value, after: String::new(), comma: true });
+        }
+    }
+    /**
+     * 删除index处的元素
+     * 删除的元素前面和上一个元素在同一行的注释(比如 "a": 1, // 说明)属于上一个元素,会被保留
+     */
+    fn remove(&mut self, index: usize) {
+        let removed = self.items.remove(index);
+        // 没有换行时前面的注释都和上一个元素在同一行
+        let line = match removed.before.find('\n') {
+            Some(end) => &removed.before[..end],
+            None => removed.before.trim_end(),
+        };
+        let comment = if index > 0 && !line.trim().is_empty() { line } else { "" };
+        if index < self.items.len() {
+            // 下一个元素前面的第一行是被删除的元素的注释,换成上一个元素的注释
+            if !comment.is_empty() {
+                let next = &mut self.items[index];
+                let rest = match (next.before.find('\n'), removed.before.rfind('\n')) {
+                    (Some(start), _) => next.before[start..].to_string(),
+                    (None, Some(start)) => removed.before[start..].to_string(),
+                    (None, None) => next.before.clone(),
+                };
+                next.before = std::format!("{}{}", comment, rest);
+            }
+            return;
+        }
+        // 删除的是最后一个元素: 把它后面(最后一个换行开始)的空白交给新的最后一个元素,末尾逗号的写法不变
+        let split = removed.after.rfind('\n').unwrap_or(0);
+        let mut after = &removed.after[split..];
+        match self.items.last_mut() {
+            Some(last) => {
+                last.comma = removed.comma;
+                if comment.is_empty() {
+                    last.after.push_str(after);
+                } else if removed.comma {
+                    // 末尾有逗号时注释在逗号后面,也就是tail的第一行
+                    let rest = match self.tail.find('\n') {
+                        Some(start) => self.tail[start..].to_string(),
+                        None => "\n".to_string(),
+                    };
+                    last.after.push_str(after);
+                    self.tail = std::format!("{}{}", comment, rest);
+                } else {
+                    // 注释后面必须换行,否则会注释掉结束括号
+                    if !after.contains('\n') {
+                        after = "\n";
+                    }
+                    last.after.push_str(comment);
+                    last.after.push_str(after);
+                }
+            },
+            None => {
+                if !self.tail.trim().is_empty() || !after.trim().is_empty() {
+                    self.tail.push_str(after);
+                } else {
+                    self.tail.clear();
+                }
+            },
+        }
+    }
+}
+
+/**
+ * 从已经检查过的合法输入构建语法树,pos是当前的字节位置
+ */
+struct CstParser<'a> {
+    str: &'a str,
+    pos: usize,
+    options: &'a ParseOptions,
+}
+
+impl<'a> CstParser<'a> {
+    fn peek(&self) -> Option<char> {
+        return self.str[self.pos..].chars().next();
+    }
+
+    fn bump(&mut self) {
+        if let Some(c) = self.peek() {
+            self.pos += c.len_utf8();
+        }
+    }
+
+    /**
+     * 读取空白和注释
+     */
+    fn trivia(&mut self) -> String {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if self.options.whitespace.contains(c) {
+                self.bump();
+            } else if c == '/' && self.options.extensions.comments {
+                let rest = &self.str[self.pos..];
+                if rest.starts_with("//") {
+                    let end = rest.find(['\n', '\r', '\u{2028}', '\u{2029}']).unwrap_or(rest.len());
+                    self.pos += end;
+                } else {
+                    let end = rest[2..].find("*/").map(|end| end + 4).unwrap_or(rest.len());
+                    self.pos += end;
+                }
+            } else {
+                break;
+            }
+        }
+        return self.str[start..self.pos].to_string();
+    }
+
+    fn value(&mut self) -> CstNode {
+        match self.peek() {
+            Some('{') => { return CstNode::Container(self.container(true)); },
+            Some('[') => { return CstNode::Container(self.container(false)); },
+            _ => { return CstNode::Scalar(self.scalar()); },
+        }
+    }
+
+    /**
+     * 读取string,number,true,false,null的原始文本
+     */
+    fn scalar(&mut self) -> String {
+        let start = self.pos;
+        match self.peek() {
+            Some(quote) if quote == '"' || quote == '\'' => {
+                self.bump();
+                while let Some(c) = self.peek() {
+                    self.bump();
+                    if c == '\\' {
+                        self.bump();
+                    } else if c == quote {
+                        break;
+                    }
+                }
+            },
+            _ => {
+                while let Some(c) = self.peek() {
+                    if matches!(c, ',' | ']' | '}' | ':' | '/') || self.options.whitespace.contains(c) || c.is_whitespace() {
+                        break;
+                    }
+                    self.bump();
+                }
+            },
+        }
+        return self.str[start..self.pos].to_string();
+    }
+
+    fn key(&mut self) -> CstKey {
+        let raw = match self.peek() {
+            Some('"') | Some('\'') => self.scalar(),
+            _ => {
+                let start = self.pos;
+                while matches!(self.peek(), Some(c) if is_identifier_part(c)) {
+                    self.bump();
+                }
+                self.str[start..self.pos].to_string()
+            },
+        };
+        let name = decode_key(&raw, self.options);
+        let after_key = self.trivia();
+        // 冒号
+        self.bump();
+        let before_value = self.trivia();
+        return CstKey { raw, name, after_key, before_value };
+    }
+
+    fn container(&mut self, is_object: bool) -> CstContainer {
+        let close = if is_object { '}' } else { ']' };
+        // 开始括号
+        self.bump();
+        let mut items = Vec::new();
+        loop {
+            let before = self.trivia();
+            if self.peek() == Some(close) || self.peek().is_none() {
+                self.bump();
+                return CstContainer { is_object, items, tail: before };
+            }
+            let key = if is_object { Some(self.key()) } else { None };
+            let value = self.value();
+            let after = self.trivia();
+            let comma = self.peek() == Some(',');
+            self.bump();
+            items.push(CstItem { before, key, value, after, comma });
+            if !comma {
+                return CstContainer { is_object, items, tail: String::new() };
+            }
+        }
+    }
+}
+
+/**
+ * 把key的原始文本转换成转义之后的key
+ */
+fn decode_key(raw: &str, options: &ParseOptions) -> String {
+    let mut chars = raw.chars();
+    match chars.next() {
+        Some(quote) if quote == '"' || quote == '\'' => {
+            let mut context = StringContext::new_str(quote);
+            for c in chars {
+                if context.check_string(c, options).is_err() || *context.get_mode() == StringMode::end {
+                    break;
+                }
+            }
+            return context.get_str().to_string();
+        },
+        _ => { return raw.to_string(); },
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_cst() {
+    // 没有修改时逐字节还原
+    for file in ["tests/testobject.json", "tests/testarray.json", "tests/teststring.json", "tests/testnull.json"] {
+        let str = std::fs::read_to_string(file).unwrap();
+        assert_eq!(CstDocument::parse(&str).unwrap().to_string(), str);
+    }
+    let config = "// 配置
+{
+    \"name\" : \"zjy\",   // 名字
+    \"size\": 1.50e1,
+    \"tags\": [ \"a\",\"b\" ],
+    \"escape\": \"\\u0041\",
+}
+";
+    let mut doc = CstDocument::parse_with_options(config, &ParseOptions::jsonc()).unwrap();
+    assert_eq!(doc.to_string(), config);
+    assert_eq!(doc.get("/escape").unwrap().to_string(), "\"A\"");
+    // 只重写修改过的值
+    doc.set("/size", &Json::Number(16.0)).unwrap();
+    assert_eq!(doc.to_string(), config.replace("1.50e1", "16"));
+    doc.rename_key("/name", "title").unwrap();
+    assert!(doc.to_string().contains("\"title\" : \"zjy\",   // 名字"));
+    doc.insert("/tags/1", &Json::String("x".to_string())).unwrap();
+    doc.insert("/tags/-", &Json::Null).unwrap();
+    assert!(doc.to_string().contains("\"tags\": [ \"a\",\"x\",\"b\",null ],"));
+    doc.remove("/escape").unwrap();
+    doc.set("/new", &Json::Boolean(true)).unwrap();
+    assert_eq!(doc.to_string(), "// 配置
+{
+    \"title\" : \"zjy\",   // 名字
+    \"size\": 16,
+    \"tags\": [ \"a\",\"x\",\"b\",null ],
+    \"new\": true,
+}
+");
+    assert!(doc.insert("/new", &Json::Null).is_err());
+    assert!(doc.rename_key("/size", "new").is_err());
+    assert!(doc.remove("/missing").is_err());
+    assert!(doc.insert("/tags/01", &Json::Null).is_err() && doc.remove("/tags/+1").is_err());
+    assert_eq!(doc.to_json().unwrap().pointer("/tags/3").unwrap().to_string(), "null");
+    // 删除最后一个成员时修正逗号
+    let mut doc = CstDocument::parse("{\n  \"a\": 1,\n  \"b\": 2\n}").unwrap();
+    doc.remove("/b").unwrap();
+    assert_eq!(doc.to_string(), "{\n  \"a\": 1\n}");
+    doc.insert("/c", &Json::Array(vec![])).unwrap();
+    assert_eq!(doc.to_string(), "{\n  \"a\": 1,\n  \"c\": []\n}");
+    doc.remove("/a").unwrap();
+    doc.remove("/c").unwrap();
+    assert_eq!(doc.to_string(), "{}");
+    // 删除成员时保留上一个成员同一行的注释
+    let config = "{\n  \"a\": 1, // a的说明\n  \"b\": 2, // b的说明\n  \"c\": 3, /* c */ \"d\": 4 // d的说明\n}";
+    let mut doc = CstDocument::parse_with_options(config, &ParseOptions::jsonc()).unwrap();
+    doc.remove("/b").unwrap();
+    assert_eq!(doc.to_string(), "{\n  \"a\": 1, // a的说明\n  \"c\": 3, /* c */ \"d\": 4 // d的说明\n}");
+    doc.remove("/d").unwrap();
+    assert_eq!(doc.to_string(), "{\n  \"a\": 1, // a的说明\n  \"c\": 3 /* c */\n}");
+    doc.remove("/c").unwrap();
+    assert_eq!(doc.to_string(), "{\n  \"a\": 1 // a的说明\n}");
+    let config = "[\n  1, // 一\n  2, // 二\n]";
+    let mut doc = CstDocument::parse_with_options(config, &ParseOptions::jsonc()).unwrap();
+    doc.remove("/1").unwrap();
+    assert_eq!(doc.to_string(), "[\n  1, // 一\n]");
+    let mut doc = CstDocument::parse_with_options("{\"a\": 1, // 说明\n\"b\": 2}", &ParseOptions::jsonc()).unwrap();
+    doc.remove("/b").unwrap();
+    assert_eq!(doc.to_string(), "{\"a\": 1 // 说明\n}");
+    assert!(Json::jsonc_to_json(&doc.to_string()).is_ok());
+    let mut doc = CstDocument::parse_with_options("[1, /* 一 */ 2, 3]", &ParseOptions::jsonc()).unwrap();
+    doc.remove("/1").unwrap();
+    assert_eq!(doc.to_string(), "[1, /* 一 */ 3]");
+}
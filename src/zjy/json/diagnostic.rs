@@ -0,0 +1,460 @@
+/**
+ * 文件名: "src/zjy/json/diagnostic.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::fmt;
+
+use crate::zjy::json::{judgment_json_type, Json, JsonError, Limit, ParseOptions};
+use crate::zjy::json::ast::root::RootContext;
+use crate::zjy::json::json5::is_identifier_start;
+use crate::zjy::json::parser::parse_str_diagnostic;
+use crate::zjy::json::span::Position;
+
+/**
+ * 出错位置需要的符号
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Expected {
+    /// 一个具体的字符,比如 ',' 或 '}'
+    Char(char),
+    /// 任意json值
+    Value,
+    /// object的key
+    Key,
+    /// null,true或false中剩下的字符
+    Literal(&'static str),
+    /// 输入结束(只允许空白)
+    EndOfInput,
+}
+
+/**
+ * 错误的种类,用于选择本地化的错误信息
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// 出现了不应该出现的字符
+    UnexpectedChar(char),
+    /// 输入在json值结束之前结束
+    UnexpectedEnd,
+    /// number不合法
+    InvalidNumber,
+    /// string不合法(转义,控制字符等)
+    InvalidString,
+    /// 超出资源限制
+    LimitExceeded(Limit),
+    /// 其他错误,比如重复的key,详细信息见error
+    Invalid,
+}
+
+/**
+ * 错误信息使用的语言
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    Chinese,
+    English,
+}
+
+/**
+ * 一种语言的错误信息表,模板中的 "{}" 按顺序替换成参数
+ */
+pub struct Catalog {
+    pub error: &'static str,
+    pub note: &'static str,
+    pub location: &'static str,
+    pub unexpected_char: &'static str,
+    pub unexpected_end: &'static str,
+    pub invalid_number: &'static str,
+    pub invalid_string: &'static str,
+    pub invalid: &'static str,
+    pub limit_exceeded: &'static str,
+    pub expected: &'static str,
+    pub or: &'static str,
+    pub value: &'static str,
+    pub key: &'static str,
+    pub end_of_input: &'static str,
+    pub depth: &'static str,
+    pub total_bytes: &'static str,
+    pub string_length: &'static str,
+    pub array_length: &'static str,
+    pub object_members: &'static str,
+    pub number_length: &'static str,
+}
+
+static CHINESE: Catalog = Catalog {
+    error: "错误",
+    note: "说明",
+    location: "第{}行,第{}列",
+    unexpected_char: "此处不应是:'{}'",
+    unexpected_end: "输入意外结束",
+    invalid_number: "不是一个合法的数字",
+    invalid_string: "不是一个合法的string",
+    invalid: "json不合法",
+    limit_exceeded: "超出资源限制:{}",
+    expected: "需要{}",
+    or: "或",
+    value: "json值",
+    key: "key(string)",
+    end_of_input: "输入结束",
+    depth: "嵌套深度超过{}",
+    total_bytes: "文档大小超过{}字节",
+    string_length: "string长度超过{}字节",
+    array_length: "array元素个数超过{}",
+    object_members: "object成员个数超过{}",
+    number_length: "number长度超过{}个字符",
+};
+
+static ENGLISH: Catalog = Catalog {
+    error: "error",
+    note: "note",
+    location: "line {}, column {}",
+    unexpected_char: "unexpected character '{}'",
+    unexpected_end: "unexpected end of input",
+    invalid_number: "invalid number",
+    invalid_string: "invalid string",
+    invalid: "invalid JSON",
+    limit_exceeded: "resource limit exceeded: {}",
+    expected: "expected {}",
+    or: "or",
+    value: "a value",
+    key: "a key (string)",
+    end_of_input: "end of input",
+    depth: "nesting depth over {}",
+    total_bytes: "document larger than {} bytes",
+    string_length: "string longer than {} bytes",
+    array_length: "array with more than {} elements",
+    object_members: "object with more than {} members",
+    number_length: "number longer than {} characters",
+};
+
+impl Language {
+    /**
+     * 获取这种语言的错误信息表
+     */
+    pub fn catalog(&self) -> &'static Catalog {
+        match self {
+            Language::Chinese => { return &CHINESE; },
+            Language::English => { return &ENGLISH; },
+        }
+    }
+}
+
+/**
+ * 把模板中的 "{}" 按顺序替换成参数
+ */
+fn fill(template: &str, args: &[&dyn fmt::Display]) -> String {
+    let mut out = String::new();
+    let mut args = args.iter();
+    let mut rest = template;
+    while let Some(index) = rest.find("{}") {
+        out.push_str(&rest[..index]);
+        if let Some(arg) = args.next() {
+            out.push_str(&arg.to_string());
+        }
+        rest = &rest[index + 2..];
+    }
+    out.push_str(rest);
+    return out;
+}
+
+/**
+ * 带有位置和需要的符号的解析错误,可以通过DiagnosticRenderer渲染成带源码片段的信息
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    /// 出错字符的位置,输入意外结束时是输入末尾的位置
+    pub position: Position,
+    /// 出错位置需要的符号,不能确定时为空
+    pub expected: Vec<Expected>,
+    /// 原始的错误
+    pub error: JsonError,
+}
+
+impl Diagnostic {
+    /**
+     * 根据出错时解析上下文的状态生成诊断信息
+     */
+    pub(crate) fn new(error: JsonError, position: Position, source: &str, context: &RootContext, options: &ParseOptions) -> Diagnostic {
+        let found = source.get(position.offset..).and_then(|rest| rest.chars().next());
+        let expected = if error.is_limit_exceeded() { None } else { context.expected(options) };
+        let kind = match (&error, found, &expected) {
+            (JsonError::LimitExceeded(limit), _, _) => DiagnosticKind::LimitExceeded(*limit),
+            (_, None, _) => DiagnosticKind::UnexpectedEnd,
+            (_, Some(c), Some(expected)) if !expected.iter().any(|e| e.matches(c, options)) => DiagnosticKind::UnexpectedChar(c),
+            (_, Some(_), Some(_)) => DiagnosticKind::Invalid,
+            (_, Some(_), None) if context.in_number() => DiagnosticKind::InvalidNumber,
+            (_, Some(_), None) => DiagnosticKind::InvalidString,
+        };
+        return Diagnostic { kind, position, expected: expected.unwrap_or_default(), error };
+    }
+    /**
+     * 错误信息的标题,比如 "此处不应是:'x',需要 ',' 或 '}'"
+     */
+    pub fn message(&self, language: Language) -> String {
+        let catalog = language.catalog();
+        let mut message = match self.kind {
+            DiagnosticKind::UnexpectedChar(c) => fill(catalog.unexpected_char, &[&c.escape_debug()]),
+            DiagnosticKind::UnexpectedEnd => catalog.unexpected_end.to_string(),
+            DiagnosticKind::InvalidNumber => catalog.invalid_number.to_string(),
+            DiagnosticKind::InvalidString => catalog.invalid_string.to_string(),
+            DiagnosticKind::Invalid => catalog.invalid.to_string(),
+            DiagnosticKind::LimitExceeded(limit) => fill(catalog.limit_exceeded, &[&limit_message(limit, catalog)]),
+        };
+        if let Some(hint) = self.expected_hint(language) {
+            message.push_str(if language == Language::Chinese { "," } else { ", " });
+            message.push_str(&hint);
+        }
+        return message;
+    }
+    /**
+     * 需要的符号的提示,比如 "expected ',' or '}'",没有时返回None
+     */
+    pub fn expected_hint(&self, language: Language) -> Option<String> {
+        if self.expected.is_empty() || matches!(self.kind, DiagnosticKind::LimitExceeded(_)) {
+            return None;
+        }
+        let catalog = language.catalog();
+        let items: Vec<String> = self.expected.iter().map(|expected| match expected {
+            Expected::Char(c) => std::format!("'{}'", c),
+            Expected::Value => catalog.value.to_string(),
+            Expected::Key => catalog.key.to_string(),
+            Expected::Literal(literal) => std::format!("'{}'", literal),
+            Expected::EndOfInput => catalog.end_of_input.to_string(),
+        }).collect();
+        let list = match items.split_last() {
+            Some((last, rest)) if !rest.is_empty() => std::format!("{} {} {}", rest.join(", "), catalog.or, last),
+            _ => items.join(""),
+        };
+        let separator = if language == Language::Chinese { " " } else { "" };
+        return Some(fill(catalog.expected, &[&std::format!("{}{}", separator, list)]));
+    }
+    /**
+     * 原始错误信息中去掉位置前缀之后的部分,作为补充说明
+     */
+    fn detail(&self) -> Option<String> {
+        match (&self.error, self.kind) {
+            (JsonError::Syntax(msg), DiagnosticKind::Invalid | DiagnosticKind::InvalidNumber | DiagnosticKind::InvalidString | DiagnosticKind::UnexpectedEnd) => {
+                let detail = match msg.find("个字符:") {
+                    Some(index) if msg.starts_with('第') => &msg[index + "个字符:".len()..],
+                    _ => msg.as_str(),
+                };
+                return Some(detail.to_string());
+            },
+            _ => { return None; },
+        }
+    }
+    /**
+     * 使用默认的渲染器(中文,不带颜色)渲染
+     */
+    pub fn render(&self, source: &str) -> String {
+        return DiagnosticRenderer::new().render(source, self);
+    }
+}
+
+impl Expected {
+    /**
+     * 判断字符c是否满足需要的符号
+     */
+    fn matches(&self, c: char, options: &ParseOptions) -> bool {
+        match self {
+            Expected::Char(expected) => { return c == *expected; },
+            Expected::Value => { return judgment_json_type(c, options).is_ok(); },
+            Expected::Key => {
+                return c == '"' || (c == '\'' && options.extensions.single_quoted_strings)
+                    || (options.extensions.unquoted_keys && is_identifier_start(c));
+            },
+            Expected::Literal(literal) => { return literal.contains(c); },
+            Expected::EndOfInput => { return options.whitespace.contains(c); },
+        }
+    }
+}
+
+fn limit_message(limit: Limit, catalog: &Catalog) -> String {
+    match limit {
+        Limit::Depth(max) => { return fill(catalog.depth, &[&max]); },
+        Limit::TotalBytes(max) => { return fill(catalog.total_bytes, &[&max]); },
+        Limit::StringLength(max) => { return fill(catalog.string_length, &[&max]); },
+        Limit::ArrayLength(max) => { return fill(catalog.array_length, &[&max]); },
+        Limit::ObjectMembers(max) => { return fill(catalog.object_members, &[&max]); },
+        Limit::NumberLength(max) => { return fill(catalog.number_length, &[&max]); },
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{}:{}", fill(CHINESE.location, &[&self.position.line, &self.position.column]), self.message(Language::Chinese));
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/**
+ * 把诊断信息渲染成带源码片段的文本:
+ * 出错的行和前后若干行,出错的列下面用 '^' 标出,并给出需要的符号
+ */
+#[derive(Clone, Debug)]
+pub struct DiagnosticRenderer {
+    language: Language,
+    color: bool,
+    context_lines: usize,
+}
+
+impl Default for DiagnosticRenderer {
+    fn default() -> Self {
+        return DiagnosticRenderer::new();
+    }
+}
+
+impl DiagnosticRenderer {
+    /**
+     * 默认使用中文,不带颜色,显示出错行前后各1行
+     */
+    pub fn new() -> DiagnosticRenderer { DiagnosticRenderer { language: Language::Chinese, color: false, context_lines: 1 } }
+    pub fn language(mut self, language: Language) -> DiagnosticRenderer { self.language = language; return self; }
+    /// 是否使用ANSI颜色,输出到终端时可以开启
+    pub fn color(mut self, color: bool) -> DiagnosticRenderer { self.color = color; return self; }
+    pub fn context_lines(mut self, lines: usize) -> DiagnosticRenderer { self.context_lines = lines; return self; }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            return std::format!("{}{}{}", style, text, RESET);
+        }
+        return text.to_string();
+    }
+
+    /**
+     * 渲染诊断信息,source需要是解析时的输入
+     */
+    pub fn render(&self, source: &str, diagnostic: &Diagnostic) -> String {
+        let catalog = self.language.catalog();
+        let lines: Vec<&str> = source.split('\n').map(|line| line.strip_suffix('\r').unwrap_or(line)).collect();
+        let line = diagnostic.position.line.clamp(1, lines.len());
+        let first = line.saturating_sub(self.context_lines).max(1);
+        let last = (line + self.context_lines).min(lines.len());
+        let width = last.to_string().len();
+        let gutter = |number: Option<usize>| -> String {
+            let label = match number {
+                Some(number) => std::format!("{:>width$} |", number, width = width),
+                None => std::format!("{:>width$} |", "", width = width),
+            };
+            return self.paint(BLUE, &label);
+        };
+
+        let mut out = String::new();
+        out.push_str(&self.paint(RED, catalog.error));
+        out.push_str(&self.paint(BOLD, &std::format!(": {}", diagnostic.message(self.language))));
+        out.push('\n');
+        out.push_str(&std::format!("{:>width$}{} {}\n", "", self.paint(BLUE, "-->"), fill(catalog.location, &[&diagnostic.position.line, &diagnostic.position.column]), width = width));
+        out.push_str(&gutter(None));
+        out.push('\n');
+        for number in first..=last {
+            let text = lines[number - 1];
+            out.push_str(&gutter(Some(number)));
+            if !text.is_empty() {
+                out.push(' ');
+                out.push_str(text);
+            }
+            out.push('\n');
+            if number == line {
+                // 出错列前面的字符替换成等宽的空白,保留tab
+                let mut pad = String::new();
+                for c in text.chars().take(diagnostic.position.column.saturating_sub(1)) {
+                    pad.push_str(if c == '\t' { "\t" } else if is_wide(c) { "  " } else { " " });
+                }
+                let mut caret = self.paint(RED, "^");
+                if let Some(hint) = diagnostic.expected_hint(self.language) {
+                    caret.push(' ');
+                    caret.push_str(&self.paint(RED, &hint));
+                }
+                out.push_str(&std::format!("{} {}{}\n", gutter(None), pad, caret));
+            }
+        }
+        if let Some(detail) = diagnostic.detail() {
+            out.push_str(&std::format!("{:>width$} = {}: {}\n", "", self.paint(BOLD, catalog.note), detail, width = width));
+        }
+        return out;
+    }
+}
+
+/**
+ * 判断字符在终端中是否占两列(中日韩文字,全角符号和大部分emoji)
+ */
+fn is_wide(c: char) -> bool {
+    return matches!(c as u32, 0x1100..=0x115F | 0x2E80..=0xA4CF | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F | 0xFF00..=0xFF60 | 0xFFE0..=0xFFE6 | 0x1F300..=0x1F64F | 0x1F900..=0x1F9FF | 0x20000..=0x3FFFD);
+}
+
+impl Json {
+    /**
+     * 按照RFC 8259严格解析,失败时返回可以渲染成带源码片段的诊断信息
+     */
+    pub fn str_to_json_diagnostic(str: &str) -> Result<Json, Diagnostic> {
+        return parse_str_diagnostic(str, &ParseOptions::default());
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_diagnostic() {
+    let source = "{\n    \"a\": 1,\n    \"b\": 2 x\n}\n";
+    let diagnostic = Json::str_to_json_diagnostic(source).err().unwrap();
+    assert_eq!(diagnostic.kind, DiagnosticKind::UnexpectedChar('x'));
+    assert_eq!((diagnostic.position.line, diagnostic.position.column), (3, 12));
+    assert_eq!(diagnostic.render(source), "错误: 此处不应是:'x',需要 ',' 或 '}'
+ --> 第3行,第12列
+  |
+2 |     \"a\": 1,
+3 |     \"b\": 2 x
+  |            ^ 需要 ',' 或 '}'
+4 | }
+");
+    let english = DiagnosticRenderer::new().language(Language::English).context_lines(0).render(source, &diagnostic);
+    assert_eq!(english, "error: unexpected character 'x', expected ',' or '}'
+ --> line 3, column 12
+  |
+3 |     \"b\": 2 x
+  |            ^ expected ',' or '}'
+");
+    let colored = DiagnosticRenderer::new().color(true).render(source, &diagnostic);
+    assert!(colored.contains("\x1b[1;31m^\x1b[0m"));
+    // 输入意外结束
+    let diagnostic = Json::str_to_json_diagnostic("[1, 2").err().unwrap();
+    assert_eq!(diagnostic.kind, DiagnosticKind::UnexpectedEnd);
+    assert_eq!(diagnostic.position.column, 6);
+    // 不合法的number和string带有补充说明
+    let diagnostic = Json::str_to_json_diagnostic("[01]").err().unwrap();
+    assert_eq!(diagnostic.kind, DiagnosticKind::InvalidNumber);
+    assert!(diagnostic.render("[01]").contains("= 说明: "));
+    let diagnostic = Json::str_to_json_diagnostic("[\"\\q\"]").err().unwrap();
+    assert_eq!(diagnostic.kind, DiagnosticKind::InvalidString);
+    assert_eq!(Json::str_to_json_diagnostic("nul").err().unwrap().expected, vec![Expected::Literal("null")]);
+    // 中文宽字符对齐
+    let diagnostic = Json::str_to_json_diagnostic("{\"中文\" 1}").err().unwrap();
+    assert!(diagnostic.render("{\"中文\" 1}").contains("  |         ^ 需要 ':'"));
+    // 资源限制
+    let diagnostic = crate::zjy::json::Parser::new().max_depth(1).parse_diagnostic("[[1]]").err().unwrap();
+    assert_eq!(diagnostic.kind, DiagnosticKind::LimitExceeded(Limit::Depth(1)));
+    assert_eq!(diagnostic.message(Language::English), "resource limit exceeded: nesting depth over 1");
+}
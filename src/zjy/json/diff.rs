@@ -0,0 +1,249 @@
+/**
+ * 文件名: "src/zjy/json/diff.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::collections::HashMap;
+
+use crate::zjy::json::pointer::join;
+use crate::zjy::json::{Json, NonFinite, SerializeOptions};
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/**
+ * 比较array的方式
+ * Index代表按照下标逐个比较;Set代表忽略顺序,只报告增加和删除的元素;
+ * ById(key)代表按照元素(object)中key的值配对,配对的元素继续比较,其他元素按照Set的方式比较
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ArrayDiff {
+    #[default]
+    Index,
+    Set,
+    ById(String),
+}
+
+/**
+ * 比较时的选项
+ * object总是忽略key的顺序比较(Json::Object本身没有顺序)
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// 比较array的方式
+    pub arrays: ArrayDiff,
+}
+
+/**
+ * 一处差异,pointer是RFC 6901的JSON Pointer
+ * 增加的值和修改后的值使用新文档中的位置,删除的值使用旧文档中的位置
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    Added { pointer: String, value: Json },
+    Removed { pointer: String, value: Json },
+    Changed { pointer: String, old: Json, new: Json },
+}
+
+impl Change {
+    /**
+     * 差异所在的位置
+     */
+    pub fn pointer(&self) -> &str {
+        match self {
+            Change::Added { pointer, .. } | Change::Removed { pointer, .. } | Change::Changed { pointer, .. } => { return pointer; },
+        }
+    }
+}
+
+impl Json {
+    /**
+     * 比较self(旧文档)和other(新文档),按照文档中的顺序(object按照key的字典序)返回所有差异,相等时返回空的Vec
+     */
+    pub fn diff(&self, other: &Json, options: &DiffOptions) -> Vec<Change> {
+        let mut changes = Vec::new();
+        diff_value(self, other, "", options, &mut changes);
+        return changes;
+    }
+}
+
+fn diff_value(old: &Json, new: &Json, pointer: &str, options: &DiffOptions, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Json::Object(a), Json::Object(b)) => {
+            let mut keys: Vec<&String> = a.keys().chain(b.keys().filter(|key| !a.contains_key(*key))).collect();
+            keys.sort();
+            for key in keys {
+                let pointer = join(pointer, key);
+                match (a.get(key), b.get(key)) {
+                    (Some(a), Some(b)) => { diff_value(a, b, &pointer, options, changes); },
+                    (Some(a), None) => { changes.push(Change::Removed { pointer, value: a.clone() }); },
+                    (None, Some(b)) => { changes.push(Change::Added { pointer, value: b.clone() }); },
+                    (None, None) => {},
+                }
+            }
+        },
+        (Json::Array(a), Json::Array(b)) => {
+            match &options.arrays {
+                ArrayDiff::Index => { diff_index(a, b, pointer, options, changes); },
+                ArrayDiff::Set => {
+                    let old: Vec<usize> = (0..a.len()).collect();
+                    let new: Vec<usize> = (0..b.len()).collect();
+                    diff_set(a, b, &old, &new, pointer, changes);
+                },
+                ArrayDiff::ById(key) => { diff_by_id(a, b, key, pointer, options, changes); },
+            }
+        },
+        _ => {
+            if old != new {
+                changes.push(Change::Changed { pointer: pointer.to_string(), old: old.clone(), new: new.clone() });
+            }
+        },
+    }
+}
+
+fn diff_index(a: &[Json], b: &[Json], pointer: &str, options: &DiffOptions, changes: &mut Vec<Change>) {
+    for i in 0..a.len().max(b.len()) {
+        let pointer = join(pointer, &i.to_string());
+        match (a.get(i), b.get(i)) {
+            (Some(a), Some(b)) => { diff_value(a, b, &pointer, options, changes); },
+            (Some(a), None) => { changes.push(Change::Removed { pointer, value: a.clone() }); },
+            (None, Some(b)) => { changes.push(Change::Added { pointer, value: b.clone() }); },
+            (None, None) => {},
+        }
+    }
+}
+
+/**
+ * 忽略顺序比较a中下标为old和b中下标为new的元素,相同的元素按照出现的次数抵消
+ */
+fn diff_set(a: &[Json], b: &[Json], old: &[usize], new: &[usize], pointer: &str, changes: &mut Vec<Change>) {
+    let mut remaining = counts(b, new);
+    for i in old {
+        match remaining.get_mut(&a[*i]) {
+            Some(count) if *count > 0 => { *count -= 1; },
+            _ => { changes.push(Change::Removed { pointer: join(pointer, &i.to_string()), value: a[*i].clone() }); },
+        }
+    }
+    let mut remaining = counts(a, old);
+    for i in new {
+        match remaining.get_mut(&b[*i]) {
+            Some(count) if *count > 0 => { *count -= 1; },
+            _ => { changes.push(Change::Added { pointer: join(pointer, &i.to_string()), value: b[*i].clone() }); },
+        }
+    }
+}
+
+/**
+ * 统计values中下标为indexes的元素出现的次数
+ */
+fn counts<'a>(values: &'a [Json], indexes: &[usize]) -> HashMap<&'a Json, usize> {
+    let mut counts = HashMap::new();
+    for i in indexes {
+        *counts.entry(&values[*i]).or_insert(0) += 1;
+    }
+    return counts;
+}
+
+fn diff_by_id(a: &[Json], b: &[Json], key: &str, pointer: &str, options: &DiffOptions, changes: &mut Vec<Change>) {
+    let id = |value: &Json| -> Option<Json> {
+        match value {
+            Json::Object(obj) => { return obj.get(key).cloned(); },
+            _ => { return None; },
+        }
+    };
+    let mut old_ids: HashMap<Json, usize> = HashMap::new();
+    for (i, value) in a.iter().enumerate() {
+        if let Some(id) = id(value) {
+            old_ids.entry(id).or_insert(i);
+        }
+    }
+    let mut matched = vec![false; a.len()];
+    let mut unmatched_new = Vec::new();
+    for (j, value) in b.iter().enumerate() {
+        match id(value).and_then(|id| old_ids.remove(&id)) {
+            Some(i) => {
+                matched[i] = true;
+                diff_value(&a[i], value, &join(pointer, &j.to_string()), options, changes);
+            },
+            None => { unmatched_new.push(j); },
+        }
+    }
+    let unmatched_old: Vec<usize> = (0..a.len()).filter(|i| !matched[*i]).collect();
+    diff_set(a, b, &unmatched_old, &unmatched_new, pointer, changes);
+}
+
+/**
+ * 把差异输出成类似unified diff的文本,每处差异一行或两行:
+ * "- /pointer: 旧值" 和 "+ /pointer: 新值",color为true时使用ANSI颜色(删除红色,增加绿色,pointer青色)
+ */
+pub fn render_diff(changes: &[Change], color: bool) -> String {
+    let mut out = String::new();
+    let mut line = |sign: char, pointer: &str, value: &Json| {
+        let value = value.to_string_with_options(&SerializeOptions { non_finite: NonFinite::Null, ..SerializeOptions::compact() }).unwrap_or_default();
+        let pointer = if pointer.is_empty() { "(根)" } else { pointer };
+        if color {
+            let sign_color = if sign == '-' { RED } else { GREEN };
+            out.push_str(&std::format!("{}{} {}{}{}:{} {}{}\n", sign_color, sign, CYAN, pointer, RESET, sign_color, value, RESET));
+        } else {
+            out.push_str(&std::format!("{} {}: {}\n", sign, pointer, value));
+        }
+    };
+    for change in changes {
+        match change {
+            Change::Added { pointer, value } => { line('+', pointer, value); },
+            Change::Removed { pointer, value } => { line('-', pointer, value); },
+            Change::Changed { pointer, old, new } => {
+                line('-', pointer, old);
+                line('+', pointer, new);
+            },
+        }
+    }
+    return out;
+}
+
+#[cfg(test)]
+#[test]
+fn test_diff() {
+    let old = Json::str_to_json(r#"{"name":"a","port":80,"tags":["x","y"],"users":[{"id":1,"role":"admin"},{"id":2,"role":"dev"}],"a/b":true}"#).unwrap();
+    let new = Json::str_to_json(r#"{"port":8080,"tags":["y","x","z"],"users":[{"id":2,"role":"ops"},{"id":3,"role":"dev"}],"a/b":true,"debug":null}"#).unwrap();
+    assert!(old.diff(&old.clone(), &DiffOptions::default()).is_empty());
+
+    let changes = old.diff(&new, &DiffOptions::default());
+    let pointers: Vec<&str> = changes.iter().map(|change| change.pointer()).collect();
+    assert_eq!(pointers, ["/debug", "/name", "/port", "/tags/0", "/tags/1", "/tags/2", "/users/0/id", "/users/0/role", "/users/1/id"]);
+    assert_eq!(changes[0], Change::Added { pointer: "/debug".to_string(), value: Json::Null });
+    assert_eq!(changes[1], Change::Removed { pointer: "/name".to_string(), value: Json::String("a".to_string()) });
+    assert_eq!(changes[2], Change::Changed { pointer: "/port".to_string(), old: Json::Number(80.0), new: Json::Number(8080.0) });
+
+    let options = DiffOptions { arrays: ArrayDiff::Set };
+    let pointers: Vec<String> = old.diff(&new, &options).iter().map(|change| change.pointer().to_string()).collect();
+    assert_eq!(pointers, ["/debug", "/name", "/port", "/tags/2", "/users/0", "/users/1", "/users/0", "/users/1"]);
+
+    let options = DiffOptions { arrays: ArrayDiff::ById("id".to_string()) };
+    let changes = old.diff(&new, &options);
+    assert_eq!(changes[3], Change::Added { pointer: "/tags/2".to_string(), value: Json::String("z".to_string()) });
+    assert_eq!(changes[4], Change::Changed { pointer: "/users/0/role".to_string(), old: Json::String("dev".to_string()), new: Json::String("ops".to_string()) });
+    assert!(matches!(&changes[5], Change::Removed { pointer, .. } if pointer == "/users/0"));
+    assert!(matches!(&changes[6], Change::Added { pointer, .. } if pointer == "/users/1"));
+    assert_eq!(changes.len(), 7);
+
+    let report = render_diff(&old.diff(&new, &DiffOptions::default())[..3], false);
+    assert_eq!(report, "+ /debug: null\n- /name: \"a\"\n- /port: 80\n+ /port: 8080\n");
+    assert_eq!(render_diff(&Json::Number(1.0).diff(&Json::Null, &DiffOptions::default()), true), "\x1b[31m- \x1b[36m(根)\x1b[0m:\x1b[31m 1\x1b[0m\n\x1b[32m+ \x1b[36m(根)\x1b[0m:\x1b[32m null\x1b[0m\n");
+}
@@ -0,0 +1,126 @@
+/**
+ * 文件名: "src/zjy/json/error.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::fmt;
+
+/**
+ * 解析json时产生的错误
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JsonError {
+    /// json语法错误,保存了错误信息
+    Syntax(String),
+    /// 超出了ParseOptions中设置的资源限制
+    LimitExceeded(Limit),
+    /// 从Read/BufRead读取输入时发生的io错误,保存了错误信息
+    Io(String),
+    /// 按行读取(NDJSON)时出现在第几行(从1开始)的错误,保存了原来的错误
+    Line(usize, Box<JsonError>),
+}
+
+/**
+ * 超出的是哪一项资源限制,保存了设置的最大值
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    /// 嵌套深度
+    Depth(usize),
+    /// 整个文档的字节数
+    TotalBytes(usize),
+    /// 单个string的字节数(转义之后)
+    StringLength(usize),
+    /// 单个array的元素个数
+    ArrayLength(usize),
+    /// 单个object的成员个数
+    ObjectMembers(usize),
+    /// 单个number的字符个数
+    NumberLength(usize),
+}
+
+impl JsonError {
+    /**
+     * 给语法错误的信息加上前缀,资源限制错误保持原样
+     */
+    pub fn prefix(self, prefix: &str) -> JsonError {
+        match self {
+            JsonError::Syntax(msg) => { return JsonError::Syntax(std::format!("{}{}", prefix, msg)); },
+            other => { return other; },
+        }
+    }
+    /**
+     * 给语法错误加上字符位置,char_index从1开始计数
+     */
+    pub fn at(self, char_index: usize) -> JsonError {
+        return self.prefix(&std::format!("第{}个字符:", char_index));
+    }
+    /**
+     * 给错误加上行号(从1开始),原来的错误保存在JsonError::Line中,不会丢失行号
+     */
+    pub fn line(self, line: usize) -> JsonError {
+        return JsonError::Line(line, Box::new(self));
+    }
+    /**
+     * 判断是否是资源限制错误
+     */
+    pub fn is_limit_exceeded(&self) -> bool {
+        match self {
+            JsonError::LimitExceeded(_) => { return true; },
+            JsonError::Line(_, err) => { return err.is_limit_exceeded(); },
+            _ => { return false; },
+        }
+    }
+}
+
+impl From<String> for JsonError {
+    fn from(msg: String) -> Self {
+        return JsonError::Syntax(msg);
+    }
+}
+
+impl From<std::io::Error> for JsonError {
+    fn from(err: std::io::Error) -> Self {
+        return JsonError::Io(err.to_string());
+    }
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::Syntax(msg) => { return write!(f, "{}", msg); },
+            JsonError::LimitExceeded(limit) => { return write!(f, "超出资源限制:{}", limit); },
+            JsonError::Io(msg) => { return write!(f, "读取失败:{}", msg); },
+            JsonError::Line(line, err) => { return write!(f, "第{}行:{}", line, err); },
+        }
+    }
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::Depth(max) => { return write!(f, "嵌套深度超过{}", max); },
+            Limit::TotalBytes(max) => { return write!(f, "文档大小超过{}字节", max); },
+            Limit::StringLength(max) => { return write!(f, "string长度超过{}字节", max); },
+            Limit::ArrayLength(max) => { return write!(f, "array元素个数超过{}", max); },
+            Limit::ObjectMembers(max) => { return write!(f, "object成员个数超过{}", max); },
+            Limit::NumberLength(max) => { return write!(f, "number长度超过{}个字符", max); },
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
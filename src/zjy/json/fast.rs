@@ -0,0 +1,505 @@
+/**
+ * 文件名: "src/zjy/json/fast.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::Entry;
+
+use crate::zjy::json::{Json, JsonError, ParseOptions};
+use crate::zjy::json::options::DuplicateKeys;
+use crate::zjy::json::parser;
+use crate::zjy::json::simd::{find_string_special, skip_whitespace};
+
+impl Json {
+    /**
+     * 以字节为单位解析json,字符串正文使用SIMD批量扫描,
+     * 比逐个字符匹配的str_to_json快得多
+     * bytes必须是utf8编码
+     */
+    pub fn bytes_to_json(bytes: &[u8]) -> Result<Json, String> {
+        return Json::bytes_to_json_with_options(bytes, &ParseOptions::default()).map_err(|err| err.to_string());
+    }
+    /**
+     * 按照options中的选项以字节为单位解析json
+     * 超出资源限制时返回JsonError::LimitExceeded
+     */
+    pub fn bytes_to_json_with_options(bytes: &[u8], options: &ParseOptions) -> Result<Json, JsonError> {
+        return parser::parse_bytes(bytes, options);
+    }
+}
+
+/**
+ * 字节解析器,pos是下一个要读取的字节的下标,depth是当前所在的嵌套深度
+ * 调用者需要保证bytes是合法的utf8
+ */
+pub(crate) struct ByteParser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    depth: usize,
+    options: &'a ParseOptions,
+}
+
+impl<'a> ByteParser<'a> {
+    pub(crate) fn new(bytes: &'a [u8], options: &'a ParseOptions) -> ByteParser<'a> { ByteParser { bytes, pos: 0, depth: 0, options } }
+    /**
+     * 从pos开始解析,depth是pos处的值所在的嵌套深度
+     */
+    pub(crate) fn new_at(bytes: &'a [u8], pos: usize, depth: usize, options: &'a ParseOptions) -> ByteParser<'a> { ByteParser { bytes, pos, depth, options } }
+    pub(crate) fn position(&self) -> usize { return self.pos; }
+    pub(crate) fn skip_whitespace(&mut self) { self.pos = skip_whitespace(self.bytes, self.pos); }
+    /**
+     * 检查pos处的字节,是expect时跳过它并返回true
+     */
+    pub(crate) fn eat(&mut self, expect: u8) -> bool {
+        if self.bytes.get(self.pos) == Some(&expect) {
+            self.pos += 1;
+            return true;
+        }
+        return false;
+    }
+    /**
+     * 只检查整个文档的语法和资源限制,不生成json值
+     */
+    pub(crate) fn validate_document(&mut self) -> Result<(), JsonError> {
+        self.pos = skip_whitespace(self.bytes, self.pos);
+        if self.pos >= self.bytes.len() {
+            return Err("没能解析出任何json对象".to_string().into());
+        }
+        self.skip_value()?;
+        self.pos = skip_whitespace(self.bytes, self.pos);
+        if self.pos < self.bytes.len() {
+            return Err(self.unexpected());
+        }
+        return Ok(());
+    }
+
+    /**
+     * 解析整个文档,json值前后只允许出现空白
+     */
+    pub(crate) fn parse_document(&mut self) -> Result<Json, JsonError> {
+        self.pos = skip_whitespace(self.bytes, self.pos);
+        if self.pos >= self.bytes.len() {
+            return Err("没能解析出任何json对象".to_string().into());
+        }
+        let json = self.parse_value()?;
+        self.pos = skip_whitespace(self.bytes, self.pos);
+        if self.pos < self.bytes.len() {
+            return Err(self.unexpected());
+        }
+        return Ok(json);
+    }
+
+    /**
+     * 生成 "此处不应是" 的错误,下标从1开始计数
+     */
+    fn unexpected(&self) -> JsonError {
+        match self.current_char() {
+            Some(c) => { return std::format!("第{}个字节:此处不应是:'{}'", self.pos + 1, c).into(); },
+            None => { return std::format!("第{}个字节:json没有结束", self.pos + 1).into(); },
+        }
+    }
+    fn current_char(&self) -> Option<char> {
+        // pos总是落在字符的边界上
+        let s = unsafe { std::str::from_utf8_unchecked(&self.bytes[self.pos..]) };
+        return s.chars().next();
+    }
+
+    pub(crate) fn parse_value(&mut self) -> Result<Json, JsonError> {
+        match self.bytes.get(self.pos) {
+            Some(b'n') => { self.literal(b"null")?; return Ok(Json::Null); },
+            Some(b't') => { self.literal(b"true")?; return Ok(Json::Boolean(true)); },
+            Some(b'f') => { self.literal(b"false")?; return Ok(Json::Boolean(false)); },
+            Some(b'"') => { return Ok(Json::String(self.parse_string()?)); },
+            Some(b'[') => { return self.parse_array(); },
+            Some(b'{') => { return self.parse_object(); },
+            Some(b'-' | b'0'..=b'9') => { return Ok(Json::Number(self.parse_number()?)); },
+            _ => { return Err(self.unexpected()); },
+        }
+    }
+
+    /**
+     * 检查并跳过一个json值,和parse_value的检查相同,但是不生成json值
+     */
+    pub(crate) fn skip_value(&mut self) -> Result<(), JsonError> {
+        match self.bytes.get(self.pos) {
+            Some(b'n') => { return self.literal(b"null"); },
+            Some(b't') => { return self.literal(b"true"); },
+            Some(b'f') => { return self.literal(b"false"); },
+            Some(b'"') => { return self.skip_string(); },
+            Some(b'[') => { return self.skip_array(); },
+            Some(b'{') => { return self.skip_object(); },
+            Some(b'-' | b'0'..=b'9') => { self.parse_number()?; return Ok(()); },
+            _ => { return Err(self.unexpected()); },
+        }
+    }
+
+    fn literal(&mut self, lit: &[u8]) -> Result<(), JsonError> {
+        for b in lit {
+            if self.bytes.get(self.pos) != Some(b) {
+                return Err(self.unexpected());
+            }
+            self.pos += 1;
+        }
+        return Ok(());
+    }
+
+    fn parse_array(&mut self) -> Result<Json, JsonError> {
+        // 跳过 '['
+        self.pos += 1;
+        self.depth += 1;
+        self.options.check_depth(self.depth)?;
+        let mut arr = Vec::new();
+        self.pos = skip_whitespace(self.bytes, self.pos);
+        if self.bytes.get(self.pos) == Some(&b']') {
+            self.pos += 1;
+            self.depth -= 1;
+            return Ok(Json::Array(arr));
+        }
+        loop {
+            self.pos = skip_whitespace(self.bytes, self.pos);
+            arr.push(self.parse_value()?);
+            self.options.check_array_length(arr.len())?;
+            self.pos = skip_whitespace(self.bytes, self.pos);
+            match self.bytes.get(self.pos) {
+                Some(b',') => { self.pos += 1; },
+                Some(b']') => { self.pos += 1; self.depth -= 1; return Ok(Json::Array(arr)); },
+                _ => { return Err(self.unexpected()); },
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Json, JsonError> {
+        // 跳过 '{'
+        self.pos += 1;
+        self.depth += 1;
+        self.options.check_depth(self.depth)?;
+        let mut obj = HashMap::new();
+        self.pos = skip_whitespace(self.bytes, self.pos);
+        if self.bytes.get(self.pos) == Some(&b'}') {
+            self.pos += 1;
+            self.depth -= 1;
+            return Ok(Json::Object(obj));
+        }
+        loop {
+            self.pos = skip_whitespace(self.bytes, self.pos);
+            if self.bytes.get(self.pos) != Some(&b'"') {
+                return Err(self.unexpected());
+            }
+            let key = self.parse_string()?;
+            self.pos = skip_whitespace(self.bytes, self.pos);
+            if self.bytes.get(self.pos) != Some(&b':') {
+                return Err(self.unexpected());
+            }
+            self.pos += 1;
+            self.pos = skip_whitespace(self.bytes, self.pos);
+            let value = self.parse_value()?;
+            match obj.entry(key) {
+                Entry::Vacant(entry) => { entry.insert(value); },
+                Entry::Occupied(mut entry) => {
+                    match self.options.duplicate_keys {
+                        DuplicateKeys::Last => { entry.insert(value); },
+                        DuplicateKeys::First => {},
+                        DuplicateKeys::Error => { return Err(std::format!("object中的key重复:\"{}\"", entry.key()).into()); },
+                    }
+                },
+            }
+            self.options.check_object_members(obj.len())?;
+            self.pos = skip_whitespace(self.bytes, self.pos);
+            match self.bytes.get(self.pos) {
+                Some(b',') => { self.pos += 1; },
+                Some(b'}') => { self.pos += 1; self.depth -= 1; return Ok(Json::Object(obj)); },
+                _ => { return Err(self.unexpected()); },
+            }
+        }
+    }
+
+    fn skip_array(&mut self) -> Result<(), JsonError> {
+        self.pos += 1;
+        self.depth += 1;
+        self.options.check_depth(self.depth)?;
+        self.pos = skip_whitespace(self.bytes, self.pos);
+        if self.eat(b']') {
+            self.depth -= 1;
+            return Ok(());
+        }
+        let mut len = 0;
+        loop {
+            self.pos = skip_whitespace(self.bytes, self.pos);
+            self.skip_value()?;
+            len += 1;
+            self.options.check_array_length(len)?;
+            self.pos = skip_whitespace(self.bytes, self.pos);
+            match self.bytes.get(self.pos) {
+                Some(b',') => { self.pos += 1; },
+                Some(b']') => { self.pos += 1; self.depth -= 1; return Ok(()); },
+                _ => { return Err(self.unexpected()); },
+            }
+        }
+    }
+
+    fn skip_object(&mut self) -> Result<(), JsonError> {
+        self.pos += 1;
+        self.depth += 1;
+        self.options.check_depth(self.depth)?;
+        self.pos = skip_whitespace(self.bytes, self.pos);
+        if self.eat(b'}') {
+            self.depth -= 1;
+            return Ok(());
+        }
+        // 只有重复的key需要报错时才记录key
+        let mut keys = HashSet::new();
+        let mut len = 0;
+        loop {
+            self.pos = skip_whitespace(self.bytes, self.pos);
+            if self.bytes.get(self.pos) != Some(&b'"') {
+                return Err(self.unexpected());
+            }
+            if self.options.duplicate_keys == DuplicateKeys::Error {
+                let key = self.parse_string()?;
+                if keys.contains(&key) {
+                    return Err(std::format!("object中的key重复:\"{}\"", key).into());
+                }
+                keys.insert(key);
+            } else {
+                self.skip_string()?;
+            }
+            self.pos = skip_whitespace(self.bytes, self.pos);
+            if !self.eat(b':') {
+                return Err(self.unexpected());
+            }
+            self.pos = skip_whitespace(self.bytes, self.pos);
+            self.skip_value()?;
+            len += 1;
+            self.options.check_object_members(len)?;
+            self.pos = skip_whitespace(self.bytes, self.pos);
+            match self.bytes.get(self.pos) {
+                Some(b',') => { self.pos += 1; },
+                Some(b'}') => { self.pos += 1; self.depth -= 1; return Ok(()); },
+                _ => { return Err(self.unexpected()); },
+            }
+        }
+    }
+
+    /**
+     * 检查并跳过string,只有带转义的string需要少量分配
+     */
+    pub(crate) fn skip_string(&mut self) -> Result<(), JsonError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut escaped = String::new();
+        loop {
+            self.pos = find_string_special(self.bytes, self.pos);
+            match self.bytes.get(self.pos) {
+                Some(b'"') => {
+                    // 转义之后的长度不会超过原始长度,没有超过限制时不需要精确计算
+                    if self.pos - start - 1 > self.options.max_string_length {
+                        self.pos = start;
+                        self.parse_string()?;
+                    } else {
+                        self.pos += 1;
+                    }
+                    return Ok(());
+                },
+                Some(b'\\') => {
+                    self.pos += 1;
+                    escaped.clear();
+                    self.parse_escape(&mut escaped)?;
+                },
+                Some(_) => { return Err(self.unexpected()); },
+                None => { return Err(std::format!("第{}个字节:这个Sting类型缺少 '{}' 结束", self.pos + 1, '"').into()); },
+            }
+        }
+    }
+
+    /**
+     * 解析string,调用时pos指向开头的引号,返回时pos指向结尾引号的下一个字节
+     */
+    pub(crate) fn parse_string(&mut self) -> Result<String, JsonError> {
+        self.pos += 1;
+        // 大多数字符串没有转义,整段复制一次就够了
+        let end = find_string_special(self.bytes, self.pos);
+        if self.bytes.get(end) == Some(&b'"') {
+            self.options.check_string_length(end - self.pos)?;
+            let s = unsafe { std::str::from_utf8_unchecked(&self.bytes[self.pos..end]) };
+            self.pos = end + 1;
+            return Ok(s.to_string());
+        }
+        let mut result = String::with_capacity(end - self.pos + 16);
+        loop {
+            // 批量跳过普通字符
+            let end = find_string_special(self.bytes, self.pos);
+            // 原始字节是合法utf8,而特殊字节都是ascii,不会切断多字节字符
+            result.push_str(unsafe { std::str::from_utf8_unchecked(&self.bytes[self.pos..end]) });
+            self.options.check_string_length(result.len())?;
+            self.pos = end;
+            match self.bytes.get(self.pos) {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(result);
+                },
+                Some(b'\\') => {
+                    self.pos += 1;
+                    self.parse_escape(&mut result)?;
+                    self.options.check_string_length(result.len())?;
+                },
+                Some(_) => {
+                    // 控制字符必须转义
+                    return Err(self.unexpected());
+                },
+                None => {
+                    return Err(std::format!("第{}个字节:这个Sting类型缺少 '{}' 结束", self.pos + 1, '"').into());
+                },
+            }
+        }
+    }
+
+    fn parse_escape(&mut self, result: &mut String) -> Result<(), JsonError> {
+        let c = match self.bytes.get(self.pos) {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\x08',
+            Some(b'f') => '\x0c',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                self.pos += 1;
+                let high = self.parse_hex4()?;
+                let point: u32 = if (0xD800..=0xDBFF).contains(&high) {
+                    // 高代理项后面必须紧跟低代理项
+                    if self.bytes.get(self.pos) != Some(&b'\\') || self.bytes.get(self.pos + 1) != Some(&b'u') {
+                        return Err(std::format!("第{}个字节:缺少unicode低代理项", self.pos + 1).into());
+                    }
+                    self.pos += 2;
+                    let low = self.parse_hex4()?;
+                    if !(0xDC00..=0xDFFF).contains(&low) {
+                        return Err(std::format!("第{}个字节:unicode低代理项不合法", self.pos).into());
+                    }
+                    0x10000 + ((high as u32 - 0xD800) << 10) + (low as u32 - 0xDC00)
+                } else {
+                    high as u32
+                };
+                match char::from_u32(point) {
+                    Some(ch) => { result.push(ch); },
+                    None => { return Err(std::format!("第{}个字节:不是合法的unicode码点", self.pos).into()); },
+                }
+                return Ok(());
+            },
+            _ => { return Err(self.unexpected()); },
+        };
+        result.push(c);
+        self.pos += 1;
+        return Ok(());
+    }
+
+    fn parse_hex4(&mut self) -> Result<u16, JsonError> {
+        let mut point: u16 = 0;
+        for _ in 0..4 {
+            let digit = match self.bytes.get(self.pos) {
+                Some(b) => (*b as char).to_digit(16),
+                None => None,
+            };
+            match digit {
+                Some(d) => { point = point * 16 + d as u16; },
+                None => { return Err(self.unexpected()); },
+            }
+            self.pos += 1;
+        }
+        return Ok(point);
+    }
+
+    /**
+     * 按照json的数字语法扫描: -?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?
+     */
+    pub(crate) fn parse_number(&mut self) -> Result<f64, JsonError> {
+        let start = self.pos;
+        let negative = self.bytes.get(self.pos) == Some(&b'-');
+        if negative {
+            self.pos += 1;
+        }
+        let int_start = self.pos;
+        match self.bytes.get(self.pos) {
+            Some(b'0') => { self.pos += 1; },
+            Some(b'1'..=b'9') => { self.skip_digits(); },
+            _ => { return Err(self.unexpected()); },
+        }
+        // 不超过15位的整数可以精确地用f64表示,直接计算,不走标准库的解析
+        if self.pos - int_start <= 15 && !matches!(self.bytes.get(self.pos), Some(b'.' | b'e' | b'E')) {
+            self.options.check_number_length(self.pos - start)?;
+            let mut n: u64 = 0;
+            for b in &self.bytes[int_start..self.pos] {
+                n = n * 10 + (b - b'0') as u64;
+            }
+            return Ok(if negative { -(n as f64) } else { n as f64 });
+        }
+        if self.bytes.get(self.pos) == Some(&b'.') {
+            self.pos += 1;
+            if !matches!(self.bytes.get(self.pos), Some(b'0'..=b'9')) {
+                return Err(self.unexpected());
+            }
+            self.skip_digits();
+        }
+        if matches!(self.bytes.get(self.pos), Some(b'e' | b'E')) {
+            self.pos += 1;
+            if matches!(self.bytes.get(self.pos), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            if !matches!(self.bytes.get(self.pos), Some(b'0'..=b'9')) {
+                return Err(self.unexpected());
+            }
+            self.skip_digits();
+        }
+        self.options.check_number_length(self.pos - start)?;
+        // 数字都是ascii
+        let s = unsafe { std::str::from_utf8_unchecked(&self.bytes[start..self.pos]) };
+        match s.parse::<f64>() {
+            Ok(n) if !n.is_finite() => { return Err(std::format!("'{}'超出了f64的范围", s).into()); },
+            Ok(n) => { return Ok(n); },
+            Err(err) => { return Err(std::format!("字符串:{}不是一个合法的数字,错误对象:{}", s, err).into()); },
+        }
+    }
+    fn skip_digits(&mut self) {
+        while matches!(self.bytes.get(self.pos), Some(b'0'..=b'9')) {
+            self.pos += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_bytes_to_json() {
+    for file in ["tests/testarray.json", "tests/testnull.json", "tests/teststring.json"] {
+        let json_str = std::fs::read_to_string(file).unwrap();
+        let fast = Json::bytes_to_json(json_str.as_bytes()).unwrap();
+        let slow = Json::str_to_json(&json_str).unwrap();
+        assert_eq!(fast.to_string(), slow.to_string());
+    }
+    let json_str = std::fs::read_to_string("tests/testobject.json").unwrap();
+    match Json::bytes_to_json(json_str.as_bytes()).unwrap() {
+        Json::Object(obj) => {
+            assert_eq!(obj.len(), 10);
+            assert!(obj.contains_key("obj2"));
+        },
+        _ => { panic!("应该解析出object"); },
+    }
+    assert!(Json::bytes_to_json(b"[1,2,]").is_err());
+    assert!(Json::bytes_to_json(b"\"\\ud83e\"").is_err());
+    assert!(Json::bytes_to_json(b"01").is_err());
+    assert!(Json::bytes_to_json(b"null x").is_err());
+}
@@ -0,0 +1,129 @@
+/**
+ * 文件名: "src/zjy/json/format.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::zjy::json::NonFinite;
+
+/**
+ * 按照ECMAScript的Number::prototype.toString格式化number,和JavaScript的JSON.stringify输出相同
+ * 例如1e21输出为"1e+21",0.0000001输出为"1e-7",-0输出为"0";NaN和Infinity返回None
+ */
+pub fn es_number(n: f64) -> Option<String> {
+    if !n.is_finite() {
+        return None;
+    }
+    let mut out = String::new();
+    write_es_number(n, &mut out);
+    return Some(out);
+}
+
+/**
+ * 按照ECMAScript的Number::toString输出有限的number
+ * 数字部分是能够还原出同一个f64的最短十进制表示,-0输出为0
+ */
+pub(crate) fn write_es_number(n: f64, out: &mut String) {
+    if n == 0.0 {
+        out.push('0');
+        return;
+    }
+    if n < 0.0 {
+        out.push('-');
+    }
+    // 标准库的科学计数法输出最短的可以还原的数字,例如 "1.2345e-7"
+    let sci = std::format!("{:e}", n.abs());
+    let (mantissa, exp) = sci.split_once('e').unwrap_or((&sci, "0"));
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let k = digits.len() as i32;
+    // 小数点在第point个数字之后
+    let point = exp.parse::<i32>().unwrap_or(0) + 1;
+    if k <= point && point <= 21 {
+        out.push_str(&digits);
+        for _ in 0..point - k {
+            out.push('0');
+        }
+    } else if 0 < point && point <= 21 {
+        out.push_str(&digits[..point as usize]);
+        out.push('.');
+        out.push_str(&digits[point as usize..]);
+    } else if -6 < point && point <= 0 {
+        out.push_str("0.");
+        for _ in 0..-point {
+            out.push('0');
+        }
+        out.push_str(&digits);
+    } else {
+        out.push_str(&digits[..1]);
+        if k > 1 {
+            out.push('.');
+            out.push_str(&digits[1..]);
+        }
+        out.push('e');
+        out.push(if point - 1 < 0 { '-' } else { '+' });
+        out.push_str(&(point - 1).abs().to_string());
+    }
+}
+
+/**
+ * 按照policy输出number,有限的number使用ECMAScript的格式
+ * NonFinite::Error时遇到NaN和Infinity返回Err
+ */
+pub(crate) fn write_number(n: f64, policy: NonFinite, out: &mut String) -> Result<(), String> {
+    if n.is_finite() {
+        write_es_number(n, out);
+        return Ok(());
+    }
+    match policy {
+        NonFinite::Error => { return Err(std::format!("number不能是{}", n)); },
+        NonFinite::Null => { out.push_str("null"); },
+        NonFinite::String => {
+            out.push('"');
+            out.push_str(non_finite_name(n));
+            out.push('"');
+        },
+    }
+    return Ok(());
+}
+
+/**
+ * NaN和Infinity在JavaScript中的名字
+ */
+pub(crate) fn non_finite_name(n: f64) -> &'static str {
+    if n.is_nan() {
+        return "NaN";
+    }
+    return if n > 0.0 { "Infinity" } else { "-Infinity" };
+}
+
+#[cfg(test)]
+#[test]
+fn test_es_number() {
+    let cases: [(f64, &str); 12] = [
+        (0.0, "0"), (-0.0, "0"), (1.0, "1"), (-1.5, "-1.5"), (0.1 + 0.2, "0.30000000000000004"),
+        (1e21, "1e+21"), (1e20, "100000000000000000000"), (123456789012345680000.0, "123456789012345680000"),
+        (0.000001, "0.000001"), (0.0000001, "1e-7"), (1.5e-10, "1.5e-10"), (f64::MAX, "1.7976931348623157e+308"),
+    ];
+    for (n, expect) in cases {
+        assert_eq!(es_number(n).unwrap(), expect);
+    }
+    assert!(es_number(f64::NAN).is_none());
+    let mut out = String::new();
+    write_number(f64::NEG_INFINITY, NonFinite::String, &mut out).unwrap();
+    write_number(f64::NAN, NonFinite::Null, &mut out).unwrap();
+    assert_eq!(out, "\"-Infinity\"null");
+    assert!(write_number(f64::NAN, NonFinite::Error, &mut out).is_err());
+}
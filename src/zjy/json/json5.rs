@@ -0,0 +1,216 @@
+/**
+ * 文件名: "src/zjy/json/json5.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::zjy::json::{Json, JsonError, ParseOptions};
+use crate::zjy::json::format::{es_number, non_finite_name};
+use crate::zjy::json::parser::parse_str;
+
+impl Json {
+    /**
+     * 将json5字符串转换为json实例对象,等价于Parser::with_options(ParseOptions::json5()).parse(str)
+     */
+    pub fn json5_to_json(str: &str) -> Result<Json, JsonError> {
+        return parse_str(str, &ParseOptions::json5());
+    }
+    /**
+     * 序列化成紧凑的json5字符串
+     * 符合标识符语法的key不加引号,非有限的number输出为Infinity,-Infinity和NaN
+     * object的key按照字典序输出,保证每次输出相同
+     */
+    pub fn to_json5_string(&self) -> String {
+        let mut out = String::new();
+        write_json5(self, &mut out, None, 0);
+        return out;
+    }
+    /**
+     * 序列化成带缩进的json5字符串,indent是每一层缩进的空格数
+     */
+    pub fn to_json5_string_pretty(&self, indent: usize) -> String {
+        let mut out = String::new();
+        write_json5(self, &mut out, Some(indent), 0);
+        return out;
+    }
+}
+
+/**
+ * 判断c是否可以作为json5标识符的第一个字符
+ */
+pub(crate) fn is_identifier_start(c: char) -> bool {
+    return c == '$' || c == '_' || c.is_ascii_alphabetic() || (!c.is_ascii() && c.is_alphabetic());
+}
+/**
+ * 判断c是否可以作为json5标识符后面的字符
+ */
+pub(crate) fn is_identifier_part(c: char) -> bool {
+    return is_identifier_start(c) || c.is_ascii_digit() || c == '\u{200c}' || c == '\u{200d}'
+        || (!c.is_ascii() && c.is_alphanumeric());
+}
+/**
+ * 判断key是否可以不加引号输出
+ */
+pub(crate) fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    match chars.next() {
+        Some(c) if is_identifier_start(c) => { return chars.all(is_identifier_part); },
+        _ => { return false; },
+    }
+}
+
+fn write_newline(out: &mut String, indent: Option<usize>, level: usize) {
+    if let Some(indent) = indent {
+        out.push('\n');
+        for _ in 0..indent * level {
+            out.push(' ');
+        }
+    }
+}
+
+fn write_json5(json: &Json, out: &mut String, indent: Option<usize>, level: usize) {
+    match json {
+        Json::Null => { out.push_str("null"); },
+        Json::Boolean(b) => { out.push_str(if *b { "true" } else { "false" }); },
+        Json::Number(n) => { write_json5_number(*n, out); },
+        Json::String(s) => { write_json5_string(s, out); },
+        Json::Array(arr) => {
+            out.push('[');
+            for (i, value) in arr.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_newline(out, indent, level + 1);
+                write_json5(value, out, indent, level + 1);
+            }
+            if !arr.is_empty() {
+                write_newline(out, indent, level);
+            }
+            out.push(']');
+        },
+        Json::Object(obj) => {
+            let mut keys: Vec<&String> = obj.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_newline(out, indent, level + 1);
+                if is_identifier(key) {
+                    out.push_str(key);
+                } else {
+                    write_json5_string(key, out);
+                }
+                out.push(':');
+                if indent.is_some() {
+                    out.push(' ');
+                }
+                write_json5(&obj[*key], out, indent, level + 1);
+            }
+            if !keys.is_empty() {
+                write_newline(out, indent, level);
+            }
+            out.push('}');
+        },
+    }
+}
+
+fn write_json5_number(n: f64, out: &mut String) {
+    match es_number(n) {
+        Some(number) => { out.push_str(&number); },
+        None => { out.push_str(non_finite_name(n)); },
+    }
+}
+
+/**
+ * 输出json5的string,默认使用双引号,只有string中包含双引号而不包含单引号时使用单引号
+ */
+fn write_json5_string(s: &str, out: &mut String) {
+    let quote = if s.contains('"') && !s.contains('\'') { '\'' } else { '"' };
+    out.push(quote);
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => { out.push_str("\\\\"); },
+            '\x08' => { out.push_str("\\b"); },
+            '\x0c' => { out.push_str("\\f"); },
+            '\n' => { out.push_str("\\n"); },
+            '\r' => { out.push_str("\\r"); },
+            '\t' => { out.push_str("\\t"); },
+            '\x0b' => { out.push_str("\\v"); },
+            // \0后面不能紧跟数字
+            '\0' if !matches!(chars.peek(), Some('0'..='9')) => { out.push_str("\\0"); },
+            '\u{2028}' => { out.push_str("\\u2028"); },
+            '\u{2029}' => { out.push_str("\\u2029"); },
+            '\x00'..='\x1f' => { out.push_str(&std::format!("\\x{:02x}", c as u32)); },
+            _ if c == quote => { out.push('\\'); out.push(c); },
+            _ => { out.push(c); },
+        }
+    }
+    out.push(quote);
+}
+
+#[cfg(test)]
+#[test]
+fn test_json5() {
+    let json5 = "// 配置文件
+{
+    unquoted: 'and you can quote me on that',
+    singleQuotes: 'I can use \"double quotes\" here',
+    lineBreaks: \"Look, Mom! \\
+No \\\\n's!\",
+    hexadecimal: 0xdecaf,
+    leadingDecimalPoint: .8675309, andTrailing: 8675309.,
+    positiveSign: +1,
+    trailingComma: 'in objects', andIn: ['arrays',],
+    \"backwardsCompatible\": \"with JSON\",
+    /* 多行
+       注释 */
+    infinity: -Infinity, nan: NaN,
+    escapes: '\\x41\\v\\0\\'',
+    中文键: [1, 2,],
+}
+";
+    let json = Json::json5_to_json(json5).unwrap();
+    let obj = match &json {
+        Json::Object(obj) => obj,
+        _ => { panic!("应该解析出object"); },
+    };
+    assert_eq!(obj.len(), 14);
+    assert_eq!(obj["hexadecimal"].to_string(), "912559");
+    assert_eq!(obj["leadingDecimalPoint"].to_string(), "0.8675309");
+    assert_eq!(obj["lineBreaks"].to_string(), r#""Look, Mom! No \\n's!""#);
+    assert_eq!(obj["escapes"].to_string(), r#""A\u000b\u0000'""#);
+    match obj["nan"] {
+        Json::Number(n) => { assert!(n.is_nan()); },
+        _ => { panic!("应该解析出NaN"); },
+    }
+    // 序列化之后可以再次解析,并且结果相同
+    let out = json.to_json5_string();
+    assert!(out.contains("infinity:-Infinity"));
+    assert!(out.contains("中文键:[1,2]"));
+    assert_eq!(Json::json5_to_json(&out).unwrap().to_json5_string(), out);
+    let pretty = json.to_json5_string_pretty(2);
+    assert_eq!(Json::json5_to_json(&pretty).unwrap().to_json5_string(), out);
+    // 严格模式不接受json5
+    assert!(Json::str_to_json(json5).is_err());
+    assert!(Json::json5_to_json("{a:1} /").is_err());
+    assert!(Json::json5_to_json("{a:1} /* ").is_err());
+    assert!(Json::json5_to_json("[01]").is_err());
+    assert!(Json::json5_to_json("'\\01'").is_err());
+    assert_eq!(Json::json5_to_json("['\\0a', '\\0']").unwrap().to_string(), r#"["\u0000a","\u0000"]"#);
+}
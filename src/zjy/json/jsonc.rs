@@ -0,0 +1,486 @@
+/**
+ * 文件名: "src/zjy/json/jsonc.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::collections::HashMap;
+
+use crate::zjy::json::{Json, JsonError, ParseOptions};
+use crate::zjy::json::ast::string::{StringContext, StringMode};
+use crate::zjy::json::json5::{is_identifier_part, is_identifier_start};
+use crate::zjy::json::parser::parse_str;
+use crate::zjy::json::format::es_number;
+use crate::zjy::json::pointer;
+
+impl Json {
+    /**
+     * 将jsonc(带注释和末尾逗号的json,比如VS Code的settings.json)转换为json实例对象,注释被丢弃
+     * 等价于Parser::with_options(ParseOptions::jsonc()).parse(str)
+     */
+    pub fn jsonc_to_json(str: &str) -> Result<Json, JsonError> {
+        return parse_str(str, &ParseOptions::jsonc());
+    }
+}
+
+/**
+ * 注释相对于它所属的json值的位置
+ * Before代表在值(object成员是key)前面
+ * Trailing代表和值在同一行,写在值(以及后面的逗号)后面
+ * End代表在array/object的最后一个元素后面,结束括号前面
+ * After代表在整个文档的最后,只属于最外层的值
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommentPlacement {
+    Before, Trailing, End, After
+}
+
+/**
+ * 一条注释,text保存原始文本(包括 "//" 或者块注释的开始和结束符号)
+ * pointer是所属json值的JSON Pointer,空字符串代表最外层的值
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JsonComment {
+    pub pointer: String,
+    pub placement: CommentPlacement,
+    pub text: String,
+}
+
+/**
+ * 保留了注释的jsonc文档
+ * 除了json值之外还记录了注释和object中key的原始顺序,重新输出时注释写回原来的位置
+ */
+#[derive(Clone)]
+pub struct JsoncDocument {
+    /// 解析出的json值
+    pub value: Json,
+    /// 所有注释,按照在文档中出现的顺序
+    pub comments: Vec<JsonComment>,
+    /// 每个object(JSON Pointer)中key出现的顺序
+    key_order: HashMap<String, Vec<String>>,
+}
+
+impl JsoncDocument {
+    /**
+     * 使用ParseOptions::jsonc()解析jsonc,并且保留注释
+     */
+    pub fn parse(str: &str) -> Result<JsoncDocument, JsonError> {
+        return JsoncDocument::parse_with_options(str, &ParseOptions::jsonc());
+    }
+    /**
+     * 按照options中的选项解析并保留注释,options需要开启注释扩展
+     */
+    pub fn parse_with_options(str: &str, options: &ParseOptions) -> Result<JsoncDocument, JsonError> {
+        let value = parse_str(str, options)?;
+        // 输入已经是合法的json,再扫描一遍把注释挂到相邻的值上
+        let mut collector = CommentCollector::new(options);
+        collector.collect(str);
+        return Ok(JsoncDocument { value, comments: collector.comments, key_order: collector.key_order });
+    }
+    /**
+     * 获取属于pointer对应值的所有注释
+     */
+    pub fn comments_of<'a>(&'a self, pointer: &'a str) -> impl Iterator<Item = &'a JsonComment> + 'a {
+        return self.comments.iter().filter(move |comment| comment.pointer == pointer);
+    }
+    /**
+     * 给pointer对应的值添加一条注释,text需要包括注释符号,比如 "// 说明"
+     */
+    pub fn add_comment(&mut self, pointer: &str, placement: CommentPlacement, text: &str) {
+        self.comments.push(JsonComment { pointer: pointer.to_string(), placement, text: text.to_string() });
+    }
+    /**
+     * 取出json值,丢弃注释
+     */
+    pub fn into_json(self) -> Json {
+        return self.value;
+    }
+    /**
+     * 序列化成带缩进和注释的jsonc字符串,indent是每一层缩进的空格数
+     * object的key按照解析时的顺序输出,之后新加的key按照字典序排在后面
+     */
+    pub fn to_string_pretty(&self, indent: usize) -> String {
+        let mut out = String::new();
+        for comment in self.comments_at("", CommentPlacement::Before) {
+            out.push_str(comment);
+            out.push('\n');
+        }
+        self.write_value(&self.value, "", &mut out, indent, 0);
+        for comment in self.comments_at("", CommentPlacement::Trailing) {
+            out.push(' ');
+            out.push_str(comment);
+        }
+        for comment in self.comments_at("", CommentPlacement::After) {
+            out.push('\n');
+            out.push_str(comment);
+        }
+        out.push('\n');
+        return out;
+    }
+
+    fn comments_at<'a>(&'a self, pointer: &'a str, placement: CommentPlacement) -> impl Iterator<Item = &'a str> + 'a {
+        return self.comments.iter()
+            .filter(move |comment| comment.pointer == pointer && comment.placement == placement)
+            .map(|comment| comment.text.as_str());
+    }
+
+    fn ordered_keys<'a>(&self, pointer: &str, obj: &'a HashMap<String, Json>) -> Vec<&'a String> {
+        let mut keys: Vec<&String> = Vec::new();
+        if let Some(order) = self.key_order.get(pointer) {
+            for key in order {
+                if let Some((key, _)) = obj.get_key_value(key) {
+                    keys.push(key);
+                }
+            }
+        }
+        let mut rest: Vec<&String> = obj.keys().filter(|key| !keys.contains(key)).collect();
+        rest.sort();
+        keys.extend(rest);
+        return keys;
+    }
+
+    fn write_value(&self, json: &Json, pointer: &str, out: &mut String, indent: usize, level: usize) {
+        // 把array和object统一成(key,值)的列表,array的key为None
+        let (open, close, children): (char, char, Vec<(Option<&String>, &Json)>) = match json {
+            Json::Array(arr) => ('[', ']', arr.iter().map(|value| (None, value)).collect()),
+            Json::Object(obj) => ('{', '}', self.ordered_keys(pointer, obj).into_iter().map(|key| (Some(key), &obj[key])).collect()),
+            _ => {
+                write_scalar(json, out);
+                return;
+            },
+        };
+        let end: Vec<&str> = self.comments_at(pointer, CommentPlacement::End).collect();
+        out.push(open);
+        if children.is_empty() && end.is_empty() {
+            out.push(close);
+            return;
+        }
+        let len = children.len();
+        for (i, (key, value)) in children.into_iter().enumerate() {
+            let child = match key {
+                Some(key) => pointer::join(pointer, key),
+                None => pointer::join(pointer, &i.to_string()),
+            };
+            write_newline(out, indent, level + 1);
+            for comment in self.comments_at(&child, CommentPlacement::Before) {
+                out.push_str(comment);
+                write_newline(out, indent, level + 1);
+            }
+            if let Some(key) = key {
+                write_json_string(key, out);
+                out.push_str(": ");
+            }
+            self.write_value(value, &child, out, indent, level + 1);
+            if i + 1 < len {
+                out.push(',');
+            }
+            for comment in self.comments_at(&child, CommentPlacement::Trailing) {
+                out.push(' ');
+                out.push_str(comment);
+            }
+        }
+        for comment in end {
+            write_newline(out, indent, level + 1);
+            out.push_str(comment);
+        }
+        write_newline(out, indent, level);
+        out.push(close);
+    }
+}
+
+fn write_newline(out: &mut String, indent: usize, level: usize) {
+    out.push('\n');
+    for _ in 0..indent * level {
+        out.push(' ');
+    }
+}
+
+fn write_scalar(json: &Json, out: &mut String) {
+    match json {
+        Json::Null => { out.push_str("null"); },
+        Json::Boolean(b) => { out.push_str(if *b { "true" } else { "false" }); },
+        Json::Number(n) => { out.push_str(&es_number(*n).unwrap_or_else(|| "null".to_string())); },
+        Json::String(s) => { write_json_string(s, out); },
+        _ => {},
+    }
+}
+
+/**
+ * 按照RFC 8259输出带双引号的string,转义 '"' '\\' 和控制字符
+ */
+pub(crate) fn write_json_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => { out.push_str("\\\""); },
+            '\\' => { out.push_str("\\\\"); },
+            '\x08' => { out.push_str("\\b"); },
+            '\x0c' => { out.push_str("\\f"); },
+            '\n' => { out.push_str("\\n"); },
+            '\r' => { out.push_str("\\r"); },
+            '\t' => { out.push_str("\\t"); },
+            '\x00'..='\x1f' => { out.push_str(&std::format!("\\u{:04x}", c as u32)); },
+            _ => { out.push(c); },
+        }
+    }
+    out.push('"');
+}
+
+/**
+ * 扫描时的一层array或object
+ * member是object中当前成员的pointer,expect_key代表下一个出现的值是key
+ */
+struct Frame {
+    pointer: String,
+    is_object: bool,
+    index: usize,
+    member: String,
+    expect_key: bool,
+}
+
+/**
+ * 扫描已经解析成功的jsonc文本,收集注释并挂到相邻的值上:
+ * 和前一个值在同一行的注释是它的Trailing注释,否则是下一个值的Before注释,
+ * 后面没有值的注释是所在array/object的End注释,或者整个文档的After注释
+ */
+struct CommentCollector<'a> {
+    options: &'a ParseOptions,
+    stack: Vec<Frame>,
+    /// 还没有找到所属值的注释
+    pending: Vec<String>,
+    /// 当前行上最后一个结束的值
+    last: Option<String>,
+    comments: Vec<JsonComment>,
+    key_order: HashMap<String, Vec<String>>,
+}
+
+impl<'a> CommentCollector<'a> {
+    fn new(options: &'a ParseOptions) -> CommentCollector<'a> {
+        return CommentCollector { options, stack: Vec::new(), pending: Vec::new(), last: None, comments: Vec::new(), key_order: HashMap::new() };
+    }
+
+    fn collect(&mut self, str: &str) {
+        let chars: Vec<char> = str.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            match c {
+                '\n' | '\r' | '\u{2028}' | '\u{2029}' => { self.last = None; i += 1; },
+                '/' => {
+                    let start = i;
+                    if chars.get(i + 1) == Some(&'/') {
+                        while i < chars.len() && !matches!(chars[i], '\n' | '\r' | '\u{2028}' | '\u{2029}') {
+                            i += 1;
+                        }
+                    } else {
+                        i += 2;
+                        while i < chars.len() && !(chars[i - 1] == '*' && chars[i] == '/' && i - 1 > start + 1) {
+                            i += 1;
+                        }
+                        i += 1;
+                    }
+                    let text: String = chars[start..i.min(chars.len())].iter().collect();
+                    self.add_comment(text);
+                },
+                '{' | '[' => {
+                    let pointer = self.value_start();
+                    if c == '{' {
+                        self.key_order.entry(pointer.clone()).or_default();
+                    }
+                    self.stack.push(Frame { pointer, is_object: c == '{', index: 0, member: String::new(), expect_key: true });
+                    self.last = None;
+                    i += 1;
+                },
+                '}' | ']' => {
+                    if let Some(frame) = self.stack.pop() {
+                        self.flush_pending(&frame.pointer, CommentPlacement::End);
+                        self.last = Some(frame.pointer);
+                    }
+                    i += 1;
+                },
+                ',' => {
+                    if let Some(frame) = self.stack.last_mut() {
+                        frame.expect_key = true;
+                    }
+                    i += 1;
+                },
+                ':' => { i += 1; },
+                _ if self.options.whitespace.contains(c) => { i += 1; },
+                _ => {
+                    let in_key = matches!(self.stack.last(), Some(frame) if frame.is_object && frame.expect_key);
+                    if in_key {
+                        i = self.read_key(&chars, i);
+                        continue;
+                    }
+                    let pointer = self.value_start();
+                    i = skip_scalar(&chars, i);
+                    self.last = Some(pointer);
+                },
+            }
+        }
+        self.flush_pending("", CommentPlacement::After);
+    }
+
+    fn add_comment(&mut self, text: String) {
+        match &self.last {
+            Some(pointer) => {
+                self.comments.push(JsonComment { pointer: pointer.clone(), placement: CommentPlacement::Trailing, text });
+            },
+            None => { self.pending.push(text); },
+        }
+    }
+
+    fn flush_pending(&mut self, pointer: &str, placement: CommentPlacement) {
+        for text in std::mem::take(&mut self.pending) {
+            self.comments.push(JsonComment { pointer: pointer.to_string(), placement, text });
+        }
+    }
+
+    /**
+     * 一个值开始了,计算它的pointer,之前没有归属的注释挂到它前面
+     */
+    fn value_start(&mut self) -> String {
+        let pointer = match self.stack.last_mut() {
+            None => String::new(),
+            Some(frame) if frame.is_object => frame.member.clone(),
+            Some(frame) => {
+                frame.index += 1;
+                pointer::join(&frame.pointer, &(frame.index - 1).to_string())
+            },
+        };
+        self.flush_pending(&pointer, CommentPlacement::Before);
+        return pointer;
+    }
+
+    /**
+     * 读取object的key,返回key后面的位置
+     */
+    fn read_key(&mut self, chars: &[char], start: usize) -> usize {
+        let mut i = start;
+        let key = if chars[i] == '"' || chars[i] == '\'' {
+            let mut context = StringContext::new_str(chars[i]);
+            i += 1;
+            while i < chars.len() && *context.get_mode() != StringMode::end {
+                let _ = context.check_string(chars[i], self.options);
+                i += 1;
+            }
+            context.get_str().to_string()
+        } else {
+            while i < chars.len() && (is_identifier_start(chars[i]) || is_identifier_part(chars[i])) {
+                i += 1;
+            }
+            chars[start..i].iter().collect()
+        };
+        if let Some(frame) = self.stack.last_mut() {
+            frame.member = pointer::join(&frame.pointer, &key);
+            frame.expect_key = false;
+            let order = self.key_order.entry(frame.pointer.clone()).or_default();
+            if !order.contains(&key) {
+                order.push(key);
+            }
+            let member = frame.member.clone();
+            self.flush_pending(&member, CommentPlacement::Before);
+        }
+        return i;
+    }
+}
+
+/**
+ * 跳过string,number,true,false,null,返回值后面的位置
+ */
+fn skip_scalar(chars: &[char], start: usize) -> usize {
+    let mut i = start;
+    if chars[i] == '"' || chars[i] == '\'' {
+        let quote = chars[i];
+        i += 1;
+        while i < chars.len() && chars[i] != quote {
+            if chars[i] == '\\' {
+                i += 1;
+            }
+            i += 1;
+        }
+        return i + 1;
+    }
+    while i < chars.len() && !matches!(chars[i], ',' | ']' | '}' | ':' | '/') && !chars[i].is_whitespace() {
+        i += 1;
+    }
+    return i;
+}
+
+#[cfg(test)]
+#[test]
+fn test_jsonc() {
+    let jsonc = "// 编辑器设置
+{
+    // 字体大小
+    \"editor.fontSize\": 14, // 像素
+    \"files.exclude\": {
+        \"**/.git\": true,
+        \"**/target\": true, /* 编译输出 */
+    },
+    \"list\": [
+        1,
+        // 第二个
+        2,
+        // 没有更多了
+    ],
+    /* 结束 */
+}
+// 文件结束
+";
+    // 不保留注释
+    let json = Json::jsonc_to_json(jsonc).unwrap();
+    assert_eq!(json.pointer("/editor.fontSize").unwrap().to_string(), "14");
+    assert!(Json::str_to_json(jsonc).is_err());
+    // 保留注释
+    let doc = JsoncDocument::parse(jsonc).unwrap();
+    let comments: Vec<(&str, CommentPlacement, &str)> = doc.comments.iter()
+        .map(|comment| (comment.pointer.as_str(), comment.placement, comment.text.as_str())).collect();
+    assert_eq!(comments, vec![
+        ("", CommentPlacement::Before, "// 编辑器设置"),
+        ("/editor.fontSize", CommentPlacement::Before, "// 字体大小"),
+        ("/editor.fontSize", CommentPlacement::Trailing, "// 像素"),
+        ("/files.exclude/**~1target", CommentPlacement::Trailing, "/* 编译输出 */"),
+        ("/list/1", CommentPlacement::Before, "// 第二个"),
+        ("/list", CommentPlacement::End, "// 没有更多了"),
+        ("", CommentPlacement::End, "/* 结束 */"),
+        ("", CommentPlacement::After, "// 文件结束"),
+    ]);
+    assert_eq!(doc.comments_of("/list/1").count(), 1);
+    // 重新输出时保留注释和key的顺序,并且可以再次解析
+    let out = doc.to_string_pretty(4);
+    assert_eq!(out, "// 编辑器设置
+{
+    // 字体大小
+    \"editor.fontSize\": 14, // 像素
+    \"files.exclude\": {
+        \"**/.git\": true,
+        \"**/target\": true /* 编译输出 */
+    },
+    \"list\": [
+        1,
+        // 第二个
+        2
+        // 没有更多了
+    ]
+    /* 结束 */
+}
+// 文件结束
+");
+    let again = JsoncDocument::parse(&out).unwrap();
+    assert_eq!(again.comments, doc.comments);
+    assert_eq!(again.to_string_pretty(4), out);
+}
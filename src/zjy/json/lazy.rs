@@ -0,0 +1,284 @@
+/**
+ * 文件名: "src/zjy/json/lazy.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::zjy::json::fast::ByteParser;
+use crate::zjy::json::options::DuplicateKeys;
+use crate::zjy::json::pointer;
+use crate::zjy::json::{Json, JsonError, ParseOptions, Parser};
+
+/**
+ * 按需解析的json值
+ * 创建时检查整个输入的语法,但是不生成json值;get/index从原始文本中向后扫描,
+ * 扫描过的子值的位置会被缓存,只有被访问的值才会生成Json
+ */
+pub struct LazyJson<'a> {
+    bytes: &'a [u8],
+    options: Rc<ParseOptions>,
+    /// 值在输入中的范围
+    start: usize,
+    end: usize,
+    /// 值所在的嵌套深度,最外层是0
+    depth: usize,
+    scan: RefCell<Scan>,
+}
+
+/**
+ * array或object已经扫描过的子值
+ * next是下一个子值(或者结束括号)之前的位置,done代表已经扫描到了结束括号
+ */
+struct Scan {
+    children: Vec<Child>,
+    next: usize,
+    done: bool,
+}
+
+/**
+ * 子值在输入中的范围,array的元素没有key
+ */
+struct Child {
+    key: Option<String>,
+    start: usize,
+    end: usize,
+}
+
+impl<'a> LazyJson<'a> {
+    /**
+     * 使用严格的默认选项检查str的语法,返回按需解析的值
+     */
+    pub fn parse(str: &'a str) -> Result<LazyJson<'a>, JsonError> {
+        return LazyJson::parse_with_options(str, &ParseOptions::default());
+    }
+    /**
+     * 使用指定的选项检查str的语法,只支持可以按字节解析的选项(严格模式,没有语法扩展)
+     */
+    pub fn parse_with_options(str: &'a str, options: &ParseOptions) -> Result<LazyJson<'a>, JsonError> {
+        if !options.fast_path_compatible() {
+            return Err("LazyJson只支持严格模式并且没有语法扩展的选项".to_string().into());
+        }
+        let bytes = str.as_bytes();
+        options.check_total_bytes(bytes.len())?;
+        ByteParser::new(bytes, options).validate_document()?;
+        let mut parser = ByteParser::new(bytes, options);
+        parser.skip_whitespace();
+        let start = parser.position();
+        parser.skip_value()?;
+        return Ok(LazyJson::new(bytes, Rc::new(options.clone()), start, parser.position(), 0));
+    }
+
+    fn new(bytes: &'a [u8], options: Rc<ParseOptions>, start: usize, end: usize, depth: usize) -> LazyJson<'a> {
+        let scan = Scan { children: Vec::new(), next: start + 1, done: !matches!(bytes[start], b'[' | b'{') };
+        return LazyJson { bytes, options, start, end, depth, scan: RefCell::new(scan) };
+    }
+
+    pub fn is_array(&self) -> bool { return self.bytes[self.start] == b'['; }
+    pub fn is_object(&self) -> bool { return self.bytes[self.start] == b'{'; }
+    /**
+     * 值在输入中的原始文本
+     */
+    pub fn raw(&self) -> &'a str {
+        // 范围的两端都是ascii字符,一定在字符边界上
+        return unsafe { std::str::from_utf8_unchecked(&self.bytes[self.start..self.end]) };
+    }
+    /**
+     * 生成整个值
+     */
+    pub fn to_json(&self) -> Json {
+        return self.materialize(self.start, self.depth);
+    }
+    /**
+     * array的元素个数或object的成员个数(重复的key分别计数),其他值返回0
+     * 需要扫描到值的结尾
+     */
+    pub fn len(&self) -> usize {
+        self.scan_while(|_| true);
+        return self.scan.borrow().children.len();
+    }
+    /**
+     * 是否是空的array或object,只需要扫描第一个子值
+     */
+    pub fn is_empty(&self) -> bool {
+        self.scan_while(|children| children.is_empty());
+        return self.scan.borrow().children.is_empty();
+    }
+    /**
+     * object的全部key,按照在输入中出现的顺序
+     */
+    pub fn keys(&self) -> Vec<String> {
+        self.scan_while(|_| true);
+        return self.scan.borrow().children.iter().filter_map(|child| child.key.clone()).collect();
+    }
+    /**
+     * 生成object中key对应的值,不是object或者没有这个key时返回None
+     */
+    pub fn get(&self, key: &str) -> Option<Json> {
+        let child = self.find_key(key)?;
+        return Some(self.materialize(child, self.depth + 1));
+    }
+    /**
+     * 生成array中下标为i的元素,不是array或者越界时返回None
+     */
+    pub fn index(&self, i: usize) -> Option<Json> {
+        let child = self.find_index(i)?;
+        return Some(self.materialize(child, self.depth + 1));
+    }
+    /**
+     * 不生成json值,返回object中key对应的按需解析的值
+     */
+    pub fn get_lazy(&self, key: &str) -> Option<LazyJson<'a>> {
+        let child = self.find_key(key)?;
+        return Some(self.child(child));
+    }
+    /**
+     * 不生成json值,返回array中下标为i的按需解析的值
+     */
+    pub fn index_lazy(&self, i: usize) -> Option<LazyJson<'a>> {
+        let child = self.find_index(i)?;
+        return Some(self.child(child));
+    }
+    /**
+     * 按照JSON Pointer查找并生成值,路径上的其他值都不会生成
+     */
+    pub fn pointer(&self, pointer: &str) -> Option<Json> {
+        let tokens = pointer::split_pointer(pointer)?;
+        let mut current: Option<LazyJson<'a>> = None;
+        for token in tokens {
+            let parent = current.as_ref().unwrap_or(self);
+            let next = if parent.is_array() {
+                parent.index_lazy(pointer::parse_index(&token)?)?
+            } else {
+                parent.get_lazy(&token)?
+            };
+            current = Some(next);
+        }
+        return Some(current.as_ref().unwrap_or(self).to_json());
+    }
+
+    /**
+     * 找到key对应的子值的开始位置,重复的key按照选项取第一个或者最后一个
+     */
+    fn find_key(&self, key: &str) -> Option<usize> {
+        if !self.is_object() {
+            return None;
+        }
+        let matches = |child: &Child| child.key.as_deref() == Some(key);
+        if self.options.duplicate_keys == DuplicateKeys::First {
+            self.scan_while(|children| !children.iter().any(matches));
+            return self.scan.borrow().children.iter().find(|child| matches(child)).map(|child| child.start);
+        }
+        self.scan_while(|_| true);
+        return self.scan.borrow().children.iter().rev().find(|child| matches(child)).map(|child| child.start);
+    }
+
+    fn find_index(&self, i: usize) -> Option<usize> {
+        if !self.is_array() {
+            return None;
+        }
+        self.scan_while(|children| children.len() <= i);
+        return self.scan.borrow().children.get(i).map(|child| child.start);
+    }
+
+    fn child(&self, start: usize) -> LazyJson<'a> {
+        let scan = self.scan.borrow();
+        let child = scan.children.iter().find(|child| child.start == start).unwrap();
+        return LazyJson::new(self.bytes, self.options.clone(), child.start, child.end, self.depth + 1);
+    }
+
+    fn materialize(&self, start: usize, depth: usize) -> Json {
+        // 创建时已经检查过语法,这里不会出错
+        return ByteParser::new_at(self.bytes, start, depth, &self.options).parse_value().unwrap_or(Json::Null);
+    }
+
+    /**
+     * 在keep_going返回true并且还没有扫描到结束括号时继续向后扫描子值
+     */
+    fn scan_while(&self, keep_going: impl Fn(&[Child]) -> bool) {
+        let mut scan = self.scan.borrow_mut();
+        let is_object = self.is_object();
+        while !scan.done && keep_going(&scan.children) {
+            let mut parser = ByteParser::new_at(self.bytes, scan.next, self.depth + 1, &self.options);
+            parser.skip_whitespace();
+            if parser.eat(b']') || parser.eat(b'}') {
+                scan.done = true;
+                break;
+            }
+            let key = if is_object {
+                let key = parser.parse_string().ok();
+                parser.skip_whitespace();
+                parser.eat(b':');
+                parser.skip_whitespace();
+                key
+            } else {
+                None
+            };
+            let start = parser.position();
+            if parser.skip_value().is_err() {
+                scan.done = true;
+                break;
+            }
+            scan.children.push(Child { key, start, end: parser.position() });
+            parser.skip_whitespace();
+            if !parser.eat(b',') {
+                scan.done = true;
+            }
+            scan.next = parser.position();
+        }
+    }
+}
+
+impl Parser {
+    /**
+     * 检查str的语法,返回按需解析的LazyJson,只支持可以按字节解析的选项
+     */
+    pub fn parse_lazy<'a>(&self, str: &'a str) -> Result<LazyJson<'a>, JsonError> {
+        return LazyJson::parse_with_options(str, self.options());
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_lazy_json() {
+    let str = std::fs::read_to_string("tests/testobject.json").unwrap();
+    let lazy = LazyJson::parse(&str).unwrap();
+    assert!(lazy.is_object());
+    assert_eq!(lazy.get("str").unwrap().to_json5_string(), "\"hello\"");
+    // 重复的key默认取最后一个,需要扫描整个object
+    assert_eq!(lazy.scan.borrow().children.len(), 13);
+    assert_eq!(lazy.get("true").unwrap().to_json5_string(), "true");
+    assert!(lazy.get("none").is_none());
+    assert_eq!(lazy.len(), 13);
+    let arr = lazy.get_lazy("arr").unwrap();
+    assert_eq!((arr.len(), arr.index(1).unwrap().to_json5_string()), (5, "2".to_string()));
+    assert!(arr.index(5).is_none());
+    assert_eq!(lazy.pointer("/obj2/arr/3/name").unwrap().to_json5_string(), "\"j🧑\"");
+    assert!(lazy.pointer("/obj2/arr/03").is_none() && lazy.pointer("/obj2/arr/+3").is_none());
+    assert_eq!(lazy.get_lazy("obj1").unwrap().raw().split_whitespace().collect::<String>(), "{\"1\":1,\"2\":2}");
+    assert_eq!(lazy.to_json().to_json5_string(), Json::str_to_json(&str).unwrap().to_json5_string());
+    // 重复的key取第一个时不需要扫描整个object
+    let first = Parser::new().duplicate_keys(DuplicateKeys::First).parse_lazy(&str).unwrap();
+    assert_eq!(first.get("true").unwrap().to_json5_string(), "\"注释1\"");
+    assert_eq!(first.scan.borrow().children.len(), 4);
+    // 创建时检查整个输入的语法
+    assert!(LazyJson::parse("{\"a\":1,\"b\":[1,]}").is_err());
+    assert!(Parser::new().duplicate_keys(DuplicateKeys::Error).parse_lazy(&str).is_err());
+    assert!(Parser::with_options(ParseOptions::json5()).parse_lazy("{}").is_err());
+    assert_eq!(LazyJson::parse(" 12 ").unwrap().to_json().to_json5_string(), "12");
+}
@@ -0,0 +1,188 @@
+/**
+ * 文件名: "src/zjy/json/lines.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::io::{self, BufRead, Write};
+
+use crate::zjy::json::{Json, JsonError, Parser, SerializeOptions};
+
+/**
+ * 按行读取NDJSON / JSON Lines,每个非空行是一个json值
+ * 迭代器每次返回一行的解析结果,错误信息带有行号,空白行会被跳过;
+ * skip_invalid(true)时跳过格式错误的行,错误记录在skipped()中,只有io错误会返回
+ */
+pub struct JsonLinesReader<R: BufRead> {
+    reader: R,
+    parser: Parser,
+    skip_invalid: bool,
+    /// 最后读取的行的行号,从1开始
+    line: usize,
+    /// 被跳过的格式错误的行(行号,错误)
+    skipped: Vec<(usize, JsonError)>,
+    /// 发生io错误之后不再继续读取
+    done: bool,
+    buf: Vec<u8>,
+}
+
+impl<R: BufRead> JsonLinesReader<R> {
+    /**
+     * 使用严格的默认选项解析每一行
+     */
+    pub fn new(reader: R) -> JsonLinesReader<R> {
+        return JsonLinesReader::with_parser(reader, Parser::new());
+    }
+    /**
+     * 使用指定的解析器解析每一行
+     */
+    pub fn with_parser(reader: R, parser: Parser) -> JsonLinesReader<R> {
+        return JsonLinesReader { reader, parser, skip_invalid: false, line: 0, skipped: Vec::new(), done: false, buf: Vec::new() };
+    }
+    /**
+     * 是否跳过格式错误的行继续读取
+     */
+    pub fn skip_invalid(mut self, skip: bool) -> JsonLinesReader<R> { self.skip_invalid = skip; return self; }
+    /**
+     * 最后读取的行的行号,还没有读取时是0
+     */
+    pub fn line(&self) -> usize { return self.line; }
+    /**
+     * 被跳过的格式错误的行和对应的错误
+     */
+    pub fn skipped(&self) -> &[(usize, JsonError)] { return &self.skipped; }
+    /**
+     * 取出内部的reader
+     */
+    pub fn into_inner(self) -> R { return self.reader; }
+
+    /**
+     * 读取下一个非空行,返回去掉行尾换行和两端空白之后的内容,输入结束时返回None
+     */
+    fn next_line(&mut self) -> Result<Option<(usize, usize)>, JsonError> {
+        loop {
+            self.buf.clear();
+            if self.reader.read_until(b'\n', &mut self.buf)? == 0 {
+                return Ok(None);
+            }
+            self.line += 1;
+            let mut start = 0;
+            let mut end = self.buf.len();
+            // 第一行开头的BOM
+            if self.line == 1 && self.buf.starts_with(&[0xef, 0xbb, 0xbf]) {
+                start = 3;
+            }
+            while start < end && matches!(self.buf[start], b' ' | b'\t' | b'\r' | b'\n') {
+                start += 1;
+            }
+            while end > start && matches!(self.buf[end - 1], b' ' | b'\t' | b'\r' | b'\n') {
+                end -= 1;
+            }
+            if start < end {
+                return Ok(Some((start, end)));
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for JsonLinesReader<R> {
+    type Item = Result<Json, JsonError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let (start, end) = match self.next_line() {
+                Ok(Some(range)) => range,
+                Ok(None) => { self.done = true; return None; },
+                Err(err) => { self.done = true; return Some(Err(err.line(self.line + 1))); },
+            };
+            match self.parser.parse_bytes(&self.buf[start..end]) {
+                Ok(json) => { return Some(Ok(json)); },
+                Err(err) => {
+                    let err = err.line(self.line);
+                    if !self.skip_invalid {
+                        return Some(Err(err));
+                    }
+                    self.skipped.push((self.line, err));
+                },
+            }
+        }
+        return None;
+    }
+}
+
+/**
+ * 写入NDJSON / JSON Lines,每个json值紧凑输出为一行,object的key按照字典序输出
+ */
+pub struct JsonLinesWriter<W: Write> {
+    writer: W,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> JsonLinesWriter<W> {
+    pub fn new(writer: W) -> JsonLinesWriter<W> { JsonLinesWriter { writer, buf: Vec::new() } }
+    /**
+     * 写入一个json值和换行符,number是NaN或Infinity时返回InvalidInput错误
+     */
+    pub fn write(&mut self, json: &Json) -> io::Result<()> {
+        self.buf.clear();
+        if let Err(err) = json.write_to(&mut self.buf, &SerializeOptions::compact()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, err.to_string()));
+        }
+        self.buf.push(b'\n');
+        return self.writer.write_all(&self.buf);
+    }
+    pub fn flush(&mut self) -> io::Result<()> { return self.writer.flush(); }
+    /**
+     * 取出内部的writer
+     */
+    pub fn into_inner(self) -> W { return self.writer; }
+}
+
+#[cfg(test)]
+#[test]
+fn test_json_lines() {
+    let input = "{\"a\":1}\n\n  [1,2]\r\n{\"a\":\n\"x\\ty\"\n";
+    let mut reader = JsonLinesReader::new(input.as_bytes());
+    assert_eq!(reader.next().unwrap().unwrap().to_json5_string(), "{a:1}");
+    assert_eq!(reader.next().unwrap().unwrap().to_json5_string(), "[1,2]");
+    assert_eq!(reader.line(), 3);
+    let err = reader.next().unwrap().err().unwrap();
+    assert!(err.to_string().starts_with("第4行:"));
+    assert_eq!(reader.next().unwrap().unwrap().to_json5_string(), "\"x\\ty\"");
+    assert!(reader.next().is_none());
+    // 跳过格式错误的行
+    let mut reader = JsonLinesReader::new(input.as_bytes()).skip_invalid(true);
+    let values: Vec<Json> = reader.by_ref().map(|json| json.unwrap()).collect();
+    assert_eq!(values.len(), 3);
+    assert_eq!(reader.skipped().len(), 1);
+    assert_eq!(reader.skipped()[0].0, 4);
+    // 资源限制和io错误也带有行号
+    let mut reader = JsonLinesReader::with_parser("[1]\n[[2]]\n".as_bytes(), Parser::new().max_depth(1));
+    assert!(reader.next().unwrap().is_ok());
+    let err = reader.next().unwrap().err().unwrap();
+    assert!(err.is_limit_exceeded());
+    assert_eq!(err.to_string(), "第2行:超出资源限制:嵌套深度超过1");
+    assert_eq!(JsonError::from(io::Error::other("断开")).line(3).to_string(), "第3行:读取失败:断开");
+    // 写入之后可以再读出来
+    let mut writer = JsonLinesWriter::new(Vec::new());
+    for value in &values {
+        writer.write(value).unwrap();
+    }
+    assert!(writer.write(&Json::Number(f64::NAN)).is_err());
+    let output = String::from_utf8(writer.into_inner()).unwrap();
+    assert_eq!(output, "{\"a\":1}\n[1,2]\n\"x\\ty\"\n");
+    assert_eq!(JsonLinesReader::new(output.as_bytes()).count(), 3);
+}
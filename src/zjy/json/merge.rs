@@ -0,0 +1,219 @@
+/**
+ * 文件名: "src/zjy/json/merge.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::collections::HashMap;
+
+use crate::zjy::json::pointer::join;
+use crate::zjy::json::{Json, JsonError};
+
+/**
+ * 合并两个array的方式
+ * Replace代表用新的array替换;Concat代表把新的元素追加到后面;Union代表只追加还没有出现过的元素;
+ * ByIndex代表相同下标的元素继续合并,多出的元素追加;ByKey(key)代表按照元素(object)中key的值配对合并,没有配对的元素追加
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ArrayMerge {
+    #[default]
+    Replace,
+    Concat,
+    Union,
+    ByIndex,
+    ByKey(String),
+}
+
+/**
+ * 一边是object或array,另一边是其他类型(或者一边是object另一边是array)时的处理方式
+ * Override代表用新的值替换,Error代表返回错误并且不修改原来的值
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MergeConflict {
+    #[default]
+    Override,
+    Error,
+}
+
+/**
+ * 深度合并的选项,默认替换array,类型冲突时用新的值替换
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MergeOptions {
+    /// 合并array的方式
+    pub arrays: ArrayMerge,
+    /// 类型冲突时的处理方式
+    pub conflicts: MergeConflict,
+}
+
+impl Json {
+    /**
+     * 把other深度合并到self中: object按照key递归合并,array按照options.arrays合并,其他值用other替换
+     * 返回值发生变化的位置(JSON Pointer),按照合并的顺序(object按照key的字典序)排列
+     * 可以依次合并默认配置,环境配置和覆盖配置实现分层配置
+     */
+    pub fn deep_merge(&mut self, other: Json, options: &MergeOptions) -> Result<Vec<String>, JsonError> {
+        let mut changed = Vec::new();
+        if options.conflicts == MergeConflict::Error {
+            // 出错时不修改self
+            let mut merged = self.clone();
+            merge_value(&mut merged, other, "", options, &mut changed)?;
+            *self = merged;
+        } else {
+            merge_value(self, other, "", options, &mut changed)?;
+        }
+        return Ok(changed);
+    }
+}
+
+fn is_container(json: &Json) -> bool {
+    return matches!(json, Json::Object(_) | Json::Array(_));
+}
+
+fn merge_value(target: &mut Json, other: Json, pointer: &str, options: &MergeOptions, changed: &mut Vec<String>) -> Result<(), JsonError> {
+    match (target, other) {
+        (Json::Object(obj), Json::Object(other)) => {
+            let mut entries: Vec<(String, Json)> = other.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            for (key, value) in entries {
+                let pointer = join(pointer, &key);
+                match obj.get_mut(&key) {
+                    Some(target) => { merge_value(target, value, &pointer, options, changed)?; },
+                    None => {
+                        obj.insert(key, value);
+                        changed.push(pointer);
+                    },
+                }
+            }
+        },
+        (Json::Array(arr), Json::Array(other)) => { merge_array(arr, other, pointer, options, changed)?; },
+        (target, other) => {
+            if (is_container(target) || is_container(&other)) && options.conflicts == MergeConflict::Error {
+                return Err(std::format!("合并冲突:{}处的类型不同", if pointer.is_empty() { "根" } else { pointer }).into());
+            }
+            if *target != other {
+                *target = other;
+                changed.push(pointer.to_string());
+            }
+        },
+    }
+    return Ok(());
+}
+
+/**
+ * 在array后面追加一个元素,记录它的位置
+ */
+fn push(arr: &mut Vec<Json>, value: Json, pointer: &str, changed: &mut Vec<String>) {
+    changed.push(join(pointer, &arr.len().to_string()));
+    arr.push(value);
+}
+
+fn merge_array(arr: &mut Vec<Json>, other: Vec<Json>, pointer: &str, options: &MergeOptions, changed: &mut Vec<String>) -> Result<(), JsonError> {
+    match &options.arrays {
+        ArrayMerge::Replace => {
+            if *arr != other {
+                *arr = other;
+                changed.push(pointer.to_string());
+            }
+        },
+        ArrayMerge::Concat => {
+            for value in other {
+                push(arr, value, pointer, changed);
+            }
+        },
+        ArrayMerge::Union => {
+            for value in other {
+                if !arr.contains(&value) {
+                    push(arr, value, pointer, changed);
+                }
+            }
+        },
+        ArrayMerge::ByIndex => {
+            for (i, value) in other.into_iter().enumerate() {
+                match arr.get_mut(i) {
+                    Some(target) => { merge_value(target, value, &join(pointer, &i.to_string()), options, changed)?; },
+                    None => { push(arr, value, pointer, changed); },
+                }
+            }
+        },
+        ArrayMerge::ByKey(key) => {
+            let id = |value: &Json| -> Option<Json> {
+                match value {
+                    Json::Object(obj) => { return obj.get(key).cloned(); },
+                    _ => { return None; },
+                }
+            };
+            let mut ids: HashMap<Json, usize> = HashMap::new();
+            for (i, value) in arr.iter().enumerate() {
+                if let Some(id) = id(value) {
+                    ids.entry(id).or_insert(i);
+                }
+            }
+            for value in other {
+                match id(&value).and_then(|id| ids.get(&id).copied()) {
+                    Some(i) => { merge_value(&mut arr[i], value, &join(pointer, &i.to_string()), options, changed)?; },
+                    None => {
+                        if let Some(id) = id(&value) {
+                            ids.insert(id, arr.len());
+                        }
+                        push(arr, value, pointer, changed);
+                    },
+                }
+            }
+        },
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+#[test]
+fn test_deep_merge() {
+    let defaults = r#"{"server":{"host":"localhost","port":80},"tags":["a","b"],"users":[{"name":"root","shell":"sh"}]}"#;
+    let env = Json::str_to_json(r#"{"server":{"port":8080,"tls":true},"tags":["b","c"],"users":[{"name":"root","shell":"bash"},{"name":"guest"}]}"#).unwrap();
+
+    let mut json = Json::str_to_json(defaults).unwrap();
+    let changed = json.deep_merge(env.clone(), &MergeOptions::default()).unwrap();
+    assert_eq!(changed, ["/server/port", "/server/tls", "/tags", "/users"]);
+    assert_eq!(json.pointer("/tags").unwrap().to_json5_string(), r#"["b","c"]"#);
+    assert_eq!(json.pointer("/server/host").unwrap().to_json5_string(), r#""localhost""#);
+
+    let merge = |arrays: ArrayMerge| {
+        let mut json = Json::str_to_json(defaults).unwrap();
+        let changed = json.deep_merge(env.clone(), &MergeOptions { arrays, ..MergeOptions::default() }).unwrap();
+        return (json, changed);
+    };
+    let (json, _) = merge(ArrayMerge::Concat);
+    assert_eq!(json.pointer("/tags").unwrap().to_json5_string(), r#"["a","b","b","c"]"#);
+    let (json, changed) = merge(ArrayMerge::Union);
+    assert_eq!(json.pointer("/tags").unwrap().to_json5_string(), r#"["a","b","c"]"#);
+    assert!(changed.contains(&"/tags/2".to_string()));
+    let (json, _) = merge(ArrayMerge::ByIndex);
+    assert_eq!(json.pointer("/tags").unwrap().to_json5_string(), r#"["b","c"]"#);
+    assert_eq!(json.pointer("/users/1/name").unwrap().to_json5_string(), r#""guest""#);
+    let (json, changed) = merge(ArrayMerge::ByKey("name".to_string()));
+    assert_eq!(json.pointer("/users").unwrap().to_json5_string(), r#"[{name:"root",shell:"bash"},{name:"guest"}]"#);
+    assert_eq!(changed, ["/server/port", "/server/tls", "/tags/2", "/tags/3", "/users/0/shell", "/users/1"]);
+
+    // 类型冲突
+    let mut json = Json::str_to_json(defaults).unwrap();
+    let options = MergeOptions { conflicts: MergeConflict::Error, ..MergeOptions::default() };
+    let err = json.deep_merge(Json::str_to_json(r#"{"server":{"port":1},"tags":{"x":1}}"#).unwrap(), &options).err().unwrap();
+    assert_eq!(err.to_string(), "合并冲突:/tags处的类型不同");
+    assert_eq!(json, Json::str_to_json(defaults).unwrap());
+    let changed = json.deep_merge(Json::str_to_json(r#"{"tags":{"x":1}}"#).unwrap(), &MergeOptions::default()).unwrap();
+    assert_eq!(changed, ["/tags"]);
+    assert!(json.deep_merge(json.clone(), &MergeOptions::default()).unwrap().is_empty());
+}
@@ -0,0 +1,110 @@
+/**
+ * 文件名: "src/zjy/json/mmap.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::fs::File;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::zjy::json::{Json, JsonError, JsonTape, LazyJson, Parser};
+
+/**
+ * 内存映射的json文件,打开时只检查utf8编码,不把文件复制到String中
+ * 可以直接解析,也可以建立借用文件内容的LazyJson和JsonTape(借用的string不需要复制);
+ * parse和Json::from_mmap得到的Json总是拥有自己的String,只省去了读取文件的复制
+ * 只适合只读的数据文件,见MappedJson::open的安全性说明
+ */
+pub struct MappedJson {
+    map: Option<Mmap>,
+}
+
+impl MappedJson {
+    /**
+     * 映射path对应的文件
+     * # Safety
+     * 在返回的MappedJson(以及从它借用的值)存在期间,文件不能被本进程或其他进程截断或修改,
+     * 否则as_str返回的内容可能不再是合法的utf8,或者访问时收到SIGBUS
+     */
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> Result<MappedJson, JsonError> {
+        let file = File::open(path)?;
+        // 空文件不能被映射
+        if file.metadata()?.len() == 0 {
+            return Ok(MappedJson { map: None });
+        }
+        // 安全性: 由调用者按照open的约定保证映射期间文件不会被截断或修改
+        let map = unsafe { Mmap::map(&file)? };
+        if let Err(err) = std::str::from_utf8(&map) {
+            return Err(std::format!("第{}个字节:不是合法的utf8编码", err.valid_up_to() + 1).into());
+        }
+        return Ok(MappedJson { map: Some(map) });
+    }
+    /**
+     * 文件的内容
+     */
+    pub fn as_str(&self) -> &str {
+        match &self.map {
+            // 打开时已经检查过utf8编码,open的约定保证之后内容不会变化
+            Some(map) => { return unsafe { std::str::from_utf8_unchecked(map) }; },
+            None => { return ""; },
+        }
+    }
+    /**
+     * 使用parser解析整个文件
+     */
+    pub fn parse(&self, parser: &Parser) -> Result<Json, JsonError> {
+        return parser.parse_bytes(self.as_str().as_bytes());
+    }
+    /**
+     * 借用文件内容的按需解析的值
+     */
+    pub fn lazy(&self) -> Result<LazyJson<'_>, JsonError> {
+        return LazyJson::parse(self.as_str());
+    }
+    /**
+     * 借用文件内容的结构索引
+     */
+    pub fn tape(&self) -> Result<JsonTape<'_>, JsonError> {
+        return JsonTape::build(self.as_str());
+    }
+}
+
+impl Json {
+    /**
+     * 通过内存映射读取并按照RFC 8259严格解析path对应的文件,不需要先读取到String中,结果中的string仍然是复制出来的
+     * # Safety
+     * 和MappedJson::open相同: 解析期间文件不能被截断或修改
+     */
+    pub unsafe fn from_mmap<P: AsRef<Path>>(path: P) -> Result<Json, JsonError> {
+        return MappedJson::open(path)?.parse(&Parser::new());
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_from_mmap() {
+    let str = std::fs::read_to_string("tests/testobject.json").unwrap();
+    // 测试文件在测试期间不会被修改
+    let json = unsafe { Json::from_mmap("tests/testobject.json") }.unwrap();
+    assert_eq!(json.to_json5_string(), Json::str_to_json(&str).unwrap().to_json5_string());
+    let mapped = unsafe { MappedJson::open("tests/testobject.json") }.unwrap();
+    assert_eq!(mapped.as_str(), str);
+    assert_eq!(mapped.lazy().unwrap().get("str").unwrap().to_json5_string(), "\"hello\"");
+    assert_eq!(mapped.tape().unwrap().pointer("/obj2/arr/0").unwrap().raw(), "114514");
+    assert!(matches!(unsafe { Json::from_mmap("tests/none.json") }, Err(JsonError::Io(_))));
+}
@@ -0,0 +1,389 @@
+/**
+ * 文件名: "src/zjy/json/options.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::zjy::json::error::{JsonError, Limit};
+
+/**
+ * 解析json时的选项
+ * 默认按照RFC 8259严格解析,嵌套深度最多DEFAULT_MAX_DEPTH(128)层,不限制其他资源,解析不可信的输入时应该设置各项最大值
+ */
+#[derive(Clone, Debug)]
+pub struct ParseOptions {
+    /// 严格程度
+    pub strictness: Strictness,
+    /// 哪些字符被当作空白
+    pub whitespace: WhitespaceSet,
+    /// object中出现重复的key时的处理方式
+    pub duplicate_keys: DuplicateKeys,
+    /// number的解析方式
+    pub number_mode: NumberMode,
+    /// 标准json之外的语法扩展
+    pub extensions: Extensions,
+    /// 最大嵌套深度,最外层的array或object深度为1;默认是DEFAULT_MAX_DEPTH,解析器是递归的,不限制深度时很深的输入会导致栈溢出
+    pub max_depth: usize,
+    /// 整个文档的最大字节数
+    pub max_total_bytes: usize,
+    /// 单个string的最大字节数(转义之后),object的key也受此限制
+    pub max_string_length: usize,
+    /// 单个array的最大元素个数
+    pub max_array_length: usize,
+    /// 单个object的最大成员个数
+    pub max_object_members: usize,
+    /// 单个number的最大字符个数
+    pub max_number_length: usize,
+}
+
+/// 默认的最大嵌套深度,足够任何正常的文档使用;debug编译时每一层大约使用5KB栈,128层在2MB栈的线程中也是安全的
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/**
+ * 严格程度
+ * Strict代表严格按照RFC 8259: json值后面只能有空白,number必须符合json的语法,
+ * string中所有的控制字符都必须转义
+ * Legacy代表兼容旧版本str_to_json的行为: 解析出第一个json值后忽略后面的内容,
+ * number交给标准库解析(允许"01","1."这样的写法),string中只检查\b \f \n \r \t
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strictness {
+    Strict,
+    Legacy,
+}
+
+/**
+ * 被当作空白的字符集合
+ * unicode代表额外允许unicode中的空白字符(Zs类别,BOM,行分隔符和段分隔符)
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WhitespaceSet {
+    pub space: bool,
+    pub tab: bool,
+    pub line_feed: bool,
+    pub carriage_return: bool,
+    pub unicode: bool,
+}
+
+/**
+ * object中出现重复的key时的处理方式
+ * Last保留最后一个值,First保留第一个值,Error直接报错
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    Last,
+    First,
+    Error,
+}
+
+/**
+ * number的解析方式
+ * Float解析成最接近的f64,超出f64范围的数字报错
+ * Lossless在Float的基础上要求整数必须能被f64精确表示,比如大于2的53次方的奇数会报错
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumberMode {
+    Float,
+    Lossless,
+}
+
+/**
+ * 标准json之外的语法扩展,默认全部关闭
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Extensions {
+    /// 允许array和object最后一个元素后面有逗号
+    pub trailing_commas: bool,
+    /// 允许 // 单行注释和 /* */ 多行注释,注释被当作空白
+    pub comments: bool,
+    /// 允许用单引号表示string
+    pub single_quoted_strings: bool,
+    /// 允许object的key是不带引号的标识符
+    pub unquoted_keys: bool,
+    /// 允许json5的number: 16进制,+号,小数点前后没有数字,Infinity和NaN
+    pub json5_numbers: bool,
+    /// 允许json5的string转义: \' \v \0 \xHH,续行,以及直接出现的\t等控制字符
+    pub json5_escapes: bool,
+}
+
+impl Extensions {
+    /**
+     * 开启json5的全部语法扩展
+     */
+    pub fn json5() -> Extensions {
+        return Extensions {
+            trailing_commas: true,
+            comments: true,
+            single_quoted_strings: true,
+            unquoted_keys: true,
+            json5_numbers: true,
+            json5_escapes: true,
+        };
+    }
+    /**
+     * 开启jsonc(VS Code使用的带注释的json)的语法扩展: 注释和末尾逗号
+     */
+    pub fn jsonc() -> Extensions {
+        return Extensions {
+            trailing_commas: true,
+            comments: true,
+            ..Extensions::default()
+        };
+    }
+}
+
+impl WhitespaceSet {
+    /**
+     * RFC 8259规定的空白: 空格,\t,\n,\r
+     */
+    pub fn json() -> WhitespaceSet {
+        return WhitespaceSet { space: true, tab: true, line_feed: true, carriage_return: true, unicode: false };
+    }
+    /**
+     * 旧版本str_to_json允许的空白: 空格,\n,\r
+     */
+    pub fn legacy() -> WhitespaceSet {
+        return WhitespaceSet { space: true, tab: false, line_feed: true, carriage_return: true, unicode: false };
+    }
+    /**
+     * 判断c是否是空白
+     */
+    #[inline]
+    pub fn contains(&self, c: char) -> bool {
+        match c {
+            ' ' => { return self.space; },
+            '\t' => { return self.tab; },
+            '\n' => { return self.line_feed; },
+            '\r' => { return self.carriage_return; },
+            _ => {
+                return self.unicode && (c == '\u{feff}' || c == '\u{0b}' || c == '\u{0c}' || (!c.is_ascii() && c.is_whitespace()));
+            },
+        }
+    }
+}
+
+impl Default for WhitespaceSet {
+    fn default() -> Self {
+        return WhitespaceSet::json();
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        return ParseOptions {
+            strictness: Strictness::Strict,
+            whitespace: WhitespaceSet::json(),
+            duplicate_keys: DuplicateKeys::Last,
+            number_mode: NumberMode::Float,
+            extensions: Extensions::default(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_total_bytes: usize::MAX,
+            max_string_length: usize::MAX,
+            max_array_length: usize::MAX,
+            max_object_members: usize::MAX,
+            max_number_length: usize::MAX,
+        };
+    }
+}
+
+impl ParseOptions {
+    /**
+     * 兼容旧版本str_to_json的选项,参考Strictness::Legacy
+     */
+    pub fn legacy() -> ParseOptions {
+        return ParseOptions { strictness: Strictness::Legacy, whitespace: WhitespaceSet::legacy(), ..ParseOptions::default() };
+    }
+    /**
+     * 解析json5的选项: 开启全部json5语法扩展,并且允许unicode空白
+     */
+    pub fn json5() -> ParseOptions {
+        return ParseOptions {
+            whitespace: WhitespaceSet { unicode: true, ..WhitespaceSet::json() },
+            extensions: Extensions::json5(),
+            ..ParseOptions::default()
+        };
+    }
+    /**
+     * 解析jsonc的选项: 允许注释和末尾逗号,其他和严格模式相同
+     */
+    pub fn jsonc() -> ParseOptions {
+        return ParseOptions {
+            extensions: Extensions::jsonc(),
+            ..ParseOptions::default()
+        };
+    }
+    /**
+     * 适合解析不可信输入的限制
+     * 深度128,文档16MB,string 1MB,array和object各100万个元素,number 64个字符
+     */
+    pub fn untrusted() -> ParseOptions {
+        return ParseOptions {
+            max_depth: 128,
+            max_total_bytes: 16 * 1024 * 1024,
+            max_string_length: 1024 * 1024,
+            max_array_length: 1_000_000,
+            max_object_members: 1_000_000,
+            max_number_length: 64,
+            ..ParseOptions::default()
+        };
+    }
+    /**
+     * 判断是否可以使用以字节为单位的快速解析器,
+     * 快速解析器只支持严格模式,RFC 8259规定的空白和NumberMode::Float,不支持语法扩展
+     */
+    pub(crate) fn fast_path_compatible(&self) -> bool {
+        return self.strictness == Strictness::Strict && self.whitespace == WhitespaceSet::json()
+            && self.number_mode == NumberMode::Float && self.extensions == Extensions::default();
+    }
+
+    pub(crate) fn check_depth(&self, depth: usize) -> Result<(), JsonError> {
+        if depth > self.max_depth {
+            return Err(JsonError::LimitExceeded(Limit::Depth(self.max_depth)));
+        }
+        return Ok(());
+    }
+    pub(crate) fn check_total_bytes(&self, len: usize) -> Result<(), JsonError> {
+        if len > self.max_total_bytes {
+            return Err(JsonError::LimitExceeded(Limit::TotalBytes(self.max_total_bytes)));
+        }
+        return Ok(());
+    }
+    pub(crate) fn check_string_length(&self, len: usize) -> Result<(), JsonError> {
+        if len > self.max_string_length {
+            return Err(JsonError::LimitExceeded(Limit::StringLength(self.max_string_length)));
+        }
+        return Ok(());
+    }
+    pub(crate) fn check_array_length(&self, len: usize) -> Result<(), JsonError> {
+        if len > self.max_array_length {
+            return Err(JsonError::LimitExceeded(Limit::ArrayLength(self.max_array_length)));
+        }
+        return Ok(());
+    }
+    pub(crate) fn check_object_members(&self, len: usize) -> Result<(), JsonError> {
+        if len > self.max_object_members {
+            return Err(JsonError::LimitExceeded(Limit::ObjectMembers(self.max_object_members)));
+        }
+        return Ok(());
+    }
+    pub(crate) fn check_number_length(&self, len: usize) -> Result<(), JsonError> {
+        if len > self.max_number_length {
+            return Err(JsonError::LimitExceeded(Limit::NumberLength(self.max_number_length)));
+        }
+        return Ok(());
+    }
+}
+
+/**
+ * 序列化json时的选项
+ * 默认紧凑输出,object的key按照HashMap的迭代顺序输出,非ascii字符原样输出
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SerializeOptions {
+    /// 每一层缩进的空格数,None代表紧凑输出
+    pub indent: Option<usize>,
+    /// object的key按照字典序输出,输出的结果是确定的
+    pub sort_keys: bool,
+    /// 把非ascii字符转义成\uXXXX
+    pub ascii_only: bool,
+    /// 按照RFC 8785(JCS)输出规范化的json,开启时忽略其他选项,NaN和Infinity总是报错
+    pub canonical: bool,
+    /// number是NaN或Infinity时的处理方式
+    pub non_finite: NonFinite,
+}
+
+/**
+ * 序列化时遇到json不能表示的NaN和Infinity的处理方式
+ * Error代表返回错误,Null代表输出null(和JavaScript的JSON.stringify相同),
+ * String代表输出"NaN","Infinity"或"-Infinity"字符串
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NonFinite {
+    #[default]
+    Error,
+    Null,
+    String,
+}
+
+impl SerializeOptions {
+    /**
+     * 紧凑输出,key按照字典序
+     */
+    pub fn compact() -> SerializeOptions {
+        return SerializeOptions { sort_keys: true, ..SerializeOptions::default() };
+    }
+    /**
+     * 带缩进输出,key按照字典序,indent是每一层缩进的空格数
+     */
+    pub fn pretty(indent: usize) -> SerializeOptions {
+        return SerializeOptions { indent: Some(indent), sort_keys: true, ..SerializeOptions::default() };
+    }
+    /**
+     * RFC 8785规范化输出: 没有空白,key按照UTF-16编码单元排序,
+     * number使用ECMAScript的格式,string只转义必须转义的字符
+     */
+    pub fn canonical() -> SerializeOptions {
+        return SerializeOptions { canonical: true, ..SerializeOptions::default() };
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_limit_exceeded() {
+    use crate::zjy::json::Json;
+
+    let mut deep = String::new();
+    for _ in 0..200 {
+        deep.push('[');
+    }
+    deep.push('1');
+    for _ in 0..200 {
+        deep.push(']');
+    }
+    let cases: Vec<(&str, Limit)> = vec![
+        (&deep, Limit::Depth(128)),
+        ("{\"a\":{\"b\":[[[1]]]}}", Limit::Depth(4)),
+        ("[1,2,3,4]", Limit::ArrayLength(3)),
+        ("{\"a\":1,\"b\":2,\"c\":3}", Limit::ObjectMembers(2)),
+        ("[\"0123456789\"]", Limit::StringLength(8)),
+        ("{\"0123456789\":1}", Limit::StringLength(8)),
+        ("[1.00000000000000000001]", Limit::NumberLength(8)),
+        ("[1,2,3,4,5,6,7,8,9,10]", Limit::TotalBytes(16)),
+    ];
+    for (json, limit) in cases {
+        let mut options = ParseOptions::untrusted();
+        match limit {
+            Limit::Depth(max) => { options.max_depth = max; },
+            Limit::TotalBytes(max) => { options.max_total_bytes = max; },
+            Limit::StringLength(max) => { options.max_string_length = max; },
+            Limit::ArrayLength(max) => { options.max_array_length = max; },
+            Limit::ObjectMembers(max) => { options.max_object_members = max; },
+            Limit::NumberLength(max) => { options.max_number_length = max; },
+        }
+        let expect = Err(JsonError::LimitExceeded(limit));
+        assert_eq!(Json::str_to_json_with_options(json, &options).map(|_| ()), expect, "{}", json);
+        assert_eq!(Json::bytes_to_json_with_options(json.as_bytes(), &options).map(|_| ()), expect, "{}", json);
+        // 不设置限制时可以正常解析
+        assert!(Json::str_to_json_with_options(json, &ParseOptions { max_depth: usize::MAX, ..ParseOptions::default() }).is_ok());
+    }
+    // 默认也限制深度,很深的输入返回错误而不是栈溢出
+    let too_deep = "[".repeat(200_000);
+    let expect = JsonError::LimitExceeded(Limit::Depth(DEFAULT_MAX_DEPTH)).to_string();
+    assert_eq!(Json::str_to_json(&too_deep).err(), Some(expect.clone()));
+    assert_eq!(Json::bytes_to_json(too_deep.as_bytes()).err(), Some(expect));
+    let max = std::format!("{}{}", "[".repeat(DEFAULT_MAX_DEPTH), "]".repeat(DEFAULT_MAX_DEPTH));
+    assert!(Json::str_to_json(&max).is_ok());
+}
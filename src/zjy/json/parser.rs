@@ -0,0 +1,325 @@
+/**
+ * 文件名: "src/zjy/json/parser.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::zjy::json::ast::comment::CommentContext;
+use crate::zjy::json::ast::root::RootContext;
+use crate::zjy::json::fast::ByteParser;
+use crate::zjy::json::options::{DuplicateKeys, Extensions, NumberMode, ParseOptions, Strictness, WhitespaceSet};
+use crate::zjy::json::diagnostic::Diagnostic;
+use crate::zjy::json::recover::{parse_str_tolerant, Recovered};
+use crate::zjy::json::span::{Position, Spanned};
+use crate::zjy::json::{Json, JsonError};
+
+/**
+ * json解析器,同时也是ParseOptions的构造器
+ * Parser::new()使用严格的默认选项,可以链式调用修改选项:
+ * Parser::new().max_depth(64).duplicate_keys(DuplicateKeys::Error).parse(str)
+ */
+#[derive(Clone, Debug, Default)]
+pub struct Parser {
+    options: ParseOptions,
+}
+
+impl Parser {
+    /**
+     * 使用默认选项(RFC 8259严格模式,嵌套深度最多DEFAULT_MAX_DEPTH(128)层,不限制其他资源)初始化解析器
+     */
+    pub fn new() -> Parser { Parser { options: ParseOptions::default() } }
+    /**
+     * 使用指定的选项初始化解析器
+     */
+    pub fn with_options(options: ParseOptions) -> Parser { Parser { options } }
+    /**
+     * 获取解析器选项的借用
+     */
+    pub fn options(&self) -> &ParseOptions { return &self.options; }
+    /**
+     * 取出解析器的选项
+     */
+    pub fn into_options(self) -> ParseOptions { return self.options; }
+
+    pub fn strictness(mut self, strictness: Strictness) -> Parser { self.options.strictness = strictness; return self; }
+    pub fn whitespace(mut self, whitespace: WhitespaceSet) -> Parser { self.options.whitespace = whitespace; return self; }
+    pub fn duplicate_keys(mut self, duplicate_keys: DuplicateKeys) -> Parser { self.options.duplicate_keys = duplicate_keys; return self; }
+    pub fn number_mode(mut self, number_mode: NumberMode) -> Parser { self.options.number_mode = number_mode; return self; }
+    pub fn extensions(mut self, extensions: Extensions) -> Parser { self.options.extensions = extensions; return self; }
+    pub fn max_depth(mut self, max: usize) -> Parser { self.options.max_depth = max; return self; }
+    pub fn max_total_bytes(mut self, max: usize) -> Parser { self.options.max_total_bytes = max; return self; }
+    pub fn max_string_length(mut self, max: usize) -> Parser { self.options.max_string_length = max; return self; }
+    pub fn max_array_length(mut self, max: usize) -> Parser { self.options.max_array_length = max; return self; }
+    pub fn max_object_members(mut self, max: usize) -> Parser { self.options.max_object_members = max; return self; }
+    pub fn max_number_length(mut self, max: usize) -> Parser { self.options.max_number_length = max; return self; }
+    /**
+     * 使用ParseOptions::untrusted()中的资源限制,不改变其他选项
+     */
+    pub fn untrusted_limits(self) -> Parser {
+        let limits = ParseOptions::untrusted();
+        return self.max_depth(limits.max_depth)
+            .max_total_bytes(limits.max_total_bytes)
+            .max_string_length(limits.max_string_length)
+            .max_array_length(limits.max_array_length)
+            .max_object_members(limits.max_object_members)
+            .max_number_length(limits.max_number_length);
+    }
+
+    /**
+     * 将字符串形式的json转换为json实例对象
+     */
+    pub fn parse(&self, str: &str) -> Result<Json, JsonError> {
+        return parse_str(str, &self.options);
+    }
+    /**
+     * 将字符串形式的json转换为json实例对象,同时记录每个值和key在输入中的范围
+     */
+    pub fn parse_spanned(&self, str: &str) -> Result<Spanned, JsonError> {
+        return parse_str_spanned(str, &self.options);
+    }
+    /**
+     * 将字符串形式的json转换为json实例对象,失败时返回可以渲染成带源码片段的诊断信息
+     */
+    pub fn parse_diagnostic(&self, str: &str) -> Result<Json, Diagnostic> {
+        return parse_str_diagnostic(str, &self.options);
+    }
+    /**
+     * 容错解析,返回尽量解析出的json值和全部错误,不会因为第一个错误停止
+     */
+    pub fn parse_tolerant(&self, str: &str) -> Recovered {
+        return parse_str_tolerant(str, &self.options);
+    }
+    /**
+     * 解析str开头的一个json值,返回这个值和它结束的字节偏移,后面的内容不会被检查
+     * 例如 "{\"a\":1}{\"b\":2}" 返回第一个object和7
+     */
+    pub fn parse_prefix(&self, str: &str) -> Result<(Json, usize), JsonError> {
+        match parse_prefix(str, &self.options, true)? {
+            Prefix::Value(json, end) => { return Ok((json, end)); },
+            Prefix::Empty | Prefix::Incomplete => { return Err("没能解析出任何json对象".to_string().into()); },
+        }
+    }
+    /**
+     * 将utf8编码的字节形式的json转换为json实例对象
+     * 选项允许时使用以字节为单位的快速解析器
+     */
+    pub fn parse_bytes(&self, bytes: &[u8]) -> Result<Json, JsonError> {
+        return parse_bytes(bytes, &self.options);
+    }
+}
+
+/**
+ * 逐个字符解析json,所有的解析入口最终都会调用这个函数
+ */
+pub(crate) fn parse_str(str: &str, options: &ParseOptions) -> Result<Json, JsonError> {
+    return parse_root(str, options, &mut RootContext::new()).map_err(|(err, _)| err);
+}
+
+/**
+ * 逐个字符解析json,同时由各个上下文记录每个值和key的范围
+ */
+pub(crate) fn parse_str_spanned(str: &str, options: &ParseOptions) -> Result<Spanned, JsonError> {
+    let mut context = RootContext::new_spanned();
+    let value = parse_root(str, options, &mut context).map_err(|(err, _)| err)?;
+    return Ok(Spanned { value, spans: context.take_spans().unwrap_or_default() });
+}
+
+/**
+ * 逐个字符解析json,失败时生成包含位置和需要的符号的诊断信息
+ */
+pub(crate) fn parse_str_diagnostic(str: &str, options: &ParseOptions) -> Result<Json, Diagnostic> {
+    let mut context = RootContext::new();
+    match parse_root(str, options, &mut context) {
+        Ok(json) => { return Ok(json); },
+        Err((err, pos)) => { return Err(Diagnostic::new(err, pos, str, &context, options)); },
+    }
+}
+
+/**
+ * 逐个字符解析json,失败时同时返回出错字符的位置(输入结束时是输入末尾的位置)
+ */
+fn parse_root(str: &str, options: &ParseOptions, context: &mut RootContext) -> Result<Json, (JsonError, Position)> {
+    if str.is_empty() {
+        return Err(("str参数长度为0".to_string().into(), Position::start()));
+    }
+    if let Err(err) = options.check_total_bytes(str.len()) {
+        return Err((err, Position::start()));
+    }
+    let mut comment = CommentContext::new();
+    // 当前字符在输入中的位置
+    let mut pos = Position::start();
+    // 初始化计数器
+    let mut char_index: usize = 0;
+    for c in str.chars() {
+        // 兼容模式下解析出第一个json值后忽略后面的内容
+        if options.strictness == Strictness::Legacy && context.is_end() {
+            break;
+        }
+        char_index += 1;
+        let current = pos;
+        pos = pos.advance(c);
+        let c = if options.extensions.comments {
+            match comment.check_comment(c, options) {
+                Ok(Some(c)) => c,
+                Ok(None) => { continue; },
+                Err(err) => { return Err((JsonError::from(err).at(char_index), current)); },
+            }
+        } else {
+            c
+        };
+        context.check_root(c, current, options).map_err(|err| (err.at(char_index), current))?;
+    }
+    if let Err(err) = comment.finish() {
+        return Err((JsonError::from(err).at(char_index), pos));
+    }
+    return context.finish(pos, options).map_err(|err| (err.at(char_index), pos));
+}
+
+/**
+ * 前缀解析的结果
+ * Value是解析出的值和它结束的字节偏移,Empty代表只有空白和注释,
+ * Incomplete代表输入在值的中间结束了,需要更多的输入
+ */
+pub(crate) enum Prefix {
+    Value(Json, usize),
+    Empty,
+    Incomplete,
+}
+
+/**
+ * 逐个字符解析str开头的一个json值,在值结束的位置停止
+ * at_eof为true时str就是全部的输入,最外层的number可以在输入结束时结束;
+ * 为false时后面还可能有更多的输入,没有结束的值返回Prefix::Incomplete
+ */
+pub(crate) fn parse_prefix(str: &str, options: &ParseOptions, at_eof: bool) -> Result<Prefix, JsonError> {
+    return PrefixParser::new().resume(str, options, at_eof);
+}
+
+/**
+ * 可以分多次输入的前缀解析,解析状态保存在各个上下文中,
+ * 返回Prefix::Incomplete之后可以继续输入后面的内容,每个字符只会被解析一次
+ */
+pub(crate) struct PrefixParser {
+    context: RootContext,
+    comment: CommentContext,
+    /// 下一个字符的位置,offset是已经解析的字节数
+    pos: Position,
+    char_index: usize,
+}
+
+impl PrefixParser {
+    pub(crate) fn new() -> PrefixParser {
+        return PrefixParser { context: RootContext::new(), comment: CommentContext::new(), pos: Position::start(), char_index: 0 };
+    }
+    /**
+     * 继续解析紧跟在已经解析的内容后面的str,返回的结束位置从第一次输入的开头计算
+     * at_eof为true时后面没有更多的输入
+     */
+    pub(crate) fn resume(&mut self, str: &str, options: &ParseOptions, at_eof: bool) -> Result<Prefix, JsonError> {
+        for c in str.chars() {
+            // 在注释过滤之前判断,值后面紧跟的注释不属于这个值
+            if self.context.ends_before(c, options) {
+                return Ok(Prefix::Value(self.context.finish(self.pos, options).map_err(|err| err.at(self.char_index))?, self.pos.offset));
+            }
+            self.char_index += 1;
+            let current = self.pos;
+            self.pos = self.pos.advance(c);
+            let c = if options.extensions.comments {
+                match self.comment.check_comment(c, options) {
+                    Ok(Some(c)) => c,
+                    Ok(None) => { continue; },
+                    Err(err) => { return Err(JsonError::from(err).at(self.char_index)); },
+                }
+            } else {
+                c
+            };
+            self.context.check_root(c, current, options).map_err(|err| err.at(self.char_index))?;
+        }
+        if self.context.is_end() {
+            return Ok(Prefix::Value(self.context.finish(self.pos, options)?, self.pos.offset));
+        }
+        if self.context.is_start() && self.comment.finish().is_ok() {
+            return Ok(Prefix::Empty);
+        }
+        if !at_eof {
+            return Ok(Prefix::Incomplete);
+        }
+        if let Err(err) = self.comment.finish() {
+            return Err(JsonError::from(err).at(self.char_index));
+        }
+        return Ok(Prefix::Value(self.context.finish(self.pos, options).map_err(|err| err.at(self.char_index))?, self.pos.offset));
+    }
+    /**
+     * 已经解析的字节数
+     */
+    pub(crate) fn offset(&self) -> usize { return self.pos.offset; }
+}
+
+/**
+ * 以字节为单位解析json,选项不支持时退回到逐个字符解析
+ */
+pub(crate) fn parse_bytes(bytes: &[u8], options: &ParseOptions) -> Result<Json, JsonError> {
+    if bytes.is_empty() {
+        return Err("bytes参数长度为0".to_string().into());
+    }
+    options.check_total_bytes(bytes.len())?;
+    let str = match std::str::from_utf8(bytes) {
+        Ok(str) => str,
+        Err(err) => { return Err(std::format!("第{}个字节:不是合法的utf8编码", err.valid_up_to() + 1).into()); },
+    };
+    if !options.fast_path_compatible() {
+        return parse_str(str, options);
+    }
+    let mut parser = ByteParser::new(bytes, options);
+    return parser.parse_document();
+}
+
+#[cfg(test)]
+#[test]
+fn test_parser_options() {
+    // 严格模式
+    assert!(Parser::new().parse("[]").is_ok());
+    assert!(Parser::new().parse("{}").is_ok());
+    assert!(Parser::new().parse(" 1.5 ").is_ok());
+    assert!(Parser::new().parse("null x").is_err());
+    assert!(Parser::new().parse("[01]").is_err());
+    assert!(Parser::new().parse("[1,]").is_err());
+    assert!(Parser::new().parse("\"a\u{1}\"").is_err());
+    // 兼容模式
+    let legacy = Parser::with_options(ParseOptions::legacy());
+    assert!(legacy.parse("null x").is_ok());
+    assert!(legacy.parse("[01]").is_ok());
+    assert!(legacy.parse("[1,\t2]").is_err());
+    // 空白
+    let unicode = Parser::new().whitespace(WhitespaceSet { unicode: true, ..WhitespaceSet::json() });
+    assert!(unicode.parse("\u{feff}[1,\u{3000}2]").is_ok());
+    // 末尾逗号
+    let trailing = Parser::new().extensions(Extensions { trailing_commas: true, ..Extensions::default() });
+    assert!(trailing.parse("[1,2,]").is_ok());
+    assert!(trailing.parse("{\"a\":[1,],}").is_ok());
+    assert!(trailing.parse("[,]").is_err());
+    // 重复的key
+    let json = "{\"a\":1,\"a\":2}";
+    let first = Parser::new().duplicate_keys(DuplicateKeys::First).parse(json).unwrap();
+    assert_eq!(first.to_string(), "{\"a\":1}");
+    let last = Parser::new().parse(json).unwrap();
+    assert_eq!(last.to_string(), "{\"a\":2}");
+    assert!(Parser::new().duplicate_keys(DuplicateKeys::Error).parse(json).is_err());
+    assert!(Parser::new().duplicate_keys(DuplicateKeys::Error).parse_bytes(json.as_bytes()).is_err());
+    // number
+    assert!(Parser::new().number_mode(NumberMode::Lossless).parse("[12345678901234567890]").is_err());
+    assert!(Parser::new().parse("[12345678901234567890]").is_ok());
+}
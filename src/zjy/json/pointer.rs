@@ -0,0 +1,106 @@
+/**
+ * 文件名: "src/zjy/json/pointer.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::zjy::json::Json;
+
+impl Json {
+    /**
+     * 按照RFC 6901的JSON Pointer(比如 "/obj2/arr/3")查找json值
+     * 空字符串代表整个文档,找不到时返回None
+     */
+    pub fn pointer<'a>(&'a self, pointer: &str) -> Option<&'a Json> {
+        let mut current = self;
+        for token in split_pointer(pointer)? {
+            current = match current {
+                Json::Object(obj) => obj.get(&token)?,
+                Json::Array(arr) => arr.get(parse_index(&token)?)?,
+                _ => { return None; },
+            };
+        }
+        return Some(current);
+    }
+    /**
+     * 和pointer相同,但是返回可变借用
+     */
+    pub fn pointer_mut<'a>(&'a mut self, pointer: &str) -> Option<&'a mut Json> {
+        let mut current = self;
+        for token in split_pointer(pointer)? {
+            current = match current {
+                Json::Object(obj) => obj.get_mut(&token)?,
+                Json::Array(arr) => arr.get_mut(parse_index(&token)?)?,
+                _ => { return None; },
+            };
+        }
+        return Some(current);
+    }
+}
+
+/**
+ * 把一级key或下标转义之后加到pointer后面: '~'转义为"~0", '/'转义为"~1"
+ */
+pub fn push_token(pointer: &mut String, token: &str) {
+    pointer.push('/');
+    for c in token.chars() {
+        match c {
+            '~' => { pointer.push_str("~0"); },
+            '/' => { pointer.push_str("~1"); },
+            _ => { pointer.push(c); },
+        }
+    }
+}
+/**
+ * 生成父pointer加上一级key或下标之后的pointer
+ */
+pub fn join(parent: &str, token: &str) -> String {
+    let mut pointer = String::from(parent);
+    push_token(&mut pointer, token);
+    return pointer;
+}
+/**
+ * 把pointer拆分成转义之前的各级key,pointer不是以 '/' 开头(并且不为空)时返回None
+ */
+pub fn split_pointer(pointer: &str) -> Option<Vec<String>> {
+    if pointer.is_empty() {
+        return Some(Vec::new());
+    }
+    let rest = pointer.strip_prefix('/')?;
+    return Some(rest.split('/').map(|token| token.replace("~1", "/").replace("~0", "~")).collect());
+}
+/**
+ * 把pointer中的一级解析成数组下标,不允许前导0
+ */
+pub(crate) fn parse_index(token: &str) -> Option<usize> {
+    if token.is_empty() || (token.len() > 1 && token.starts_with('0')) || !token.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    return token.parse().ok();
+}
+
+#[cfg(test)]
+#[test]
+fn test_pointer() {
+    let json = Json::str_to_json(&std::fs::read_to_string("tests/testobject.json").unwrap()).unwrap();
+    assert_eq!(json.pointer("/obj2/arr/3/name").unwrap().to_string(), "\"j\u{1f9d1}\"");
+    assert_eq!(json.pointer("/obj2/arr/0").unwrap().to_string(), "114514");
+    assert!(json.pointer("/obj2/arr/01").is_none());
+    assert!(json.pointer("obj2").is_none());
+    assert!(json.pointer("").is_some());
+    assert_eq!(join("/a", "b/c~d"), "/a/b~1c~0d");
+    assert_eq!(split_pointer("/a/b~1c~0d").unwrap(), vec!["a", "b/c~d"]);
+}
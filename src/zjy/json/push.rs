@@ -0,0 +1,169 @@
+/**
+ * 文件名: "src/zjy/json/push.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::zjy::json::ast::comment::CommentContext;
+use crate::zjy::json::ast::root::RootContext;
+use crate::zjy::json::options::Strictness;
+use crate::zjy::json::span::Position;
+use crate::zjy::json::{Json, JsonError, ParseOptions, Parser};
+
+/**
+ * 增量(推送式)解析器: 输入可以分成任意多块通过feed推送,解析状态保存在各个上下文中,
+ * 不需要缓存整个输入,也不会重复解析;全部推送之后调用finish取出json值
+ * 块的边界可以在utf8字符的中间
+ */
+pub struct PushParser {
+    options: ParseOptions,
+    context: RootContext,
+    comment: CommentContext,
+    /// 上一块末尾不完整的utf8字符
+    partial: Vec<u8>,
+    /// 下一个字符的位置
+    pos: Position,
+    char_index: usize,
+}
+
+impl PushParser {
+    /**
+     * 使用指定的选项初始化解析器
+     */
+    pub fn new(options: ParseOptions) -> PushParser {
+        return PushParser { options, context: RootContext::new(), comment: CommentContext::new(), partial: Vec::new(), pos: Position::start(), char_index: 0 };
+    }
+    /**
+     * 是否还没有推送过空白和注释以外的内容
+     */
+    pub fn is_empty(&self) -> bool {
+        return self.context.is_start() && self.partial.is_empty() && self.comment.finish().is_ok();
+    }
+    /**
+     * 已经推送的字节数
+     */
+    pub fn offset(&self) -> usize { return self.pos.offset + self.partial.len(); }
+    /**
+     * 推送一块输入,出错之后需要调用reset才能继续使用
+     */
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<(), JsonError> {
+        self.options.check_total_bytes(self.offset() + bytes.len())?;
+        let joined;
+        let bytes = if self.partial.is_empty() {
+            bytes
+        } else {
+            let mut partial = std::mem::take(&mut self.partial);
+            partial.extend_from_slice(bytes);
+            joined = partial;
+            &joined[..]
+        };
+        let str = match std::str::from_utf8(bytes) {
+            Ok(str) => str,
+            Err(err) if err.error_len().is_none() => {
+                // 末尾的字符不完整,留到下一块
+                self.partial = bytes[err.valid_up_to()..].to_vec();
+                std::str::from_utf8(&bytes[..err.valid_up_to()]).unwrap_or_default()
+            },
+            Err(err) => { return Err(std::format!("第{}个字节:不是合法的utf8编码", self.pos.offset + err.valid_up_to() + 1).into()); },
+        };
+        for c in str.chars() {
+            // 兼容模式下解析出第一个json值后忽略后面的内容
+            if self.options.strictness == Strictness::Legacy && self.context.is_end() {
+                break;
+            }
+            self.char_index += 1;
+            let current = self.pos;
+            self.pos = self.pos.advance(c);
+            let c = if self.options.extensions.comments {
+                match self.comment.check_comment(c, &self.options) {
+                    Ok(Some(c)) => c,
+                    Ok(None) => { continue; },
+                    Err(err) => { return Err(JsonError::from(err).at(self.char_index)); },
+                }
+            } else {
+                c
+            };
+            self.context.check_root(c, current, &self.options).map_err(|err| err.at(self.char_index))?;
+        }
+        return Ok(());
+    }
+    /**
+     * 输入已经结束,取出解析完成的json值,之后解析器回到初始状态,可以解析下一个文档
+     */
+    pub fn finish(&mut self) -> Result<Json, JsonError> {
+        let result = self.finish_document();
+        self.reset();
+        return result;
+    }
+    /**
+     * 丢弃已经推送的内容,回到初始状态
+     */
+    pub fn reset(&mut self) {
+        self.context = RootContext::new();
+        self.comment = CommentContext::new();
+        self.partial.clear();
+        self.pos = Position::start();
+        self.char_index = 0;
+    }
+
+    fn finish_document(&mut self) -> Result<Json, JsonError> {
+        if !self.partial.is_empty() {
+            return Err(std::format!("第{}个字节:不是合法的utf8编码", self.pos.offset + 1).into());
+        }
+        if self.pos.offset == 0 {
+            return Err("str参数长度为0".to_string().into());
+        }
+        if let Err(err) = self.comment.finish() {
+            return Err(JsonError::from(err).at(self.char_index));
+        }
+        return self.context.finish(self.pos, &self.options).map_err(|err| err.at(self.char_index));
+    }
+}
+
+impl Parser {
+    /**
+     * 使用解析器的选项创建增量解析器
+     */
+    pub fn push_parser(&self) -> PushParser {
+        return PushParser::new(self.options().clone());
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_push_parser() {
+    let str = std::fs::read_to_string("tests/testobject.json").unwrap();
+    let expect = Json::str_to_json(&str).unwrap().to_json5_string();
+    // 任意大小的块,包括切断多字节字符的块
+    for size in [1, 2, 3, 7, 4096] {
+        let mut parser = Parser::new().push_parser();
+        for chunk in str.as_bytes().chunks(size) {
+            parser.feed(chunk).unwrap();
+        }
+        assert_eq!(parser.finish().unwrap().to_json5_string(), expect);
+        assert!(parser.is_empty());
+    }
+    let mut parser = Parser::new().push_parser();
+    parser.feed(b"[1, 2").unwrap();
+    assert!(parser.finish().is_err());
+    assert!(parser.feed(b"[1 2]").is_err());
+    parser.reset();
+    parser.feed(b" 12").unwrap();
+    parser.feed(b"3 ").unwrap();
+    assert_eq!(parser.finish().unwrap().to_json5_string(), "123");
+    parser.feed("\"中".as_bytes().split_at(2).0).unwrap();
+    assert!(parser.finish().is_err());
+}
@@ -0,0 +1,574 @@
+/**
+ * 文件名: "src/zjy/json/query/builtin.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::collections::HashMap;
+
+use crate::zjy::json::query::eval::{
+    collect, collect_paths, delete_paths, eval, get_path, iterate, recurse, recurse_paths, set_path, sorted_members,
+    split, to_json_text, to_text, truthy, type_name, binary, raise, Emit, EvalResult, Interrupt, PathValue, Vars,
+};
+use crate::zjy::json::query::parser::{AssignOp, BinaryOp, Expr};
+use crate::zjy::json::Json;
+
+/**
+ * 所有的内置函数和参数个数
+ */
+const BUILTINS: [(&str, usize); 68] = [
+    ("empty", 0), ("not", 0), ("length", 0), ("utf8bytelength", 0), ("keys", 0), ("keys_unsorted", 0), ("values", 0),
+    ("type", 0), ("add", 0), ("to_entries", 0), ("from_entries", 0), ("sort", 0), ("unique", 0), ("min", 0), ("max", 0),
+    ("reverse", 0), ("tostring", 0), ("tojson", 0), ("fromjson", 0), ("tonumber", 0), ("ascii_downcase", 0), ("ascii_upcase", 0),
+    ("first", 0), ("last", 0), ("flatten", 0), ("floor", 0), ("ceil", 0), ("round", 0), ("sqrt", 0), ("abs", 0),
+    ("paths", 0), ("any", 0), ("all", 0), ("recurse", 0), ("error", 0), ("explode", 0), ("implode", 0),
+    ("select", 1), ("map", 1), ("map_values", 1), ("has", 1), ("sort_by", 1), ("group_by", 1), ("unique_by", 1),
+    ("min_by", 1), ("max_by", 1), ("join", 1), ("split", 1), ("startswith", 1), ("endswith", 1), ("ltrimstr", 1),
+    ("rtrimstr", 1), ("contains", 1), ("range", 1), ("first", 1), ("last", 1), ("any", 1), ("all", 1), ("flatten", 1),
+    ("error", 1), ("with_entries", 1), ("del", 1), ("path", 1), ("recurse", 1), ("getpath", 1), ("limit", 2), ("range", 2), ("setpath", 2),
+];
+
+/**
+ * 是否有这个名字和参数个数的内置函数
+ */
+pub(crate) fn exists(name: &str, arity: usize) -> bool {
+    return BUILTINS.contains(&(name, arity));
+}
+
+fn error<T>(msg: String) -> EvalResult<T> {
+    return raise(Json::String(msg));
+}
+
+fn string(s: &str) -> Json {
+    return Json::String(s.to_string());
+}
+
+/**
+ * 对每个参数的每个结果调用f,多个结果时和jq一样产生多个输出
+ */
+fn each_arg(arg: &Expr, input: &Json, vars: &mut Vars, emit: &mut Emit, f: impl Fn(&Json) -> EvalResult<Json>) -> EvalResult<()> {
+    for value in collect(arg, input, vars)? {
+        emit(vars, f(&value)?)?;
+    }
+    return Ok(());
+}
+
+fn expect_array<'a>(name: &str, input: &'a Json) -> EvalResult<&'a Vec<Json>> {
+    match input {
+        Json::Array(arr) => { return Ok(arr); },
+        other => { return error(std::format!("{}需要array,不能是{}", name, type_name(other))); },
+    }
+}
+
+fn expect_string<'a>(name: &str, input: &'a Json) -> EvalResult<&'a str> {
+    match input {
+        Json::String(s) => { return Ok(s); },
+        other => { return error(std::format!("{}需要string,不能是{}", name, type_name(other))); },
+    }
+}
+
+fn expect_number(name: &str, input: &Json) -> EvalResult<f64> {
+    match input {
+        Json::Number(n) => { return Ok(*n); },
+        other => { return error(std::format!("{}需要number,不能是{}", name, type_name(other))); },
+    }
+}
+
+/**
+ * 调用内置函数,参数是延迟求值的表达式
+ */
+pub(crate) fn call(name: &str, args: &[Expr], input: &Json, vars: &mut Vars, emit: &mut Emit) -> EvalResult<()> {
+    match (name, args) {
+        ("empty", []) => {},
+        ("not", []) => { emit(vars, Json::Boolean(!truthy(input)))?; },
+        ("length", []) => {
+            let len = match input {
+                Json::Null => 0.0,
+                Json::Number(n) => n.abs(),
+                Json::String(s) => s.chars().count() as f64,
+                Json::Array(arr) => arr.len() as f64,
+                Json::Object(obj) => obj.len() as f64,
+                Json::Boolean(_) => { return error("boolean没有长度".to_string()); },
+            };
+            emit(vars, Json::Number(len))?;
+        },
+        ("utf8bytelength", []) => { emit(vars, Json::Number(expect_string(name, input)?.len() as f64))?; },
+        ("keys", []) | ("keys_unsorted", []) => {
+            match input {
+                Json::Object(obj) => { emit(vars, Json::Array(sorted_members(obj).into_iter().map(|(key, _)| string(key)).collect()))?; },
+                Json::Array(arr) => { emit(vars, Json::Array((0..arr.len()).map(|i| Json::Number(i as f64)).collect()))?; },
+                other => { return error(std::format!("{}没有key", type_name(other))); },
+            }
+        },
+        ("values", []) => {
+            if *input != Json::Null {
+                emit(vars, input.clone())?;
+            }
+        },
+        ("type", []) => { emit(vars, string(type_name(input)))?; },
+        ("add", []) => {
+            let items = if *input == Json::Null { Vec::new() } else { iterate(input)? };
+            let mut sum = Json::Null;
+            for item in &items {
+                sum = binary(BinaryOp::Add, &sum, item)?;
+            }
+            emit(vars, sum)?;
+        },
+        ("to_entries", []) => { emit(vars, to_entries(input)?)?; },
+        ("from_entries", []) => { emit(vars, from_entries(input)?)?; },
+        ("sort", []) => {
+            let mut arr = expect_array(name, input)?.clone();
+            arr.sort();
+            emit(vars, Json::Array(arr))?;
+        },
+        ("unique", []) => {
+            let mut arr = expect_array(name, input)?.clone();
+            arr.sort();
+            arr.dedup();
+            emit(vars, Json::Array(arr))?;
+        },
+        ("min", []) => { emit(vars, expect_array(name, input)?.iter().min().cloned().unwrap_or(Json::Null))?; },
+        ("max", []) => { emit(vars, expect_array(name, input)?.iter().max().cloned().unwrap_or(Json::Null))?; },
+        ("reverse", []) => {
+            match input {
+                Json::Null => { emit(vars, Json::Array(Vec::new()))?; },
+                Json::String(s) => { emit(vars, Json::String(s.chars().rev().collect()))?; },
+                _ => { emit(vars, Json::Array(expect_array(name, input)?.iter().rev().cloned().collect()))?; },
+            }
+        },
+        ("tostring", []) => { emit(vars, Json::String(to_text(input)))?; },
+        ("tojson", []) => { emit(vars, Json::String(to_json_text(input)))?; },
+        ("fromjson", []) => {
+            let text = expect_string(name, input)?;
+            emit(vars, Json::str_to_json(text).map_err(|err| Interrupt::Error(Json::String(std::format!("{}不是合法的json:{}", to_json_text(input), err))))?)?;
+        },
+        ("tonumber", []) => {
+            match input {
+                Json::Number(_) => { emit(vars, input.clone())?; },
+                Json::String(s) => {
+                    match Json::str_to_json(s.trim()) {
+                        Ok(Json::Number(n)) => { emit(vars, Json::Number(n))?; },
+                        _ => { return error(std::format!("{}不能转换成number", to_json_text(input))); },
+                    }
+                },
+                other => { return error(std::format!("{}不能转换成number", type_name(other))); },
+            }
+        },
+        ("ascii_downcase", []) => { emit(vars, Json::String(expect_string(name, input)?.to_ascii_lowercase()))?; },
+        ("ascii_upcase", []) => { emit(vars, Json::String(expect_string(name, input)?.to_ascii_uppercase()))?; },
+        ("first", []) => { emit(vars, expect_array(name, input)?.first().cloned().unwrap_or(Json::Null))?; },
+        ("last", []) => { emit(vars, expect_array(name, input)?.last().cloned().unwrap_or(Json::Null))?; },
+        ("flatten", []) => { emit(vars, Json::Array(flatten(expect_array(name, input)?, f64::INFINITY)))?; },
+        ("floor", []) | ("ceil", []) | ("round", []) | ("sqrt", []) | ("abs", []) => {
+            let n = expect_number(name, input)?;
+            let result = match name {
+                "floor" => n.floor(),
+                "ceil" => n.ceil(),
+                "round" => n.round(),
+                "sqrt" => n.sqrt(),
+                _ => n.abs(),
+            };
+            emit(vars, Json::Number(result))?;
+        },
+        ("paths", []) => {
+            let mut paths = Vec::new();
+            recurse_paths(&[], input, &mut paths);
+            for (path, _) in paths.into_iter().skip(1) {
+                emit(vars, Json::Array(path))?;
+            }
+        },
+        ("any", []) => { emit(vars, Json::Boolean(expect_array(name, input)?.iter().any(truthy)))?; },
+        ("all", []) => { emit(vars, Json::Boolean(expect_array(name, input)?.iter().all(truthy)))?; },
+        ("recurse", []) => { recurse(input, vars, emit)?; },
+        ("error", []) => { return raise(input.clone()); },
+        ("explode", []) => { emit(vars, Json::Array(expect_string(name, input)?.chars().map(|c| Json::Number(c as u32 as f64)).collect()))?; },
+        ("implode", []) => {
+            let mut s = String::new();
+            for item in expect_array(name, input)? {
+                let code = expect_number(name, item)?;
+                s.push(char::from_u32(code as u32).ok_or_else(|| Interrupt::Error(Json::String(std::format!("{}不是合法的码点", code))))?);
+            }
+            emit(vars, Json::String(s))?;
+        },
+
+        ("select", [f]) => {
+            for cond in collect(f, input, vars)? {
+                if truthy(&cond) {
+                    emit(vars, input.clone())?;
+                }
+            }
+        },
+        ("map", [f]) => {
+            let mut mapped = Vec::new();
+            for item in iterate(input)? {
+                mapped.extend(collect(f, &item, vars)?);
+            }
+            emit(vars, Json::Array(mapped))?;
+        },
+        ("map_values", [f]) => {
+            let update = Expr::Assign(AssignOp::Update, Box::new(Expr::Iterate(Box::new(Expr::Identity))), Box::new(f.clone()));
+            eval(&update, input, vars, emit)?;
+        },
+        ("has", [key]) => {
+            each_arg(key, input, vars, emit, |key| {
+                match (input, key) {
+                    (Json::Object(obj), Json::String(key)) => { return Ok(Json::Boolean(obj.contains_key(key))); },
+                    (Json::Array(arr), Json::Number(n)) => { return Ok(Json::Boolean(*n >= 0.0 && (*n as usize) < arr.len())); },
+                    _ => { return error(std::format!("不能检查{}中是否有{}类型的key", type_name(input), type_name(key))); },
+                }
+            })?;
+        },
+        ("sort_by", [f]) | ("group_by", [f]) | ("unique_by", [f]) | ("min_by", [f]) | ("max_by", [f]) => {
+            let mut keyed = Vec::new();
+            for item in expect_array(name, input)? {
+                keyed.push((Json::Array(collect(f, item, vars)?), item.clone()));
+            }
+            // 稳定排序,相同key的元素保持原来的顺序
+            keyed.sort_by(|a, b| a.0.cmp(&b.0));
+            match name {
+                "sort_by" => { emit(vars, Json::Array(keyed.into_iter().map(|(_, item)| item).collect()))?; },
+                "min_by" => { emit(vars, keyed.into_iter().next().map(|(_, item)| item).unwrap_or(Json::Null))?; },
+                "max_by" => {
+                    // 相同的key取最后一个,和jq相同
+                    emit(vars, keyed.into_iter().last().map(|(_, item)| item).unwrap_or(Json::Null))?;
+                },
+                _ => {
+                    let mut groups: Vec<(Json, Vec<Json>)> = Vec::new();
+                    for (key, item) in keyed {
+                        match groups.last_mut() {
+                            Some((last, group)) if *last == key => { group.push(item); },
+                            _ => { groups.push((key, vec![item])); },
+                        }
+                    }
+                    if name == "group_by" {
+                        emit(vars, Json::Array(groups.into_iter().map(|(_, group)| Json::Array(group)).collect()))?;
+                    } else {
+                        emit(vars, Json::Array(groups.into_iter().filter_map(|(_, group)| group.into_iter().next()).collect()))?;
+                    }
+                },
+            }
+        },
+        ("join", [separator]) => {
+            let items = expect_array(name, input)?;
+            each_arg(separator, input, vars, emit, |separator| {
+                let separator = expect_string(name, separator)?;
+                let mut parts = Vec::new();
+                for item in items {
+                    match item {
+                        Json::Null => { parts.push(String::new()); },
+                        Json::Object(_) | Json::Array(_) => { return error(std::format!("join不能连接{}", type_name(item))); },
+                        _ => { parts.push(to_text(item)); },
+                    }
+                }
+                return Ok(Json::String(parts.join(separator)));
+            })?;
+        },
+        ("split", [separator]) => {
+            let s = expect_string(name, input)?;
+            each_arg(separator, input, vars, emit, |separator| Ok(Json::Array(split(s, expect_string(name, separator)?))))?;
+        },
+        ("startswith", [arg]) | ("endswith", [arg]) | ("ltrimstr", [arg]) | ("rtrimstr", [arg]) => {
+            each_arg(arg, input, vars, emit, |arg| {
+                let (s, arg) = match (input, arg) {
+                    (Json::String(s), Json::String(arg)) => (s, arg),
+                    _ if name.ends_with("trimstr") => { return Ok(input.clone()); },
+                    _ => { return error(std::format!("{}需要string", name)); },
+                };
+                let result = match name {
+                    "startswith" => Json::Boolean(s.starts_with(arg.as_str())),
+                    "endswith" => Json::Boolean(s.ends_with(arg.as_str())),
+                    "ltrimstr" => string(s.strip_prefix(arg.as_str()).unwrap_or(s)),
+                    _ => string(s.strip_suffix(arg.as_str()).unwrap_or(s)),
+                };
+                return Ok(result);
+            })?;
+        },
+        ("contains", [arg]) => { each_arg(arg, input, vars, emit, |arg| Ok(Json::Boolean(contains(input, arg)?)))?; },
+        ("range", [to]) => {
+            for to in collect(to, input, vars)? {
+                range(0.0, expect_number(name, &to)?, vars, emit)?;
+            }
+        },
+        ("range", [from, to]) => {
+            let tos = collect(to, input, vars)?;
+            for from in collect(from, input, vars)? {
+                for to in &tos {
+                    range(expect_number(name, &from)?, expect_number(name, to)?, vars, emit)?;
+                }
+            }
+        },
+        ("first", [f]) => {
+            // 得到第一个结果之后立即停止对f求值,后面的结果和错误都不需要
+            let mut first = None;
+            let result = eval(f, input, vars, &mut |_, value| {
+                first = Some(value);
+                return Err(Interrupt::Stop);
+            });
+            if let Err(Interrupt::Error(err)) = result {
+                return raise(err);
+            }
+            if let Some(value) = first {
+                emit(vars, value)?;
+            }
+        },
+        ("last", [f]) => {
+            if let Some(value) = collect(f, input, vars)?.pop() {
+                emit(vars, value)?;
+            }
+        },
+        ("limit", [n, f]) => {
+            for n in collect(n, input, vars)? {
+                let n = expect_number(name, &n)?;
+                if n <= 0.0 {
+                    continue;
+                }
+                // 只保存前n个结果,够了之后停止对f求值;出错时先输出出错之前的结果
+                let mut values = Vec::new();
+                let result = eval(f, input, vars, &mut |_, value| {
+                    values.push(value);
+                    return if values.len() as f64 >= n { Err(Interrupt::Stop) } else { Ok(()) };
+                });
+                for value in values {
+                    emit(vars, value)?;
+                }
+                if let Err(Interrupt::Error(err)) = result {
+                    return raise(err);
+                }
+            }
+        },
+        ("any", [f]) | ("all", [f]) => {
+            let mut results = Vec::new();
+            for item in expect_array(name, input)? {
+                results.push(collect(f, item, vars)?.iter().any(truthy));
+            }
+            let result = if name == "any" { results.iter().any(|b| *b) } else { results.iter().all(|b| *b) };
+            emit(vars, Json::Boolean(result))?;
+        },
+        ("flatten", [depth]) => {
+            let items = expect_array(name, input)?;
+            each_arg(depth, input, vars, emit, |depth| {
+                let depth = expect_number(name, depth)?;
+                if depth < 0.0 {
+                    return error("flatten的深度不能是负数".to_string());
+                }
+                return Ok(Json::Array(flatten(items, depth)));
+            })?;
+        },
+        ("error", [msg]) => {
+            if let Some(msg) = collect(msg, input, vars)?.into_iter().next() {
+                return raise(msg);
+            }
+        },
+        ("with_entries", [f]) => {
+            let mut mapped = Vec::new();
+            for entry in iterate(&to_entries(input)?)? {
+                mapped.extend(collect(f, &entry, vars)?);
+            }
+            emit(vars, from_entries(&Json::Array(mapped))?)?;
+        },
+        ("del", [f]) => {
+            let paths = collect_paths(f, &[], input, vars)?;
+            let mut result = input.clone();
+            delete_paths(&mut result, paths.into_iter().map(|(path, _)| path).collect())?;
+            emit(vars, result)?;
+        },
+        ("path", [f]) => {
+            for (path, _) in collect_paths(f, &[], input, vars)? {
+                emit(vars, Json::Array(path))?;
+            }
+        },
+        ("recurse", [f]) => { recurse_with(f, input, vars, emit, 0)?; },
+        ("getpath", [path]) => {
+            for path in collect(path, input, vars)? {
+                emit(vars, get_path(input, expect_array(name, &path)?)?)?;
+            }
+        },
+        ("setpath", [path, value]) => {
+            let values = collect(value, input, vars)?;
+            for path in collect(path, input, vars)? {
+                for value in &values {
+                    let mut result = input.clone();
+                    set_path(&mut result, expect_array(name, &path)?, value.clone())?;
+                    emit(vars, result)?;
+                }
+            }
+        },
+        _ => { return error(std::format!("没有函数{}/{}", name, args.len())); },
+    }
+    return Ok(());
+}
+
+/**
+ * 作为路径表达式调用内置函数,支持select,empty,recurse,first,last和getpath
+ */
+pub(crate) fn call_paths(name: &str, args: &[Expr], path: &[Json], value: &Json, vars: &mut Vars, out: &mut Vec<PathValue>) -> EvalResult<()> {
+    match (name, args) {
+        ("empty", []) => {},
+        ("select", [f]) => {
+            for cond in collect(f, value, vars)? {
+                if truthy(&cond) {
+                    out.push((path.to_vec(), value.clone()));
+                }
+            }
+        },
+        ("recurse", []) => { recurse_paths(path, value, out); },
+        ("first", [f]) => { out.extend(collect_paths(f, path, value, vars)?.into_iter().next()); },
+        ("last", [f]) => { out.extend(collect_paths(f, path, value, vars)?.pop()); },
+        ("getpath", [p]) => {
+            for p in collect(p, value, vars)? {
+                let p = expect_array(name, &p)?;
+                let mut full = path.to_vec();
+                full.extend(p.iter().cloned());
+                out.push((full, get_path(value, p)?));
+            }
+        },
+        _ => { return error(std::format!("{}/{}不是路径表达式,不能用于赋值,del和path", name, args.len())); },
+    }
+    return Ok(());
+}
+
+/// recurse(f)的最大深度,防止f没有终止时栈溢出
+const MAX_RECURSE_DEPTH: usize = 10000;
+
+fn recurse_with(f: &Expr, input: &Json, vars: &mut Vars, emit: &mut Emit, depth: usize) -> EvalResult<()> {
+    if depth > MAX_RECURSE_DEPTH {
+        return error(std::format!("recurse的深度超过{}", MAX_RECURSE_DEPTH));
+    }
+    emit(vars, input.clone())?;
+    for next in collect(f, input, vars)? {
+        recurse_with(f, &next, vars, emit, depth + 1)?;
+    }
+    return Ok(());
+}
+
+fn range(from: f64, to: f64, vars: &mut Vars, emit: &mut Emit) -> EvalResult<()> {
+    let mut n = from;
+    while n < to {
+        emit(vars, Json::Number(n))?;
+        n += 1.0;
+    }
+    return Ok(());
+}
+
+fn flatten(items: &[Json], depth: f64) -> Vec<Json> {
+    let mut result = Vec::new();
+    for item in items {
+        match item {
+            Json::Array(inner) if depth > 0.0 => { result.extend(flatten(inner, depth - 1.0)); },
+            _ => { result.push(item.clone()); },
+        }
+    }
+    return result;
+}
+
+/**
+ * a是否包含b: string包含子串,array中的每个元素都被a的某个元素包含,object中的每个成员都被a的同名成员包含
+ */
+fn contains(a: &Json, b: &Json) -> EvalResult<bool> {
+    match (a, b) {
+        (Json::String(a), Json::String(b)) => { return Ok(a.contains(b.as_str())); },
+        (Json::Array(a), Json::Array(b)) => {
+            for item in b {
+                let mut found = false;
+                for candidate in a {
+                    if contains(candidate, item).unwrap_or(false) {
+                        found = true;
+                        break;
+                    }
+                }
+                if !found {
+                    return Ok(false);
+                }
+            }
+            return Ok(true);
+        },
+        (Json::Object(a), Json::Object(b)) => {
+            for (key, item) in b {
+                match a.get(key) {
+                    Some(candidate) if contains(candidate, item)? => {},
+                    _ => { return Ok(false); },
+                }
+            }
+            return Ok(true);
+        },
+        _ if type_name(a) == type_name(b) => { return Ok(a == b); },
+        _ => { return error(std::format!("{}不能包含{}", type_name(a), type_name(b))); },
+    }
+}
+
+fn to_entries(input: &Json) -> EvalResult<Json> {
+    match input {
+        Json::Object(obj) => {
+            let entries = sorted_members(obj).into_iter().map(|(key, value)| {
+                let mut entry = HashMap::new();
+                entry.insert("key".to_string(), string(key));
+                entry.insert("value".to_string(), value.clone());
+                return Json::Object(entry);
+            });
+            return Ok(Json::Array(entries.collect()));
+        },
+        other => { return error(std::format!("to_entries需要object,不能是{}", type_name(other))); },
+    }
+}
+
+/**
+ * 把[{key, value}]转换成object,key也可以是k,name,Name,Key或K,value也可以是v,Value或V
+ */
+fn from_entries(input: &Json) -> EvalResult<Json> {
+    let mut obj = HashMap::new();
+    for entry in expect_array("from_entries", input)? {
+        let entry = match entry {
+            Json::Object(entry) => entry,
+            other => { return error(std::format!("from_entries的元素需要object,不能是{}", type_name(other))); },
+        };
+        let find = |names: &[&str]| names.iter().find_map(|name| entry.get(*name).filter(|value| truthy(value)));
+        let key = match find(&["key", "k", "name", "Name", "Key", "K"]) {
+            Some(Json::String(key)) => key.clone(),
+            Some(key @ (Json::Number(_) | Json::Boolean(_))) => to_text(key),
+            _ => { return error("from_entries的元素需要string类型的key".to_string()); },
+        };
+        let value = ["value", "v", "Value", "V"].iter().find_map(|name| entry.get(*name)).cloned().unwrap_or(Json::Null);
+        obj.insert(key, value);
+    }
+    return Ok(Json::Object(obj));
+}
+
+#[cfg(test)]
+#[test]
+fn test_query_builtin() {
+    let run = |name: &str, args: &[Expr], input: &str| -> Vec<String> {
+        let mut out = Vec::new();
+        call(name, args, &Json::str_to_json(input).unwrap(), &mut Vec::new(), &mut |_, value| {
+            out.push(to_json_text(&value));
+            return Ok(());
+        }).unwrap();
+        return out;
+    };
+    let lit = |json: &str| Expr::Literal(Json::str_to_json(json).unwrap());
+    assert_eq!(run("length", &[], "\"中文\""), ["2"]);
+    assert_eq!(run("keys", &[], r#"{"b":1,"a":2}"#), [r#"["a","b"]"#]);
+    assert_eq!(run("add", &[], r#"[[1],[2,3]]"#), ["[1,2,3]"]);
+    assert_eq!(run("sort_by", &[Expr::Field(Box::new(Expr::Identity), "n".to_string())], r#"[{"n":2},{"n":1}]"#), [r#"[{"n":1},{"n":2}]"#]);
+    assert_eq!(run("group_by", &[Expr::Identity], "[1,2,1]"), ["[[1,1],[2]]"]);
+    assert_eq!(run("join", &[lit("\"-\"")], r#"["a",1,null]"#), [r#""a-1-""#]);
+    assert_eq!(run("contains", &[lit(r#"{"a":["x"]}"#)], r#"{"a":["xyz"],"b":1}"#), ["true"]);
+    assert_eq!(run("range", &[lit("1"), lit("3")], "null"), ["1", "2"]);
+    assert_eq!(run("from_entries", &[], r#"[{"k":"a","v":1},{"name":2}]"#), [r#"{"2":null,"a":1}"#]);
+    assert_eq!(run("flatten", &[lit("1")], "[1,[2,[3]]]"), ["[1,2,[3]]"]);
+    assert_eq!(run("tonumber", &[], "\" 1.5\""), ["1.5"]);
+    assert!(call("tonumber", &[], &string("abc"), &mut Vec::new(), &mut |_, _| Ok(())).is_err());
+    assert!(exists("range", 2) && exists("map", 1) && !exists("map", 0));
+}
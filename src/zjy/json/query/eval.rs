@@ -0,0 +1,831 @@
+/**
+ * 文件名: "src/zjy/json/query/eval.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::collections::HashMap;
+
+use crate::zjy::json::query::builtin;
+use crate::zjy::json::query::parser::{AssignOp, BinaryOp, Expr};
+use crate::zjy::json::{Json, MergeOptions, NonFinite, SerializeOptions};
+
+/**
+ * 中断求值的原因
+ * Error和jq相同,错误本身是一个json值(error函数可以抛出任意值,try ... catch可以取出);
+ * Stop代表first,limit等已经得到了需要的结果,要求停止产生更多的结果,不能被try捕获
+ */
+#[derive(Debug, PartialEq)]
+pub(crate) enum Interrupt {
+    Error(Json),
+    Stop,
+}
+
+pub(crate) type EvalResult<T> = Result<T, Interrupt>;
+
+/**
+ * 接收结果的回调,每产生一个结果调用一次;返回Err时停止求值
+ */
+pub(crate) type Emit<'a> = dyn FnMut(&mut Vars, Json) -> EvalResult<()> + 'a;
+
+/**
+ * 变量表,内层的绑定在后面
+ */
+pub(crate) type Vars = Vec<(String, Json)>;
+
+/**
+ * 一条路径和路径上的值,路径的每一级是string(object的key)或number(array的下标)
+ */
+pub(crate) type PathValue = (Vec<Json>, Json);
+
+/**
+ * 抛出一个json值作为错误
+ */
+pub(crate) fn raise<T>(value: Json) -> EvalResult<T> {
+    return Err(Interrupt::Error(value));
+}
+
+fn error<T>(msg: String) -> EvalResult<T> {
+    return raise(Json::String(msg));
+}
+
+/**
+ * json值的类型名
+ */
+pub(crate) fn type_name(json: &Json) -> &'static str {
+    match json {
+        Json::Null => { return "null"; },
+        Json::Boolean(_) => { return "boolean"; },
+        Json::Number(_) => { return "number"; },
+        Json::String(_) => { return "string"; },
+        Json::Array(_) => { return "array"; },
+        Json::Object(_) => { return "object"; },
+    }
+}
+
+/**
+ * null和false以外的值都是真
+ */
+pub(crate) fn truthy(json: &Json) -> bool {
+    return !matches!(json, Json::Null | Json::Boolean(false));
+}
+
+/**
+ * string原样返回,其他值输出成紧凑的json
+ */
+pub(crate) fn to_text(json: &Json) -> String {
+    match json {
+        Json::String(s) => { return s.clone(); },
+        _ => { return to_json_text(json); },
+    }
+}
+
+/**
+ * 输出成紧凑的json,key按照字典序,NaN和Infinity输出为null
+ */
+pub(crate) fn to_json_text(json: &Json) -> String {
+    return json.to_string_with_options(&SerializeOptions { non_finite: NonFinite::Null, ..SerializeOptions::compact() }).unwrap_or_default();
+}
+
+/**
+ * object的成员,按照key的字典序排列,保证输出的顺序是确定的
+ */
+pub(crate) fn sorted_members(obj: &HashMap<String, Json>) -> Vec<(&String, &Json)> {
+    let mut members: Vec<(&String, &Json)> = obj.iter().collect();
+    members.sort_by(|a, b| a.0.cmp(b.0));
+    return members;
+}
+
+/**
+ * 求出expr的所有结果
+ */
+pub(crate) fn collect(expr: &Expr, input: &Json, vars: &mut Vars) -> EvalResult<Vec<Json>> {
+    let mut out = Vec::new();
+    eval(expr, input, vars, &mut |_, value| {
+        out.push(value);
+        return Ok(());
+    })?;
+    return Ok(out);
+}
+
+/**
+ * 对input求值,每个结果依次交给emit,不需要先求出所有的结果,emit返回Err时立即停止
+ */
+pub(crate) fn eval(expr: &Expr, input: &Json, vars: &mut Vars, emit: &mut Emit) -> EvalResult<()> {
+    // 嵌套的表达式会递归调用eval,复杂的分支放在单独的函数中,减少每一层占用的栈空间
+    match expr {
+        Expr::Identity => { emit(vars, input.clone())?; },
+        Expr::Recurse => { recurse(input, vars, emit)?; },
+        Expr::Literal(value) => { emit(vars, value.clone())?; },
+        Expr::Format(parts) => { eval_format(parts, input, vars, emit)?; },
+        Expr::Var(name) => {
+            let value = match vars.iter().rev().find(|(var, _)| var == name) {
+                Some((_, value)) => value.clone(),
+                None => { return error(std::format!("变量${}没有定义", name)); },
+            };
+            emit(vars, value)?;
+        },
+        Expr::Field(target, name) => {
+            let key = Json::String(name.clone());
+            eval(target, input, vars, &mut |vars, value| emit(vars, index(&value, &key)?))?;
+        },
+        Expr::Index(target, key) => { eval_index(target, key, input, vars, emit)?; },
+        Expr::Slice(target, from, to) => { eval_slice(target, from.as_deref(), to.as_deref(), input, vars, emit)?; },
+        Expr::Iterate(target) => {
+            eval(target, input, vars, &mut |vars, value| {
+                for item in iterate(&value)? {
+                    emit(vars, item)?;
+                }
+                return Ok(());
+            })?;
+        },
+        Expr::Try(body, handler) => { eval_try(body, handler.as_deref(), input, vars, emit)?; },
+        Expr::Array(body) => {
+            let values = match body { Some(body) => collect(body, input, vars)?, None => Vec::new() };
+            emit(vars, Json::Array(values))?;
+        },
+        Expr::Object(entries) => { eval_object(entries, input, vars, emit)?; },
+        Expr::Neg(body) => {
+            eval(body, input, vars, &mut |vars, value| {
+                match value {
+                    Json::Number(n) => { return emit(vars, Json::Number(-n)); },
+                    other => { return error(std::format!("{}不能取负数", type_name(&other))); },
+                }
+            })?;
+        },
+        Expr::Binary(op, lhs, rhs) => { eval_binary(*op, lhs, rhs, input, vars, emit)?; },
+        Expr::And(lhs, rhs) => { eval_logic(true, lhs, rhs, input, vars, emit)?; },
+        Expr::Or(lhs, rhs) => { eval_logic(false, lhs, rhs, input, vars, emit)?; },
+        Expr::Alternative(lhs, rhs) => { eval_alternative(lhs, rhs, input, vars, emit)?; },
+        Expr::Pipe(lhs, rhs) => { eval(lhs, input, vars, &mut |vars, value| eval(rhs, &value, vars, emit))?; },
+        Expr::Comma(lhs, rhs) => {
+            eval(lhs, input, vars, emit)?;
+            eval(rhs, input, vars, emit)?;
+        },
+        Expr::Bind(source, name, body) => {
+            eval(source, input, vars, &mut |vars, value| {
+                vars.push((name.clone(), value));
+                let result = eval(body, input, vars, emit);
+                vars.pop();
+                return result;
+            })?;
+        },
+        Expr::Reduce(source, name, init, update) => { eval_reduce(source, name, init, update, input, vars, emit)?; },
+        Expr::Foreach(source, name, init, update, extract) => {
+            for mut acc in collect(init, input, vars)? {
+                eval(source, input, vars, &mut |vars, item| {
+                    vars.push((name.clone(), item));
+                    let result = foreach_step(update, extract.as_deref(), &mut acc, vars, emit);
+                    vars.pop();
+                    return result;
+                })?;
+            }
+        },
+        Expr::If(branches, otherwise) => { eval_if(branches, otherwise.as_deref(), input, vars, emit)?; },
+        Expr::Assign(op, lhs, rhs) => { assign(*op, lhs, rhs, input, vars, emit)?; },
+        Expr::Call(name, args) => { builtin::call(name, args, input, vars, emit)?; },
+    }
+    return Ok(());
+}
+
+/**
+ * string插值: 每个部分的所有结果的组合
+ */
+fn eval_format(parts: &[Expr], input: &Json, vars: &mut Vars, emit: &mut Emit) -> EvalResult<()> {
+    let mut texts = vec![String::new()];
+    for part in parts {
+        let values = collect(part, input, vars)?;
+        texts = texts.iter().flat_map(|prefix| values.iter().map(move |value| std::format!("{}{}", prefix, to_text(value)))).collect();
+    }
+    for text in texts {
+        emit(vars, Json::String(text))?;
+    }
+    return Ok(());
+}
+
+fn eval_index(target: &Expr, key: &Expr, input: &Json, vars: &mut Vars, emit: &mut Emit) -> EvalResult<()> {
+    let keys = collect(key, input, vars)?;
+    return eval(target, input, vars, &mut |vars, value| {
+        for key in &keys {
+            emit(vars, index(&value, key)?)?;
+        }
+        return Ok(());
+    });
+}
+
+fn eval_slice(target: &Expr, from: Option<&Expr>, to: Option<&Expr>, input: &Json, vars: &mut Vars, emit: &mut Emit) -> EvalResult<()> {
+    let froms = match from { Some(from) => collect(from, input, vars)?, None => vec![Json::Null] };
+    let tos = match to { Some(to) => collect(to, input, vars)?, None => vec![Json::Null] };
+    return eval(target, input, vars, &mut |vars, value| {
+        for to in &tos {
+            for from in &froms {
+                emit(vars, slice(&value, from, to)?)?;
+            }
+        }
+        return Ok(());
+    });
+}
+
+fn eval_try(body: &Expr, handler: Option<&Expr>, input: &Json, vars: &mut Vars, emit: &mut Emit) -> EvalResult<()> {
+    // emit(后面的表达式)产生的错误不是body的错误,不能被捕获,先保存起来
+    let mut downstream = None;
+    let result = eval(body, input, vars, &mut |vars, value| {
+        return emit(vars, value).map_err(|err| {
+            downstream = Some(err);
+            return Interrupt::Stop;
+        });
+    });
+    if let Some(err) = downstream {
+        return Err(err);
+    }
+    match result {
+        Err(Interrupt::Error(err)) => {
+            if let Some(handler) = handler {
+                eval(handler, &err, vars, emit)?;
+            }
+        },
+        other => { other?; },
+    }
+    return Ok(());
+}
+
+fn eval_object(entries: &[(Expr, Expr)], input: &Json, vars: &mut Vars, emit: &mut Emit) -> EvalResult<()> {
+    let mut objects = vec![HashMap::new()];
+    for (key, value) in entries {
+        let keys = collect(key, input, vars)?;
+        let values = collect(value, input, vars)?;
+        let mut next = Vec::new();
+        for obj in &objects {
+            for key in &keys {
+                let key = match key {
+                    Json::String(key) => key,
+                    other => { return error(std::format!("object的key必须是string,不能是{}", type_name(other))); },
+                };
+                for value in &values {
+                    let mut obj = obj.clone();
+                    obj.insert(key.clone(), value.clone());
+                    next.push(obj);
+                }
+            }
+        }
+        objects = next;
+    }
+    for obj in objects {
+        emit(vars, Json::Object(obj))?;
+    }
+    return Ok(());
+}
+
+fn eval_binary(op: BinaryOp, lhs: &Expr, rhs: &Expr, input: &Json, vars: &mut Vars, emit: &mut Emit) -> EvalResult<()> {
+    let lefts = collect(lhs, input, vars)?;
+    return eval(rhs, input, vars, &mut |vars, right| {
+        for left in &lefts {
+            emit(vars, binary(op, left, &right)?)?;
+        }
+        return Ok(());
+    });
+}
+
+/**
+ * and(is_and为true)和or,lhs可以决定结果时不对rhs求值
+ */
+fn eval_logic(is_and: bool, lhs: &Expr, rhs: &Expr, input: &Json, vars: &mut Vars, emit: &mut Emit) -> EvalResult<()> {
+    return eval(lhs, input, vars, &mut |vars, left| {
+        if truthy(&left) != is_and {
+            return emit(vars, Json::Boolean(!is_and));
+        }
+        return eval(rhs, input, vars, &mut |vars, right| emit(vars, Json::Boolean(truthy(&right))));
+    });
+}
+
+fn eval_alternative(lhs: &Expr, rhs: &Expr, input: &Json, vars: &mut Vars, emit: &mut Emit) -> EvalResult<()> {
+    // lhs的错误被忽略
+    let mut values = Vec::new();
+    let _ = eval(lhs, input, vars, &mut |_, value| {
+        if truthy(&value) {
+            values.push(value);
+        }
+        return Ok(());
+    });
+    if values.is_empty() {
+        return eval(rhs, input, vars, emit);
+    }
+    for value in values {
+        emit(vars, value)?;
+    }
+    return Ok(());
+}
+
+fn eval_reduce(source: &Expr, name: &str, init: &Expr, update: &Expr, input: &Json, vars: &mut Vars, emit: &mut Emit) -> EvalResult<()> {
+    for mut acc in collect(init, input, vars)? {
+        eval(source, input, vars, &mut |vars, item| {
+            vars.push((name.to_string(), item));
+            let result = collect(update, &acc, vars);
+            vars.pop();
+            acc = result?.pop().unwrap_or(Json::Null);
+            return Ok(());
+        })?;
+        emit(vars, acc)?;
+    }
+    return Ok(());
+}
+
+/**
+ * foreach的一步: update的每个结果依次成为新的状态,并且输出extract的结果
+ */
+fn foreach_step(update: &Expr, extract: Option<&Expr>, acc: &mut Json, vars: &mut Vars, emit: &mut Emit) -> EvalResult<()> {
+    for state in collect(update, acc, vars)? {
+        match extract {
+            Some(extract) => { eval(extract, &state, vars, emit)?; },
+            None => { emit(vars, state.clone())?; },
+        }
+        *acc = state;
+    }
+    return Ok(());
+}
+
+fn eval_if(branches: &[(Expr, Expr)], otherwise: Option<&Expr>, input: &Json, vars: &mut Vars, emit: &mut Emit) -> EvalResult<()> {
+    let (cond, body) = match branches.first() {
+        Some(branch) => branch,
+        None => {
+            match otherwise {
+                Some(otherwise) => { return eval(otherwise, input, vars, emit); },
+                None => { return emit(vars, input.clone()); },
+            }
+        },
+    };
+    return eval(cond, input, vars, &mut |vars, value| {
+        if truthy(&value) {
+            return eval(body, input, vars, emit);
+        }
+        return eval_if(&branches[1..], otherwise, input, vars, emit);
+    });
+}
+
+/**
+ * 先序输出value和所有的子孙
+ */
+pub(crate) fn recurse(value: &Json, vars: &mut Vars, emit: &mut Emit) -> EvalResult<()> {
+    emit(vars, value.clone())?;
+    match value {
+        Json::Array(arr) => {
+            for item in arr {
+                recurse(item, vars, emit)?;
+            }
+        },
+        Json::Object(obj) => {
+            for (_, item) in sorted_members(obj) {
+                recurse(item, vars, emit)?;
+            }
+        },
+        _ => {},
+    }
+    return Ok(());
+}
+
+/**
+ * .[key]: object用string索引,array用number索引(负数从后往前),null的任何成员都是null
+ */
+pub(crate) fn index(value: &Json, key: &Json) -> EvalResult<Json> {
+    match (value, key) {
+        (Json::Null, Json::String(_) | Json::Number(_)) => { return Ok(Json::Null); },
+        (Json::Object(obj), Json::String(key)) => { return Ok(obj.get(key).cloned().unwrap_or(Json::Null)); },
+        (Json::Array(arr), Json::Number(n)) => {
+            return Ok(array_index(arr.len(), *n).and_then(|i| arr.get(i)).cloned().unwrap_or(Json::Null));
+        },
+        _ => { return error(std::format!("不能用{}索引{}", type_name(key), type_name(value))); },
+    }
+}
+
+/// 赋值时array最多可以扩展到的长度,避免.[1e10] = 1这样的查询耗尽内存
+const MAX_ARRAY_LEN: usize = 1 << 22;
+/// string * number结果的最大字节数
+const MAX_STRING_LEN: usize = 1 << 30;
+
+/**
+ * 把可能是负数或者小数的下标转换成array中的位置,超出开头时返回None
+ */
+fn array_index(len: usize, n: f64) -> Option<usize> {
+    let n = n.floor();
+    let i = if n < 0.0 { len as f64 + n } else { n };
+    if i < 0.0 || i.is_nan() {
+        return None;
+    }
+    return Some(i as usize);
+}
+
+/**
+ * .[from:to]: array和string的切片,下标可以是负数,null代表开头或结尾
+ */
+fn slice(value: &Json, from: &Json, to: &Json) -> EvalResult<Json> {
+    let bound = |bound: &Json, len: usize, default: usize| -> EvalResult<usize> {
+        match bound {
+            Json::Null => { return Ok(default); },
+            Json::Number(n) => {
+                let n = n.floor();
+                let i = if n < 0.0 { (len as f64 + n).max(0.0) } else { n.min(len as f64) };
+                return Ok(i as usize);
+            },
+            other => { return error(std::format!("切片的下标必须是number,不能是{}", type_name(other))); },
+        }
+    };
+    match value {
+        Json::Null => { return Ok(Json::Null); },
+        Json::Array(arr) => {
+            let (from, to) = (bound(from, arr.len(), 0)?, bound(to, arr.len(), arr.len())?);
+            return Ok(Json::Array(if from < to { arr[from..to].to_vec() } else { Vec::new() }));
+        },
+        Json::String(s) => {
+            let chars: Vec<char> = s.chars().collect();
+            let (from, to) = (bound(from, chars.len(), 0)?, bound(to, chars.len(), chars.len())?);
+            return Ok(Json::String(if from < to { chars[from..to].iter().collect() } else { String::new() }));
+        },
+        other => { return error(std::format!("不能对{}切片", type_name(other))); },
+    }
+}
+
+/**
+ * .[]: array的元素,object的值(按照key的字典序)
+ */
+pub(crate) fn iterate(value: &Json) -> EvalResult<Vec<Json>> {
+    match value {
+        Json::Array(arr) => { return Ok(arr.clone()); },
+        Json::Object(obj) => { return Ok(sorted_members(obj).into_iter().map(|(_, value)| value.clone()).collect()); },
+        other => { return error(std::format!("不能遍历{}", type_name(other))); },
+    }
+}
+
+/**
+ * 二元运算,语义和jq相同
+ */
+pub(crate) fn binary(op: BinaryOp, left: &Json, right: &Json) -> EvalResult<Json> {
+    let result = match (op, left, right) {
+        (BinaryOp::Eq, _, _) => Json::Boolean(left == right),
+        (BinaryOp::Ne, _, _) => Json::Boolean(left != right),
+        (BinaryOp::Lt, _, _) => Json::Boolean(left < right),
+        (BinaryOp::Le, _, _) => Json::Boolean(left <= right),
+        (BinaryOp::Gt, _, _) => Json::Boolean(left > right),
+        (BinaryOp::Ge, _, _) => Json::Boolean(left >= right),
+        (BinaryOp::Add, Json::Null, other) | (BinaryOp::Add, other, Json::Null) => other.clone(),
+        (BinaryOp::Add, Json::Number(a), Json::Number(b)) => Json::Number(a + b),
+        (BinaryOp::Add, Json::String(a), Json::String(b)) => Json::String(std::format!("{}{}", a, b)),
+        (BinaryOp::Add, Json::Array(a), Json::Array(b)) => Json::Array(a.iter().chain(b).cloned().collect()),
+        (BinaryOp::Add, Json::Object(a), Json::Object(b)) => {
+            let mut merged = a.clone();
+            merged.extend(b.iter().map(|(key, value)| (key.clone(), value.clone())));
+            Json::Object(merged)
+        },
+        (BinaryOp::Sub, Json::Number(a), Json::Number(b)) => Json::Number(a - b),
+        (BinaryOp::Sub, Json::Array(a), Json::Array(b)) => Json::Array(a.iter().filter(|item| !b.contains(item)).cloned().collect()),
+        (BinaryOp::Mul, Json::Number(a), Json::Number(b)) => Json::Number(a * b),
+        (BinaryOp::Mul, Json::String(s), Json::Number(n)) | (BinaryOp::Mul, Json::Number(n), Json::String(s)) => {
+            if *n <= 0.0 {
+                Json::Null
+            } else {
+                // n很大时as usize得到usize::MAX,乘法溢出
+                match s.len().checked_mul(n.ceil() as usize) {
+                    Some(len) if len <= MAX_STRING_LEN => Json::String(s.repeat(n.ceil() as usize)),
+                    _ => { return error(std::format!("string重复{}次太长", to_json_text(&Json::Number(*n)))); },
+                }
+            }
+        },
+        (BinaryOp::Mul, Json::Object(_), Json::Object(_)) => {
+            // 递归合并object,其他值用右边的替换
+            let mut merged = left.clone();
+            merged.deep_merge(right.clone(), &MergeOptions::default()).map_err(|err| Interrupt::Error(Json::String(err.to_string())))?;
+            merged
+        },
+        (BinaryOp::Div, Json::Number(a), Json::Number(b)) => {
+            if *b == 0.0 {
+                return error(std::format!("{}不能除以0", a));
+            }
+            Json::Number(a / b)
+        },
+        (BinaryOp::Div, Json::String(a), Json::String(b)) => Json::Array(split(a, b)),
+        (BinaryOp::Mod, Json::Number(a), Json::Number(b)) => {
+            let (a, b) = (*a as i64, *b as i64);
+            if b == 0 {
+                return error(std::format!("{}不能对0取余数", a));
+            }
+            Json::Number(a.wrapping_rem(b) as f64)
+        },
+        _ => {
+            let symbol = match op {
+                BinaryOp::Add => "+",
+                BinaryOp::Sub => "-",
+                BinaryOp::Mul => "*",
+                BinaryOp::Div => "/",
+                _ => "%",
+            };
+            return error(std::format!("{}和{}不能进行'{}'运算", type_name(left), type_name(right), symbol));
+        },
+    };
+    return Ok(result);
+}
+
+/**
+ * 按照分隔符拆分string,分隔符为空时拆分成单个字符,string为空时返回空的array(和jq相同)
+ */
+pub(crate) fn split(s: &str, separator: &str) -> Vec<Json> {
+    if s.is_empty() {
+        return Vec::new();
+    }
+    if separator.is_empty() {
+        return s.chars().map(|c| Json::String(c.to_string())).collect();
+    }
+    return s.split(separator).map(|part| Json::String(part.to_string())).collect();
+}
+
+/**
+ * 求出路径表达式expr选中的所有路径,path和value是当前的路径和路径上的值
+ */
+pub(crate) fn eval_paths(expr: &Expr, path: &[Json], value: &Json, vars: &mut Vars, out: &mut Vec<PathValue>) -> EvalResult<()> {
+    let child = |path: &[Json], key: Json| -> Vec<Json> {
+        let mut path = path.to_vec();
+        path.push(key);
+        return path;
+    };
+    match expr {
+        Expr::Identity => { out.push((path.to_vec(), value.clone())); },
+        Expr::Recurse => { recurse_paths(path, value, out); },
+        Expr::Field(target, name) => {
+            let key = Json::String(name.clone());
+            for (path, value) in collect_paths(target, path, value, vars)? {
+                out.push((child(&path, key.clone()), index(&value, &key)?));
+            }
+        },
+        Expr::Index(target, key) => {
+            let keys = collect(key, value, vars)?;
+            for (path, value) in collect_paths(target, path, value, vars)? {
+                for key in &keys {
+                    out.push((child(&path, key.clone()), index(&value, key)?));
+                }
+            }
+        },
+        Expr::Iterate(target) => {
+            for (path, value) in collect_paths(target, path, value, vars)? {
+                match &value {
+                    Json::Array(arr) => {
+                        for (i, item) in arr.iter().enumerate() {
+                            out.push((child(&path, Json::Number(i as f64)), item.clone()));
+                        }
+                    },
+                    Json::Object(obj) => {
+                        for (key, item) in sorted_members(obj) {
+                            out.push((child(&path, Json::String(key.clone())), item.clone()));
+                        }
+                    },
+                    Json::Null => {},
+                    other => { return error(std::format!("不能遍历{}", type_name(other))); },
+                }
+            }
+        },
+        Expr::Try(body, None) => { let _ = eval_paths(body, path, value, vars, out); },
+        Expr::Pipe(lhs, rhs) => {
+            for (path, value) in collect_paths(lhs, path, value, vars)? {
+                eval_paths(rhs, &path, &value, vars, out)?;
+            }
+        },
+        Expr::Comma(lhs, rhs) => {
+            eval_paths(lhs, path, value, vars, out)?;
+            eval_paths(rhs, path, value, vars, out)?;
+        },
+        Expr::Alternative(lhs, rhs) => {
+            let mut paths = Vec::new();
+            let _ = eval_paths(lhs, path, value, vars, &mut paths);
+            paths.retain(|(_, value)| truthy(value));
+            if paths.is_empty() {
+                eval_paths(rhs, path, value, vars, out)?;
+            } else {
+                out.extend(paths);
+            }
+        },
+        Expr::If(branches, otherwise) => {
+            match branches.split_first() {
+                Some(((cond, body), rest)) => {
+                    for c in collect(cond, value, vars)? {
+                        if truthy(&c) {
+                            eval_paths(body, path, value, vars, out)?;
+                        } else {
+                            eval_paths(&Expr::If(rest.to_vec(), otherwise.clone()), path, value, vars, out)?;
+                        }
+                    }
+                },
+                None => {
+                    match otherwise {
+                        Some(otherwise) => { eval_paths(otherwise, path, value, vars, out)?; },
+                        None => { out.push((path.to_vec(), value.clone())); },
+                    }
+                },
+            }
+        },
+        Expr::Bind(source, name, body) => {
+            for bound in collect(source, value, vars)? {
+                vars.push((name.clone(), bound));
+                let result = eval_paths(body, path, value, vars, out);
+                vars.pop();
+                result?;
+            }
+        },
+        Expr::Call(name, args) => { builtin::call_paths(name, args, path, value, vars, out)?; },
+        _ => { return error("不是路径表达式,不能用于赋值,del和path".to_string()); },
+    }
+    return Ok(());
+}
+
+pub(crate) fn collect_paths(expr: &Expr, path: &[Json], value: &Json, vars: &mut Vars) -> EvalResult<Vec<PathValue>> {
+    let mut out = Vec::new();
+    eval_paths(expr, path, value, vars, &mut out)?;
+    return Ok(out);
+}
+
+/**
+ * value和所有子孙的路径,先序
+ */
+pub(crate) fn recurse_paths(path: &[Json], value: &Json, out: &mut Vec<PathValue>) {
+    out.push((path.to_vec(), value.clone()));
+    let mut child = path.to_vec();
+    match value {
+        Json::Array(arr) => {
+            for (i, item) in arr.iter().enumerate() {
+                child.push(Json::Number(i as f64));
+                recurse_paths(&child, item, out);
+                child.pop();
+            }
+        },
+        Json::Object(obj) => {
+            for (key, item) in sorted_members(obj) {
+                child.push(Json::String(key.clone()));
+                recurse_paths(&child, item, out);
+                child.pop();
+            }
+        },
+        _ => {},
+    }
+}
+
+/**
+ * 取出路径上的值,不存在时是null
+ */
+pub(crate) fn get_path(root: &Json, path: &[Json]) -> EvalResult<Json> {
+    let mut current = root.clone();
+    for key in path {
+        current = index(&current, key)?;
+    }
+    return Ok(current);
+}
+
+/**
+ * 设置路径上的值,需要时创建object和array,array用null补齐
+ */
+pub(crate) fn set_path(root: &mut Json, path: &[Json], value: Json) -> EvalResult<()> {
+    let (key, rest) = match path.split_first() {
+        Some(split) => split,
+        None => {
+            *root = value;
+            return Ok(());
+        },
+    };
+    if let Json::Null = root {
+        match key {
+            Json::String(_) => { *root = Json::Object(HashMap::new()); },
+            Json::Number(_) => { *root = Json::Array(Vec::new()); },
+            _ => {},
+        }
+    }
+    match (root, key) {
+        (Json::Object(obj), Json::String(key)) => { return set_path(obj.entry(key.clone()).or_insert(Json::Null), rest, value); },
+        (Json::Array(arr), Json::Number(n)) => {
+            let i = match array_index(arr.len(), *n) {
+                Some(i) if n.is_finite() && (i < arr.len() || i < MAX_ARRAY_LEN) => i,
+                _ => { return error(std::format!("array的下标{}超出范围", to_json_text(&Json::Number(*n)))); },
+            };
+            if i >= arr.len() {
+                arr.resize(i + 1, Json::Null);
+            }
+            return set_path(&mut arr[i], rest, value);
+        },
+        (root, key) => { return error(std::format!("不能用{}索引{}", type_name(key), type_name(root))); },
+    }
+}
+
+/**
+ * 删除所有的路径,从后往前删除,保证array的下标不受前面的删除影响
+ */
+pub(crate) fn delete_paths(root: &mut Json, mut paths: Vec<Vec<Json>>) -> EvalResult<()> {
+    paths.sort();
+    paths.dedup();
+    for path in paths.iter().rev() {
+        delete_path(root, path)?;
+    }
+    return Ok(());
+}
+
+/**
+ * 删除一条路径,路径不存在时什么也不做
+ */
+fn delete_path(value: &mut Json, path: &[Json]) -> EvalResult<()> {
+    let (key, rest) = match path.split_first() {
+        Some(split) => split,
+        None => {
+            *value = Json::Null;
+            return Ok(());
+        },
+    };
+    if rest.is_empty() {
+        match (value, key) {
+            (Json::Object(obj), Json::String(key)) => { obj.remove(key); },
+            (Json::Array(arr), Json::Number(n)) => {
+                if let Some(i) = array_index(arr.len(), *n).filter(|i| *i < arr.len()) {
+                    arr.remove(i);
+                }
+            },
+            (Json::Null, _) => {},
+            (value, key) => { return error(std::format!("不能从{}中删除{}", type_name(value), to_json_text(key))); },
+        }
+        return Ok(());
+    }
+    let child = match (value, key) {
+        (Json::Object(obj), Json::String(key)) => obj.get_mut(key),
+        (Json::Array(arr), Json::Number(n)) => {
+            let len = arr.len();
+            array_index(len, *n).and_then(|i| arr.get_mut(i))
+        },
+        _ => None,
+    };
+    match child {
+        Some(child) => { return delete_path(child, rest); },
+        None => { return Ok(()); },
+    }
+}
+
+/**
+ * 赋值: lhs是路径表达式,"="和算术赋值的rhs对整个输入求值,"|="的rhs对路径上的值求值
+ */
+fn assign(op: AssignOp, lhs: &Expr, rhs: &Expr, input: &Json, vars: &mut Vars, emit: &mut Emit) -> EvalResult<()> {
+    let paths = collect_paths(lhs, &[], input, vars)?;
+    if op == AssignOp::Update {
+        let mut result = input.clone();
+        let mut deleted = Vec::new();
+        for (path, _) in paths {
+            let current = get_path(&result, &path)?;
+            match collect(rhs, &current, vars)?.into_iter().next() {
+                Some(value) => { set_path(&mut result, &path, value)?; },
+                None => { deleted.push(path); },
+            }
+        }
+        delete_paths(&mut result, deleted)?;
+        return emit(vars, result);
+    }
+    for value in collect(rhs, input, vars)? {
+        let mut result = input.clone();
+        for (path, _) in &paths {
+            let new = match op {
+                AssignOp::Arithmetic(op) => binary(op, &get_path(&result, path)?, &value)?,
+                AssignOp::Alternative => {
+                    let current = get_path(&result, path)?;
+                    if truthy(&current) { current } else { value.clone() }
+                },
+                _ => value.clone(),
+            };
+            set_path(&mut result, path, new)?;
+        }
+        emit(vars, result)?;
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+#[test]
+fn test_query_eval() {
+    let json = Json::str_to_json(r#"{"a":[1,{"b":null}],"c":"x"}"#).unwrap();
+    assert_eq!(to_json_text(&index(&json, &Json::String("c".to_string())).unwrap()), "\"x\"");
+    assert_eq!(to_json_text(&get_path(&json, &[Json::String("a".to_string()), Json::Number(-1.0)]).unwrap()), r#"{"b":null}"#);
+    assert_eq!(index(&json, &Json::Number(0.0)).err().unwrap(), Interrupt::Error(Json::String("不能用number索引object".to_string())));
+
+    let mut root = Json::Null;
+    set_path(&mut root, &[Json::String("x".to_string()), Json::Number(2.0)], Json::Boolean(true)).unwrap();
+    assert_eq!(to_json_text(&root), r#"{"x":[null,null,true]}"#);
+    delete_paths(&mut root, vec![vec![Json::String("x".to_string()), Json::Number(0.0)], vec![Json::String("x".to_string()), Json::Number(1.0)]]).unwrap();
+    assert_eq!(to_json_text(&root), r#"{"x":[true]}"#);
+
+    let mut out = Vec::new();
+    recurse_paths(&[], &json, &mut out);
+    assert_eq!(out.len(), 6);
+    assert_eq!(to_json_text(&binary(BinaryOp::Mod, &Json::Number(-7.0), &Json::Number(3.0)).unwrap()), "-1");
+    assert_eq!(to_json_text(&Json::Array(split("a,b", ","))), r#"["a","b"]"#);
+    assert!(binary(BinaryOp::Sub, &Json::String("a".to_string()), &Json::Number(1.0)).is_err());
+}
@@ -0,0 +1,277 @@
+/**
+ * 文件名: "src/zjy/json/query/lexer.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::zjy::json::query::parser::MAX_DEPTH;
+
+/**
+ * 查询语言的词法单元
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Token {
+    /// 单独的 '.'
+    Dot,
+    /// ".."
+    DotDot,
+    /// ".name"
+    Field(String),
+    /// 标识符和关键字
+    Ident(String),
+    /// "$name"
+    Var(String),
+    Number(f64),
+    /// 可以带有插值 "\(...)" 的string
+    Str(Vec<StrPart>),
+    /// 运算符和标点
+    Punct(&'static str),
+}
+
+/**
+ * string中的一段: 原样的文本或者插值表达式的词法单元
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum StrPart {
+    Literal(String),
+    Interpolation(Vec<(Token, usize)>),
+}
+
+/// 按照长度从长到短排列,优先匹配长的运算符
+const PUNCTS: [&str; 32] = [
+    "//=", "|=", "+=", "-=", "*=", "/=", "%=", "==", "!=", "<=", ">=", "//",
+    "|", ",", "(", ")", "[", "]", "{", "}", ":", ";", "=", "<", ">", "+", "-", "*", "/", "%", "?", "$",
+];
+
+/**
+ * 把查询拆分成词法单元,每个单元带有从1开始的字符位置
+ */
+pub(crate) fn tokenize(src: &str) -> Result<Vec<(Token, usize)>, String> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut pos = 0;
+    let tokens = lex(&chars, &mut pos, false, 0)?;
+    return Ok(tokens);
+}
+
+fn is_ident_start(c: char) -> bool {
+    return c.is_ascii_alphabetic() || c == '_';
+}
+
+fn is_ident_char(c: char) -> bool {
+    return c.is_ascii_alphanumeric() || c == '_';
+}
+
+fn read_ident(chars: &[char], pos: &mut usize) -> String {
+    let start = *pos;
+    while *pos < chars.len() && is_ident_char(chars[*pos]) {
+        *pos += 1;
+    }
+    return chars[start..*pos].iter().collect();
+}
+
+/**
+ * 读取词法单元直到输入结束;interpolation为true时读取到和 "\(" 配对的 ')' 为止(不包括 ')'),
+ * nesting是string插值嵌套的层数
+ */
+fn lex(chars: &[char], pos: &mut usize, interpolation: bool, nesting: usize) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = Vec::new();
+    let mut depth = 0usize;
+    while *pos < chars.len() {
+        let c = chars[*pos];
+        let start = *pos + 1;
+        if c.is_whitespace() {
+            *pos += 1;
+            continue;
+        }
+        if c == '#' {
+            while *pos < chars.len() && chars[*pos] != '\n' {
+                *pos += 1;
+            }
+            continue;
+        }
+        if c == '.' {
+            *pos += 1;
+            if chars.get(*pos) == Some(&'.') {
+                *pos += 1;
+                tokens.push((Token::DotDot, start));
+            } else if chars.get(*pos).is_some_and(|c| is_ident_start(*c)) {
+                tokens.push((Token::Field(read_ident(chars, pos)), start));
+            } else {
+                tokens.push((Token::Dot, start));
+            }
+            continue;
+        }
+        if c == '"' {
+            *pos += 1;
+            tokens.push((Token::Str(lex_string(chars, pos, nesting)?), start));
+            continue;
+        }
+        if c.is_ascii_digit() {
+            tokens.push((Token::Number(lex_number(chars, pos)?), start));
+            continue;
+        }
+        if is_ident_start(c) {
+            tokens.push((Token::Ident(read_ident(chars, pos)), start));
+            continue;
+        }
+        if c == '$' && chars.get(*pos + 1).is_some_and(|c| is_ident_start(*c)) {
+            *pos += 1;
+            tokens.push((Token::Var(read_ident(chars, pos)), start));
+            continue;
+        }
+        if interpolation && c == ')' && depth == 0 {
+            return Ok(tokens);
+        }
+        let rest: String = chars[*pos..chars.len().min(*pos + 3)].iter().collect();
+        match PUNCTS.iter().find(|p| rest.starts_with(**p)) {
+            Some(p) => {
+                match *p {
+                    "(" => { depth += 1; },
+                    ")" => { depth = depth.saturating_sub(1); },
+                    _ => {},
+                }
+                *pos += p.chars().count();
+                tokens.push((Token::Punct(p), start));
+            },
+            None => { return Err(std::format!("第{}个字符:不能识别的字符'{}'", start, c)); },
+        }
+    }
+    if interpolation {
+        return Err("string插值没有结束,需要')'".to_string());
+    }
+    return Ok(tokens);
+}
+
+fn lex_number(chars: &[char], pos: &mut usize) -> Result<f64, String> {
+    let start = *pos;
+    let digits = |pos: &mut usize| {
+        while *pos < chars.len() && chars[*pos].is_ascii_digit() {
+            *pos += 1;
+        }
+    };
+    digits(pos);
+    if chars.get(*pos) == Some(&'.') && chars.get(*pos + 1).is_some_and(|c| c.is_ascii_digit()) {
+        *pos += 1;
+        digits(pos);
+    }
+    if matches!(chars.get(*pos), Some('e') | Some('E')) {
+        let mut next = *pos + 1;
+        if matches!(chars.get(next), Some('+') | Some('-')) {
+            next += 1;
+        }
+        if chars.get(next).is_some_and(|c| c.is_ascii_digit()) {
+            *pos = next;
+            digits(pos);
+        }
+    }
+    let text: String = chars[start..*pos].iter().collect();
+    return text.parse().map_err(|_| std::format!("第{}个字符:不是合法的数字:{}", start + 1, text));
+}
+
+/**
+ * 读取string,pos在开头的 '"' 之后,结束时在结尾的 '"' 之后
+ */
+fn lex_string(chars: &[char], pos: &mut usize, nesting: usize) -> Result<Vec<StrPart>, String> {
+    let start = *pos;
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    loop {
+        let c = match chars.get(*pos) {
+            Some(c) => *c,
+            None => { return Err(std::format!("第{}个字符:string没有结束", start)); },
+        };
+        *pos += 1;
+        match c {
+            '"' => { break; },
+            '\\' => {
+                let escape = chars.get(*pos).copied().unwrap_or('\0');
+                *pos += 1;
+                match escape {
+                    '"' | '\\' | '/' => { literal.push(escape); },
+                    'b' => { literal.push('\u{8}'); },
+                    'f' => { literal.push('\u{c}'); },
+                    'n' => { literal.push('\n'); },
+                    'r' => { literal.push('\r'); },
+                    't' => { literal.push('\t'); },
+                    'u' => { literal.push(lex_unicode(chars, pos)?); },
+                    '(' => {
+                        if nesting >= MAX_DEPTH {
+                            return Err(std::format!("第{}个字符:查询嵌套太深,最多{}层", *pos - 1, MAX_DEPTH));
+                        }
+                        if !literal.is_empty() {
+                            parts.push(StrPart::Literal(std::mem::take(&mut literal)));
+                        }
+                        parts.push(StrPart::Interpolation(lex(chars, pos, true, nesting + 1)?));
+                        // 跳过结尾的 ')'
+                        *pos += 1;
+                    },
+                    _ => { return Err(std::format!("第{}个字符:不合法的转义字符'\\{}'", *pos - 1, escape)); },
+                }
+            },
+            _ => { literal.push(c); },
+        }
+    }
+    if !literal.is_empty() || parts.is_empty() {
+        parts.push(StrPart::Literal(literal));
+    }
+    return Ok(parts);
+}
+
+/**
+ * 读取\u后面的4个十六进制数字,代理对需要两个\u
+ */
+fn lex_unicode(chars: &[char], pos: &mut usize) -> Result<char, String> {
+    let hex = |pos: &mut usize| -> Result<u32, String> {
+        let text: String = chars.get(*pos..*pos + 4).map(|s| s.iter().collect()).unwrap_or_default();
+        let code = u32::from_str_radix(&text, 16).map_err(|_| std::format!("第{}个字符:\\u后面需要4个十六进制数字", *pos))?;
+        *pos += 4;
+        return Ok(code);
+    };
+    let high = hex(pos)?;
+    if (0xD800..0xDC00).contains(&high) && chars.get(*pos) == Some(&'\\') && chars.get(*pos + 1) == Some(&'u') {
+        let mut next = *pos + 2;
+        let low = hex(&mut next)?;
+        if (0xDC00..0xE000).contains(&low) {
+            *pos = next;
+            return Ok(char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)).unwrap_or('\u{fffd}'));
+        }
+    }
+    return Ok(char::from_u32(high).unwrap_or('\u{fffd}'));
+}
+
+#[cfg(test)]
+#[test]
+fn test_query_lexer() {
+    let tokens: Vec<Token> = tokenize(".a.b[0] | .. //= $x # 注释\n").unwrap().into_iter().map(|(token, _)| token).collect();
+    assert_eq!(tokens, [
+        Token::Field("a".to_string()), Token::Field("b".to_string()), Token::Punct("["), Token::Number(0.0), Token::Punct("]"),
+        Token::Punct("|"), Token::DotDot, Token::Punct("//="), Token::Var("x".to_string()),
+    ]);
+    let tokens = tokenize("\"a\\(.x + (1)) \\ud83d\\ude00\"").unwrap();
+    match &tokens[0].0 {
+        Token::Str(parts) => {
+            assert_eq!(parts.len(), 3);
+            assert_eq!(parts[0], StrPart::Literal("a".to_string()));
+            assert!(matches!(&parts[1], StrPart::Interpolation(tokens) if tokens.len() == 5));
+            assert_eq!(parts[2], StrPart::Literal(" \u{1f600}".to_string()));
+        },
+        _ => { panic!("需要string"); },
+    }
+    assert_eq!(tokenize("1.5e3 .5").unwrap()[0].0, Token::Number(1500.0));
+    assert_eq!(tokenize(". @").err().unwrap(), "第3个字符:不能识别的字符'@'");
+    assert!(tokenize("\"abc").is_err());
+    assert!(tokenize("\"\\(1\"").is_err());
+}
@@ -0,0 +1,162 @@
+/**
+ * 文件名: "src/zjy/json/query/mod.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+mod builtin;
+mod eval;
+mod lexer;
+mod parser;
+
+use crate::zjy::json::query::eval::Interrupt;
+use crate::zjy::json::query::parser::{parse_query, Expr};
+use crate::zjy::json::{Json, JsonError};
+
+/**
+ * 编译好的查询,语法是jq的一个常用子集:
+ * 路径(.a.b, .[0], .[1:3], .[], .., ?),管道(|),逗号(,),object和array的构造,算术和比较运算,
+ * and/or,//,if/elif/else,reduce,foreach,try/catch,变量(... as $x | ...),string插值("\(.x)"),
+ * 赋值(=, |=, +=等)和常用的内置函数(select,map,keys,length,sort_by,group_by,to_entries,del等)
+ * object没有顺序,遍历object时按照key的字典序
+ */
+#[derive(Clone, Debug)]
+pub struct Query {
+    source: String,
+    expr: Expr,
+}
+
+impl Query {
+    /**
+     * 编译查询,语法错误时返回带有字符位置的错误
+     */
+    pub fn compile(source: &str) -> Result<Query, JsonError> {
+        let expr = parse_query(source).map_err(|msg| JsonError::from(msg).prefix("查询语法错误:"))?;
+        return Ok(Query { source: source.to_string(), expr });
+    }
+    /**
+     * 查询的源代码
+     */
+    pub fn source(&self) -> &str { return &self.source; }
+    /**
+     * 对input执行查询,返回所有的结果
+     */
+    pub fn run(&self, input: &Json) -> Result<Vec<Json>, JsonError> {
+        return self.run_with_vars(input, &[]);
+    }
+    /**
+     * 执行查询,vars是预先定义的变量(不带$),比如命令行的--arg
+     */
+    pub fn run_with_vars(&self, input: &Json, vars: &[(String, Json)]) -> Result<Vec<Json>, JsonError> {
+        let mut vars = vars.to_vec();
+        let mut out = Vec::new();
+        let result = eval::eval(&self.expr, input, &mut vars, &mut |_, value| {
+            out.push(value);
+            return Ok(());
+        });
+        let msg = match result {
+            // 最外层不会要求停止
+            Ok(()) | Err(Interrupt::Stop) => { return Ok(out); },
+            Err(Interrupt::Error(Json::String(msg))) => msg,
+            Err(Interrupt::Error(other)) => std::format!("{} (不是string)", eval::to_json_text(&other)),
+        };
+        return Err(JsonError::from(msg).prefix("查询错误:"));
+    }
+}
+
+impl Json {
+    /**
+     * 编译并执行查询,返回所有的结果
+     */
+    pub fn query(&self, source: &str) -> Result<Vec<Json>, JsonError> {
+        return Query::compile(source)?.run(self);
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_query() {
+    let json = Json::str_to_json(r#"{
+        "users": [
+            {"name": "alice", "age": 30, "roles": ["admin", "dev"]},
+            {"name": "bob", "age": 25, "roles": ["dev"]},
+            {"name": "carol", "age": 35, "roles": []}
+        ],
+        "meta": {"version": 2, "tags": {"a": 1, "b": 2}}
+    }"#).unwrap();
+    let q = |source: &str| -> Vec<String> {
+        return json.query(source).unwrap().iter().map(eval::to_json_text).collect();
+    };
+    // 路径和管道
+    assert_eq!(q(".users[0].name"), [r#""alice""#]);
+    assert_eq!(q(".users[-1].age, .meta.version"), ["35", "2"]);
+    assert_eq!(q(".users[] | .name"), [r#""alice""#, r#""bob""#, r#""carol""#]);
+    assert_eq!(q(".users[1:].[0].name"), [r#""bob""#]);
+    assert_eq!(q(".missing.x?, .meta.\"version\""), ["null", "2"]);
+    // select,map,keys,length
+    assert_eq!(q("[.users[] | select(.age > 28) | .name]"), [r#"["alice","carol"]"#]);
+    assert_eq!(q(".users | map(.age) | add / length"), ["30"]);
+    assert_eq!(q(".meta.tags | keys"), [r#"["a","b"]"#]);
+    assert_eq!(q("[.users[] | .roles | length]"), ["[2,1,0]"]);
+    // 构造,运算,变量,插值
+    assert_eq!(q("{name: .users[0].name, n: (.users | length)}"), [r#"{"n":3,"name":"alice"}"#]);
+    assert_eq!(q(".meta.version as $v | [.users[] | {name, older: (.age > 28 and $v == 2)}] | .[1]"), [r#"{"name":"bob","older":false}"#]);
+    assert_eq!(q(r#".users[] | "\(.name) is \(.age)" | select(startswith("b"))"#), [r#""bob is 25""#]);
+    assert_eq!(q("1 + 2 * 3 - 4 / 2, 7 % 3, \"a\" + \"b\", [1,2,3] - [2], {a:1} * {a:{b:2}}"), ["5", "1", r#""ab""#, "[1,3]", r#"{"a":{"b":2}}"#]);
+    // reduce,foreach,if,try,//
+    assert_eq!(q("reduce .users[] as $u (0; . + $u.age)"), ["90"]);
+    assert_eq!(q("[foreach (1,2,3) as $x (0; . + $x)]"), ["[1,3,6]"]);
+    assert_eq!(q(".users[] | if .age < 28 then \"young\" elif .age < 32 then \"mid\" else \"old\" end"), [r#""mid""#, r#""young""#, r#""old""#]);
+    assert_eq!(q("try error(\"x\") catch ., .nothing // \"default\", (.users[0].name | tonumber?) // 0"), [r#""x""#, r#""default""#, "0"]);
+    // 赋值和del
+    assert_eq!(q(".meta.tags | .a += 10 | .c = 3 | del(.b)"), [r#"{"a":11,"c":3}"#]);
+    assert_eq!(q(".users | map_values(.age) | .[0] |= . * 2"), ["[60,25,35]"]);
+    assert_eq!(q(".meta | [paths], [path(.tags[])]"), [r#"[["tags"],["tags","a"],["tags","b"],["version"]]"#, r#"[["tags","a"],["tags","b"]]"#]);
+    assert_eq!(q(".meta.tags | to_entries | map(\"\\(.key)=\\(.value)\") | join(\",\")"), [r#""a=1,b=2""#]);
+    assert_eq!(q(".meta.tags | with_entries(.value += 1)"), [r#"{"a":2,"b":3}"#]);
+    // 预先定义的变量和错误
+    let query = Query::compile("$x + 1").unwrap();
+    assert_eq!(eval::to_json_text(&query.run_with_vars(&Json::Null, &[("x".to_string(), Json::Number(1.0))]).unwrap()[0]), "2");
+    assert_eq!(query.run(&Json::Null).err().unwrap().to_string(), "查询错误:变量$x没有定义");
+    assert_eq!(Query::compile(".a |").err().unwrap().to_string(), "查询语法错误:查询意外结束");
+    // first和limit得到需要的结果之后停止求值,try不捕获后面的表达式中的错误
+    assert_eq!(q("first(range(1e9)), [limit(3; range(1e9) | . * 2)], first(1, error(\"x\"))"), ["0", "[0,2,4]", "1"]);
+    assert_eq!(q("[limit(2; 1, 2, error(\"x\"))], [first(range(10; 0)), 1]"), ["[1,2]", "[1]"]);
+    assert_eq!(json.query("[limit(5; 1, error(\"x\"))]").err().unwrap().to_string(), "查询错误:x");
+    assert_eq!(q("[.users[] | try .name catch \"-\"], [(try (1, 2)) | try error(.) catch . + 10]"), [r#"["alice","bob","carol"]"#, "[11,12]"]);
+    assert!(json.query("(try 1) | error(\"y\")").is_err());
+    // 嵌套很深的语法错误不会反复解析
+    let deep = std::format!("{}1 +{}", "(".repeat(40), ")".repeat(40));
+    assert_eq!(Query::compile(&deep).err().unwrap().to_string(), "查询语法错误:第44个字符:这里不能是')'");
+    // 嵌套太深的查询返回错误,不会栈溢出;debug编译的每一层需要十几KB的栈,在和主线程一样大的栈上测试
+    std::thread::Builder::new().stack_size(8 << 20).spawn(|| {
+        for (open, close) in [("(", ")"), ("[", "]"), ("{a:", "}"), ("1 + (", ")"), ("-", ""), (". as $x | ", ""), ("1 // ", ""), ("\"\\(", ")\"")] {
+            let deep = std::format!("{}1{}", open.repeat(100_000), close.repeat(100_000));
+            assert!(Query::compile(&deep).err().unwrap().to_string().contains("查询嵌套太深,最多256层"));
+            let nested = std::format!("{}1{}", open.repeat(100), close.repeat(100));
+            assert!(Json::Null.query(&nested).is_ok());
+        }
+    }).unwrap().join().unwrap();
+    assert_eq!(json.query(".users + 1").err().unwrap().to_string(), "查询错误:array和number不能进行'+'运算");
+    assert_eq!(json.query("error({a:1})").err().unwrap().to_string(), r#"查询错误:{"a":1} (不是string)"#);
+    // 过大的下标和重复次数
+    assert_eq!(q("null | .[2] = 1"), ["[null,null,1]"]);
+    assert_eq!(Json::Null.query(".[1e300] = 1").err().unwrap().to_string(), "查询错误:array的下标1e+300超出范围");
+    assert!(Json::Null.query(".[1e10] = 1").is_err());
+    assert!(Json::Null.query("[] | .[infinite] = 1").is_err());
+    assert_eq!(q("\"ab\" * 2.5"), [r#""ababab""#]);
+    assert!(Json::Null.query("\"ab\" * 1e19").is_err());
+}
@@ -0,0 +1,663 @@
+/**
+ * 文件名: "src/zjy/json/query/parser.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::zjy::json::query::builtin;
+use crate::zjy::json::query::lexer::{tokenize, StrPart, Token};
+use crate::zjy::json::Json;
+
+/**
+ * 二元运算符
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/**
+ * 赋值运算符: "=", "|=", "//=" 和 "+=" 等算术赋值
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum AssignOp {
+    Set,
+    Update,
+    Alternative,
+    Arithmetic(BinaryOp),
+}
+
+/**
+ * 查询的语法树
+ */
+#[derive(Clone, Debug)]
+pub(crate) enum Expr {
+    /// "."
+    Identity,
+    /// ".."
+    Recurse,
+    Literal(Json),
+    /// 带插值的string,每一部分的结果转换成string之后连接
+    Format(Vec<Expr>),
+    Var(String),
+    /// target.name
+    Field(Box<Expr>, String),
+    /// target[index]
+    Index(Box<Expr>, Box<Expr>),
+    /// target[from:to]
+    Slice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>),
+    /// target[]
+    Iterate(Box<Expr>),
+    /// try body catch handler,后缀 '?' 是没有handler的try
+    Try(Box<Expr>, Option<Box<Expr>>),
+    /// [body]
+    Array(Option<Box<Expr>>),
+    /// {key: value, ...}
+    Object(Vec<(Expr, Expr)>),
+    Neg(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    /// a // b
+    Alternative(Box<Expr>, Box<Expr>),
+    Pipe(Box<Expr>, Box<Expr>),
+    Comma(Box<Expr>, Box<Expr>),
+    /// source as $name | body
+    Bind(Box<Expr>, String, Box<Expr>),
+    /// reduce source as $name (init; update)
+    Reduce(Box<Expr>, String, Box<Expr>, Box<Expr>),
+    /// foreach source as $name (init; update; extract)
+    Foreach(Box<Expr>, String, Box<Expr>, Box<Expr>, Option<Box<Expr>>),
+    /// if c1 then b1 elif c2 then b2 else e end
+    If(Vec<(Expr, Expr)>, Option<Box<Expr>>),
+    Assign(AssignOp, Box<Expr>, Box<Expr>),
+    /// 内置函数调用,参数是延迟求值的表达式
+    Call(String, Vec<Expr>),
+}
+
+/// 不能作为函数名的关键字
+const KEYWORDS: [&str; 14] = ["if", "then", "elif", "else", "end", "as", "reduce", "foreach", "try", "catch", "and", "or", "def", "label"];
+
+/// 查询的最大嵌套深度,求值时每一层嵌套都会递归,防止很深的查询导致栈溢出
+pub(crate) const MAX_DEPTH: usize = 256;
+
+/**
+ * 把查询编译成语法树
+ */
+pub(crate) fn parse_query(src: &str) -> Result<Expr, String> {
+    let tokens = tokenize(src)?;
+    let mut parser = QueryParser { tokens, pos: 0, pending: None, depth: 0 };
+    if parser.tokens.is_empty() {
+        return Ok(Expr::Identity);
+    }
+    let expr = parser.parse_pipe()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(parser.unexpected());
+    }
+    return Ok(expr);
+}
+
+struct QueryParser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    /// parse_pipe为了判断 "as" 预先解析的term: (开始位置,结束位置,语法树或错误),避免重复解析;
+    /// 出错时也要保留,否则每一层括号都会重新解析一次,解析时间随着嵌套层数指数增长
+    pending: Option<(usize, usize, Result<Expr, String>)>,
+    /// 当前语法树的嵌套深度
+    depth: usize,
+}
+
+fn boxed(expr: Expr) -> Box<Expr> {
+    return Box::new(expr);
+}
+
+impl QueryParser {
+    fn peek(&self) -> Option<&Token> {
+        return self.tokens.get(self.pos).map(|(token, _)| token);
+    }
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        return self.tokens.get(self.pos + offset).map(|(token, _)| token);
+    }
+    fn is_punct(&self, punct: &str) -> bool {
+        return matches!(self.peek(), Some(Token::Punct(p)) if *p == punct);
+    }
+    fn is_keyword(&self, keyword: &str) -> bool {
+        return matches!(self.peek(), Some(Token::Ident(name)) if name == keyword);
+    }
+    fn eat_punct(&mut self, punct: &str) -> bool {
+        if self.is_punct(punct) {
+            self.pos += 1;
+            return true;
+        }
+        return false;
+    }
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.is_keyword(keyword) {
+            self.pos += 1;
+            return true;
+        }
+        return false;
+    }
+    /**
+     * 进入一层嵌套,超过MAX_DEPTH时返回错误
+     */
+    fn nest(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            let at = self.tokens.get(self.pos).or(self.tokens.last()).map(|(_, at)| *at).unwrap_or(0);
+            return Err(std::format!("第{}个字符:查询嵌套太深,最多{}层", at, MAX_DEPTH));
+        }
+        return Ok(());
+    }
+    /**
+     * 在下一层嵌套中解析,解析之后回到原来的深度
+     */
+    fn nested(&mut self, parse: fn(&mut QueryParser) -> Result<Expr, String>) -> Result<Expr, String> {
+        let depth = self.depth;
+        self.nest()?;
+        let result = parse(self);
+        self.depth = depth;
+        return result;
+    }
+    fn unexpected(&self) -> String {
+        match self.tokens.get(self.pos) {
+            Some((token, at)) => { return std::format!("第{}个字符:这里不能是{}", at, describe(token)); },
+            None => { return "查询意外结束".to_string(); },
+        }
+    }
+    fn expect_punct(&mut self, punct: &str) -> Result<(), String> {
+        if self.eat_punct(punct) {
+            return Ok(());
+        }
+        return Err(std::format!("{},需要'{}'", self.unexpected(), punct));
+    }
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        if self.eat_keyword(keyword) {
+            return Ok(());
+        }
+        return Err(std::format!("{},需要'{}'", self.unexpected(), keyword));
+    }
+    fn expect_var(&mut self) -> Result<String, String> {
+        if let Some(Token::Var(name)) = self.peek() {
+            let name = name.clone();
+            self.pos += 1;
+            return Ok(name);
+        }
+        return Err(std::format!("{},需要变量", self.unexpected()));
+    }
+
+    /**
+     * pipe: term as $x | pipe, 或者 comma | pipe
+     */
+    fn parse_pipe(&mut self) -> Result<Expr, String> {
+        let start = self.pos;
+        let depth = self.depth;
+        let source = self.parse_postfix();
+        self.depth = depth;
+        if source.is_ok() && self.eat_keyword("as") {
+            let name = self.expect_var()?;
+            self.expect_punct("|")?;
+            let body = self.nested(QueryParser::parse_pipe)?;
+            return Ok(Expr::Bind(boxed(source?), name, boxed(body)));
+        }
+        self.pending = Some((start, self.pos, source));
+        self.pos = start;
+        let lhs = self.parse_comma()?;
+        if self.eat_punct("|") {
+            let rhs = self.nested(QueryParser::parse_pipe)?;
+            return Ok(Expr::Pipe(boxed(lhs), boxed(rhs)));
+        }
+        return Ok(lhs);
+    }
+    fn parse_comma(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        let mut lhs = self.parse_alternative()?;
+        while self.eat_punct(",") {
+            // 左结合的表达式每连接一次,语法树就深一层
+            self.nest()?;
+            let rhs = self.parse_alternative()?;
+            lhs = Expr::Comma(boxed(lhs), boxed(rhs));
+        }
+        self.depth = depth;
+        return Ok(lhs);
+    }
+    fn parse_alternative(&mut self) -> Result<Expr, String> {
+        let lhs = self.parse_assign()?;
+        if self.eat_punct("//") {
+            let rhs = self.nested(QueryParser::parse_alternative)?;
+            return Ok(Expr::Alternative(boxed(lhs), boxed(rhs)));
+        }
+        return Ok(lhs);
+    }
+    fn parse_assign(&mut self) -> Result<Expr, String> {
+        let lhs = self.parse_or()?;
+        let op = match self.peek() {
+            Some(Token::Punct("=")) => AssignOp::Set,
+            Some(Token::Punct("|=")) => AssignOp::Update,
+            Some(Token::Punct("//=")) => AssignOp::Alternative,
+            Some(Token::Punct("+=")) => AssignOp::Arithmetic(BinaryOp::Add),
+            Some(Token::Punct("-=")) => AssignOp::Arithmetic(BinaryOp::Sub),
+            Some(Token::Punct("*=")) => AssignOp::Arithmetic(BinaryOp::Mul),
+            Some(Token::Punct("/=")) => AssignOp::Arithmetic(BinaryOp::Div),
+            Some(Token::Punct("%=")) => AssignOp::Arithmetic(BinaryOp::Mod),
+            _ => { return Ok(lhs); },
+        };
+        self.pos += 1;
+        let rhs = self.parse_or()?;
+        return Ok(Expr::Assign(op, boxed(lhs), boxed(rhs)));
+    }
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        let mut lhs = self.parse_and()?;
+        while self.eat_keyword("or") {
+            self.nest()?;
+            let rhs = self.parse_and()?;
+            lhs = Expr::Or(boxed(lhs), boxed(rhs));
+        }
+        self.depth = depth;
+        return Ok(lhs);
+    }
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        let mut lhs = self.parse_comparison()?;
+        while self.eat_keyword("and") {
+            self.nest()?;
+            let rhs = self.parse_comparison()?;
+            lhs = Expr::And(boxed(lhs), boxed(rhs));
+        }
+        self.depth = depth;
+        return Ok(lhs);
+    }
+    fn parse_comparison(&mut self) -> Result<Expr, String> {
+        let lhs = self.parse_additive()?;
+        let op = match self.peek() {
+            Some(Token::Punct("==")) => BinaryOp::Eq,
+            Some(Token::Punct("!=")) => BinaryOp::Ne,
+            Some(Token::Punct("<")) => BinaryOp::Lt,
+            Some(Token::Punct("<=")) => BinaryOp::Le,
+            Some(Token::Punct(">")) => BinaryOp::Gt,
+            Some(Token::Punct(">=")) => BinaryOp::Ge,
+            _ => { return Ok(lhs); },
+        };
+        self.pos += 1;
+        let rhs = self.parse_additive()?;
+        return Ok(Expr::Binary(op, boxed(lhs), boxed(rhs)));
+    }
+    fn parse_additive(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        let mut lhs = self.parse_multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Punct("+")) => BinaryOp::Add,
+                Some(Token::Punct("-")) => BinaryOp::Sub,
+                _ => {
+                    self.depth = depth;
+                    return Ok(lhs);
+                },
+            };
+            self.pos += 1;
+            self.nest()?;
+            let rhs = self.parse_multiplicative()?;
+            lhs = Expr::Binary(op, boxed(lhs), boxed(rhs));
+        }
+    }
+    fn parse_multiplicative(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        let mut lhs = self.parse_unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Punct("*")) => BinaryOp::Mul,
+                Some(Token::Punct("/")) => BinaryOp::Div,
+                Some(Token::Punct("%")) => BinaryOp::Mod,
+                _ => {
+                    self.depth = depth;
+                    return Ok(lhs);
+                },
+            };
+            self.pos += 1;
+            self.nest()?;
+            let rhs = self.parse_unary()?;
+            lhs = Expr::Binary(op, boxed(lhs), boxed(rhs));
+        }
+    }
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        if self.eat_punct("-") {
+            return Ok(Expr::Neg(boxed(self.nested(QueryParser::parse_unary)?)));
+        }
+        return self.parse_postfix();
+    }
+
+    /**
+     * term后面跟着任意个 .name, ."name", [index], [from:to], [] 和 ?
+     */
+    fn parse_postfix(&mut self) -> Result<Expr, String> {
+        if let Some((start, end, expr)) = self.pending.take() {
+            if start == self.pos {
+                self.pos = end;
+                return expr;
+            }
+        }
+        let depth = self.depth;
+        let mut expr = self.nested(QueryParser::parse_term)?;
+        loop {
+            // 每个后缀都会让语法树深一层
+            if matches!(self.peek(), Some(Token::Field(_) | Token::Dot | Token::Punct("[") | Token::Punct("?"))) {
+                self.nest()?;
+            }
+            match self.peek() {
+                Some(Token::Field(name)) => {
+                    expr = Expr::Field(boxed(expr), name.clone());
+                    self.pos += 1;
+                },
+                Some(Token::Dot) if matches!(self.peek_at(1), Some(Token::Str(_))) => {
+                    self.pos += 1;
+                    let key = self.parse_string()?;
+                    expr = Expr::Index(boxed(expr), boxed(key));
+                },
+                Some(Token::Dot) if matches!(self.peek_at(1), Some(Token::Punct("["))) => {
+                    self.pos += 1;
+                },
+                Some(Token::Punct("[")) => {
+                    self.pos += 1;
+                    expr = self.parse_bracket(expr)?;
+                },
+                Some(Token::Punct("?")) => {
+                    self.pos += 1;
+                    expr = Expr::Try(boxed(expr), None);
+                },
+                _ => {
+                    self.depth = depth;
+                    return Ok(expr);
+                },
+            }
+        }
+    }
+    /**
+     * '[' 之后的部分: ], index], from:], :to] 或 from:to]
+     */
+    fn parse_bracket(&mut self, target: Expr) -> Result<Expr, String> {
+        if self.eat_punct("]") {
+            return Ok(Expr::Iterate(boxed(target)));
+        }
+        if self.eat_punct(":") {
+            let to = self.parse_pipe()?;
+            self.expect_punct("]")?;
+            return Ok(Expr::Slice(boxed(target), None, Some(boxed(to))));
+        }
+        let index = self.parse_pipe()?;
+        if self.eat_punct(":") {
+            let to = if self.is_punct("]") { None } else { Some(boxed(self.parse_pipe()?)) };
+            self.expect_punct("]")?;
+            return Ok(Expr::Slice(boxed(target), Some(boxed(index)), to));
+        }
+        self.expect_punct("]")?;
+        return Ok(Expr::Index(boxed(target), boxed(index)));
+    }
+    fn parse_string(&mut self) -> Result<Expr, String> {
+        let parts = match self.peek() {
+            Some(Token::Str(parts)) => parts.clone(),
+            _ => { return Err(std::format!("{},需要string", self.unexpected())); },
+        };
+        self.pos += 1;
+        let mut exprs = Vec::new();
+        for part in parts {
+            match part {
+                StrPart::Literal(text) => { exprs.push(Expr::Literal(Json::String(text))); },
+                StrPart::Interpolation(tokens) => {
+                    // 插值中的表达式在下一层嵌套中
+                    let mut inner = QueryParser { tokens, pos: 0, pending: None, depth: self.depth + 1 };
+                    let expr = inner.parse_pipe()?;
+                    if inner.pos < inner.tokens.len() {
+                        return Err(inner.unexpected());
+                    }
+                    exprs.push(expr);
+                },
+            }
+        }
+        if exprs.len() == 1 && matches!(exprs[0], Expr::Literal(_)) {
+            return Ok(exprs.remove(0));
+        }
+        return Ok(Expr::Format(exprs));
+    }
+
+    fn parse_term(&mut self) -> Result<Expr, String> {
+        let token = match self.peek() {
+            Some(token) => token.clone(),
+            None => { return Err(self.unexpected()); },
+        };
+        match token {
+            Token::Dot => {
+                if matches!(self.peek_at(1), Some(Token::Str(_))) {
+                    // ."name"由parse_postfix处理
+                    return Ok(Expr::Identity);
+                }
+                self.pos += 1;
+                return Ok(Expr::Identity);
+            },
+            Token::DotDot => {
+                self.pos += 1;
+                return Ok(Expr::Recurse);
+            },
+            Token::Field(name) => {
+                self.pos += 1;
+                return Ok(Expr::Field(boxed(Expr::Identity), name));
+            },
+            Token::Number(n) => {
+                self.pos += 1;
+                return Ok(Expr::Literal(Json::Number(n)));
+            },
+            Token::Str(_) => { return self.parse_string(); },
+            Token::Var(name) => {
+                self.pos += 1;
+                return Ok(Expr::Var(name));
+            },
+            Token::Punct("(") => {
+                self.pos += 1;
+                let expr = self.parse_pipe()?;
+                self.expect_punct(")")?;
+                return Ok(expr);
+            },
+            Token::Punct("[") => {
+                self.pos += 1;
+                if self.eat_punct("]") {
+                    return Ok(Expr::Array(None));
+                }
+                let expr = self.parse_pipe()?;
+                self.expect_punct("]")?;
+                return Ok(Expr::Array(Some(boxed(expr))));
+            },
+            Token::Punct("{") => {
+                self.pos += 1;
+                return self.parse_object();
+            },
+            Token::Ident(name) => {
+                self.pos += 1;
+                return self.parse_ident(&name);
+            },
+            _ => { return Err(self.unexpected()); },
+        }
+    }
+
+    /**
+     * 关键字开头的表达式,字面量和函数调用
+     */
+    fn parse_ident(&mut self, name: &str) -> Result<Expr, String> {
+        match name {
+            "true" => { return Ok(Expr::Literal(Json::Boolean(true))); },
+            "false" => { return Ok(Expr::Literal(Json::Boolean(false))); },
+            "null" => { return Ok(Expr::Literal(Json::Null)); },
+            "if" => {
+                let mut branches = Vec::new();
+                loop {
+                    let cond = self.parse_pipe()?;
+                    self.expect_keyword("then")?;
+                    let body = self.parse_pipe()?;
+                    branches.push((cond, body));
+                    if !self.eat_keyword("elif") {
+                        break;
+                    }
+                }
+                let otherwise = if self.eat_keyword("else") { Some(boxed(self.parse_pipe()?)) } else { None };
+                self.expect_keyword("end")?;
+                return Ok(Expr::If(branches, otherwise));
+            },
+            "reduce" | "foreach" => {
+                let source = self.parse_postfix()?;
+                self.expect_keyword("as")?;
+                let var = self.expect_var()?;
+                self.expect_punct("(")?;
+                let init = self.parse_pipe()?;
+                self.expect_punct(";")?;
+                let update = self.parse_pipe()?;
+                if name == "reduce" {
+                    self.expect_punct(")")?;
+                    return Ok(Expr::Reduce(boxed(source), var, boxed(init), boxed(update)));
+                }
+                let extract = if self.eat_punct(";") { Some(boxed(self.parse_pipe()?)) } else { None };
+                self.expect_punct(")")?;
+                return Ok(Expr::Foreach(boxed(source), var, boxed(init), boxed(update), extract));
+            },
+            "try" => {
+                let body = self.parse_postfix()?;
+                let handler = if self.eat_keyword("catch") { Some(boxed(self.parse_postfix()?)) } else { None };
+                return Ok(Expr::Try(boxed(body), handler));
+            },
+            _ if KEYWORDS.contains(&name) => {
+                self.pos -= 1;
+                return Err(self.unexpected());
+            },
+            _ => {
+                let at = self.tokens[self.pos - 1].1;
+                let mut args = Vec::new();
+                if self.eat_punct("(") {
+                    loop {
+                        args.push(self.parse_pipe()?);
+                        if !self.eat_punct(";") {
+                            break;
+                        }
+                    }
+                    self.expect_punct(")")?;
+                }
+                if !builtin::exists(name, args.len()) {
+                    return Err(std::format!("第{}个字符:没有函数{}/{}", at, name, args.len()));
+                }
+                return Ok(Expr::Call(name.to_string(), args));
+            },
+        }
+    }
+
+    /**
+     * '{' 之后的object构造: {a, $x, "k": v, (expr): v, "\(e)": v}
+     */
+    fn parse_object(&mut self) -> Result<Expr, String> {
+        let mut entries = Vec::new();
+        if self.eat_punct("}") {
+            return Ok(Expr::Object(entries));
+        }
+        loop {
+            let (key, shorthand) = self.parse_object_key()?;
+            let value = if self.eat_punct(":") {
+                self.parse_alternative()?
+            } else {
+                match shorthand {
+                    Some(value) => value,
+                    None => { return Err(std::format!("{},需要':'", self.unexpected())); },
+                }
+            };
+            entries.push((key, value));
+            if self.eat_punct("}") {
+                return Ok(Expr::Object(entries));
+            }
+            self.expect_punct(",")?;
+        }
+    }
+
+    /**
+     * object的key和省略值时的简写({a}等于{a: .a}),单独解析,递归解析值的时候不占用栈空间
+     */
+    fn parse_object_key(&mut self) -> Result<(Expr, Option<Expr>), String> {
+        let token = self.peek().cloned();
+        match token {
+            Some(Token::Var(name)) => {
+                self.pos += 1;
+                return Ok((Expr::Literal(Json::String(name.clone())), Some(Expr::Var(name))));
+            },
+            Some(Token::Ident(name)) => {
+                self.pos += 1;
+                return Ok((Expr::Literal(Json::String(name.clone())), Some(Expr::Field(boxed(Expr::Identity), name))));
+            },
+            Some(Token::Str(_)) => {
+                let key = self.parse_string()?;
+                let shorthand = Expr::Index(boxed(Expr::Identity), boxed(key.clone()));
+                return Ok((key, Some(shorthand)));
+            },
+            Some(Token::Punct("(")) => {
+                self.pos += 1;
+                let key = self.parse_pipe()?;
+                self.expect_punct(")")?;
+                return Ok((key, None));
+            },
+            _ => { return Err(std::format!("{},需要object的key", self.unexpected())); },
+        }
+    }
+}
+
+/**
+ * 错误信息中词法单元的描述
+ */
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Dot => { return "'.'".to_string(); },
+        Token::DotDot => { return "'..'".to_string(); },
+        Token::Field(name) => { return std::format!("'.{}'", name); },
+        Token::Ident(name) => { return std::format!("'{}'", name); },
+        Token::Var(name) => { return std::format!("'${}'", name); },
+        Token::Number(n) => { return std::format!("数字{}", n); },
+        Token::Str(_) => { return "string".to_string(); },
+        Token::Punct(p) => { return std::format!("'{}'", p); },
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_query_parser() {
+    assert!(matches!(parse_query("").unwrap(), Expr::Identity));
+    assert!(matches!(parse_query(".a.b[0]?").unwrap(), Expr::Try(inner, None) if matches!(*inner, Expr::Index(..))));
+    assert!(matches!(parse_query(".[] as $x | $x").unwrap(), Expr::Bind(_, name, _) if name == "x"));
+    assert!(matches!(parse_query("1, 2 | 3").unwrap(), Expr::Pipe(lhs, _) if matches!(*lhs, Expr::Comma(..))));
+    assert!(matches!(parse_query("1 + 2 * 3").unwrap(), Expr::Binary(BinaryOp::Add, _, rhs) if matches!(*rhs, Expr::Binary(BinaryOp::Mul, ..))));
+    assert!(matches!(parse_query(".a // .b // 1").unwrap(), Expr::Alternative(_, rhs) if matches!(*rhs, Expr::Alternative(..))));
+    assert!(matches!(parse_query(".a |= . + 1").unwrap(), Expr::Assign(AssignOp::Update, ..)));
+    assert!(matches!(parse_query("{a, $x, \"b\": 1, (.k): 2}").unwrap(), Expr::Object(entries) if entries.len() == 4));
+    assert!(matches!(parse_query("reduce .[] as $x (0; . + $x)").unwrap(), Expr::Reduce(..)));
+    assert!(matches!(parse_query("if . then 1 elif false then 2 end").unwrap(), Expr::If(branches, None) if branches.len() == 2));
+    assert!(matches!(parse_query(".[1:]").unwrap(), Expr::Slice(_, Some(_), None)));
+    assert!(matches!(parse_query(".\"a b\".c").unwrap(), Expr::Field(..)));
+    assert_eq!(parse_query(".a |").err().unwrap(), "查询意外结束");
+    assert_eq!(parse_query("[1, 2").err().unwrap(), "查询意外结束,需要']'");
+    assert_eq!(parse_query("map(.a; 1)").err().unwrap(), "第1个字符:没有函数map/2");
+    assert_eq!(parse_query(". then").err().unwrap(), "第3个字符:这里不能是'then'");
+}
@@ -0,0 +1,614 @@
+/**
+ * 文件名: "src/zjy/json/recover.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use crate::zjy::json::{judgment_json_type, DuplicateKeys, Json, JsonError, Limit, ParseOptions, Strictness};
+use crate::zjy::json::ast::number::NumberContext;
+use crate::zjy::json::ast::string::{StringContext, StringMode};
+use crate::zjy::json::diagnostic::{Diagnostic, DiagnosticKind, Expected};
+use crate::zjy::json::json5::{is_identifier_part, is_identifier_start};
+use crate::zjy::json::pointer;
+use crate::zjy::json::span::Position;
+
+/**
+ * 容错解析的结果
+ * value是尽量解析出的json值,缺失或者无法识别的值用Json::Null占位,
+ * placeholders是这些占位值的JSON Pointer,diagnostics是按位置排列的所有错误
+ */
+#[derive(Clone)]
+pub struct Recovered {
+    pub value: Json,
+    pub diagnostics: Vec<Diagnostic>,
+    pub placeholders: Vec<String>,
+}
+
+impl Recovered {
+    /**
+     * 判断输入是否没有任何错误
+     */
+    pub fn is_ok(&self) -> bool {
+        return self.diagnostics.is_empty();
+    }
+}
+
+impl Json {
+    /**
+     * 按照RFC 8259容错解析,适合编辑器和语言服务器:
+     * 遇到缺少的逗号,没有结束的string,没有闭合的括号和多余的字符时继续解析,返回部分结果和全部错误
+     */
+    pub fn str_to_json_tolerant(str: &str) -> Recovered {
+        return parse_str_tolerant(str, &ParseOptions::default());
+    }
+}
+
+/**
+ * 按照options中的选项容错解析
+ */
+pub(crate) fn parse_str_tolerant(str: &str, options: &ParseOptions) -> Recovered {
+    let mut parser = TolerantParser {
+        chars: str.chars().collect(),
+        index: 0,
+        pos: Position::start(),
+        options,
+        depth: 0,
+        diagnostics: Vec::new(),
+        placeholders: Vec::new(),
+    };
+    let value = parser.parse_document(str.len());
+    return Recovered { value, diagnostics: parser.diagnostics, placeholders: parser.placeholders };
+}
+
+/// 容错解析时的最大嵌套深度,超过时跳过整个array或object
+const MAX_TOLERANT_DEPTH: usize = 512;
+
+/**
+ * 容错解析器,逐个字符读取输入,出错时记录诊断信息并尽量继续
+ */
+struct TolerantParser<'a> {
+    chars: Vec<char>,
+    /// 下一个字符的下标
+    index: usize,
+    /// 下一个字符的位置
+    pos: Position,
+    options: &'a ParseOptions,
+    depth: usize,
+    diagnostics: Vec<Diagnostic>,
+    placeholders: Vec<String>,
+}
+
+impl<'a> TolerantParser<'a> {
+    fn peek(&self) -> Option<char> {
+        return self.chars.get(self.index).copied();
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.index += 1;
+        self.pos = self.pos.advance(c);
+        return Some(c);
+    }
+
+    /**
+     * 在当前位置记录一个语法错误
+     */
+    fn report(&mut self, kind: DiagnosticKind, expected: Vec<Expected>, msg: &str) {
+        let at = (self.pos, self.index);
+        self.report_at(at, kind, expected, msg);
+    }
+
+    fn report_at(&mut self, (position, index): (Position, usize), kind: DiagnosticKind, expected: Vec<Expected>, msg: &str) {
+        // 同一个位置只报告第一个错误,后面的通常是同一个错误引起的
+        if matches!(self.diagnostics.last(), Some(last) if last.position == position) {
+            return;
+        }
+        let error = JsonError::from(msg.to_string()).at(index + 1);
+        self.diagnostics.push(Diagnostic { kind, position, expected, error });
+    }
+
+    fn report_limit(&mut self, result: Result<(), JsonError>) {
+        if let Err(JsonError::LimitExceeded(limit)) = result {
+            self.diagnostics.push(Diagnostic { kind: DiagnosticKind::LimitExceeded(limit), position: self.pos, expected: Vec::new(), error: JsonError::LimitExceeded(limit) });
+        }
+    }
+
+    /**
+     * 缺少值的地方使用null占位
+     */
+    fn placeholder(&mut self, pointer: &str) -> Json {
+        self.placeholders.push(pointer.to_string());
+        return Json::Null;
+    }
+
+    /**
+     * 当前字符的意外情况: 输入结束或者不应该出现的字符
+     */
+    fn unexpected_kind(&self) -> DiagnosticKind {
+        match self.peek() {
+            Some(c) => { return DiagnosticKind::UnexpectedChar(c); },
+            None => { return DiagnosticKind::UnexpectedEnd; },
+        }
+    }
+
+    fn parse_document(&mut self, len: usize) -> Json {
+        if len == 0 {
+            self.report(DiagnosticKind::UnexpectedEnd, vec![Expected::Value], "str参数长度为0");
+            return self.placeholder("");
+        }
+        if let Err(err) = self.options.check_total_bytes(len) {
+            self.report_limit(Err(err));
+            return self.placeholder("");
+        }
+        let value = self.parse_value("");
+        self.skip_trivia();
+        if self.peek().is_some() && self.options.strictness == Strictness::Strict {
+            let c = self.peek().unwrap_or(' ');
+            self.report(DiagnosticKind::UnexpectedChar(c), vec![Expected::EndOfInput], &std::format!("json已经结束,此处不应是:'{}'", c));
+        }
+        return value;
+    }
+
+    /**
+     * 跳过空白和注释,没有开启的注释和不允许的空白会被报告,但仍然跳过
+     */
+    fn skip_trivia(&mut self) {
+        while let Some(c) = self.peek() {
+            if self.options.whitespace.contains(c) {
+                self.bump();
+            } else if c.is_whitespace() {
+                self.report(DiagnosticKind::UnexpectedChar(c), Vec::new(), &std::format!("不允许的空白:'{}'", c.escape_unicode()));
+                self.bump();
+            } else if c == '/' && matches!(self.chars.get(self.index + 1), Some('/') | Some('*')) {
+                if !self.options.extensions.comments {
+                    self.report(DiagnosticKind::Invalid, Vec::new(), "没有开启注释扩展,不允许注释");
+                }
+                self.bump();
+                if self.bump() == Some('/') {
+                    while !matches!(self.peek(), None | Some('\n') | Some('\r') | Some('\u{2028}') | Some('\u{2029}')) {
+                        self.bump();
+                    }
+                } else {
+                    loop {
+                        match self.bump() {
+                            None => {
+                                self.report(DiagnosticKind::UnexpectedEnd, Vec::new(), "多行注释没有结束,缺少'*/'");
+                                break;
+                            },
+                            Some('*') if self.peek() == Some('/') => {
+                                self.bump();
+                                break;
+                            },
+                            _ => {},
+                        }
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    /**
+     * 判断c是否可以作为一个值的开始,用于判断是否缺少逗号
+     */
+    fn starts_value(&self, c: char) -> bool {
+        return judgment_json_type(c, self.options).is_ok() || c == '\'';
+    }
+    /**
+     * 判断接下来是否是 "key": 的形式,array中出现这种形式说明缺少 ']'
+     */
+    fn looks_like_key(&self) -> bool {
+        let quote = match self.peek() {
+            Some(c) if c == '"' || c == '\'' => c,
+            _ => { return false; },
+        };
+        let mut i = self.index + 1;
+        while let Some(&c) = self.chars.get(i) {
+            match c {
+                '\\' => { i += 1; },
+                '\n' | '\r' => { return false; },
+                _ if c == quote => { break; },
+                _ => {},
+            }
+            i += 1;
+        }
+        i += 1;
+        while matches!(self.chars.get(i), Some(c) if self.options.whitespace.contains(*c)) {
+            i += 1;
+        }
+        return self.chars.get(i) == Some(&':');
+    }
+
+    fn parse_value(&mut self, pointer: &str) -> Json {
+        loop {
+            self.skip_trivia();
+            match self.peek() {
+                None => {
+                    self.report(DiagnosticKind::UnexpectedEnd, vec![Expected::Value], "缺少json值");
+                    return self.placeholder(pointer);
+                },
+                Some('{') | Some('[') => { return self.parse_container(pointer); },
+                Some('"') | Some('\'') => { return Json::String(self.parse_string()); },
+                Some(c) if c.is_ascii_digit() || c == '-' || (self.options.extensions.json5_numbers && (c == '+' || c == '.')) => {
+                    return self.parse_number(pointer);
+                },
+                Some(c) if is_identifier_start(c) => { return self.parse_word(pointer); },
+                Some(c) if matches!(c, ',' | ']' | '}' | ':') => {
+                    self.report(DiagnosticKind::UnexpectedChar(c), vec![Expected::Value], "缺少json值");
+                    return self.placeholder(pointer);
+                },
+                Some(c) => {
+                    // 无法开始任何值的字符,跳过之后重新尝试
+                    self.report(DiagnosticKind::UnexpectedChar(c), vec![Expected::Value], &std::format!("此处不应是:'{}'", c));
+                    self.bump();
+                },
+            }
+        }
+    }
+
+    /**
+     * 读取null,true,false(json5中还有Infinity和NaN),其他单词用null占位
+     */
+    fn parse_word(&mut self, pointer: &str) -> Json {
+        let at = (self.pos, self.index);
+        let mut word = String::new();
+        while let Some(c) = self.peek() {
+            if !is_identifier_part(c) {
+                break;
+            }
+            word.push(c);
+            self.bump();
+        }
+        match word.as_str() {
+            "null" => { return Json::Null; },
+            "true" => { return Json::Boolean(true); },
+            "false" => { return Json::Boolean(false); },
+            "Infinity" if self.options.extensions.json5_numbers => { return Json::Number(f64::INFINITY); },
+            "NaN" if self.options.extensions.json5_numbers => { return Json::Number(f64::NAN); },
+            _ => {
+                let expected = ["null", "true", "false"].iter().find(|literal| literal.starts_with(word.as_str()) || word.starts_with(*literal));
+                let expected = expected.map(|literal| vec![Expected::Literal(literal)]).unwrap_or_else(|| vec![Expected::Value]);
+                self.report_at(at, DiagnosticKind::Invalid, expected, &std::format!("无法识别的值:'{}'", word));
+                return self.placeholder(pointer);
+            },
+        }
+    }
+
+    fn parse_number(&mut self, pointer: &str) -> Json {
+        let at = (self.pos, self.index);
+        let mut context = NumberContext::new();
+        while let Some(c) = self.peek() {
+            if !NumberContext::is_number_char(c, self.options) {
+                break;
+            }
+            context.get_number_str_mut().push(c);
+            self.bump();
+        }
+        let len = context.get_number_str_mut().len();
+        self.report_limit(self.options.check_number_length(len));
+        match context.check_number("", self.options) {
+            Ok(_) => { return Json::Number(context.get_number()); },
+            Err(err) => {
+                self.report_at(at, DiagnosticKind::InvalidNumber, Vec::new(), &std::format!("不是一个合法的数字,错误对象:{}", err));
+                // 尽量按照浮点数解析,仍然失败时用null占位
+                match context.get_number_str_mut().parse::<f64>() {
+                    Ok(number) if number.is_finite() => { return Json::Number(number); },
+                    _ => { return self.placeholder(pointer); },
+                }
+            },
+        }
+    }
+
+    /**
+     * 读取string,不合法的转义和控制字符按原样保留,
+     * 在行尾或输入结束时还没有结束的string会被自动结束
+     */
+    fn parse_string(&mut self) -> String {
+        let quote = self.bump().unwrap_or('"');
+        if quote == '\'' && !self.options.extensions.single_quoted_strings {
+            self.report(DiagnosticKind::Invalid, Vec::new(), "没有开启单引号string扩展");
+        }
+        let mut context = StringContext::new_str(quote);
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => {
+                    self.report(DiagnosticKind::UnexpectedEnd, vec![Expected::Char(quote)], &std::format!("这个string缺少 '{}' 结束", quote));
+                    break;
+                },
+            };
+            if (c == '\n' || c == '\r') && context.mode == StringMode::str {
+                self.report(DiagnosticKind::InvalidString, vec![Expected::Char(quote)], &std::format!("这个string缺少 '{}' 结束", quote));
+                break;
+            }
+            let at = (self.pos, self.index);
+            self.bump();
+            if context.check_string(c, self.options).is_err() {
+                self.report_at(at, DiagnosticKind::InvalidString, Vec::new(), &std::format!("string中不合法的字符:'{}'", c.escape_debug()));
+                // 保留已经解析的内容,从string正文模式继续
+                let str = std::mem::take(&mut context.str);
+                context = StringContext::new_str(quote);
+                context.str = str;
+                if c == quote {
+                    break;
+                }
+                context.str.push(c);
+            }
+            if context.mode == StringMode::end {
+                break;
+            }
+        }
+        self.report_limit(self.options.check_string_length(context.str.len()));
+        return context.str;
+    }
+
+    /**
+     * 超出嵌套深度时跳过整个array或object,返回null占位
+     */
+    fn skip_container(&mut self, pointer: &str) -> Json {
+        let mut depth = 0usize;
+        while let Some(c) = self.peek() {
+            match c {
+                '"' | '\'' => {
+                    self.parse_string();
+                    continue;
+                },
+                '[' | '{' => { depth += 1; },
+                ']' | '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.bump();
+                        break;
+                    }
+                },
+                _ => {},
+            }
+            self.bump();
+        }
+        return self.placeholder(pointer);
+    }
+
+    /**
+     * 解析array或者object
+     */
+    fn parse_container(&mut self, pointer: &str) -> Json {
+        let is_object = self.peek() == Some('{');
+        // 容错解析是递归的,即使options没有限制深度也要限制,避免栈溢出
+        let checked = if self.depth + 1 > MAX_TOLERANT_DEPTH {
+            Err(JsonError::LimitExceeded(Limit::Depth(MAX_TOLERANT_DEPTH)))
+        } else {
+            self.options.check_depth(self.depth + 1)
+        };
+        if let Err(err) = checked {
+            self.report_limit(Err(err));
+            return self.skip_container(pointer);
+        }
+        self.depth += 1;
+        let value = if is_object { self.parse_object(pointer) } else { self.parse_array(pointer) };
+        self.depth -= 1;
+        return value;
+    }
+
+    /**
+     * 处理array或object中一个元素后面的字符: 逗号,结束括号,或者缺少逗号
+     * 返回是否读到了逗号
+     */
+    fn after_item(&mut self, close: char) -> bool {
+        // 连续的多余字符只报告第一个,循环跳过(编辑器中的内容可能有很长的一段多余字符,不能递归)
+        let mut skipping = false;
+        loop {
+            self.skip_trivia();
+            match self.peek() {
+                Some(',') => {
+                    self.bump();
+                    return true;
+                },
+                Some(c) if c == close || c == ']' || c == '}' => { return false; },
+                None => { return false; },
+                Some(_) if close == ']' && self.looks_like_key() => { return false; },
+                Some(c) if self.starts_value(c) => {
+                    if !skipping {
+                        self.report(DiagnosticKind::UnexpectedChar(c), vec![Expected::Char(','), Expected::Char(close)], "缺少 ','");
+                    }
+                    return false;
+                },
+                Some(c) => {
+                    if !skipping {
+                        self.report(DiagnosticKind::UnexpectedChar(c), vec![Expected::Char(','), Expected::Char(close)], &std::format!("此处需要 ',' 但实际为{}", c));
+                        skipping = true;
+                    }
+                    self.bump();
+                },
+            }
+        }
+    }
+
+    /**
+     * 处理array或object的结束: 返回true代表已经结束
+     * 缺少结束括号(输入结束或者遇到另一种结束括号)时报告错误并结束,不读取另一种括号
+     */
+    fn check_close(&mut self, close: char, after_comma: bool, empty: bool) -> bool {
+        match self.peek() {
+            Some(c) if c == close => {
+                if after_comma && !empty && !self.options.extensions.trailing_commas {
+                    self.report(DiagnosticKind::Invalid, vec![Expected::Value], "没有开启末尾逗号扩展,不允许末尾逗号");
+                }
+                self.bump();
+                return true;
+            },
+            Some(c) if c == ']' || c == '}' => {
+                self.report(DiagnosticKind::UnexpectedChar(c), vec![Expected::Char(close)], &std::format!("缺少 '{}'", close));
+                return true;
+            },
+            None => {
+                self.report(DiagnosticKind::UnexpectedEnd, vec![Expected::Char(close)], &std::format!("缺少 '{}'", close));
+                return true;
+            },
+            _ => { return false; },
+        }
+    }
+
+    fn parse_array(&mut self, pointer: &str) -> Json {
+        self.bump();
+        let mut arr = Vec::new();
+        let mut after_comma = false;
+        loop {
+            self.skip_trivia();
+            if self.check_close(']', after_comma, arr.is_empty()) {
+                break;
+            }
+            if self.looks_like_key() {
+                let kind = self.unexpected_kind();
+                self.report(kind, vec![Expected::Char(']')], "缺少 ']'");
+                break;
+            }
+            let child = pointer::join(pointer, &arr.len().to_string());
+            if self.peek() == Some(',') {
+                // 两个逗号之间缺少值
+                let kind = self.unexpected_kind();
+                self.report(kind, vec![Expected::Value], "缺少json值");
+                self.bump();
+                let value = self.placeholder(&child);
+                arr.push(value);
+                after_comma = true;
+                continue;
+            }
+            let value = self.parse_value(&child);
+            arr.push(value);
+            self.report_limit(self.options.check_array_length(arr.len()));
+            after_comma = self.after_item(']');
+        }
+        return Json::Array(arr);
+    }
+
+    fn parse_object(&mut self, pointer: &str) -> Json {
+        self.bump();
+        let mut obj: HashMap<String, Json> = HashMap::new();
+        let mut after_comma = false;
+        loop {
+            self.skip_trivia();
+            if self.check_close('}', after_comma, obj.is_empty()) {
+                break;
+            }
+            // key
+            let at = (self.pos, self.index);
+            let key = match self.peek() {
+                Some('"') | Some('\'') => self.parse_string(),
+                Some(c) if is_identifier_start(c) => {
+                    let mut key = String::new();
+                    while let Some(c) = self.peek() {
+                        if !is_identifier_part(c) {
+                            break;
+                        }
+                        key.push(c);
+                        self.bump();
+                    }
+                    if !self.options.extensions.unquoted_keys {
+                        self.report_at(at, DiagnosticKind::Invalid, vec![Expected::Key], &std::format!("object中的key需要引号:{}", key));
+                    }
+                    key
+                },
+                Some(c) => {
+                    self.report(DiagnosticKind::UnexpectedChar(c), vec![Expected::Key, Expected::Char('}')], &std::format!("这个object中的name不合法:'{}'", c));
+                    self.bump();
+                    continue;
+                },
+                None => { continue; },
+            };
+            // 冒号
+            self.skip_trivia();
+            if self.peek() == Some(':') {
+                self.bump();
+            } else {
+                let kind = self.unexpected_kind();
+                self.report(kind, vec![Expected::Char(':')], "此处需要 ':'");
+            }
+            let value = self.parse_value(&pointer::join(pointer, &key));
+            match obj.entry(key) {
+                Entry::Vacant(entry) => { entry.insert(value); },
+                Entry::Occupied(mut entry) => {
+                    if self.options.duplicate_keys == DuplicateKeys::Error {
+                        let message = std::format!("object中的key重复:\"{}\"", entry.key());
+                        self.report_at(at, DiagnosticKind::Invalid, Vec::new(), &message);
+                    }
+                    // 重复的key按照选项保留第一个或者最后一个值,报错时也保留最后一个值
+                    if self.options.duplicate_keys != DuplicateKeys::First {
+                        entry.insert(value);
+                    }
+                },
+            }
+            self.report_limit(self.options.check_object_members(obj.len()));
+            after_comma = self.after_item('}');
+        }
+        return Json::Object(obj);
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_tolerant() {
+    // 合法的输入和严格解析结果相同
+    let str = std::fs::read_to_string("tests/testobject.json").unwrap();
+    let recovered = Json::str_to_json_tolerant(&str);
+    assert!(recovered.is_ok());
+    assert_eq!(recovered.value.to_json5_string(), Json::str_to_json(&str).unwrap().to_json5_string());
+
+    let source = "{
+  \"a\": 1
+  \"b\": [1, 2,, 3
+  \"c\": \"没有结束
+  \"d\": tru,
+  e: 5 x,
+  \"f\": {\"g\": [}
+";
+    let recovered = Json::str_to_json_tolerant(source);
+    let kinds: Vec<(usize, DiagnosticKind)> = recovered.diagnostics.iter().map(|d| (d.position.line, d.kind)).collect();
+    assert_eq!(kinds, vec![
+        (3, DiagnosticKind::UnexpectedChar('"')),
+        (3, DiagnosticKind::UnexpectedChar(',')),
+        (4, DiagnosticKind::UnexpectedChar('"')),
+        (4, DiagnosticKind::InvalidString),
+        (5, DiagnosticKind::UnexpectedChar('"')),
+        (5, DiagnosticKind::Invalid),
+        (6, DiagnosticKind::Invalid),
+        (6, DiagnosticKind::UnexpectedChar('x')),
+        (7, DiagnosticKind::UnexpectedChar('}')),
+        (8, DiagnosticKind::UnexpectedEnd),
+    ]);
+    let value = &recovered.value;
+    assert_eq!(value.pointer("/a").unwrap().to_string(), "1");
+    assert_eq!(value.pointer("/b").unwrap().to_string(), "[1,2,null,3]");
+    assert_eq!(value.pointer("/c").unwrap().to_string(), "\"没有结束\"");
+    assert_eq!(value.pointer("/e").unwrap().to_string(), "5");
+    assert_eq!(value.pointer("/f/g").unwrap().to_json5_string(), "[]");
+    assert_eq!(recovered.placeholders, vec!["/b/2", "/d"]);
+    // 诊断信息可以渲染
+    assert!(recovered.diagnostics[0].render(source).contains("^ 需要 ',' 或 '}'"));
+    // 超出嵌套深度时跳过
+    let recovered = parse_str_tolerant("[[[1]], 2", &ParseOptions { max_depth: 2, ..ParseOptions::default() });
+    assert_eq!(recovered.value.to_string(), "[[null],2]");
+    assert_eq!(recovered.diagnostics.len(), 2);
+    // 很长的多余字符和很深的嵌套不会栈溢出,连续的多余字符只报告一次
+    let recovered = Json::str_to_json_tolerant(&std::format!("[1 {}", "#".repeat(1_000_000)));
+    assert_eq!(recovered.value.to_string(), "[1]");
+    assert_eq!(recovered.diagnostics.len(), 2);
+    let recovered = parse_str_tolerant(&"[".repeat(200_000), &ParseOptions { max_depth: usize::MAX, ..ParseOptions::default() });
+    assert!(recovered.diagnostics.iter().any(|d| d.kind == DiagnosticKind::LimitExceeded(Limit::Depth(MAX_TOLERANT_DEPTH))));
+}
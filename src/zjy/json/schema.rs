@@ -0,0 +1,343 @@
+/**
+ * 文件名: "src/zjy/json/schema.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::collections::HashMap;
+
+use crate::zjy::json::Json;
+
+/// 生成的schema使用的JSON Schema版本
+pub const SCHEMA_DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/**
+ * 从样本推断JSON Schema: 合并所有样本中object的成员,所有样本中都出现的key是required,
+ * 同一个位置出现多种类型时type是array,array的所有元素合并成一个items,
+ * number记录最小值和最大值(全部是整数时type是integer),所有string都是同一种格式时记录format(date,date-time,uuid,email)
+ */
+#[derive(Clone, Debug, Default)]
+pub struct SchemaInferrer {
+    shape: Shape,
+}
+
+/**
+ * 一个位置上观察到的所有值的形状
+ */
+#[derive(Clone, Debug, Default)]
+struct Shape {
+    null: bool,
+    boolean: bool,
+    number: Option<NumberShape>,
+    string: Option<StringShape>,
+    /// array元素的形状,没有元素时是默认值
+    array: Option<Box<Shape>>,
+    object: Option<ObjectShape>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct NumberShape {
+    min: f64,
+    max: f64,
+    integer: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct StringShape {
+    /// 所有string共同的格式,有一个不符合时是None
+    format: Option<&'static str>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct ObjectShape {
+    /// 观察到的object个数
+    count: usize,
+    /// 每个key的形状和出现的次数
+    properties: HashMap<String, (Shape, usize)>,
+}
+
+impl SchemaInferrer {
+    pub fn new() -> SchemaInferrer {
+        return SchemaInferrer::default();
+    }
+    /**
+     * 加入一个样本
+     */
+    pub fn add(&mut self, sample: &Json) {
+        self.shape.add(sample);
+    }
+    /**
+     * 输出推断出的schema,带有"$schema"
+     */
+    pub fn to_schema(&self) -> Json {
+        let mut schema = match self.shape.to_schema() {
+            Json::Object(schema) => schema,
+            _ => HashMap::new(),
+        };
+        schema.insert("$schema".to_string(), Json::String(SCHEMA_DRAFT.to_string()));
+        return Json::Object(schema);
+    }
+}
+
+impl Json {
+    /**
+     * 从一个或多个样本推断JSON Schema
+     */
+    pub fn infer_schema<'a>(samples: impl IntoIterator<Item = &'a Json>) -> Json {
+        let mut inferrer = SchemaInferrer::new();
+        for sample in samples {
+            inferrer.add(sample);
+        }
+        return inferrer.to_schema();
+    }
+}
+
+impl Shape {
+    fn add(&mut self, value: &Json) {
+        match value {
+            Json::Null => { self.null = true; },
+            Json::Boolean(_) => { self.boolean = true; },
+            Json::Number(n) => {
+                let integer = n.is_finite() && n.fract() == 0.0;
+                self.number = Some(match self.number {
+                    Some(shape) => NumberShape { min: shape.min.min(*n), max: shape.max.max(*n), integer: shape.integer && integer },
+                    None => NumberShape { min: *n, max: *n, integer },
+                });
+            },
+            Json::String(s) => {
+                let format = string_format(s);
+                self.string = Some(match self.string {
+                    Some(shape) => StringShape { format: shape.format.filter(|f| Some(*f) == format) },
+                    None => StringShape { format },
+                });
+            },
+            Json::Array(arr) => {
+                let items = self.array.get_or_insert_with(Box::default);
+                for item in arr {
+                    items.add(item);
+                }
+            },
+            Json::Object(obj) => {
+                let shape = self.object.get_or_insert_with(ObjectShape::default);
+                shape.count += 1;
+                for (key, item) in obj {
+                    let (property, count) = shape.properties.entry(key.clone()).or_default();
+                    property.add(item);
+                    *count += 1;
+                }
+            },
+        }
+    }
+
+    fn to_schema(&self) -> Json {
+        let mut schema = HashMap::new();
+        let mut types = Vec::new();
+        if self.null {
+            types.push("null");
+        }
+        if self.boolean {
+            types.push("boolean");
+        }
+        if let Some(number) = &self.number {
+            types.push(if number.integer { "integer" } else { "number" });
+            if number.min.is_finite() && number.max.is_finite() {
+                schema.insert("minimum".to_string(), Json::Number(number.min));
+                schema.insert("maximum".to_string(), Json::Number(number.max));
+            }
+        }
+        if let Some(string) = &self.string {
+            types.push("string");
+            if let Some(format) = string.format {
+                schema.insert("format".to_string(), Json::String(format.to_string()));
+            }
+        }
+        if let Some(items) = &self.array {
+            types.push("array");
+            if !items.is_empty() {
+                schema.insert("items".to_string(), items.to_schema());
+            }
+        }
+        if let Some(object) = &self.object {
+            types.push("object");
+            let mut properties = HashMap::new();
+            let mut required = Vec::new();
+            for (key, (shape, count)) in &object.properties {
+                properties.insert(key.clone(), shape.to_schema());
+                if *count == object.count {
+                    required.push(key.clone());
+                }
+            }
+            required.sort();
+            schema.insert("properties".to_string(), Json::Object(properties));
+            if !required.is_empty() {
+                schema.insert("required".to_string(), Json::Array(required.into_iter().map(Json::String).collect()));
+            }
+        }
+        match types.len() {
+            0 => {},
+            1 => { schema.insert("type".to_string(), Json::String(types[0].to_string())); },
+            _ => { schema.insert("type".to_string(), Json::Array(types.iter().map(|t| Json::String(t.to_string())).collect())); },
+        }
+        return Json::Object(schema);
+    }
+
+    /**
+     * 是否还没有观察到任何值
+     */
+    fn is_empty(&self) -> bool {
+        return !self.null && !self.boolean && self.number.is_none() && self.string.is_none() && self.array.is_none() && self.object.is_none();
+    }
+}
+
+/**
+ * 识别string的格式,不是已知的格式时返回None
+ */
+fn string_format(s: &str) -> Option<&'static str> {
+    if is_date(s) {
+        return Some("date");
+    }
+    if is_date_time(s) {
+        return Some("date-time");
+    }
+    if is_uuid(s) {
+        return Some("uuid");
+    }
+    if is_email(s) {
+        return Some("email");
+    }
+    return None;
+}
+
+/**
+ * 从bytes中读取固定位数的十进制数字
+ */
+fn digits(bytes: &[u8]) -> Option<u32> {
+    if bytes.is_empty() || !bytes.iter().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    return Some(bytes.iter().fold(0, |n, b| n * 10 + (b - b'0') as u32));
+}
+
+/**
+ * RFC 3339的full-date: YYYY-MM-DD
+ */
+fn is_date(s: &str) -> bool {
+    let b = s.as_bytes();
+    if b.len() != 10 || b[4] != b'-' || b[7] != b'-' {
+        return false;
+    }
+    let (year, month, day) = match (digits(&b[0..4]), digits(&b[5..7]), digits(&b[8..10])) {
+        (Some(year), Some(month), Some(day)) => (year, month, day),
+        _ => { return false; },
+    };
+    let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 => if leap { 29 } else { 28 },
+        _ => { return false; },
+    };
+    return day >= 1 && day <= days;
+}
+
+/**
+ * RFC 3339的date-time: YYYY-MM-DDTHH:MM:SS[.小数](Z|+HH:MM|-HH:MM)
+ */
+fn is_date_time(s: &str) -> bool {
+    let b = s.as_bytes();
+    if b.len() < 20 || !s.is_char_boundary(10) || !is_date(&s[..10]) || !matches!(b[10], b'T' | b't' | b' ') {
+        return false;
+    }
+    let time = &b[11..];
+    if time.len() < 9 || time[2] != b':' || time[5] != b':' {
+        return false;
+    }
+    match (digits(&time[0..2]), digits(&time[3..5]), digits(&time[6..8])) {
+        (Some(h), Some(m), Some(sec)) if h < 24 && m < 60 && sec <= 60 => {},
+        _ => { return false; },
+    }
+    let mut rest = &time[8..];
+    if rest.first() == Some(&b'.') {
+        let len = rest[1..].iter().take_while(|b| b.is_ascii_digit()).count();
+        if len == 0 {
+            return false;
+        }
+        rest = &rest[1 + len..];
+    }
+    match rest {
+        [b'Z' | b'z'] => { return true; },
+        [b'+' | b'-', h1, h2, b':', m1, m2] => {
+            return matches!((digits(&[*h1, *h2]), digits(&[*m1, *m2])), (Some(h), Some(m)) if h < 24 && m < 60);
+        },
+        _ => { return false; },
+    }
+}
+
+/**
+ * 8-4-4-4-12个十六进制数字
+ */
+fn is_uuid(s: &str) -> bool {
+    let groups: Vec<&str> = s.split('-').collect();
+    let lens = [8, 4, 4, 4, 12];
+    return groups.len() == 5 && groups.iter().zip(lens).all(|(group, len)| group.len() == len && group.bytes().all(|b| b.is_ascii_hexdigit()));
+}
+
+/**
+ * 简单的邮箱地址: local@domain,domain中至少有一个 '.',都不能有空白
+ */
+fn is_email(s: &str) -> bool {
+    let (local, domain) = match s.split_once('@') {
+        Some(parts) => parts,
+        None => { return false; },
+    };
+    if local.is_empty() || domain.contains('@') || s.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return false;
+    }
+    return domain.split('.').count() >= 2 && domain.split('.').all(|label| !label.is_empty() && !label.starts_with('-') && !label.ends_with('-'));
+}
+
+#[cfg(test)]
+#[test]
+fn test_infer_schema() {
+    let samples: Vec<Json> = [
+        r#"{"id":"6f1c2a9e-3b4d-4c5e-8f90-1a2b3c4d5e6f","name":"张三","age":30,"email":"zs@example.com","born":"1994-02-28","tags":["a"],"score":9.5}"#,
+        r#"{"id":"0b1c2a9e-3b4d-4c5e-8f90-1a2b3c4d5e6f","name":"李四","age":41,"email":null,"born":"1983-12-01","tags":[],"login":"2024-05-06T07:08:09.5+08:00"}"#,
+    ].iter().map(|s| Json::str_to_json(s).unwrap()).collect();
+    let schema = Json::infer_schema(&samples);
+    let get = |pointer: &str| schema.pointer(pointer).map(|json| json.to_json5_string()).unwrap_or_default();
+    assert_eq!(get("/$schema"), std::format!("\"{}\"", SCHEMA_DRAFT));
+    assert_eq!(get("/type"), "\"object\"");
+    assert_eq!(get("/required"), r#"["age","born","email","id","name","tags"]"#);
+    assert_eq!(get("/properties/id/format"), "\"uuid\"");
+    assert_eq!(get("/properties/born/format"), "\"date\"");
+    assert_eq!(get("/properties/login/format"), "\"date-time\"");
+    assert_eq!(get("/properties/email"), r#"{format:"email",type:["null","string"]}"#);
+    assert_eq!(get("/properties/age"), r#"{maximum:41,minimum:30,type:"integer"}"#);
+    assert_eq!(get("/properties/score/type"), "\"number\"");
+    assert_eq!(get("/properties/name"), r#"{type:"string"}"#);
+    assert_eq!(get("/properties/tags"), r#"{items:{type:"string"},type:"array"}"#);
+
+    // 测试文件中的混合类型
+    let array = Json::str_to_json(&std::fs::read_to_string("tests/testarray.json").unwrap()).unwrap();
+    let schema = Json::infer_schema([&array]);
+    assert_eq!(schema.pointer("/items/type").unwrap().to_json5_string(), r#"["null","boolean","number","string","array","object"]"#);
+    assert_eq!(Json::infer_schema([]).to_json5_string(), std::format!("{{$schema:\"{}\"}}", SCHEMA_DRAFT));
+
+    assert!(is_date("2024-02-29") && !is_date("2023-02-29") && !is_date("2024-13-01"));
+    assert!(is_date_time("2024-01-01T00:00:00Z") && !is_date_time("2024-01-01T25:00:00Z"));
+    assert!(is_email("a.b@c.cn") && !is_email("a@b") && !is_email("a b@c.cn"));
+    assert!(!is_uuid("6f1c2a9e-3b4d-4c5e-8f90-1a2b3c4d5e6"));
+}
@@ -0,0 +1,397 @@
+/**
+ * 文件名: "src/zjy/json/simd.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/**
+ * 批量扫描字节时使用的指令集
+ * Portable代表不使用SIMD,一次处理8个字节(SWAR)
+ * Sse2和Avx2分别一次处理16和32个字节,只在x86_64上运行时检测
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SimdLevel {
+    Portable,
+    Sse2,
+    Avx2,
+}
+
+/// 0代表还没有检测,其他值是SimdLevel加一
+static LEVEL: AtomicU8 = AtomicU8::new(0);
+
+/**
+ * 获取当前cpu可用的指令集,只在第一次调用时检测,之后读取缓存
+ */
+pub fn simd_level() -> SimdLevel {
+    match LEVEL.load(Ordering::Relaxed) {
+        1 => { return SimdLevel::Portable; },
+        2 => { return SimdLevel::Sse2; },
+        3 => { return SimdLevel::Avx2; },
+        _ => {},
+    }
+    let level = detect();
+    let tag = match level {
+        SimdLevel::Portable => 1,
+        SimdLevel::Sse2 => 2,
+        SimdLevel::Avx2 => 3,
+    };
+    LEVEL.store(tag, Ordering::Relaxed);
+    return level;
+}
+
+#[cfg(target_arch = "x86_64")]
+fn detect() -> SimdLevel {
+    if is_x86_feature_detected!("avx2") {
+        return SimdLevel::Avx2;
+    }
+    if is_x86_feature_detected!("sse2") {
+        return SimdLevel::Sse2;
+    }
+    return SimdLevel::Portable;
+}
+#[cfg(not(target_arch = "x86_64"))]
+fn detect() -> SimdLevel {
+    return SimdLevel::Portable;
+}
+
+/**
+ * 把指定的指令集限制在当前cpu支持的范围内,避免在不支持的cpu上执行SIMD指令
+ */
+fn supported(level: SimdLevel) -> SimdLevel {
+    let rank = |level: SimdLevel| -> u8 {
+        match level {
+            SimdLevel::Portable => { return 0; },
+            SimdLevel::Sse2 => { return 1; },
+            SimdLevel::Avx2 => { return 2; },
+        }
+    };
+    let detected = simd_level();
+    return if rank(level) <= rank(detected) { level } else { detected };
+}
+
+/**
+ * 从start开始,查找字符串正文中第一个需要特殊处理的字节:
+ * 引号 '"' ,反斜杠 '\\' ,以及必须转义的控制字符(0x00到0x1F)
+ * # 返回值: 找到的下标,如果没有找到,返回bytes.len()
+ */
+pub fn find_string_special(bytes: &[u8], start: usize) -> usize {
+    return find_string_special_with(simd_level(), bytes, start);
+}
+/**
+ * 和find_string_special相同,但是指定使用的指令集,用于测试各个实现是否一致;
+ * 当前cpu不支持指定的指令集时使用支持的最高的指令集
+ */
+pub fn find_string_special_with(level: SimdLevel, bytes: &[u8], start: usize) -> usize {
+    if start >= bytes.len() {
+        return bytes.len();
+    }
+    match supported(level) {
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx2 => { return unsafe { x86::find_string_special_avx2(bytes, start) }; },
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Sse2 => { return unsafe { x86::find_string_special_sse2(bytes, start) }; },
+        _ => { return portable::find_string_special(bytes, start); },
+    }
+}
+
+/**
+ * 从start开始,查找第一个结构字符: { } [ ] : , 以及引号 '"'
+ * # 返回值: 找到的下标,如果没有找到,返回bytes.len()
+ */
+pub fn find_structural(bytes: &[u8], start: usize) -> usize {
+    return find_structural_with(simd_level(), bytes, start);
+}
+/**
+ * 和find_structural相同,但是指定使用的指令集,同样不会超过当前cpu支持的指令集
+ */
+pub fn find_structural_with(level: SimdLevel, bytes: &[u8], start: usize) -> usize {
+    if start >= bytes.len() {
+        return bytes.len();
+    }
+    match supported(level) {
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx2 => { return unsafe { x86::find_structural_avx2(bytes, start) }; },
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Sse2 => { return unsafe { x86::find_structural_sse2(bytes, start) }; },
+        _ => { return portable::find_structural(bytes, start); },
+    }
+}
+
+/**
+ * 从start开始跳过json空白字符(空格,\t,\n,\r)
+ * # 返回值: 第一个非空白字符的下标,如果没有,返回bytes.len()
+ */
+pub fn skip_whitespace(bytes: &[u8], start: usize) -> usize {
+    let mut i = start;
+    // 绝大多数情况下空白很短,先逐个判断
+    while i < bytes.len() && i < start + 8 {
+        if !is_whitespace(bytes[i]) {
+            return i;
+        }
+        i += 1;
+    }
+    if i >= bytes.len() {
+        return bytes.len();
+    }
+    // 空白很长(比如缩进),批量跳过
+    match simd_level() {
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx2 | SimdLevel::Sse2 => { return unsafe { x86::skip_whitespace_sse2(bytes, i) }; },
+        _ => {
+            while i < bytes.len() && is_whitespace(bytes[i]) {
+                i += 1;
+            }
+            return i;
+        },
+    }
+}
+
+/**
+ * 判断字节是否是json空白字符
+ */
+#[inline]
+pub fn is_whitespace(b: u8) -> bool {
+    return b == b' ' || b == b'\n' || b == b'\r' || b == b'\t';
+}
+
+#[inline]
+fn is_string_special(b: u8) -> bool {
+    return b == b'"' || b == b'\\' || b < 0x20;
+}
+
+#[inline]
+fn is_structural(b: u8) -> bool {
+    return matches!(b, b'{' | b'}' | b'[' | b']' | b':' | b',' | b'"');
+}
+
+/**
+ * 不依赖指令集的实现,一次读取8个字节到u64中并行判断(SWAR)
+ */
+mod portable {
+    use super::{is_string_special, is_structural};
+
+    const ONES: u64 = 0x0101_0101_0101_0101;
+    const HIGHS: u64 = 0x8080_8080_8080_8080;
+
+    /// 等于b的字节,对应位置的最高位为1(只保证第一个为1的位置准确)
+    #[inline]
+    fn eq_mask(v: u64, b: u8) -> u64 {
+        let x = v ^ (ONES * b as u64);
+        return x.wrapping_sub(ONES) & !x & HIGHS;
+    }
+    /// 小于n(n<=128)的字节,对应位置的最高位为1
+    #[inline]
+    fn lt_mask(v: u64, n: u8) -> u64 {
+        return v.wrapping_sub(ONES * n as u64) & !v & HIGHS;
+    }
+    #[inline]
+    fn load(bytes: &[u8], i: usize) -> u64 {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(&bytes[i..i + 8]);
+        return u64::from_le_bytes(buf);
+    }
+
+    pub fn find_string_special(bytes: &[u8], start: usize) -> usize {
+        let mut i = start;
+        while i + 8 <= bytes.len() {
+            let v = load(bytes, i);
+            let mask = eq_mask(v, b'"') | eq_mask(v, b'\\') | lt_mask(v, 0x20);
+            if mask != 0 {
+                return i + (mask.trailing_zeros() / 8) as usize;
+            }
+            i += 8;
+        }
+        while i < bytes.len() && !is_string_special(bytes[i]) {
+            i += 1;
+        }
+        return i;
+    }
+
+    pub fn find_structural(bytes: &[u8], start: usize) -> usize {
+        let mut i = start;
+        while i + 8 <= bytes.len() {
+            let v = load(bytes, i);
+            let mask = eq_mask(v, b'{') | eq_mask(v, b'}') | eq_mask(v, b'[') | eq_mask(v, b']')
+                | eq_mask(v, b':') | eq_mask(v, b',') | eq_mask(v, b'"');
+            if mask != 0 {
+                return i + (mask.trailing_zeros() / 8) as usize;
+            }
+            i += 8;
+        }
+        while i < bytes.len() && !is_structural(bytes[i]) {
+            i += 1;
+        }
+        return i;
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    use super::{is_string_special, is_structural, is_whitespace};
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn find_string_special_sse2(bytes: &[u8], start: usize) -> usize {
+        let quote = _mm_set1_epi8(b'"' as i8);
+        let slash = _mm_set1_epi8(b'\\' as i8);
+        let ctrl = _mm_set1_epi8(0x1F);
+        let mut i = start;
+        while i + 16 <= bytes.len() {
+            let v = _mm_loadu_si128(bytes.as_ptr().add(i) as *const __m128i);
+            // 无符号比较: v<=0x1F 等价于 min(v,0x1F)==v
+            let m = _mm_or_si128(
+                _mm_or_si128(_mm_cmpeq_epi8(v, quote), _mm_cmpeq_epi8(v, slash)),
+                _mm_cmpeq_epi8(_mm_min_epu8(v, ctrl), v),
+            );
+            let mask = _mm_movemask_epi8(m) as u32;
+            if mask != 0 {
+                return i + mask.trailing_zeros() as usize;
+            }
+            i += 16;
+        }
+        while i < bytes.len() && !is_string_special(bytes[i]) {
+            i += 1;
+        }
+        return i;
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn find_string_special_avx2(bytes: &[u8], start: usize) -> usize {
+        let quote = _mm256_set1_epi8(b'"' as i8);
+        let slash = _mm256_set1_epi8(b'\\' as i8);
+        let ctrl = _mm256_set1_epi8(0x1F);
+        let mut i = start;
+        while i + 32 <= bytes.len() {
+            let v = _mm256_loadu_si256(bytes.as_ptr().add(i) as *const __m256i);
+            let m = _mm256_or_si256(
+                _mm256_or_si256(_mm256_cmpeq_epi8(v, quote), _mm256_cmpeq_epi8(v, slash)),
+                _mm256_cmpeq_epi8(_mm256_min_epu8(v, ctrl), v),
+            );
+            let mask = _mm256_movemask_epi8(m) as u32;
+            if mask != 0 {
+                return i + mask.trailing_zeros() as usize;
+            }
+            i += 32;
+        }
+        // 剩下不足32个字节的部分交给sse2
+        return find_string_special_sse2(bytes, i);
+    }
+
+    #[target_feature(enable = "sse2")]
+    unsafe fn structural_mask_sse2(v: __m128i) -> u32 {
+        let m = _mm_or_si128(
+            _mm_or_si128(
+                _mm_or_si128(_mm_cmpeq_epi8(v, _mm_set1_epi8(b'{' as i8)), _mm_cmpeq_epi8(v, _mm_set1_epi8(b'}' as i8))),
+                _mm_or_si128(_mm_cmpeq_epi8(v, _mm_set1_epi8(b'[' as i8)), _mm_cmpeq_epi8(v, _mm_set1_epi8(b']' as i8))),
+            ),
+            _mm_or_si128(
+                _mm_or_si128(_mm_cmpeq_epi8(v, _mm_set1_epi8(b':' as i8)), _mm_cmpeq_epi8(v, _mm_set1_epi8(b',' as i8))),
+                _mm_cmpeq_epi8(v, _mm_set1_epi8(b'"' as i8)),
+            ),
+        );
+        return _mm_movemask_epi8(m) as u32;
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn find_structural_sse2(bytes: &[u8], start: usize) -> usize {
+        let mut i = start;
+        while i + 16 <= bytes.len() {
+            let v = _mm_loadu_si128(bytes.as_ptr().add(i) as *const __m128i);
+            let mask = structural_mask_sse2(v);
+            if mask != 0 {
+                return i + mask.trailing_zeros() as usize;
+            }
+            i += 16;
+        }
+        while i < bytes.len() && !is_structural(bytes[i]) {
+            i += 1;
+        }
+        return i;
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn find_structural_avx2(bytes: &[u8], start: usize) -> usize {
+        let mut i = start;
+        while i + 32 <= bytes.len() {
+            let v = _mm256_loadu_si256(bytes.as_ptr().add(i) as *const __m256i);
+            let m = _mm256_or_si256(
+                _mm256_or_si256(
+                    _mm256_or_si256(_mm256_cmpeq_epi8(v, _mm256_set1_epi8(b'{' as i8)), _mm256_cmpeq_epi8(v, _mm256_set1_epi8(b'}' as i8))),
+                    _mm256_or_si256(_mm256_cmpeq_epi8(v, _mm256_set1_epi8(b'[' as i8)), _mm256_cmpeq_epi8(v, _mm256_set1_epi8(b']' as i8))),
+                ),
+                _mm256_or_si256(
+                    _mm256_or_si256(_mm256_cmpeq_epi8(v, _mm256_set1_epi8(b':' as i8)), _mm256_cmpeq_epi8(v, _mm256_set1_epi8(b',' as i8))),
+                    _mm256_cmpeq_epi8(v, _mm256_set1_epi8(b'"' as i8)),
+                ),
+            );
+            let mask = _mm256_movemask_epi8(m) as u32;
+            if mask != 0 {
+                return i + mask.trailing_zeros() as usize;
+            }
+            i += 32;
+        }
+        return find_structural_sse2(bytes, i);
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn skip_whitespace_sse2(bytes: &[u8], start: usize) -> usize {
+        let mut i = start;
+        while i + 16 <= bytes.len() {
+            let v = _mm_loadu_si128(bytes.as_ptr().add(i) as *const __m128i);
+            let ws = _mm_or_si128(
+                _mm_or_si128(_mm_cmpeq_epi8(v, _mm_set1_epi8(b' ' as i8)), _mm_cmpeq_epi8(v, _mm_set1_epi8(b'\n' as i8))),
+                _mm_or_si128(_mm_cmpeq_epi8(v, _mm_set1_epi8(b'\r' as i8)), _mm_cmpeq_epi8(v, _mm_set1_epi8(b'\t' as i8))),
+            );
+            // 取反得到非空白字符
+            let mask = !(_mm_movemask_epi8(ws) as u32) & 0xFFFF;
+            if mask != 0 {
+                return i + mask.trailing_zeros() as usize;
+            }
+            i += 16;
+        }
+        while i < bytes.len() && is_whitespace(bytes[i]) {
+            i += 1;
+        }
+        return i;
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_simd_same_as_portable() {
+    let json = std::fs::read_to_string("tests/testobject.json").unwrap();
+    let bytes = json.as_bytes();
+    let mut levels = vec![SimdLevel::Portable];
+    if simd_level() != SimdLevel::Portable {
+        levels.push(SimdLevel::Sse2);
+    }
+    if simd_level() == SimdLevel::Avx2 {
+        levels.push(SimdLevel::Avx2);
+    }
+    for start in 0..bytes.len() {
+        let expect_special = (start..bytes.len()).find(|i| is_string_special(bytes[*i])).unwrap_or(bytes.len());
+        let expect_structural = (start..bytes.len()).find(|i| is_structural(bytes[*i])).unwrap_or(bytes.len());
+        for level in levels.iter() {
+            assert_eq!(find_string_special_with(*level, bytes, start), expect_special);
+            assert_eq!(find_structural_with(*level, bytes, start), expect_structural);
+        }
+        let expect_ws = (start..bytes.len()).find(|i| !is_whitespace(bytes[*i])).unwrap_or(bytes.len());
+        assert_eq!(skip_whitespace(bytes, start), expect_ws);
+    }
+}
@@ -0,0 +1,165 @@
+/**
+ * 文件名: "src/zjy/json/span.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::collections::HashMap;
+
+use crate::zjy::json::{Json, JsonError, ParseOptions};
+use crate::zjy::json::parser::parse_str_spanned;
+use crate::zjy::json::pointer;
+
+/**
+ * 输入中的一个位置
+ * offset是从0开始的字节偏移,line和column从1开始,column以字符为单位
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    /**
+     * 输入的开头
+     */
+    pub fn start() -> Position { Position { offset: 0, line: 1, column: 1 } }
+    /**
+     * 读过字符c之后的位置,只有 '\n' 算作换行
+     */
+    pub fn advance(&self, c: char) -> Position {
+        if c == '\n' {
+            return Position { offset: self.offset + 1, line: self.line + 1, column: 1 };
+        }
+        return Position { offset: self.offset + c.len_utf8(), line: self.line, column: self.column + 1 };
+    }
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        return Position::start();
+    }
+}
+
+/**
+ * 输入中的一段范围,包含start,不包含end
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+/**
+ * 和json值结构相同的范围树
+ * span是值本身的范围,key是object成员的key(包括引号)的范围
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SpanTree {
+    pub span: Span,
+    pub key: Option<Span>,
+    pub children: SpanChildren,
+}
+
+/**
+ * 子值的范围,Scalar代表null,boolean,number和string
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum SpanChildren {
+    #[default]
+    Scalar,
+    Array(Vec<SpanTree>),
+    Object(HashMap<String, SpanTree>),
+}
+
+impl SpanTree {
+    /**
+     * 按照JSON Pointer查找子值的范围树
+     */
+    pub fn get(&self, pointer: &str) -> Option<&SpanTree> {
+        let mut current = self;
+        for token in pointer::split_pointer(pointer)? {
+            current = match &current.children {
+                SpanChildren::Object(obj) => obj.get(&token)?,
+                SpanChildren::Array(arr) => arr.get(pointer::parse_index(&token)?)?,
+                SpanChildren::Scalar => { return None; },
+            };
+        }
+        return Some(current);
+    }
+}
+
+/**
+ * 带有范围树的解析结果
+ */
+#[derive(Clone)]
+pub struct Spanned {
+    pub value: Json,
+    pub spans: SpanTree,
+}
+
+impl Spanned {
+    /**
+     * 获取pointer对应的值在输入中的范围
+     */
+    pub fn span_of(&self, pointer: &str) -> Option<Span> {
+        return self.spans.get(pointer).map(|tree| tree.span);
+    }
+    /**
+     * 获取pointer对应的object成员的key在输入中的范围,pointer不是object成员时返回None
+     */
+    pub fn key_span_of(&self, pointer: &str) -> Option<Span> {
+        return self.spans.get(pointer)?.key;
+    }
+}
+
+impl Json {
+    /**
+     * 按照RFC 8259严格解析,同时记录每个值和key在输入中的范围
+     */
+    pub fn str_to_json_spanned(str: &str) -> Result<Spanned, JsonError> {
+        return parse_str_spanned(str, &ParseOptions::default());
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_span_of() {
+    let str = std::fs::read_to_string("tests/testobject.json").unwrap();
+    let spanned = Json::str_to_json_spanned(&str).unwrap();
+    // 每个值的范围截取出来都可以解析成同样的值
+    for pointer in ["", "/obj2", "/obj2/arr", "/obj2/arr/0", "/obj2/arr/3", "/obj2/arr/3/name"] {
+        let span = spanned.span_of(pointer).unwrap();
+        let text = &str[span.start.offset..span.end.offset];
+        assert_eq!(Json::str_to_json(text).unwrap().to_json5_string(), spanned.value.pointer(pointer).unwrap().to_json5_string());
+    }
+    let spanned = Json::str_to_json_spanned("{\n  \"a\": [1, 22],\n  \"中\": \"x\"\n}").unwrap();
+    let span = spanned.span_of("/a/1").unwrap();
+    assert_eq!((span.start.line, span.start.column, span.start.offset, span.end.offset), (2, 12, 13, 15));
+    let key = spanned.key_span_of("/中").unwrap();
+    assert_eq!((key.start.line, key.start.column, key.end.column), (3, 3, 6));
+    assert_eq!(spanned.span_of("/中").unwrap().start.offset, 27);
+    assert!(spanned.key_span_of("/a/0").is_none());
+    assert!(spanned.span_of("/b").is_none());
+    assert!(spanned.span_of("/a/01").is_none() && spanned.span_of("/a/+1").is_none());
+    let root = spanned.span_of("").unwrap();
+    assert_eq!((root.start.offset, root.end.line, root.end.column), (0, 4, 2));
+    // 最外层的number在输入结束时结束
+    assert_eq!(Json::str_to_json_spanned(" 12 ").unwrap().span_of("").unwrap().end.offset, 3);
+    assert_eq!(Json::str_to_json_spanned("12").unwrap().span_of("").unwrap().end.offset, 2);
+}
@@ -0,0 +1,263 @@
+/**
+ * 文件名: "src/zjy/json/stream.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::io::Read;
+
+use crate::zjy::json::parser::{Prefix, PrefixParser};
+use crate::zjy::json::{Json, JsonError, Parser};
+
+/// RFC 7464中每条记录开头的分隔符
+const RECORD_SEPARATOR: u8 = 0x1e;
+/// 每次最多读取的字节数
+const READ_SIZE: usize = 8 * 1024;
+
+/**
+ * 流中json值的分隔方式
+ * Concatenated代表首尾相接的json值,值之间可以有空白,例如 {"a":1}{"b":2} 3 "x"
+ * Sequence代表RFC 7464的json文本序列,每个值前面是0x1E,后面是换行
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamFormat {
+    Concatenated,
+    Sequence,
+}
+
+/**
+ * 从任意Read中逐个读取json值的迭代器,一个值可以跨越多次读取
+ * Concatenated格式遇到语法错误之后无法找到下一个值的开头,会停止迭代;
+ * Sequence格式的每条记录是独立的,出错之后从下一个0x1E继续
+ */
+pub struct JsonStreamReader<R: Read> {
+    reader: R,
+    parser: Parser,
+    format: StreamFormat,
+    /// 已经读取还没有解析的字节
+    buf: Vec<u8>,
+    /// buf中还没有解析的部分的开头
+    start: usize,
+    /// 已经解析的字节数(不包括buf中的部分)
+    consumed: usize,
+    /// 正在解析的值,保存了已经读到的部分的解析状态
+    prefix: PrefixParser,
+    /// Sequence格式中buf[start..]已经查找过分隔符的字节数
+    searched: usize,
+    /// 每次读取使用的缓冲区
+    chunk: Vec<u8>,
+    eof: bool,
+    done: bool,
+}
+
+impl<R: Read> JsonStreamReader<R> {
+    /**
+     * 读取首尾相接的json值
+     */
+    pub fn new(reader: R) -> JsonStreamReader<R> {
+        return JsonStreamReader::with_parser(reader, StreamFormat::Concatenated, Parser::new());
+    }
+    /**
+     * 读取RFC 7464的json文本序列
+     */
+    pub fn sequence(reader: R) -> JsonStreamReader<R> {
+        return JsonStreamReader::with_parser(reader, StreamFormat::Sequence, Parser::new());
+    }
+    /**
+     * 使用指定的格式和解析器,解析器的max_total_bytes限制单个值的大小
+     */
+    pub fn with_parser(reader: R, format: StreamFormat, parser: Parser) -> JsonStreamReader<R> {
+        return JsonStreamReader { reader, parser, format, buf: Vec::new(), start: 0, consumed: 0, prefix: PrefixParser::new(), searched: 0, chunk: vec![0; READ_SIZE], eof: false, done: false };
+    }
+    /**
+     * 已经解析的字节数,也就是下一个值在流中的开始位置(前面可能有空白)
+     */
+    pub fn offset(&self) -> usize { return self.consumed + self.start; }
+    /**
+     * 取出内部的reader,已经读取还没有解析的内容会丢失
+     */
+    pub fn into_inner(self) -> R { return self.reader; }
+
+    /**
+     * 再读取一部分输入,读到任意长度的内容就返回,不会等待更多的输入
+     */
+    fn fill(&mut self) -> Result<(), JsonError> {
+        // 丢掉已经解析的部分
+        if self.start > 0 {
+            self.buf.drain(..self.start);
+            self.consumed += self.start;
+            self.start = 0;
+        }
+        loop {
+            match self.reader.read(&mut self.chunk) {
+                Ok(0) => { self.eof = true; },
+                Ok(n) => { self.buf.extend_from_slice(&self.chunk[..n]); },
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => { continue; },
+                Err(err) => { return Err(err.into()); },
+            }
+            return Ok(());
+        }
+    }
+
+    /**
+     * 检查还没有解析的内容是否超出了单个值的大小限制
+     */
+    fn check_size(&self) -> Result<(), JsonError> {
+        return self.parser.options().check_total_bytes(self.buf.len() - self.start);
+    }
+
+    fn next_concatenated(&mut self) -> Result<Option<Json>, JsonError> {
+        loop {
+            let rest = &self.buf[self.start..];
+            // 前面的部分已经解析过,只检查和解析新读到的内容;读取的边界可能在一个utf8字符的中间,只解析完整的部分
+            let scanned = self.prefix.offset();
+            let new = match std::str::from_utf8(&rest[scanned..]) {
+                Ok(str) => str,
+                Err(err) if err.error_len().is_none() && !self.eof => std::str::from_utf8(&rest[scanned..scanned + err.valid_up_to()]).unwrap_or_default(),
+                Err(err) => { return Err(std::format!("第{}个字节:不是合法的utf8编码", self.offset() + scanned + err.valid_up_to() + 1).into()); },
+            };
+            let at_eof = self.eof && scanned + new.len() == rest.len();
+            let result = self.prefix.resume(new, self.parser.options(), at_eof).map_err(|err| err.prefix(&std::format!("第{}个字节开始的值:", self.offset() + 1)))?;
+            match result {
+                Prefix::Value(json, end) => {
+                    self.start += end;
+                    self.prefix = PrefixParser::new();
+                    return Ok(Some(json));
+                },
+                Prefix::Empty if at_eof => {
+                    self.start = self.buf.len();
+                    return Ok(None);
+                },
+                Prefix::Empty | Prefix::Incomplete => {
+                    self.check_size()?;
+                    self.fill()?;
+                },
+            }
+        }
+    }
+
+    fn next_sequence(&mut self) -> Result<Option<Json>, JsonError> {
+        loop {
+            // 跳过开头的分隔符,找到这条记录的结尾(下一个分隔符或者输入结束)
+            while self.start < self.buf.len() && self.buf[self.start] == RECORD_SEPARATOR {
+                self.start += 1;
+            }
+            // 已经查找过的部分没有分隔符
+            let from = self.start + self.searched;
+            let end = self.buf[from..].iter().position(|b| *b == RECORD_SEPARATOR).map(|i| from + i);
+            self.searched = 0;
+            let end = match end {
+                Some(end) => end,
+                None if self.eof => self.buf.len(),
+                None => {
+                    self.searched = self.buf.len() - self.start;
+                    self.check_size()?;
+                    self.fill()?;
+                    continue;
+                },
+            };
+            let offset = self.offset();
+            let record = &self.buf[self.start..end];
+            self.start = end;
+            if record.iter().all(|b| matches!(b, b' ' | b'\t' | b'\r' | b'\n')) {
+                if end == self.buf.len() && self.eof {
+                    return Ok(None);
+                }
+                continue;
+            }
+            let prefix = std::format!("第{}个字节开始的记录:", offset + 1);
+            let json = self.parser.parse_bytes(record).map_err(|err| err.prefix(&prefix))?;
+            // RFC 7464: 最外层的number,true,false和null后面没有空白时可能被截断了
+            let truncated = !matches!(json, Json::Array(_) | Json::Object(_) | Json::String(_))
+                && !matches!(record.last(), Some(b' ' | b'\t' | b'\r' | b'\n'));
+            if truncated {
+                return Err(std::format!("{}记录可能被截断,值的后面没有换行", prefix).into());
+            }
+            return Ok(Some(json));
+        }
+    }
+}
+
+impl<R: Read> Iterator for JsonStreamReader<R> {
+    type Item = Result<Json, JsonError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = match self.format {
+            StreamFormat::Concatenated => self.next_concatenated(),
+            StreamFormat::Sequence => self.next_sequence(),
+        };
+        match result {
+            Ok(Some(json)) => { return Some(Ok(json)); },
+            Ok(None) => { self.done = true; return None; },
+            Err(err) => {
+                // 序列中的语法错误只影响一条记录
+                let recoverable = self.format == StreamFormat::Sequence && matches!(err, JsonError::Syntax(_));
+                if !recoverable {
+                    self.done = true;
+                }
+                return Some(Err(err));
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_json_stream() {
+    /// 每次最多返回n个字节的reader,用来测试跨越读取边界的值
+    struct Chunked<'a>(&'a [u8], usize);
+    impl Read for Chunked<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.1.min(self.0.len()).min(buf.len());
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            return Ok(n);
+        }
+    }
+    let input = "{\"a\":1}{\"b\":[true,\"中文\"]} 3 \"x\"\n-1.5e2";
+    for size in [1, 2, 5, 1024] {
+        let values: Vec<String> = JsonStreamReader::new(Chunked(input.as_bytes(), size)).map(|json| json.unwrap().to_json5_string()).collect();
+        assert_eq!(values, ["{a:1}", "{b:[true,\"中文\"]}", "3", "\"x\"", "-150"]);
+    }
+    // 很大的值分成很多次读取时不会反复解析
+    let big = std::format!("[{}\"中文\"] 7", "\"abcdefghijklmn\",".repeat(200_000));
+    for size in [1, 64 * 1024] {
+        let values: Vec<Json> = JsonStreamReader::new(Chunked(big.as_bytes(), size)).map(|json| json.unwrap()).collect();
+        assert_eq!(values.len(), 2);
+        assert!(matches!(&values[0], Json::Array(arr) if arr.len() == 200_001));
+        assert_eq!(values[1].to_string(), "7");
+    }
+    // 前缀解析
+    let (json, end) = Parser::new().parse_prefix("12 [1]").unwrap();
+    assert_eq!((json.to_json5_string(), end), ("12".to_string(), 2));
+    // 语法错误之后停止
+    let mut reader = JsonStreamReader::new("[1] [2,] [3]".as_bytes());
+    assert!(reader.next().unwrap().is_ok());
+    assert!(reader.next().unwrap().is_err());
+    assert!(reader.next().is_none());
+    // RFC 7464
+    let input = b"\x1e{\"a\":1}\n\x1e[1,\n\x1e12\n\x1e\"s\"\n\x1e12";
+    let mut reader = JsonStreamReader::sequence(Chunked(input, 3));
+    assert_eq!(reader.next().unwrap().unwrap().to_json5_string(), "{a:1}");
+    assert!(reader.next().unwrap().is_err());
+    assert_eq!(reader.next().unwrap().unwrap().to_json5_string(), "12");
+    assert_eq!(reader.next().unwrap().unwrap().to_json5_string(), "\"s\"");
+    assert!(reader.next().unwrap().is_err());
+    assert!(reader.next().is_none());
+}
@@ -0,0 +1,373 @@
+/**
+ * 文件名: "src/zjy/json/tape.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::zjy::json::fast::ByteParser;
+use crate::zjy::json::options::DuplicateKeys;
+use crate::zjy::json::pointer;
+use crate::zjy::json::{Json, JsonError, ParseOptions, Parser};
+
+/**
+ * 结构索引(tape)中一项的种类
+ * 每个值对应一项,array和object的开始和结束各对应一项,object的key是String项
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TapeKind {
+    Null,
+    True,
+    False,
+    Number,
+    String,
+    ArrayStart,
+    ArrayEnd,
+    ObjectStart,
+    ObjectEnd,
+}
+
+/**
+ * tape中的一项,offset是在输入中的字节偏移
+ * 开始项的link是对应的结束项的下标,结束项的link是对应的开始项的下标,用于O(1)跳过子树
+ */
+#[derive(Clone, Copy)]
+struct TapeEntry {
+    offset: usize,
+    link: u32,
+    kind: TapeKind,
+}
+
+/**
+ * 输入的结构索引,类似simdjson的tape
+ * 第一遍检查语法,第二遍按顺序记录每个值和括号的位置;
+ * 之后可以O(1)跳过子树,快速按照JSON Pointer查找,并且多线程生成array的元素
+ */
+pub struct JsonTape<'a> {
+    bytes: &'a [u8],
+    options: ParseOptions,
+    entries: Vec<TapeEntry>,
+}
+
+/**
+ * tape中的一个值,index是它在tape中的下标
+ */
+#[derive(Clone, Copy)]
+pub struct TapeValue<'t, 'a> {
+    tape: &'t JsonTape<'a>,
+    index: usize,
+}
+
+impl<'a> JsonTape<'a> {
+    /**
+     * 使用严格的默认选项建立结构索引
+     */
+    pub fn build(str: &'a str) -> Result<JsonTape<'a>, JsonError> {
+        return JsonTape::build_with_options(str, &ParseOptions::default());
+    }
+    /**
+     * 使用指定的选项建立结构索引,只支持可以按字节解析的选项(严格模式,没有语法扩展)
+     */
+    pub fn build_with_options(str: &'a str, options: &ParseOptions) -> Result<JsonTape<'a>, JsonError> {
+        if !options.fast_path_compatible() {
+            return Err("JsonTape只支持严格模式并且没有语法扩展的选项".to_string().into());
+        }
+        let bytes = str.as_bytes();
+        options.check_total_bytes(bytes.len())?;
+        // 第一遍: 检查语法,之后只需要识别记号
+        ByteParser::new(bytes, options).validate_document()?;
+        if bytes.len() > u32::MAX as usize {
+            return Err("JsonTape不支持超过4GB的输入".to_string().into());
+        }
+        // 第二遍: 记录结构
+        let mut entries: Vec<TapeEntry> = Vec::new();
+        let mut stack: Vec<usize> = Vec::new();
+        let mut parser = ByteParser::new(bytes, options);
+        loop {
+            parser.skip_whitespace();
+            let offset = parser.position();
+            let kind = match bytes.get(offset) {
+                None => { break; },
+                Some(b',' | b':') => {
+                    parser.eat(bytes[offset]);
+                    continue;
+                },
+                Some(b'[') => TapeKind::ArrayStart,
+                Some(b'{') => TapeKind::ObjectStart,
+                Some(b']') => TapeKind::ArrayEnd,
+                Some(b'}') => TapeKind::ObjectEnd,
+                Some(b'"') => TapeKind::String,
+                Some(b'n') => TapeKind::Null,
+                Some(b't') => TapeKind::True,
+                Some(b'f') => TapeKind::False,
+                Some(_) => TapeKind::Number,
+            };
+            let index = entries.len();
+            let mut link = 0;
+            match kind {
+                TapeKind::ArrayStart | TapeKind::ObjectStart => {
+                    stack.push(index);
+                    parser.eat(bytes[offset]);
+                },
+                TapeKind::ArrayEnd | TapeKind::ObjectEnd => {
+                    let start = stack.pop().unwrap_or_default();
+                    entries[start].link = index as u32;
+                    link = start as u32;
+                    parser.eat(bytes[offset]);
+                },
+                TapeKind::String => { parser.skip_string()?; },
+                _ => { parser.skip_value()?; },
+            }
+            entries.push(TapeEntry { offset, link, kind });
+        }
+        return Ok(JsonTape { bytes, options: options.clone(), entries });
+    }
+
+    /**
+     * tape的项数
+     */
+    pub fn len(&self) -> usize { return self.entries.len(); }
+    pub fn is_empty(&self) -> bool { return self.entries.is_empty(); }
+    /**
+     * 最外层的值
+     */
+    pub fn root(&self) -> TapeValue<'_, 'a> { return TapeValue { tape: self, index: 0 }; }
+    /**
+     * 按照JSON Pointer查找值,只访问路径上的项
+     */
+    pub fn pointer(&self, pointer: &str) -> Option<TapeValue<'_, 'a>> {
+        let mut current = self.root();
+        for token in pointer::split_pointer(pointer)? {
+            current = match current.kind() {
+                TapeKind::ArrayStart => current.index(pointer::parse_index(&token)?)?,
+                TapeKind::ObjectStart => current.get(&token)?,
+                _ => { return None; },
+            };
+        }
+        return Some(current);
+    }
+    /**
+     * 生成整个值
+     */
+    pub fn to_json(&self) -> Json { return self.root().to_json(); }
+
+    /**
+     * 下标为index的值后面一个值的下标,array和object直接跳到结束项后面
+     */
+    fn next(&self, index: usize) -> usize {
+        let entry = self.entries[index];
+        match entry.kind {
+            TapeKind::ArrayStart | TapeKind::ObjectStart => { return entry.link as usize + 1; },
+            _ => { return index + 1; },
+        }
+    }
+
+    /**
+     * 生成下标为index的值,depth是它所在的嵌套深度
+     */
+    fn materialize(&self, index: usize, depth: usize) -> Json {
+        // 建立tape时已经检查过语法,这里不会出错
+        return ByteParser::new_at(self.bytes, self.entries[index].offset, depth, &self.options).parse_value().unwrap_or(Json::Null);
+    }
+
+    /**
+     * 判断下标为index的String项是否等于key,没有转义的string直接比较原始字节
+     */
+    fn key_equals(&self, index: usize, key: &str) -> bool {
+        let offset = self.entries[index].offset;
+        let raw = &self.bytes[offset + 1..];
+        let plain = !key.bytes().any(|b| b == b'"' || b == b'\\' || b < 0x20);
+        if plain && raw.len() > key.len() && raw[key.len()] == b'"' && &raw[..key.len()] == key.as_bytes() {
+            return true;
+        }
+        if !raw.iter().take_while(|b| **b != b'"').any(|b| *b == b'\\') {
+            return false;
+        }
+        return ByteParser::new_at(self.bytes, offset, 0, &self.options).parse_string().map(|s| s == key).unwrap_or(false);
+    }
+}
+
+impl<'t, 'a> TapeValue<'t, 'a> {
+    pub fn kind(&self) -> TapeKind { return self.tape.entries[self.index].kind; }
+    pub fn is_array(&self) -> bool { return self.kind() == TapeKind::ArrayStart; }
+    pub fn is_object(&self) -> bool { return self.kind() == TapeKind::ObjectStart; }
+    /**
+     * 值在输入中的原始文本
+     */
+    pub fn raw(&self) -> &'a str {
+        let start = self.tape.entries[self.index].offset;
+        let end = match self.kind() {
+            TapeKind::ArrayStart | TapeKind::ObjectStart => self.tape.entries[self.tape.entries[self.index].link as usize].offset + 1,
+            _ => {
+                let mut parser = ByteParser::new_at(self.tape.bytes, start, 0, &self.tape.options);
+                let _ = parser.skip_value();
+                parser.position()
+            },
+        };
+        // 范围的两端都是ascii字符,一定在字符边界上
+        return unsafe { std::str::from_utf8_unchecked(&self.tape.bytes[start..end]) };
+    }
+    /**
+     * 子值的下标(object是值的下标,不包括key),每个子值O(1)跳过
+     */
+    fn children(&self) -> Vec<usize> {
+        let mut result = Vec::new();
+        if !self.is_array() && !self.is_object() {
+            return result;
+        }
+        let end = self.tape.entries[self.index].link as usize;
+        let mut i = self.index + 1;
+        while i < end {
+            if self.is_object() {
+                // 跳过key
+                i += 1;
+            }
+            result.push(i);
+            i = self.tape.next(i);
+        }
+        return result;
+    }
+    /**
+     * array的元素个数或object的成员个数,其他值返回0
+     */
+    pub fn len(&self) -> usize { return self.children().len(); }
+    pub fn is_empty(&self) -> bool {
+        return !(self.is_array() || self.is_object()) || self.tape.entries[self.index].link as usize == self.index + 1;
+    }
+    /**
+     * array中下标为i的元素
+     */
+    pub fn index(&self, i: usize) -> Option<TapeValue<'t, 'a>> {
+        if !self.is_array() {
+            return None;
+        }
+        let end = self.tape.entries[self.index].link as usize;
+        let mut current = self.index + 1;
+        for _ in 0..i {
+            if current >= end {
+                return None;
+            }
+            current = self.tape.next(current);
+        }
+        if current >= end {
+            return None;
+        }
+        return Some(TapeValue { tape: self.tape, index: current });
+    }
+    /**
+     * object中key对应的值,重复的key按照选项取第一个或者最后一个
+     */
+    pub fn get(&self, key: &str) -> Option<TapeValue<'t, 'a>> {
+        if !self.is_object() {
+            return None;
+        }
+        let end = self.tape.entries[self.index].link as usize;
+        let mut found = None;
+        let mut i = self.index + 1;
+        while i < end {
+            if self.tape.key_equals(i, key) {
+                found = Some(i + 1);
+                if self.tape.options.duplicate_keys == DuplicateKeys::First {
+                    break;
+                }
+            }
+            i = self.tape.next(i + 1);
+        }
+        return found.map(|index| TapeValue { tape: self.tape, index });
+    }
+    /**
+     * 生成这个值
+     */
+    pub fn to_json(&self) -> Json { return self.tape.materialize(self.index, self.depth()); }
+    /**
+     * 使用threads个线程生成array的元素,不是array时和to_json相同,子线程的panic会传递到调用的线程
+     */
+    pub fn to_json_parallel(&self, threads: usize) -> Json {
+        if !self.is_array() || threads <= 1 {
+            return self.to_json();
+        }
+        let children = self.children();
+        let depth = self.depth() + 1;
+        let chunk = std::cmp::max(1, children.len().div_ceil(threads));
+        let tape = self.tape;
+        let parts: Vec<Vec<Json>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = children.chunks(chunk)
+                .map(|indexes| scope.spawn(move || indexes.iter().map(|index| tape.materialize(*index, depth)).collect::<Vec<Json>>()))
+                .collect();
+            return handles.into_iter()
+                // 子线程panic时在当前线程重新panic,不能返回缺少元素的结果
+                .map(|handle| handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+                .collect();
+        });
+        return Json::Array(parts.into_iter().flatten().collect());
+    }
+
+    /**
+     * 值所在的嵌套深度,等于它前面还没有结束的array和object的个数
+     */
+    fn depth(&self) -> usize {
+        let mut depth = 0;
+        let mut i = 0;
+        while i < self.index {
+            match self.tape.entries[i].kind {
+                // 目标在这个子树外面时整个跳过
+                TapeKind::ArrayStart | TapeKind::ObjectStart if self.tape.entries[i].link as usize >= self.index => {
+                    depth += 1;
+                    i += 1;
+                },
+                _ => { i = self.tape.next(i); },
+            }
+        }
+        return depth;
+    }
+}
+
+impl Parser {
+    /**
+     * 建立str的结构索引,只支持可以按字节解析的选项
+     */
+    pub fn build_tape<'a>(&self, str: &'a str) -> Result<JsonTape<'a>, JsonError> {
+        return JsonTape::build_with_options(str, self.options());
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_json_tape() {
+    let str = std::fs::read_to_string("tests/testobject.json").unwrap();
+    let tape = JsonTape::build(&str).unwrap();
+    assert_eq!(tape.to_json().to_json5_string(), Json::str_to_json(&str).unwrap().to_json5_string());
+    let value = tape.pointer("/obj2/arr/3/name").unwrap();
+    assert_eq!((value.kind(), value.to_json().to_json5_string()), (TapeKind::String, "\"j🧑\"".to_string()));
+    assert_eq!(value.depth(), 4);
+    assert_eq!(tape.pointer("/arr").unwrap().len(), 5);
+    assert_eq!(tape.pointer("/arr/4").unwrap().raw(), "true");
+    assert!(tape.pointer("/arr/5").is_none());
+    assert!(tape.pointer("/arr/04").is_none() && tape.pointer("/arr/+4").is_none());
+    assert!(tape.pointer("/str/0").is_none());
+    // 重复的key
+    assert_eq!(tape.pointer("/true").unwrap().raw(), "true");
+    let first = Parser::new().duplicate_keys(DuplicateKeys::First).build_tape(&str).unwrap();
+    assert_eq!(first.pointer("/true").unwrap().raw(), "\"注释1\"");
+    // 带转义的key
+    let tape = JsonTape::build("{\"a\\u0062\":[1,{\"x\":[]},3], \"c\":{}}").unwrap();
+    assert_eq!(tape.pointer("/ab/1").unwrap().raw(), "{\"x\":[]}");
+    assert!(tape.pointer("/c").unwrap().is_empty());
+    // 多线程生成
+    let big = std::format!("[{}]", (0..1000).map(|i| std::format!("{{\"i\":{}}}", i)).collect::<Vec<String>>().join(","));
+    let tape = JsonTape::build(&big).unwrap();
+    assert_eq!(tape.root().to_json_parallel(4).to_json5_string(), tape.to_json().to_json5_string());
+    assert!(JsonTape::build("[1,2").is_err());
+}
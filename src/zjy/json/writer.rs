@@ -0,0 +1,361 @@
+/**
+ * 文件名: "src/zjy/json/writer.rs" json解析器源代码
+ * Copyright (C) 2023 朱浚宇
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::fmt;
+use std::io::Write;
+
+use crate::zjy::json::canonical::utf16_cmp;
+use crate::zjy::json::format::write_number;
+use crate::zjy::json::jsonc::write_json_string;
+use crate::zjy::json::{Json, JsonError, NonFinite, SerializeOptions};
+
+/// 缓冲区超过这个大小时写入输出
+const FLUSH_SIZE: usize = 8 * 1024;
+
+/**
+ * 流式json写入器,不需要先生成json树
+ * 通过begin_array / end_array / begin_object / key / end_object和各种值的调用直接输出,
+ * 会检查调用的顺序: object中的值前面必须有key,括号必须配对,最外层只能有一个值
+ */
+pub struct JsonWriter<W: Write> {
+    out: W,
+    options: SerializeOptions,
+    /// 还没有写入out的内容
+    buf: String,
+    /// 还没有结束的array和object
+    stack: Vec<Frame>,
+    /// 最外层的值已经写完
+    done: bool,
+}
+
+/**
+ * 一层array或object,count是已经写入的元素个数,has_key代表object已经写入了key,等待值
+ */
+struct Frame {
+    is_object: bool,
+    count: usize,
+    has_key: bool,
+}
+
+impl<W: Write> JsonWriter<W> {
+    /**
+     * 紧凑输出的写入器,key按照写入的顺序输出
+     */
+    pub fn new(out: W) -> JsonWriter<W> {
+        return JsonWriter::with_options(out, SerializeOptions::default());
+    }
+    /**
+     * 使用指定选项的写入器,sort_keys和canonical的key顺序只对通过value写入的object有效
+     */
+    pub fn with_options(out: W, options: SerializeOptions) -> JsonWriter<W> {
+        return JsonWriter { out, options, buf: String::new(), stack: Vec::new(), done: false };
+    }
+
+    pub fn begin_array(&mut self) -> Result<(), JsonError> {
+        self.before_value()?;
+        self.buf.push('[');
+        self.stack.push(Frame { is_object: false, count: 0, has_key: false });
+        return Ok(());
+    }
+    pub fn end_array(&mut self) -> Result<(), JsonError> {
+        return self.end(false);
+    }
+    pub fn begin_object(&mut self) -> Result<(), JsonError> {
+        self.before_value()?;
+        self.buf.push('{');
+        self.stack.push(Frame { is_object: true, count: 0, has_key: false });
+        return Ok(());
+    }
+    pub fn end_object(&mut self) -> Result<(), JsonError> {
+        return self.end(true);
+    }
+    /**
+     * 写入object成员的key,后面必须写入一个值
+     */
+    pub fn key(&mut self, key: &str) -> Result<(), JsonError> {
+        let count = match self.stack.last() {
+            Some(frame) if frame.is_object && !frame.has_key => frame.count,
+            Some(frame) if frame.is_object => { return Err("key后面需要先写入值".to_string().into()); },
+            _ => { return Err("只有object中可以写入key".to_string().into()); },
+        };
+        if count > 0 {
+            self.buf.push(',');
+        }
+        self.newline(self.stack.len());
+        self.write_string(key);
+        self.buf.push(':');
+        if self.indent().is_some() {
+            self.buf.push(' ');
+        }
+        if let Some(frame) = self.stack.last_mut() {
+            frame.has_key = true;
+        }
+        return Ok(());
+    }
+    pub fn null(&mut self) -> Result<(), JsonError> {
+        self.before_value()?;
+        self.buf.push_str("null");
+        return self.after_value();
+    }
+    pub fn boolean(&mut self, b: bool) -> Result<(), JsonError> {
+        self.before_value()?;
+        self.buf.push_str(if b { "true" } else { "false" });
+        return self.after_value();
+    }
+    /**
+     * 写入number,使用和JavaScript相同的格式,NaN和Infinity按照选项中的NonFinite处理
+     */
+    pub fn number(&mut self, n: f64) -> Result<(), JsonError> {
+        let policy = if self.options.canonical { NonFinite::Error } else { self.options.non_finite };
+        if !n.is_finite() && policy == NonFinite::Error {
+            return Err(std::format!("number不能是{}", n).into());
+        }
+        self.before_value()?;
+        write_number(n, policy, &mut self.buf)?;
+        return self.after_value();
+    }
+    pub fn string(&mut self, s: &str) -> Result<(), JsonError> {
+        self.before_value()?;
+        self.write_string(s);
+        return self.after_value();
+    }
+    /**
+     * 写入一个完整的json值,array和object会递归地写入,不生成中间的String
+     */
+    pub fn value(&mut self, json: &Json) -> Result<(), JsonError> {
+        match json {
+            Json::Null => { return self.null(); },
+            Json::Boolean(b) => { return self.boolean(*b); },
+            Json::Number(n) => { return self.number(*n); },
+            Json::String(s) => { return self.string(s); },
+            Json::Array(arr) => {
+                self.begin_array()?;
+                for value in arr {
+                    self.value(value)?;
+                }
+                return self.end_array();
+            },
+            Json::Object(obj) => {
+                self.begin_object()?;
+                if self.options.canonical {
+                    let mut keys: Vec<&String> = obj.keys().collect();
+                    keys.sort_by(|a, b| utf16_cmp(a, b));
+                    for key in keys {
+                        self.key(key)?;
+                        self.value(&obj[key])?;
+                    }
+                } else if self.options.sort_keys {
+                    let mut keys: Vec<&String> = obj.keys().collect();
+                    keys.sort();
+                    for key in keys {
+                        self.key(key)?;
+                        self.value(&obj[key])?;
+                    }
+                } else {
+                    for (key, value) in obj {
+                        self.key(key)?;
+                        self.value(value)?;
+                    }
+                }
+                return self.end_object();
+            },
+        }
+    }
+    /**
+     * 把缓冲的内容写入输出
+     */
+    pub fn flush(&mut self) -> Result<(), JsonError> {
+        self.out.write_all(self.buf.as_bytes())?;
+        self.buf.clear();
+        self.out.flush()?;
+        return Ok(());
+    }
+    /**
+     * 检查最外层的值已经写完,写入剩下的内容并取出输出
+     */
+    pub fn finish(mut self) -> Result<W, JsonError> {
+        if !self.done {
+            return Err("json值没有写完".to_string().into());
+        }
+        self.flush()?;
+        return Ok(self.out);
+    }
+
+    /**
+     * 检查当前位置是否可以写入值,并写入前面的逗号和缩进
+     */
+    fn before_value(&mut self) -> Result<(), JsonError> {
+        let level = self.stack.len();
+        match self.stack.last() {
+            None if self.done => { return Err("最外层只能写入一个json值".to_string().into()); },
+            None => {},
+            Some(frame) if frame.is_object && !frame.has_key => { return Err("object中需要先写入key".to_string().into()); },
+            Some(frame) if frame.is_object => {},
+            Some(frame) => {
+                if frame.count > 0 {
+                    self.buf.push(',');
+                }
+                self.newline(level);
+            },
+        }
+        return Ok(());
+    }
+    fn after_value(&mut self) -> Result<(), JsonError> {
+        match self.stack.last_mut() {
+            Some(frame) => {
+                frame.count += 1;
+                frame.has_key = false;
+            },
+            None => { self.done = true; },
+        }
+        if self.buf.len() >= FLUSH_SIZE {
+            self.out.write_all(self.buf.as_bytes())?;
+            self.buf.clear();
+        }
+        return Ok(());
+    }
+    fn end(&mut self, is_object: bool) -> Result<(), JsonError> {
+        match self.stack.last() {
+            Some(frame) if frame.is_object == is_object && !frame.has_key => {},
+            Some(frame) if frame.has_key => { return Err("key后面需要先写入值".to_string().into()); },
+            _ => { return Err(std::format!("没有可以结束的{}", if is_object { "object" } else { "array" }).into()); },
+        }
+        if let Some(frame) = self.stack.pop() {
+            if frame.count > 0 {
+                self.newline(self.stack.len());
+            }
+        }
+        self.buf.push(if is_object { '}' } else { ']' });
+        return self.after_value();
+    }
+    /**
+     * 规范化输出时没有空白
+     */
+    fn indent(&self) -> Option<usize> {
+        return if self.options.canonical { None } else { self.options.indent };
+    }
+    fn newline(&mut self, level: usize) {
+        if let Some(indent) = self.indent() {
+            self.buf.push('\n');
+            for _ in 0..indent * level {
+                self.buf.push(' ');
+            }
+        }
+    }
+    fn write_string(&mut self, s: &str) {
+        if !self.options.ascii_only || self.options.canonical || s.is_ascii() {
+            write_json_string(s, &mut self.buf);
+            return;
+        }
+        let mut escaped = String::new();
+        write_json_string(s, &mut escaped);
+        for c in escaped.chars() {
+            if c.is_ascii() {
+                self.buf.push(c);
+                continue;
+            }
+            // 超出基本平面的字符使用代理对
+            let mut units = [0u16; 2];
+            for unit in c.encode_utf16(&mut units) {
+                self.buf.push_str(&std::format!("\\u{:04x}", unit));
+            }
+        }
+    }
+}
+
+impl Json {
+    /**
+     * 按照options把json值流式写入out,不生成中间的String
+     */
+    pub fn write_to<W: Write>(&self, out: W, options: &SerializeOptions) -> Result<(), JsonError> {
+        let mut writer = JsonWriter::with_options(out, options.clone());
+        writer.value(self)?;
+        writer.finish()?;
+        return Ok(());
+    }
+    /**
+     * 按照options序列化成合法的json字符串(string会被转义)
+     */
+    pub fn to_string_with_options(&self, options: &SerializeOptions) -> Result<String, JsonError> {
+        let mut out = Vec::new();
+        self.write_to(&mut out, options)?;
+        // 输出的都是合法的utf8
+        return Ok(String::from_utf8(out).unwrap_or_default());
+    }
+}
+
+/**
+ * 把JsonWriter的输出转交给fmt::Formatter,Display通过它直接写入,不生成中间的String
+ */
+pub(crate) struct FmtWriter<'a, 'b>(pub(crate) &'a mut fmt::Formatter<'b>);
+
+impl Write for FmtWriter<'_, '_> {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        // JsonWriter每次写入的都是完整的utf8
+        let s = std::str::from_utf8(bytes).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        self.0.write_str(s).map_err(|_| std::io::Error::other("fmt::Error"))?;
+        return Ok(bytes.len());
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_json_writer() {
+    let json = Json::str_to_json("{\"b\":[1,\"x\\\"y\",{}],\"a\":{\"c\":null,\"d\":[]},\"e\":\"中🧑\"}").unwrap();
+    assert_eq!(json.to_string_with_options(&SerializeOptions::compact()).unwrap(), "{\"a\":{\"c\":null,\"d\":[]},\"b\":[1,\"x\\\"y\",{}],\"e\":\"中🧑\"}");
+    let pretty = json.to_string_with_options(&SerializeOptions::pretty(2)).unwrap();
+    assert_eq!(pretty, "{\n  \"a\": {\n    \"c\": null,\n    \"d\": []\n  },\n  \"b\": [\n    1,\n    \"x\\\"y\",\n    {}\n  ],\n  \"e\": \"中🧑\"\n}");
+    assert_eq!(Json::str_to_json(&pretty).unwrap().to_json5_string(), json.to_json5_string());
+    let ascii = SerializeOptions { ascii_only: true, ..SerializeOptions::compact() };
+    assert_eq!(Json::String("中🧑".to_string()).to_string_with_options(&ascii).unwrap(), "\"\\u4e2d\\ud83e\\uddd1\"");
+    assert!(Json::Number(f64::NAN).to_string_with_options(&ascii).is_err());
+    let numbers = Json::Array(vec![Json::Number(1e21), Json::Number(-0.0), Json::Number(f64::INFINITY)]);
+    let null = SerializeOptions { non_finite: NonFinite::Null, ..SerializeOptions::default() };
+    assert_eq!(numbers.to_string_with_options(&null).unwrap(), "[1e+21,0,null]");
+    let string = SerializeOptions { non_finite: NonFinite::String, ..SerializeOptions::default() };
+    assert_eq!(numbers.to_string_with_options(&string).unwrap(), "[1e+21,0,\"Infinity\"]");
+    assert_eq!(numbers.to_string(), "[1e+21,0,null]");
+    // Display和紧凑输出相同,空的array和object,string中的引号和控制字符
+    let json = Json::str_to_json(r#"[[], {}, {"k\"": "a\"b\n"}, [{}]]"#).unwrap();
+    assert_eq!(json.to_string(), r#"[[],{},{"k\"":"a\"b\n"},[{}]]"#);
+    assert_eq!(std::format!("{}", Json::Object(std::collections::HashMap::new())), "{}");
+    // 直接写入,不生成json树
+    let mut writer = JsonWriter::new(Vec::new());
+    writer.begin_object().unwrap();
+    writer.key("list").unwrap();
+    writer.begin_array().unwrap();
+    writer.number(1.5).unwrap();
+    writer.value(&Json::Boolean(true)).unwrap();
+    writer.end_array().unwrap();
+    assert!(writer.end_array().is_err());
+    assert!(writer.string("no key").is_err());
+    writer.key("k").unwrap();
+    assert!(writer.key("k2").is_err());
+    assert!(writer.end_object().is_err());
+    writer.null().unwrap();
+    writer.end_object().unwrap();
+    assert!(writer.null().is_err());
+    assert_eq!(String::from_utf8(writer.finish().unwrap()).unwrap(), "{\"list\":[1.5,true],\"k\":null}");
+    let mut writer = JsonWriter::new(Vec::new());
+    writer.begin_array().unwrap();
+    assert!(writer.finish().is_err());
+}
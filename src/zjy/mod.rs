@@ -17,18 +17,35 @@
  * You should have received a copy of the GNU Affero General Public License
  * along with this program.  If not, see <https://www.gnu.org/licenses/>. 
  */
-
 pub mod json{
     use std::fmt::{self};
     use std::collections::HashMap;
 
-    use crate::zjy::json::ast::array::ArrayContext;
-    use crate::zjy::json::ast::null::NullMode;
-    use crate::zjy::json::ast::number::NumberContext;
-    use crate::zjy::json::ast::object::{ObjectContext, ObjectMode};
-    use crate::zjy::json::ast::r#false::FalseMode;
-    use crate::zjy::json::ast::r#true::TrueMode;
-    use crate::zjy::json::ast::string::{StringContext, StringMode};
+    pub use crate::zjy::json::error::{JsonError, Limit};
+    pub use crate::zjy::json::options::{DuplicateKeys, Extensions, DEFAULT_MAX_DEPTH, NonFinite, NumberMode, ParseOptions, SerializeOptions, Strictness, WhitespaceSet};
+    pub use crate::zjy::json::parser::Parser;
+    pub use crate::zjy::json::diagnostic::{Diagnostic, DiagnosticKind, DiagnosticRenderer, Expected, Language};
+    pub use crate::zjy::json::recover::Recovered;
+    pub use crate::zjy::json::span::{Position, Span, SpanChildren, SpanTree, Spanned};
+    pub use crate::zjy::json::cst::CstDocument;
+    pub use crate::zjy::json::jsonc::{CommentPlacement, JsonComment, JsoncDocument};
+    pub use crate::zjy::json::lines::{JsonLinesReader, JsonLinesWriter};
+    pub use crate::zjy::json::stream::{JsonStreamReader, StreamFormat};
+    pub use crate::zjy::json::lazy::LazyJson;
+    pub use crate::zjy::json::tape::{JsonTape, TapeKind, TapeValue};
+    pub use crate::zjy::json::push::PushParser;
+    pub use crate::zjy::json::writer::JsonWriter;
+    pub use crate::zjy::json::cmp::NumberEquality;
+    pub use crate::zjy::json::diff::{render_diff, ArrayDiff, Change, DiffOptions};
+    pub use crate::zjy::json::merge::{ArrayMerge, MergeConflict, MergeOptions};
+    pub use crate::zjy::json::query::Query;
+    pub use crate::zjy::json::schema::SchemaInferrer;
+    pub use crate::zjy::json::convert::{FromJson, ToJson};
+    pub use crate::zjy::json::codegen::{generate_rust, CodegenOptions, CodegenStyle};
+    #[cfg(feature = "tokio")]
+    pub use crate::zjy::json::async_read::AsyncJsonLines;
+    #[cfg(feature = "mmap")]
+    pub use crate::zjy::json::mmap::MappedJson;
 
     #[allow(dead_code)]
     /**
@@ -51,219 +68,61 @@ pub mod json{
     impl Json {
             /**
              * 将字符串形式的json转换为json实例对象
+             * 按照RFC 8259严格解析,等价于Parser::new().parse(str)
              */
         pub fn str_to_json(str:&str) ->Result<Json,String>{
-            if str.len()==0{
-                return Err("str参数长度为0".to_string());
-            }
-
-            // 初始化计数器
-            let mut char_index:usize=0;
-            let mut json_type:Option<Json>=None;
-            for c in str.chars(){
-                char_index=char_index+1;
-                match judgment_json_type_option(c) {
-                    Ok(option) => {
-                        if let Some(json)=option{
-                            // 如果不为空
-                            json_type=Some(json);
-                            break;
-                        }
-                    },
-                    Err(err) => {return Err(std::format!("第1个字符:{}",err));},
-                }
-            };
-            // 判空
-            let json_type=match json_type {
-                Some(json) => json,
-                None => {return Err(std::format!("没能解析出任何json对象"));},
-            };
-            
-            // 重新指定迭代器的位置
-            let mut chars=str.chars();
-            {
-                // 这会把字符指针指定到第一个json字符的位置
-                let mut tmp:usize=0;
-                while tmp < char_index {
-                    chars.next();
-                    tmp += 1;
-                }
-            }
-            let mut chars_f=str.chars();
-            {
-                // 这会把字符指针指定到第一个json字符的前一个位置
-                let mut tmp:usize=0;
-                while tmp < char_index-1 {
-                    chars_f.next();
-                    tmp += 1;
-                }
-            }
-            
-
-            // 根据不同的类型,选择不同的上下文
-
-            match json_type {
-                Json::Null => {
-                    let mut conntest=NullMode::u;
-                    // 继续遍历
-                    for c in chars{
-                        char_index=char_index+1;
-                        match conntest.check_null(c) {
-                            Ok(isfinish) =>{
-                                if isfinish{
-                                    // 判断完毕
-                                    return Ok(Json::Null);
-                                }
-                            },
-                            Err(_err) => {return Err(std::format!("第{}个字符:{}'{}'",char_index,"此处不应是:",c));},
-                        };
-                    }
-                    // for循环结束还没返回
-                    return Err(std::format!("第{}个字符:null字符没有结束",char_index));
-                },
-                Json::Boolean(b) => {
-                    if b{
-                        let mut conntest=TrueMode::r;
-                        for c in chars {
-                            char_index=char_index+1;
-                            match conntest.check_true(c) {
-                                Ok(isfinish) => {
-                                    if isfinish{
-                                        return Ok(Json::Boolean(true));
-                                    }
-                                    
-                                },
-                                Err(_err) => {return Err(std::format!("第{}个字符:{}'{}'",char_index,"此处不应是:",c));},
-                            }
-                            
-                        }
-                        // for循环结束还没返回
-                        return Err(std::format!("第{}个字符:true字符串没有结束",char_index));
-                    }else {
-                        let mut conntest =FalseMode::a;
-                        for c in chars{
-                            char_index=char_index+1;
-                            match conntest.check_false(c) {
-                                Ok(isfinish) => {
-                                    if isfinish{
-                                        return Ok(Json::Boolean(false));
-                                    }
-                                },
-                                Err(_err) => {return Err(std::format!("第{}个字符:{}'{}'",char_index,"此处不应是:",c));},
-                            }
-                        }
-                        // for循环结束还没返回
-                        return Err(std::format!("第{}个字符:false字符串没有结束",char_index));
-                    }
-                },
-                Json::Number(_) => {
-                    // 直接将这个字符串判断
-                    let mut context: NumberContext=NumberContext::new();
-                    match context.check_number(str) {
-                        Ok(_) => {},
-                        Err(err) => {return Err(std::format!("字符串:{}不是一个合法的数字,错误对象:{}",str,err));},
-                    }
-                    return Ok(Json::Number(context.get_number()));
-                },
-                Json::String(_) => {
-                    let mut context=StringContext::new();
-                    // 需要前一个
-                    for c in chars_f{
-                        char_index=char_index+1;
-                        match context.check_string(c) {
-                            Ok(_) => {
-                                if *(context.get_mode()) == StringMode::end{
-                                    return Ok(Json::String(context.get_str().to_string()));
-                                }
-                            },
-                            Err(_) =>{
-                                {return Err(std::format!("第{}个字符:此处不应是:'{}'",char_index,c));}
-                            },
-                        }
-                    }
-                    // 如果for循环结束还没判断为一个完整的字符串
-                    return Err(std::format!("第{}个字符:这个Sting类型缺少 '{}' 结束",char_index,'"'));
-                },
-                Json::Object(_) => {
-                    let mut context=ObjectContext::new_key();
-                    for c in chars {
-                        char_index=char_index+1;
-                        match context.check_object(c) {
-                            Ok(_) => {
-                                if let ObjectMode::End=context.get_mode_ptr(){
-                                    // 如果已经完成
-                                    return Ok(context.into_json());
-                                }
-                            },
-                            Err(err) => {return Err(std::format!("第{}个字符:{}",char_index,err));},
-                        }
-                    }
-                    // for循环结束还没返回
-                    return Err(std::format!("第{}个字符:这个Object并没有结束",char_index));
-                },
-                Json::Array(_) => {
-                    let mut context=ArrayContext::new_judgment_type();
-                    for c in chars{
-                        char_index=char_index+1;
-                        match context.check_array(c) {
-                            Ok(_) => {
-                                // 判断是否完成
-                                if let ast::array::ArrayMode::End=context.get_mode_ptr(){
-                                    return Ok(context.into_json());
-                                }
-                            },
-                            Err(err) => {
-                                return Err(std::format!("第{}个字符:{}",char_index,err));
-                            },
-                        }
-                    }
-                    // for循环结束还没返回
-                    return Err(std::format!("第{}个字符:此处需要']'结束数组",char_index));
-    
-                },
-            }
+            return Json::str_to_json_with_options(str, &ParseOptions::default()).map_err(|err| err.to_string());
+        }
+            /**
+             * 按照options中的选项将字符串形式的json转换为json实例对象
+             * 超出资源限制时返回JsonError::LimitExceeded
+             */
+        pub fn str_to_json_with_options(str:&str, options:&ParseOptions) ->Result<Json,JsonError>{
+            return parser::parse_str(str, options);
         }
-        
-
-        
     }
     mod ast;
+    pub mod error;
+    pub mod options;
+    pub mod parser;
+    pub mod simd;
+    mod fast;
+    mod json5;
+    pub mod jsonc;
+    pub mod cst;
+    pub mod pointer;
+    pub mod span;
+    pub mod diagnostic;
+    pub mod recover;
+    pub mod lines;
+    pub mod stream;
+    pub mod lazy;
+    pub mod tape;
+    pub mod push;
+    pub mod writer;
+    pub mod canonical;
+    pub mod format;
+    pub mod cmp;
+    pub mod diff;
+    pub mod merge;
+    pub mod query;
+    pub mod schema;
+    pub mod convert;
+    pub mod codegen;
+    #[cfg(feature = "tokio")]
+    pub mod async_read;
+    #[cfg(feature = "mmap")]
+    pub mod mmap;
 
-    pub(crate) fn judgment_json_type_option(c:char) ->Result<Option<Json>, String>{
+    pub(crate) fn judgment_json_type(c:char, options:&ParseOptions) ->Result<Json, String>{
         match c {
-            'n'=>{
-                return Ok(Option::Some(Json::Null));
-            }
-            't'=>{
-                return Ok(Option::Some(Json::Boolean(true)));
-            }
-            'f'=>{
-                return Ok(Option::Some(Json::Boolean(false)));
-            }
-            '0'|'1'|'2'|'3'|'4'|'5'|'6'|'7'|'8'|'9'|'-'=>{
-                return Ok(Option::Some(Json::Number(0.0)));
-            }
-            '"'=>{
-                return Ok(Option::Some(Json::String(String::new())));
-            }
-            '['=>{
-                return Ok(Option::Some(Json::Array(Vec::new())));
-            }
-            '{'=>{
-                return Ok(Option::Some(Json::Object(HashMap::new())));
-            }
-            // 允许空格回车
-            '\r'|'\n'|'\x20'=>{
-                return Ok(Option::None)
+            // json5的number可以用这些字符开始
+            '+'|'.'|'I'|'N' if options.extensions.json5_numbers=>{
+                return Ok(Json::Number(0.0));
             }
-            _=>{
-                return Err(std::format!("此处不应该是: '{}'",c));
+            '\'' if options.extensions.single_quoted_strings=>{
+                return Ok(Json::String(String::new()));
             }
-        }
-    }
-
-    pub(crate) fn judgment_json_type(c:char) ->Result<Json, String>{
-        match c {
             'n'=>{
                 return Ok(Json::Null);
             }
@@ -292,59 +151,20 @@ pub mod json{
     }
 
     impl fmt::Display for Json {
+        /**
+         * 紧凑输出合法的json,string会被转义,key按照HashMap的迭代顺序,json不能表示的NaN和Infinity输出为null
+         */
         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            match self {
-                Json::Null => {
-                    return write!(f,"null"); 
-                },
-                Json::Boolean(b) => {
-                    if *b{
-                        return write!(f,"true");
-                    } else {
-                        return write!(f, "false");
-                    }
-                },
-                Json::Number(n) => {
-                    return write!(f,"{}",*n);
-                },
-                Json::String(s) => {
-                    return write!(f,"{}{}{}","\"",*s,"\"");
-                },
-                Json::Object(obj) => {
-                    let mut result="{".to_string();
-                    // 迭代
-                    for (key,value) in obj.iter(){
-                        result.push('"');
-                        result.push_str(key);
-                        result.push('"');
-                        result.push(':');
-                        result.push_str(&value.to_string());
-                        result.push(',');
-                    }
-                    result.pop();
-                    result.push('}');
-                    return write!(f,"{}", result);
-                },
-                Json::Array(arr) => {
-                    let mut tmp:String=String::new();
-                    tmp.push('[');
-                    for obj in arr{
-                        tmp.push_str(&obj.to_string());
-                        tmp.push(',');
-                    }
-                    tmp.pop();
-                    tmp.push(']');
-                    return write!(f,"{}",tmp);
-                },
-            }
+            let options = SerializeOptions { non_finite: NonFinite::Null, ..SerializeOptions::default() };
+            return self.write_to(writer::FmtWriter(f), &options).map_err(|_| fmt::Error);
         }
     }
     impl Clone for Json {
         fn clone(&self) -> Self {
         match self {
             Self::Null => Self::Null,
-            Self::Boolean(arg0) => Self::Boolean(arg0.clone()),
-            Self::Number(arg0) => Self::Number(arg0.clone()),
+            Self::Boolean(arg0) => Self::Boolean(*arg0),
+            Self::Number(arg0) => Self::Number(*arg0),
             Self::String(arg0) => Self::String(arg0.clone()),
             Self::Object(arg0) => Self::Object(arg0.clone()),
             Self::Array(arg0) => Self::Array(arg0.clone()),
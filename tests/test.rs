@@ -17,7 +17,6 @@
  * You should have received a copy of the GNU Affero General Public License
  * along with this program.  If not, see <https://www.gnu.org/licenses/>. 
  */
-
 #[cfg(test)]
 #[test]
 fn test_zjy_json(){
@@ -43,4 +42,14 @@ fn test_zjy_json(){
     let string_json_str=read_to_string("tests/teststring.json").unwrap();
     println!("String:{}",Json::str_to_json(&string_json_str).unwrap());
 
-}
\ No newline at end of file
+}
+#[cfg(feature = "mmap")]
+#[test]
+fn test_zjy_json_mmap(){
+    use zjy_json::zjy::json::Json;
+
+    // 通过内存映射直接解析,不需要先读取到String中
+    for path in ["tests/testarray.json","tests/testobject.json","tests/testnull.json","tests/teststring.json"]{
+        println!("{}:{}",path,unsafe { Json::from_mmap(path) }.unwrap());
+    }
+}